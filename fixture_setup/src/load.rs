@@ -2,7 +2,7 @@ use crate::source_models::*;
 use diesel::associations::HasTable;
 use diesel::prelude::*;
 use forte_core::context;
-use forte_core::database::song_artist;
+use forte_core::database::{song_artist, user_song_stats};
 use forte_core::models::*;
 use std::env;
 use std::fs::File;
@@ -67,31 +67,54 @@ fn load_from_file(path: &Path, conn: &SqliteConnection) -> anyhow::Result<()> {
 }
 
 fn add_all_albums(things: Vec<AlbumSource>, conn: &SqliteConnection) -> anyhow::Result<()> {
+    let user = User::default_user(conn)?;
     for thing in things {
+        let last_played = thing.stats.as_ref().and_then(|stats| stats.last_played);
         let thing: Album = thing.into();
+        let album_id = thing.id;
         thing.insert_into(Album::table()).execute(conn)?;
+
+        if let Some(last_played) = last_played {
+            UserStats::album_played(conn, user.id, album_id, last_played.into_time())?;
+        }
     }
 
     Ok(())
 }
 
 fn add_all_artists(things: Vec<ArtistSource>, conn: &SqliteConnection) -> anyhow::Result<()> {
+    let user = User::default_user(conn)?;
     for thing in things {
+        let last_played = thing.stats.as_ref().and_then(|stats| stats.last_played);
         let thing: Artist = thing.into();
+        let artist_id = thing.id;
         thing.insert_into(Artist::table()).execute(conn)?;
+
+        if let Some(last_played) = last_played {
+            UserStats::artist_played(conn, user.id, artist_id, last_played.into_time())?;
+        }
     }
 
     Ok(())
 }
 
 fn add_all_songs(things: Vec<SongSource>, conn: &SqliteConnection) -> anyhow::Result<()> {
-    for song_source in things {
+    let user = User::default_user(conn)?;
+    for mut song_source in things {
         let artist_ids = song_source.artist_ids.clone().unwrap_or_default();
+        let stats = song_source.stats.take();
         let song: Song = song_source.into();
         let song_id = song.id;
 
         song.insert_into(Song::table()).execute(conn)?;
 
+        if let Some(stats) = stats {
+            stats
+                .into_stats(user.id, song_id)
+                .insert_into(user_song_stats::table)
+                .execute(conn)?;
+        }
+
         let records: Vec<_> = artist_ids
             .into_iter()
             .map(|id| id.into())
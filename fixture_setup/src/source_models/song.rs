@@ -11,6 +11,19 @@ pub struct SongUserStatsSource {
     pub liked: Option<bool>,
 }
 
+impl SongUserStatsSource {
+    pub fn into_stats(self, user_id: UUID, song_id: UUID) -> SongStats {
+        SongStats {
+            user_id,
+            song_id,
+            play_count: self.play_count.unwrap_or(0),
+            last_played: self.last_played.map(|t| t.into_time()),
+            liked: self.liked.unwrap_or(false),
+            rating: None,
+        }
+    }
+}
+
 #[derive(Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct SongSource {
@@ -34,24 +47,12 @@ impl Into<Song> for SongSource {
             album_id: self.album_id.into(),
             track_number: self.track_number.unwrap_or(1),
             disk_number: self.disk_number.unwrap_or(1),
-            duration: self.duration,
+            // The fixtures specify durations in seconds.
+            duration: self.duration * 1000,
             time_added: self.time_added.unwrap_or(0).into_time(),
-            play_count: self
-                .stats
-                .as_ref()
-                .and_then(|stats| stats.play_count)
-                .unwrap_or(0),
-            last_played: self
-                .stats
-                .as_ref()
-                .and_then(|stats| stats.last_played)
-                .map(|t| t.into_time()),
-            liked: self
-                .stats
-                .as_ref()
-                .and_then(|stats| stats.liked)
-                .unwrap_or(false),
             path: Path::new(&UUID::new().to_string()).into(),
+            mbid: None,
+            genre: None,
         }
     }
 }
@@ -0,0 +1,12 @@
+CREATE TABLE album_picture (
+  id BINARY(128) PRIMARY KEY NOT NULL,
+  album_id BINARY(128) NOT NULL REFERENCES album(id),
+  picture_type INTEGER NOT NULL,
+  description TEXT,
+  path BLOB NOT NULL,
+  byte_size INTEGER NOT NULL,
+  width INTEGER,
+  height INTEGER
+);
+
+CREATE INDEX album_picture_album_id ON album_picture (album_id);
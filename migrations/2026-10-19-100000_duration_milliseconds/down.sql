@@ -0,0 +1,3 @@
+DROP TABLE song_rescan;
+
+UPDATE song SET duration = duration / 1000;
@@ -0,0 +1,9 @@
+UPDATE song SET duration = duration * 1000;
+
+-- Songs imported before durations were stored in milliseconds only have whole seconds. The next
+-- sync reads their tags again and removes them from here.
+CREATE TABLE song_rescan (
+  song_id BINARY(128) PRIMARY KEY NOT NULL REFERENCES song(id)
+);
+
+INSERT INTO song_rescan (song_id) SELECT id FROM song;
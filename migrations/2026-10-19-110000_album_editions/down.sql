@@ -0,0 +1,41 @@
+CREATE TABLE album_old (
+  id BINARY(128) PRIMARY KEY NOT NULL,
+  artwork_path BLOB,
+  name TEXT NOT NULL,
+  artist_id BINARY(128) NOT NULL REFERENCES artist(id),
+  release_year INTEGER,
+  time_added TIMESTAMP NOT NULL,
+
+  last_played TIMESTAMP,
+
+  UNIQUE (name, artist_id)
+);
+
+INSERT OR IGNORE INTO album_old
+  SELECT id, artwork_path, name, artist_id, release_year, time_added, last_played FROM album;
+
+DROP TABLE album;
+ALTER TABLE album_old RENAME TO album;
+
+CREATE TABLE song_old (
+  id BINARY(128) PRIMARY KEY NOT NULL,
+  name TEXT NOT NULL,
+  album_id BINARY(128) NOT NULL REFERENCES album(id),
+  track_number INTEGER NOT NULL,
+  disk_number INTEGER NOT NULL,
+  duration INTEGER NOT NULL,
+  time_added TIMESTAMP NOT NULL,
+  play_count INTEGER NOT NULL,
+  last_played TIMESTAMP,
+  liked BOOLEAN NOT NULL,
+  path BLOB UNIQUE NOT NULL,
+
+  UNIQUE(track_number, disk_number, album_id)
+);
+
+INSERT OR IGNORE INTO song_old
+  SELECT id, name, album_id, track_number, disk_number, duration, time_added, play_count,
+         last_played, liked, path FROM song;
+
+DROP TABLE song;
+ALTER TABLE song_old RENAME TO song;
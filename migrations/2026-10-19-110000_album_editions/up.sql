@@ -0,0 +1,48 @@
+-- SQLite can't drop constraints, so the album and song tables are rebuilt without their
+-- uniqueness constraints on names and track numbers.
+CREATE TABLE album_new (
+  id BINARY(128) PRIMARY KEY NOT NULL,
+  artwork_path BLOB,
+  name TEXT NOT NULL,
+  artist_id BINARY(128) NOT NULL REFERENCES artist(id),
+  release_year INTEGER,
+  time_added TIMESTAMP NOT NULL,
+
+  last_played TIMESTAMP,
+
+  mbid TEXT UNIQUE,
+  directory BLOB
+);
+
+INSERT INTO album_new (id, artwork_path, name, artist_id, release_year, time_added, last_played)
+  SELECT id, artwork_path, name, artist_id, release_year, time_added, last_played FROM album;
+
+DROP TABLE album;
+ALTER TABLE album_new RENAME TO album;
+
+CREATE INDEX album_name_artist_id ON album (name, artist_id);
+
+CREATE TABLE song_new (
+  id BINARY(128) PRIMARY KEY NOT NULL,
+  name TEXT NOT NULL,
+  album_id BINARY(128) NOT NULL REFERENCES album(id),
+  track_number INTEGER NOT NULL,
+  disk_number INTEGER NOT NULL,
+  duration INTEGER NOT NULL,
+  time_added TIMESTAMP NOT NULL,
+  play_count INTEGER NOT NULL,
+  last_played TIMESTAMP,
+  liked BOOLEAN NOT NULL,
+  path BLOB UNIQUE NOT NULL,
+  mbid TEXT
+);
+
+INSERT INTO song_new (id, name, album_id, track_number, disk_number, duration, time_added,
+                      play_count, last_played, liked, path)
+  SELECT id, name, album_id, track_number, disk_number, duration, time_added, play_count,
+         last_played, liked, path FROM song;
+
+DROP TABLE song;
+ALTER TABLE song_new RENAME TO song;
+
+CREATE INDEX song_album_id ON song (album_id);
@@ -0,0 +1,8 @@
+CREATE TABLE import_issue (
+  path BLOB PRIMARY KEY NOT NULL,
+  kind TEXT NOT NULL,
+  message TEXT NOT NULL,
+  last_seen TIMESTAMP NOT NULL
+);
+
+CREATE INDEX import_issue_last_seen ON import_issue (last_seen);
@@ -0,0 +1,19 @@
+DROP TRIGGER song_artist_fts_delete;
+DROP TRIGGER song_artist_fts_insert;
+DROP TRIGGER song_fts_update;
+DROP TRIGGER song_fts_delete;
+DROP TRIGGER song_fts_insert;
+DROP TRIGGER album_fts_update;
+DROP TRIGGER album_fts_delete;
+DROP TRIGGER album_fts_insert;
+DROP TRIGGER artist_fts_update;
+DROP TRIGGER artist_fts_delete;
+DROP TRIGGER artist_fts_insert;
+
+DROP TABLE song_fts;
+DROP TABLE album_fts;
+DROP TABLE artist_fts;
+
+DROP TABLE song_fts_key;
+DROP TABLE album_fts_key;
+DROP TABLE artist_fts_key;
@@ -0,0 +1,177 @@
+-- Full-text indexes over the names of artists, albums and songs. Diacritics are folded so "Bjork"
+-- finds "Björk".
+--
+-- Index rows are keyed by an integer, but the indexed tables have binary ids and their implicit
+-- rowids can change on VACUUM. Each indexed row gets a stable key in a `_fts_key` table instead,
+-- whose INTEGER PRIMARY KEY is the rowid of its row in the index.
+CREATE TABLE artist_fts_key (
+  key INTEGER PRIMARY KEY,
+  id BINARY(128) UNIQUE NOT NULL REFERENCES artist(id)
+);
+
+CREATE TABLE album_fts_key (
+  key INTEGER PRIMARY KEY,
+  id BINARY(128) UNIQUE NOT NULL REFERENCES album(id)
+);
+
+CREATE TABLE song_fts_key (
+  key INTEGER PRIMARY KEY,
+  id BINARY(128) UNIQUE NOT NULL REFERENCES song(id)
+);
+
+CREATE VIRTUAL TABLE artist_fts USING fts5 (
+  name,
+  tokenize = 'unicode61 remove_diacritics 2'
+);
+
+CREATE VIRTUAL TABLE album_fts USING fts5 (
+  name,
+  artist,
+  tokenize = 'unicode61 remove_diacritics 2'
+);
+
+-- Songs are also indexed by the names of their album and artists, so "beatles abbey" finds the
+-- songs on Abbey Road.
+CREATE VIRTUAL TABLE song_fts USING fts5 (
+  name,
+  album,
+  artists,
+  tokenize = 'unicode61 remove_diacritics 2'
+);
+
+INSERT INTO artist_fts_key (id) SELECT id FROM artist;
+INSERT INTO album_fts_key (id) SELECT id FROM album;
+INSERT INTO song_fts_key (id) SELECT id FROM song;
+
+INSERT INTO artist_fts (rowid, name)
+  SELECT artist_fts_key.key, artist.name
+  FROM artist_fts_key INNER JOIN artist ON artist.id = artist_fts_key.id;
+
+INSERT INTO album_fts (rowid, name, artist)
+  SELECT album_fts_key.key, album.name, artist.name
+  FROM album_fts_key
+  INNER JOIN album ON album.id = album_fts_key.id
+  INNER JOIN artist ON artist.id = album.artist_id;
+
+INSERT INTO song_fts (rowid, name, album, artists)
+  SELECT song_fts_key.key, song.name, album.name, (
+    SELECT group_concat(artist.name, ' ')
+    FROM song_artist INNER JOIN artist ON artist.id = song_artist.artist_id
+    WHERE song_artist.song_id = song.id
+  )
+  FROM song_fts_key
+  INNER JOIN song ON song.id = song_fts_key.id
+  INNER JOIN album ON album.id = song.album_id;
+
+-- Artists
+
+CREATE TRIGGER artist_fts_insert AFTER INSERT ON artist BEGIN
+  INSERT INTO artist_fts_key (id) VALUES (new.id);
+
+  INSERT INTO artist_fts (rowid, name)
+    VALUES ((SELECT key FROM artist_fts_key WHERE id = new.id), new.name);
+END;
+
+CREATE TRIGGER artist_fts_delete AFTER DELETE ON artist BEGIN
+  DELETE FROM artist_fts WHERE rowid = (SELECT key FROM artist_fts_key WHERE id = old.id);
+  DELETE FROM artist_fts_key WHERE id = old.id;
+END;
+
+CREATE TRIGGER artist_fts_update AFTER UPDATE OF name ON artist BEGIN
+  UPDATE artist_fts SET name = new.name
+  WHERE rowid = (SELECT key FROM artist_fts_key WHERE id = old.id);
+
+  UPDATE album_fts SET artist = new.name
+  WHERE rowid IN (
+    SELECT album_fts_key.key
+    FROM album_fts_key INNER JOIN album ON album.id = album_fts_key.id
+    WHERE album.artist_id = new.id
+  );
+
+  UPDATE song_fts SET artists = (
+    SELECT group_concat(artist.name, ' ')
+    FROM song_artist INNER JOIN artist ON artist.id = song_artist.artist_id
+    WHERE song_artist.song_id = (SELECT id FROM song_fts_key WHERE key = song_fts.rowid)
+  )
+  WHERE rowid IN (
+    SELECT song_fts_key.key
+    FROM song_fts_key INNER JOIN song_artist ON song_artist.song_id = song_fts_key.id
+    WHERE song_artist.artist_id = new.id
+  );
+END;
+
+-- Albums
+
+CREATE TRIGGER album_fts_insert AFTER INSERT ON album BEGIN
+  INSERT INTO album_fts_key (id) VALUES (new.id);
+
+  INSERT INTO album_fts (rowid, name, artist)
+    VALUES (
+      (SELECT key FROM album_fts_key WHERE id = new.id),
+      new.name,
+      (SELECT name FROM artist WHERE id = new.artist_id)
+    );
+END;
+
+CREATE TRIGGER album_fts_delete AFTER DELETE ON album BEGIN
+  DELETE FROM album_fts WHERE rowid = (SELECT key FROM album_fts_key WHERE id = old.id);
+  DELETE FROM album_fts_key WHERE id = old.id;
+END;
+
+CREATE TRIGGER album_fts_update AFTER UPDATE OF name, artist_id ON album BEGIN
+  UPDATE album_fts
+  SET name = new.name, artist = (SELECT name FROM artist WHERE id = new.artist_id)
+  WHERE rowid = (SELECT key FROM album_fts_key WHERE id = old.id);
+
+  UPDATE song_fts SET album = new.name
+  WHERE rowid IN (
+    SELECT song_fts_key.key
+    FROM song_fts_key INNER JOIN song ON song.id = song_fts_key.id
+    WHERE song.album_id = new.id
+  );
+END;
+
+-- Songs
+
+CREATE TRIGGER song_fts_insert AFTER INSERT ON song BEGIN
+  INSERT INTO song_fts_key (id) VALUES (new.id);
+
+  INSERT INTO song_fts (rowid, name, album, artists)
+    VALUES (
+      (SELECT key FROM song_fts_key WHERE id = new.id),
+      new.name,
+      (SELECT name FROM album WHERE id = new.album_id),
+      NULL
+    );
+END;
+
+CREATE TRIGGER song_fts_delete AFTER DELETE ON song BEGIN
+  DELETE FROM song_fts WHERE rowid = (SELECT key FROM song_fts_key WHERE id = old.id);
+  DELETE FROM song_fts_key WHERE id = old.id;
+END;
+
+CREATE TRIGGER song_fts_update AFTER UPDATE OF name, album_id ON song BEGIN
+  UPDATE song_fts
+  SET name = new.name, album = (SELECT name FROM album WHERE id = new.album_id)
+  WHERE rowid = (SELECT key FROM song_fts_key WHERE id = old.id);
+END;
+
+-- Song artists are added after the song, so they update the song's row.
+
+CREATE TRIGGER song_artist_fts_insert AFTER INSERT ON song_artist BEGIN
+  UPDATE song_fts SET artists = (
+    SELECT group_concat(artist.name, ' ')
+    FROM song_artist INNER JOIN artist ON artist.id = song_artist.artist_id
+    WHERE song_artist.song_id = new.song_id
+  )
+  WHERE rowid = (SELECT key FROM song_fts_key WHERE id = new.song_id);
+END;
+
+CREATE TRIGGER song_artist_fts_delete AFTER DELETE ON song_artist BEGIN
+  UPDATE song_fts SET artists = (
+    SELECT group_concat(artist.name, ' ')
+    FROM song_artist INNER JOIN artist ON artist.id = song_artist.artist_id
+    WHERE song_artist.song_id = old.song_id
+  )
+  WHERE rowid = (SELECT key FROM song_fts_key WHERE id = old.song_id);
+END;
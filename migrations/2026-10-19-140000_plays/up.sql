@@ -0,0 +1,18 @@
+CREATE TABLE play (
+  id BINARY(128) PRIMARY KEY NOT NULL,
+  song_id BINARY(128) NOT NULL REFERENCES song(id),
+  time TIMESTAMP NOT NULL,
+
+  -- The album or artist the song was played from, if any.
+  album_id BINARY(128) REFERENCES album(id),
+  artist_id BINARY(128) REFERENCES artist(id),
+
+  -- The name of the client which played the song.
+  client TEXT,
+
+  -- How long the song was listened to, in milliseconds.
+  duration INTEGER
+);
+
+CREATE INDEX play_time ON play (time);
+CREATE INDEX play_song_id ON play (song_id);
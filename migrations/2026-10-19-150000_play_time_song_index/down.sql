@@ -0,0 +1,2 @@
+DROP INDEX play_time_song_id_duration;
+CREATE INDEX play_time ON play (time);
@@ -0,0 +1,69 @@
+-- SQLite can only drop columns since 3.35, so the song table is rebuilt without the rating. The
+-- triggers keeping the full-text index of songs up to date refer to it, so they're dropped during
+-- the rebuild and created again afterwards.
+DROP TRIGGER album_fts_update;
+DROP TRIGGER song_fts_insert;
+DROP TRIGGER song_fts_delete;
+DROP TRIGGER song_fts_update;
+
+CREATE TABLE song_old (
+  id BINARY(128) PRIMARY KEY NOT NULL,
+  name TEXT NOT NULL,
+  album_id BINARY(128) NOT NULL REFERENCES album(id),
+  track_number INTEGER NOT NULL,
+  disk_number INTEGER NOT NULL,
+  duration INTEGER NOT NULL,
+  time_added TIMESTAMP NOT NULL,
+  play_count INTEGER NOT NULL,
+  last_played TIMESTAMP,
+  liked BOOLEAN NOT NULL,
+  path BLOB UNIQUE NOT NULL,
+  mbid TEXT
+);
+
+INSERT INTO song_old (id, name, album_id, track_number, disk_number, duration, time_added,
+                      play_count, last_played, liked, path, mbid)
+  SELECT id, name, album_id, track_number, disk_number, duration, time_added, play_count,
+         last_played, liked, path, mbid
+  FROM song;
+
+DROP TABLE song;
+ALTER TABLE song_old RENAME TO song;
+
+CREATE INDEX song_album_id ON song (album_id);
+
+CREATE TRIGGER album_fts_update AFTER UPDATE OF name, artist_id ON album BEGIN
+  UPDATE album_fts
+  SET name = new.name, artist = (SELECT name FROM artist WHERE id = new.artist_id)
+  WHERE rowid = (SELECT key FROM album_fts_key WHERE id = old.id);
+
+  UPDATE song_fts SET album = new.name
+  WHERE rowid IN (
+    SELECT song_fts_key.key
+    FROM song_fts_key INNER JOIN song ON song.id = song_fts_key.id
+    WHERE song.album_id = new.id
+  );
+END;
+
+CREATE TRIGGER song_fts_insert AFTER INSERT ON song BEGIN
+  INSERT INTO song_fts_key (id) VALUES (new.id);
+
+  INSERT INTO song_fts (rowid, name, album, artists)
+    VALUES (
+      (SELECT key FROM song_fts_key WHERE id = new.id),
+      new.name,
+      (SELECT name FROM album WHERE id = new.album_id),
+      NULL
+    );
+END;
+
+CREATE TRIGGER song_fts_delete AFTER DELETE ON song BEGIN
+  DELETE FROM song_fts WHERE rowid = (SELECT key FROM song_fts_key WHERE id = old.id);
+  DELETE FROM song_fts_key WHERE id = old.id;
+END;
+
+CREATE TRIGGER song_fts_update AFTER UPDATE OF name, album_id ON song BEGIN
+  UPDATE song_fts
+  SET name = new.name, album = (SELECT name FROM album WHERE id = new.album_id)
+  WHERE rowid = (SELECT key FROM song_fts_key WHERE id = old.id);
+END;
@@ -0,0 +1,2 @@
+-- The rating of the song from 1 to 5 stars. Unrated songs have no rating.
+ALTER TABLE song ADD COLUMN rating INTEGER;
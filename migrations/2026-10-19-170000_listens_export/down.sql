@@ -0,0 +1 @@
+DROP TABLE listens_export;
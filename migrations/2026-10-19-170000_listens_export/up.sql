@@ -0,0 +1,5 @@
+-- The time of the newest play written by the last incremental export of listens in each format.
+CREATE TABLE listens_export (
+    format TEXT PRIMARY KEY NOT NULL,
+    last_play_time TIMESTAMP NOT NULL
+);
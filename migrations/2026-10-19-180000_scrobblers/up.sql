@@ -0,0 +1,33 @@
+-- Services plays are submitted to with the ListenBrainz API.
+CREATE TABLE scrobbler (
+  id BINARY(128) PRIMARY KEY NOT NULL,
+  name TEXT NOT NULL,
+
+  -- The root of the API, like https://api.listenbrainz.org.
+  api_url TEXT NOT NULL,
+  token TEXT NOT NULL,
+  time_added TIMESTAMP NOT NULL
+);
+
+-- Submissions which haven't been accepted yet. Listens stay here until the scrobbler accepts
+-- them, so nothing is lost while offline.
+CREATE TABLE scrobble_queue (
+  id BINARY(128) PRIMARY KEY NOT NULL,
+  scrobbler_id BINARY(128) NOT NULL REFERENCES scrobbler(id),
+
+  -- The play a listen submits, so the listen is dropped when the play is deleted. Songs playing
+  -- now have none.
+  play_id BINARY(128) REFERENCES play(id),
+
+  -- Either `single` for a listen or `playing_now`.
+  listen_type TEXT NOT NULL,
+
+  -- The listen as ListenBrainz JSON.
+  payload TEXT NOT NULL,
+  time_added TIMESTAMP NOT NULL,
+  attempts INTEGER NOT NULL DEFAULT 0,
+  next_attempt TIMESTAMP NOT NULL,
+  last_error TEXT
+);
+
+CREATE INDEX scrobble_queue_next_attempt ON scrobble_queue (scrobbler_id, next_attempt);
@@ -0,0 +1,72 @@
+-- Only the oldest user's stats survive going back to global stats.
+CREATE TABLE listens_export_old (
+  format TEXT PRIMARY KEY NOT NULL,
+  last_play_time TIMESTAMP NOT NULL
+);
+
+INSERT INTO listens_export_old (format, last_play_time)
+  SELECT format, max(last_play_time) FROM listens_export GROUP BY format;
+
+DROP TABLE listens_export;
+ALTER TABLE listens_export_old RENAME TO listens_export;
+
+CREATE TABLE scrobbler_old (
+  id BINARY(128) PRIMARY KEY NOT NULL,
+  name TEXT NOT NULL,
+  api_url TEXT NOT NULL,
+  token TEXT NOT NULL,
+  time_added TIMESTAMP NOT NULL
+);
+
+INSERT INTO scrobbler_old (id, name, api_url, token, time_added)
+  SELECT id, name, api_url, token, time_added FROM scrobbler;
+
+DROP TABLE scrobbler;
+ALTER TABLE scrobbler_old RENAME TO scrobbler;
+
+CREATE TABLE play_old (
+  id BINARY(128) PRIMARY KEY NOT NULL,
+  song_id BINARY(128) NOT NULL REFERENCES song(id),
+  time TIMESTAMP NOT NULL,
+  album_id BINARY(128) REFERENCES album(id),
+  artist_id BINARY(128) REFERENCES artist(id),
+  client TEXT,
+  duration INTEGER
+);
+
+INSERT INTO play_old (id, song_id, time, album_id, artist_id, client, duration)
+  SELECT id, song_id, time, album_id, artist_id, client, duration FROM play;
+
+DROP TABLE play;
+ALTER TABLE play_old RENAME TO play;
+
+CREATE INDEX play_song_id ON play (song_id);
+CREATE INDEX play_time_song_id_duration ON play (time, song_id, duration);
+
+ALTER TABLE song ADD COLUMN play_count INTEGER NOT NULL DEFAULT 0;
+ALTER TABLE song ADD COLUMN last_played TIMESTAMP;
+ALTER TABLE song ADD COLUMN liked BOOLEAN NOT NULL DEFAULT 0;
+ALTER TABLE song ADD COLUMN rating INTEGER;
+ALTER TABLE album ADD COLUMN last_played TIMESTAMP;
+ALTER TABLE artist ADD COLUMN last_played TIMESTAMP;
+
+UPDATE song SET
+  play_count = coalesce((SELECT play_count FROM user_song_stats
+    WHERE song_id = song.id AND user_id = (SELECT id FROM user ORDER BY time_added LIMIT 1)), 0),
+  last_played = (SELECT last_played FROM user_song_stats
+    WHERE song_id = song.id AND user_id = (SELECT id FROM user ORDER BY time_added LIMIT 1)),
+  liked = coalesce((SELECT liked FROM user_song_stats
+    WHERE song_id = song.id AND user_id = (SELECT id FROM user ORDER BY time_added LIMIT 1)), 0),
+  rating = (SELECT rating FROM user_song_stats
+    WHERE song_id = song.id AND user_id = (SELECT id FROM user ORDER BY time_added LIMIT 1));
+
+UPDATE album SET last_played = (SELECT last_played FROM user_album_stats
+  WHERE album_id = album.id AND user_id = (SELECT id FROM user ORDER BY time_added LIMIT 1));
+
+UPDATE artist SET last_played = (SELECT last_played FROM user_artist_stats
+  WHERE artist_id = artist.id AND user_id = (SELECT id FROM user ORDER BY time_added LIMIT 1));
+
+DROP TABLE user_artist_stats;
+DROP TABLE user_album_stats;
+DROP TABLE user_song_stats;
+DROP TABLE user;
@@ -0,0 +1,286 @@
+CREATE TABLE user (
+  id BINARY(128) PRIMARY KEY NOT NULL,
+  name TEXT NOT NULL UNIQUE,
+  time_added TIMESTAMP NOT NULL
+);
+
+-- Everything played, liked and rated so far belongs to the first user.
+INSERT INTO user (id, name, time_added) VALUES (randomblob(16), 'default', datetime('now'));
+
+CREATE TABLE user_song_stats (
+  user_id BINARY(128) NOT NULL REFERENCES user(id),
+  song_id BINARY(128) NOT NULL REFERENCES song(id),
+  play_count INTEGER NOT NULL DEFAULT 0,
+  last_played TIMESTAMP,
+  liked BOOLEAN NOT NULL DEFAULT 0,
+
+  -- The rating of the song from 1 to 5 stars. Unrated songs have no rating.
+  rating INTEGER,
+
+  PRIMARY KEY (user_id, song_id)
+);
+
+INSERT INTO user_song_stats (user_id, song_id, play_count, last_played, liked, rating)
+  SELECT (SELECT id FROM user), id, play_count, last_played, liked, rating FROM song
+  WHERE play_count > 0 OR last_played IS NOT NULL OR liked OR rating IS NOT NULL;
+
+CREATE TABLE user_album_stats (
+  user_id BINARY(128) NOT NULL REFERENCES user(id),
+  album_id BINARY(128) NOT NULL REFERENCES album(id),
+  last_played TIMESTAMP,
+
+  PRIMARY KEY (user_id, album_id)
+);
+
+INSERT INTO user_album_stats (user_id, album_id, last_played)
+  SELECT (SELECT id FROM user), id, last_played FROM album WHERE last_played IS NOT NULL;
+
+CREATE TABLE user_artist_stats (
+  user_id BINARY(128) NOT NULL REFERENCES user(id),
+  artist_id BINARY(128) NOT NULL REFERENCES artist(id),
+  last_played TIMESTAMP,
+
+  PRIMARY KEY (user_id, artist_id)
+);
+
+INSERT INTO user_artist_stats (user_id, artist_id, last_played)
+  SELECT (SELECT id FROM user), id, last_played FROM artist WHERE last_played IS NOT NULL;
+
+-- SQLite can only drop columns since 3.35, so the song, album and artist tables are rebuilt
+-- without the stats which moved. The triggers keeping the full-text indexes up to date refer to
+-- these tables, so they're dropped during the rebuild and created again afterwards.
+DROP TRIGGER artist_fts_insert;
+DROP TRIGGER artist_fts_delete;
+DROP TRIGGER artist_fts_update;
+DROP TRIGGER album_fts_insert;
+DROP TRIGGER album_fts_delete;
+DROP TRIGGER album_fts_update;
+DROP TRIGGER song_fts_insert;
+DROP TRIGGER song_fts_delete;
+DROP TRIGGER song_fts_update;
+DROP TRIGGER song_artist_fts_insert;
+DROP TRIGGER song_artist_fts_delete;
+
+CREATE TABLE song_new (
+  id BINARY(128) PRIMARY KEY NOT NULL,
+  name TEXT NOT NULL,
+  album_id BINARY(128) NOT NULL REFERENCES album(id),
+  track_number INTEGER NOT NULL,
+  disk_number INTEGER NOT NULL,
+  duration INTEGER NOT NULL,
+  time_added TIMESTAMP NOT NULL,
+  path BLOB UNIQUE NOT NULL,
+  mbid TEXT
+);
+
+INSERT INTO song_new (id, name, album_id, track_number, disk_number, duration, time_added, path,
+                      mbid)
+  SELECT id, name, album_id, track_number, disk_number, duration, time_added, path, mbid
+  FROM song;
+
+DROP TABLE song;
+ALTER TABLE song_new RENAME TO song;
+
+CREATE INDEX song_album_id ON song (album_id);
+
+CREATE TABLE album_new (
+  id BINARY(128) PRIMARY KEY NOT NULL,
+  artwork_path BLOB,
+  name TEXT NOT NULL,
+  artist_id BINARY(128) NOT NULL REFERENCES artist(id),
+  release_year INTEGER,
+  time_added TIMESTAMP NOT NULL,
+  mbid TEXT UNIQUE,
+  directory BLOB
+);
+
+INSERT INTO album_new (id, artwork_path, name, artist_id, release_year, time_added, mbid,
+                       directory)
+  SELECT id, artwork_path, name, artist_id, release_year, time_added, mbid, directory FROM album;
+
+DROP TABLE album;
+ALTER TABLE album_new RENAME TO album;
+
+CREATE INDEX album_name_artist_id ON album (name, artist_id);
+
+CREATE TABLE artist_new (
+  id BINARY(128) PRIMARY KEY NOT NULL,
+  name TEXT NOT NULL,
+  time_added TIMESTAMP NOT NULL,
+
+  UNIQUE (name)
+);
+
+INSERT INTO artist_new (id, name, time_added) SELECT id, name, time_added FROM artist;
+
+DROP TABLE artist;
+ALTER TABLE artist_new RENAME TO artist;
+
+CREATE TRIGGER artist_fts_insert AFTER INSERT ON artist BEGIN
+  INSERT INTO artist_fts_key (id) VALUES (new.id);
+
+  INSERT INTO artist_fts (rowid, name)
+    VALUES ((SELECT key FROM artist_fts_key WHERE id = new.id), new.name);
+END;
+
+CREATE TRIGGER artist_fts_delete AFTER DELETE ON artist BEGIN
+  DELETE FROM artist_fts WHERE rowid = (SELECT key FROM artist_fts_key WHERE id = old.id);
+  DELETE FROM artist_fts_key WHERE id = old.id;
+END;
+
+CREATE TRIGGER artist_fts_update AFTER UPDATE OF name ON artist BEGIN
+  UPDATE artist_fts SET name = new.name
+  WHERE rowid = (SELECT key FROM artist_fts_key WHERE id = old.id);
+
+  UPDATE album_fts SET artist = new.name
+  WHERE rowid IN (
+    SELECT album_fts_key.key
+    FROM album_fts_key INNER JOIN album ON album.id = album_fts_key.id
+    WHERE album.artist_id = new.id
+  );
+
+  UPDATE song_fts SET artists = (
+    SELECT group_concat(artist.name, ' ')
+    FROM song_artist INNER JOIN artist ON artist.id = song_artist.artist_id
+    WHERE song_artist.song_id = (SELECT id FROM song_fts_key WHERE key = song_fts.rowid)
+  )
+  WHERE rowid IN (
+    SELECT song_fts_key.key
+    FROM song_fts_key INNER JOIN song_artist ON song_artist.song_id = song_fts_key.id
+    WHERE song_artist.artist_id = new.id
+  );
+END;
+
+CREATE TRIGGER album_fts_insert AFTER INSERT ON album BEGIN
+  INSERT INTO album_fts_key (id) VALUES (new.id);
+
+  INSERT INTO album_fts (rowid, name, artist)
+    VALUES (
+      (SELECT key FROM album_fts_key WHERE id = new.id),
+      new.name,
+      (SELECT name FROM artist WHERE id = new.artist_id)
+    );
+END;
+
+CREATE TRIGGER album_fts_delete AFTER DELETE ON album BEGIN
+  DELETE FROM album_fts WHERE rowid = (SELECT key FROM album_fts_key WHERE id = old.id);
+  DELETE FROM album_fts_key WHERE id = old.id;
+END;
+
+CREATE TRIGGER album_fts_update AFTER UPDATE OF name, artist_id ON album BEGIN
+  UPDATE album_fts
+  SET name = new.name, artist = (SELECT name FROM artist WHERE id = new.artist_id)
+  WHERE rowid = (SELECT key FROM album_fts_key WHERE id = old.id);
+
+  UPDATE song_fts SET album = new.name
+  WHERE rowid IN (
+    SELECT song_fts_key.key
+    FROM song_fts_key INNER JOIN song ON song.id = song_fts_key.id
+    WHERE song.album_id = new.id
+  );
+END;
+
+CREATE TRIGGER song_fts_insert AFTER INSERT ON song BEGIN
+  INSERT INTO song_fts_key (id) VALUES (new.id);
+
+  INSERT INTO song_fts (rowid, name, album, artists)
+    VALUES (
+      (SELECT key FROM song_fts_key WHERE id = new.id),
+      new.name,
+      (SELECT name FROM album WHERE id = new.album_id),
+      NULL
+    );
+END;
+
+CREATE TRIGGER song_fts_delete AFTER DELETE ON song BEGIN
+  DELETE FROM song_fts WHERE rowid = (SELECT key FROM song_fts_key WHERE id = old.id);
+  DELETE FROM song_fts_key WHERE id = old.id;
+END;
+
+CREATE TRIGGER song_fts_update AFTER UPDATE OF name, album_id ON song BEGIN
+  UPDATE song_fts
+  SET name = new.name, album = (SELECT name FROM album WHERE id = new.album_id)
+  WHERE rowid = (SELECT key FROM song_fts_key WHERE id = old.id);
+END;
+
+CREATE TRIGGER song_artist_fts_insert AFTER INSERT ON song_artist BEGIN
+  UPDATE song_fts SET artists = (
+    SELECT group_concat(artist.name, ' ')
+    FROM song_artist INNER JOIN artist ON artist.id = song_artist.artist_id
+    WHERE song_artist.song_id = new.song_id
+  )
+  WHERE rowid = (SELECT key FROM song_fts_key WHERE id = new.song_id);
+END;
+
+CREATE TRIGGER song_artist_fts_delete AFTER DELETE ON song_artist BEGIN
+  UPDATE song_fts SET artists = (
+    SELECT group_concat(artist.name, ' ')
+    FROM song_artist INNER JOIN artist ON artist.id = song_artist.artist_id
+    WHERE song_artist.song_id = old.song_id
+  )
+  WHERE rowid = (SELECT key FROM song_fts_key WHERE id = old.song_id);
+END;
+
+-- Plays, scrobblers and incremental exports belong to a user. SQLite can't add a column without
+-- a default which isn't null, so these tables are rebuilt.
+CREATE TABLE play_new (
+  id BINARY(128) PRIMARY KEY NOT NULL,
+  user_id BINARY(128) NOT NULL REFERENCES user(id),
+  song_id BINARY(128) NOT NULL REFERENCES song(id),
+  time TIMESTAMP NOT NULL,
+
+  -- The album or artist the song was played from, if any.
+  album_id BINARY(128) REFERENCES album(id),
+  artist_id BINARY(128) REFERENCES artist(id),
+
+  -- The name of the client which played the song.
+  client TEXT,
+
+  -- How long the song was listened to, in milliseconds.
+  duration INTEGER
+);
+
+INSERT INTO play_new (id, user_id, song_id, time, album_id, artist_id, client, duration)
+  SELECT id, (SELECT id FROM user), song_id, time, album_id, artist_id, client, duration FROM play;
+
+DROP TABLE play;
+ALTER TABLE play_new RENAME TO play;
+
+CREATE INDEX play_song_id ON play (song_id);
+
+-- Listening statistics only read these columns of a user's plays in a time window, so they can
+-- be computed from the index without touching the table.
+CREATE INDEX play_user_id_time_song_id_duration ON play (user_id, time, song_id, duration);
+
+CREATE TABLE scrobbler_new (
+  id BINARY(128) PRIMARY KEY NOT NULL,
+  user_id BINARY(128) NOT NULL REFERENCES user(id),
+  name TEXT NOT NULL,
+
+  -- The root of the API, like https://api.listenbrainz.org.
+  api_url TEXT NOT NULL,
+  token TEXT NOT NULL,
+  time_added TIMESTAMP NOT NULL
+);
+
+INSERT INTO scrobbler_new (id, user_id, name, api_url, token, time_added)
+  SELECT id, (SELECT id FROM user), name, api_url, token, time_added FROM scrobbler;
+
+DROP TABLE scrobbler;
+ALTER TABLE scrobbler_new RENAME TO scrobbler;
+
+-- The time of the newest play written by the last incremental export of each user's listens in
+-- each format.
+CREATE TABLE listens_export_new (
+  user_id BINARY(128) NOT NULL REFERENCES user(id),
+  format TEXT NOT NULL,
+  last_play_time TIMESTAMP NOT NULL,
+
+  PRIMARY KEY (user_id, format)
+);
+
+INSERT INTO listens_export_new (user_id, format, last_play_time)
+  SELECT (SELECT id FROM user), format, last_play_time FROM listens_export;
+
+DROP TABLE listens_export;
+ALTER TABLE listens_export_new RENAME TO listens_export;
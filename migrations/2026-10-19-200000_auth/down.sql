@@ -0,0 +1,14 @@
+DROP TABLE api_key;
+DROP TABLE session;
+
+-- SQLite can only drop columns since 3.35, so the user table is rebuilt without passwords.
+CREATE TABLE user_old (
+  id BINARY(128) PRIMARY KEY NOT NULL,
+  name TEXT NOT NULL UNIQUE,
+  time_added TIMESTAMP NOT NULL
+);
+
+INSERT INTO user_old (id, name, time_added) SELECT id, name, time_added FROM user;
+
+DROP TABLE user;
+ALTER TABLE user_old RENAME TO user;
@@ -0,0 +1,20 @@
+ALTER TABLE user ADD COLUMN password_hash TEXT;
+
+-- Tokens are stored as SHA-256 hashes so the database can't be used to sign in.
+CREATE TABLE session (
+  token_hash TEXT PRIMARY KEY NOT NULL,
+  user_id BINARY(128) NOT NULL REFERENCES user(id),
+  time_added TIMESTAMP NOT NULL,
+  expires TIMESTAMP NOT NULL
+);
+
+CREATE INDEX session_user_id ON session(user_id);
+
+CREATE TABLE api_key (
+  id BINARY(128) PRIMARY KEY NOT NULL,
+  user_id BINARY(128) NOT NULL REFERENCES user(id),
+  name TEXT NOT NULL,
+  token_hash TEXT NOT NULL UNIQUE,
+  time_added TIMESTAMP NOT NULL,
+  last_used TIMESTAMP
+);
@@ -0,0 +1,13 @@
+-- SQLite can only drop columns since 3.35, so the user table is rebuilt without roles.
+CREATE TABLE user_old (
+  id BINARY(128) PRIMARY KEY NOT NULL,
+  name TEXT NOT NULL UNIQUE,
+  time_added TIMESTAMP NOT NULL,
+  password_hash TEXT
+);
+
+INSERT INTO user_old (id, name, time_added, password_hash)
+  SELECT id, name, time_added, password_hash FROM user;
+
+DROP TABLE user;
+ALTER TABLE user_old RENAME TO user;
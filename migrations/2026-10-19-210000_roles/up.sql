@@ -0,0 +1,6 @@
+-- One of `admin`, `listener` or `guest`.
+ALTER TABLE user ADD COLUMN role TEXT NOT NULL DEFAULT 'listener';
+
+-- The user which existed before there were accounts manages the library.
+UPDATE user SET role = 'admin'
+WHERE id = (SELECT id FROM user ORDER BY time_added, name LIMIT 1);
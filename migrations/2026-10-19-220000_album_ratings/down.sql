@@ -0,0 +1,14 @@
+-- SQLite can only drop columns since 3.35, so the album stats are rebuilt without ratings.
+CREATE TABLE user_album_stats_old (
+  user_id BINARY(128) NOT NULL REFERENCES user(id),
+  album_id BINARY(128) NOT NULL REFERENCES album(id),
+  last_played TIMESTAMP,
+
+  PRIMARY KEY (user_id, album_id)
+);
+
+INSERT INTO user_album_stats_old (user_id, album_id, last_played)
+  SELECT user_id, album_id, last_played FROM user_album_stats;
+
+DROP TABLE user_album_stats;
+ALTER TABLE user_album_stats_old RENAME TO user_album_stats;
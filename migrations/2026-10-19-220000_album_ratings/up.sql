@@ -0,0 +1,2 @@
+-- The rating of the album from 1 to 5 stars.
+ALTER TABLE user_album_stats ADD COLUMN rating INTEGER;
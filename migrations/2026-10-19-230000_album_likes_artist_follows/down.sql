@@ -0,0 +1,30 @@
+-- SQLite can only drop columns since 3.35, so the album and artist stats are rebuilt without
+-- likes and follows.
+CREATE TABLE user_artist_stats_old (
+  user_id BINARY(128) NOT NULL REFERENCES user(id),
+  artist_id BINARY(128) NOT NULL REFERENCES artist(id),
+  last_played TIMESTAMP,
+
+  PRIMARY KEY (user_id, artist_id)
+);
+
+INSERT INTO user_artist_stats_old (user_id, artist_id, last_played)
+  SELECT user_id, artist_id, last_played FROM user_artist_stats;
+
+DROP TABLE user_artist_stats;
+ALTER TABLE user_artist_stats_old RENAME TO user_artist_stats;
+
+CREATE TABLE user_album_stats_old (
+  user_id BINARY(128) NOT NULL REFERENCES user(id),
+  album_id BINARY(128) NOT NULL REFERENCES album(id),
+  last_played TIMESTAMP,
+  rating INTEGER,
+
+  PRIMARY KEY (user_id, album_id)
+);
+
+INSERT INTO user_album_stats_old (user_id, album_id, last_played, rating)
+  SELECT user_id, album_id, last_played, rating FROM user_album_stats;
+
+DROP TABLE user_album_stats;
+ALTER TABLE user_album_stats_old RENAME TO user_album_stats;
@@ -0,0 +1,5 @@
+-- When the user liked the album, or null if they don't like it.
+ALTER TABLE user_album_stats ADD COLUMN time_liked TIMESTAMP;
+
+-- When the user followed the artist, or null if they don't follow them.
+ALTER TABLE user_artist_stats ADD COLUMN time_followed TIMESTAMP;
@@ -0,0 +1,3 @@
+DROP TABLE user_playlist_stats;
+DROP TABLE playlist_item;
+DROP TABLE playlist;
@@ -0,0 +1,37 @@
+CREATE TABLE playlist (
+  id BINARY(128) PRIMARY KEY NOT NULL,
+
+  -- The user who made the playlist. Only they can change it.
+  user_id BINARY(128) NOT NULL REFERENCES user(id),
+  name TEXT NOT NULL,
+
+  -- Whether other users can see and play the playlist.
+  shared BOOLEAN NOT NULL DEFAULT 0,
+  time_added TIMESTAMP NOT NULL,
+  time_modified TIMESTAMP NOT NULL
+);
+
+CREATE INDEX playlist_user_id ON playlist(user_id);
+
+CREATE TABLE playlist_item (
+  id BINARY(128) PRIMARY KEY NOT NULL,
+  playlist_id BINARY(128) NOT NULL REFERENCES playlist(id),
+  song_id BINARY(128) NOT NULL REFERENCES song(id),
+
+  -- Items are ordered by comparing their positions as strings. New positions are picked between
+  -- the positions of the neighbours, so moving an item doesn't change any other item.
+  position TEXT NOT NULL,
+  time_added TIMESTAMP NOT NULL,
+
+  UNIQUE (playlist_id, position)
+);
+
+CREATE INDEX playlist_item_song_id ON playlist_item(song_id);
+
+CREATE TABLE user_playlist_stats (
+  user_id BINARY(128) NOT NULL REFERENCES user(id),
+  playlist_id BINARY(128) NOT NULL REFERENCES playlist(id),
+  last_played TIMESTAMP,
+
+  PRIMARY KEY (user_id, playlist_id)
+);
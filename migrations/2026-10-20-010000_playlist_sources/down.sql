@@ -0,0 +1,17 @@
+-- SQLite can only drop columns since 3.35, so the playlist table is rebuilt without sources.
+CREATE TABLE playlist_old (
+  id BINARY(128) PRIMARY KEY NOT NULL,
+  user_id BINARY(128) NOT NULL REFERENCES user(id),
+  name TEXT NOT NULL,
+  shared BOOLEAN NOT NULL DEFAULT 0,
+  time_added TIMESTAMP NOT NULL,
+  time_modified TIMESTAMP NOT NULL
+);
+
+INSERT INTO playlist_old (id, user_id, name, shared, time_added, time_modified)
+  SELECT id, user_id, name, shared, time_added, time_modified FROM playlist;
+
+DROP TABLE playlist;
+ALTER TABLE playlist_old RENAME TO playlist;
+
+CREATE INDEX playlist_user_id ON playlist(user_id);
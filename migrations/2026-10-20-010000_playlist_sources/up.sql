@@ -0,0 +1,8 @@
+-- The playlist file in the library the playlist was imported from, or null if it was made in
+-- forte. Imported playlists are refreshed from their file when it's modified.
+ALTER TABLE playlist ADD COLUMN source_path BINARY;
+
+-- When the file was last modified when the playlist was imported from it.
+ALTER TABLE playlist ADD COLUMN source_modified TIMESTAMP;
+
+CREATE UNIQUE INDEX playlist_source_path ON playlist(source_path);
@@ -0,0 +1,10 @@
+-- The genre of the song from its tags, or null if it has none.
+ALTER TABLE song ADD COLUMN genre TEXT;
+
+-- Songs which are already imported have their genres read by the next sync.
+INSERT OR IGNORE INTO song_rescan (song_id) SELECT id FROM song;
+
+-- The rules picking the songs of a smart playlist as JSON, or null for playlists whose songs were
+-- added one by one. The songs of smart playlists are picked whenever they're listed, so they
+-- have no playlist items.
+ALTER TABLE playlist ADD COLUMN rules TEXT;
@@ -0,0 +1,2 @@
+DROP TABLE play_queue_item;
+DROP TABLE play_queue;
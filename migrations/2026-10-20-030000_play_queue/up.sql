@@ -0,0 +1,27 @@
+-- What each user is listening to, saved so they can carry on from another device.
+CREATE TABLE play_queue (
+  user_id BINARY(128) PRIMARY KEY NOT NULL REFERENCES user(id),
+
+  -- The position of the item being played.
+  current_index INTEGER NOT NULL DEFAULT 0,
+
+  -- How far into the current song playback got, in milliseconds.
+  elapsed INTEGER NOT NULL DEFAULT 0,
+  shuffle BOOLEAN NOT NULL DEFAULT 0,
+
+  -- off, all to start over after the last song or one to play the current song again.
+  repeat_mode TEXT NOT NULL DEFAULT 'off',
+  time_modified TIMESTAMP NOT NULL
+);
+
+CREATE TABLE play_queue_item (
+  user_id BINARY(128) NOT NULL REFERENCES user(id),
+
+  -- The place of the song in the queue, counting from 0.
+  position INTEGER NOT NULL,
+  song_id BINARY(128) NOT NULL REFERENCES song(id),
+
+  PRIMARY KEY (user_id, position)
+);
+
+CREATE INDEX play_queue_item_song_id ON play_queue_item(song_id);
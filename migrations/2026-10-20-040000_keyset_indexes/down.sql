@@ -0,0 +1,7 @@
+DROP INDEX playlist_item_playlist_id_position_id;
+DROP INDEX artist_time_added_id;
+DROP INDEX artist_name_id;
+DROP INDEX album_time_added_id;
+DROP INDEX album_name_id;
+DROP INDEX song_time_added_id;
+DROP INDEX song_name_id;
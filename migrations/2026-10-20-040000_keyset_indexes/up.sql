@@ -0,0 +1,10 @@
+-- Connections seek past the sort key and id of the last item of a page, which these indexes
+-- turn into a range scan however deep the page is. Sorting by when the user last played items or
+-- by their ratings reads the user's stats for every item, which no index here helps with.
+CREATE INDEX song_name_id ON song(name, id);
+CREATE INDEX song_time_added_id ON song(time_added, id);
+CREATE INDEX album_name_id ON album(name, id);
+CREATE INDEX album_time_added_id ON album(time_added, id);
+CREATE INDEX artist_name_id ON artist(name, id);
+CREATE INDEX artist_time_added_id ON artist(time_added, id);
+CREATE INDEX playlist_item_playlist_id_position_id ON playlist_item(playlist_id, position, id);
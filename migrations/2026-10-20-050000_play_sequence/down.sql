@@ -0,0 +1,26 @@
+CREATE TABLE listens_export_old (
+  user_id BINARY(128) NOT NULL REFERENCES user(id),
+  format TEXT NOT NULL,
+  last_play_time TIMESTAMP NOT NULL,
+
+  PRIMARY KEY (user_id, format)
+);
+
+INSERT INTO listens_export_old (user_id, format, last_play_time)
+  SELECT user_id, format, last_play_time FROM (
+    SELECT user_id, format, (
+      SELECT max(play.time)
+      FROM play INNER JOIN play_sequence ON play_sequence.play_id = play.id
+      WHERE play.user_id = listens_export.user_id
+        AND play_sequence.sequence <= listens_export.last_sequence
+    ) AS last_play_time
+    FROM listens_export
+  )
+  WHERE last_play_time IS NOT NULL;
+
+DROP TABLE listens_export;
+ALTER TABLE listens_export_old RENAME TO listens_export;
+
+DROP TRIGGER play_sequence_delete;
+DROP TRIGGER play_sequence_insert;
+DROP TABLE play_sequence;
@@ -0,0 +1,39 @@
+-- The order plays were added in. Plays can be added with times older than plays added before
+-- them, like imported listens or plays made offline, so incremental exports of listens keep
+-- track of this instead of play times.
+CREATE TABLE play_sequence (
+  sequence INTEGER PRIMARY KEY AUTOINCREMENT,
+  play_id BINARY(128) UNIQUE NOT NULL REFERENCES play(id)
+);
+
+INSERT INTO play_sequence (play_id) SELECT id FROM play ORDER BY time, id;
+
+CREATE TRIGGER play_sequence_insert AFTER INSERT ON play BEGIN
+  INSERT INTO play_sequence (play_id) VALUES (new.id);
+END;
+
+CREATE TRIGGER play_sequence_delete AFTER DELETE ON play BEGIN
+  DELETE FROM play_sequence WHERE play_id = old.id;
+END;
+
+-- The sequence of the newest play written by the last incremental export of each user's listens
+-- in each format. Existing plays were numbered in the order of their times, so the plays up to
+-- the last exported time keep counting as exported.
+CREATE TABLE listens_export_new (
+  user_id BINARY(128) NOT NULL REFERENCES user(id),
+  format TEXT NOT NULL,
+  last_sequence INTEGER NOT NULL,
+
+  PRIMARY KEY (user_id, format)
+);
+
+INSERT INTO listens_export_new (user_id, format, last_sequence)
+  SELECT user_id, format, (
+    SELECT coalesce(max(play_sequence.sequence), 0)
+    FROM play INNER JOIN play_sequence ON play_sequence.play_id = play.id
+    WHERE play.user_id = listens_export.user_id AND play.time <= listens_export.last_play_time
+  )
+  FROM listens_export;
+
+DROP TABLE listens_export;
+ALTER TABLE listens_export_new RENAME TO listens_export;
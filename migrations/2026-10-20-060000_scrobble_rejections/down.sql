@@ -0,0 +1,24 @@
+-- SQLite can only drop columns since 3.35, so the queue is rebuilt without rejected listens.
+CREATE TABLE scrobble_queue_old (
+  id BINARY(128) PRIMARY KEY NOT NULL,
+  scrobbler_id BINARY(128) NOT NULL REFERENCES scrobbler(id),
+  play_id BINARY(128) REFERENCES play(id),
+  listen_type TEXT NOT NULL,
+  payload TEXT NOT NULL,
+  time_added TIMESTAMP NOT NULL,
+  attempts INTEGER NOT NULL DEFAULT 0,
+  next_attempt TIMESTAMP NOT NULL,
+  last_error TEXT
+);
+
+INSERT INTO scrobble_queue_old (id, scrobbler_id, play_id, listen_type, payload, time_added,
+                                attempts, next_attempt, last_error)
+  SELECT id, scrobbler_id, play_id, listen_type, payload, time_added, attempts, next_attempt,
+         last_error
+  FROM scrobble_queue
+  WHERE NOT rejected;
+
+DROP TABLE scrobble_queue;
+ALTER TABLE scrobble_queue_old RENAME TO scrobble_queue;
+
+CREATE INDEX scrobble_queue_next_attempt ON scrobble_queue (scrobbler_id, next_attempt);
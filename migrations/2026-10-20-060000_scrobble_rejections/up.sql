@@ -0,0 +1,3 @@
+-- Listens the scrobbler rejected as invalid. They aren't retried, but stay here with the error
+-- they were rejected with until the scrobbler is removed.
+ALTER TABLE scrobble_queue ADD COLUMN rejected BOOLEAN NOT NULL DEFAULT 0;
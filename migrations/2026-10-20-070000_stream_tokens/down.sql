@@ -0,0 +1,2 @@
+DROP TABLE stream_token_song;
+DROP TABLE stream_token;
@@ -0,0 +1,15 @@
+-- Tokens put into the stream URLs of exported playlists, since the players they're for can't sign
+-- in. Unlike sessions and API keys they expire soon and only stream the songs they were made for.
+CREATE TABLE stream_token (
+  token_hash TEXT PRIMARY KEY NOT NULL,
+  user_id BINARY(128) NOT NULL REFERENCES user(id),
+  time_added TIMESTAMP NOT NULL,
+  expires TIMESTAMP NOT NULL
+);
+
+CREATE TABLE stream_token_song (
+  token_hash TEXT NOT NULL REFERENCES stream_token(token_hash),
+  song_id BINARY(128) NOT NULL REFERENCES song(id),
+
+  PRIMARY KEY (token_hash, song_id)
+);
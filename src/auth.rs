@@ -0,0 +1,371 @@
+//! Password logins, the sessions they start and API keys for clients which can't log in, like
+//! scripts and headless players. Exported playlists get stream tokens, which only stream their
+//! songs for a while. All kinds of tokens are stored as SHA-256 hashes.
+
+use crate::database::{api_key, session, song, stream_token, stream_token_song, user};
+use crate::models::*;
+use argon2::password_hash::{PasswordHash, PasswordHasher, PasswordVerifier, SaltString};
+use argon2::Argon2;
+use chrono::Duration;
+use diesel::prelude::*;
+use rand::rngs::OsRng;
+use rand::RngCore;
+use sha2::{Digest, Sha256};
+use std::collections::HashSet;
+
+/// How long a session lasts after logging in.
+const SESSION_DAYS: i64 = 30;
+
+/// How long the stream URLs of an exported playlist work.
+const STREAM_TOKEN_HOURS: i64 = 24;
+
+/// The number of random bytes in a token.
+const TOKEN_BYTES: usize = 32;
+
+/// A hash made like `hash_password` makes them, of a password nobody is given. Logins with unknown
+/// names or of users without a password are checked against it, so they take as long as logins
+/// with a wrong password and don't give away which names exist.
+const DUMMY_PASSWORD_HASH: &str =
+    "$argon2id$v=19$m=4096,t=3,p=1$Zm9ydGUtbm8tc3VjaC11c2Vy$+1U12OTxqo3Ep1ara7HZo4wWRxXY3zaCeieN2x0THtc";
+
+#[derive(thiserror::Error, Debug)]
+pub enum Error {
+    #[error(transparent)]
+    Diesel(#[from] diesel::result::Error),
+
+    #[error("failed to hash the password: {0}")]
+    PasswordHash(#[from] argon2::password_hash::Error),
+}
+
+#[derive(Queryable, Insertable)]
+#[table_name = "session"]
+pub struct Session {
+    pub token_hash: String,
+    pub user_id: UUID,
+    pub time_added: NaiveDateTime,
+    pub expires: NaiveDateTime,
+}
+
+#[derive(Queryable, Insertable, Clone)]
+#[table_name = "api_key"]
+pub struct ApiKey {
+    pub id: UUID,
+    pub user_id: UUID,
+
+    /// A name to recognize the key by, like the client using it.
+    pub name: String,
+    pub token_hash: String,
+    pub time_added: NaiveDateTime,
+    pub last_used: Option<NaiveDateTime>,
+}
+
+#[derive(Queryable, Insertable)]
+#[table_name = "stream_token"]
+pub struct StreamToken {
+    pub token_hash: String,
+    pub user_id: UUID,
+    pub time_added: NaiveDateTime,
+    pub expires: NaiveDateTime,
+}
+
+#[derive(Insertable)]
+#[table_name = "stream_token_song"]
+struct StreamTokenSong<'a> {
+    token_hash: &'a str,
+    song_id: UUID,
+}
+
+/// What a stream token is used to read.
+#[derive(Clone, Copy)]
+pub enum Streamed {
+    /// The file of a song.
+    Song(UUID),
+
+    /// The artwork of an album.
+    Artwork(UUID),
+}
+
+/// A random token, hex encoded.
+fn new_token() -> String {
+    let mut bytes = [0u8; TOKEN_BYTES];
+    OsRng.fill_bytes(&mut bytes);
+
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+fn hash_token(token: &str) -> String {
+    format!("{:x}", Sha256::digest(token.as_bytes()))
+}
+
+pub fn hash_password(password: &str) -> Result<String, Error> {
+    let salt = SaltString::generate(&mut OsRng);
+    let hash = Argon2::default().hash_password(password.as_bytes(), &salt)?;
+
+    Ok(hash.to_string())
+}
+
+fn verify_password(hash: &str, password: &str) -> bool {
+    match PasswordHash::new(hash) {
+        Ok(hash) => Argon2::default()
+            .verify_password(password.as_bytes(), &hash)
+            .is_ok(),
+        Err(_) => false,
+    }
+}
+
+pub fn set_password(conn: &SqliteConnection, user_id: UUID, password: &str) -> Result<(), Error> {
+    let hash = hash_password(password)?;
+
+    conn.transaction::<_, diesel::result::Error, _>(|| {
+        diesel::update(user::table.find(user_id))
+            .set(user::password_hash.eq(hash))
+            .execute(conn)?;
+
+        // Changing the password signs out everywhere.
+        diesel::delete(session::table.filter(session::user_id.eq(user_id))).execute(conn)?;
+
+        Ok(())
+    })?;
+
+    Ok(())
+}
+
+/// Whether anyone can sign in, either with a password or an API key.
+pub fn has_credentials(conn: &SqliteConnection) -> QueryResult<bool> {
+    let passwords: i64 = user::table
+        .filter(user::password_hash.is_not_null())
+        .count()
+        .get_result(conn)?;
+    let keys: i64 = api_key::table.count().get_result(conn)?;
+
+    Ok(passwords + keys > 0)
+}
+
+/// Checks the password of the user named `name` and starts a session for them. Returns the
+/// session token and the session, or `None` when the name or password is wrong.
+pub fn login(
+    conn: &SqliteConnection,
+    name: &str,
+    password: &str,
+    now: NaiveDateTime,
+) -> QueryResult<Option<(String, Session)>> {
+    let user = User::by_name(conn, name)?;
+    let hash = user.as_ref().and_then(|user| user.password_hash.as_deref());
+    let has_password = hash.is_some();
+    let matches = verify_password(hash.unwrap_or(DUMMY_PASSWORD_HASH), password);
+
+    let user = match user {
+        Some(user) if has_password && matches => user,
+        _ => return Ok(None),
+    };
+
+    diesel::delete(session::table.filter(session::expires.le(now))).execute(conn)?;
+
+    let token = new_token();
+    let session = Session {
+        token_hash: hash_token(&token),
+        user_id: user.id,
+        time_added: now,
+        expires: now + Duration::days(SESSION_DAYS),
+    };
+
+    diesel::insert_into(session::table)
+        .values(&session)
+        .execute(conn)?;
+
+    Ok(Some((token, session)))
+}
+
+/// Ends the session started with `token`. Returns whether there was one.
+pub fn logout(conn: &SqliteConnection, token: &str) -> QueryResult<bool> {
+    let deleted = diesel::delete(session::table.find(hash_token(token))).execute(conn)?;
+    Ok(deleted > 0)
+}
+
+/// The user a session token or API key belongs to, if it's valid.
+pub fn authenticate(
+    conn: &SqliteConnection,
+    token: &str,
+    now: NaiveDateTime,
+) -> QueryResult<Option<UUID>> {
+    let token_hash = hash_token(token);
+
+    let session_user = session::table
+        .find(&token_hash)
+        .filter(session::expires.gt(now))
+        .select(session::user_id)
+        .first::<UUID>(conn)
+        .optional()?;
+
+    if session_user.is_some() {
+        return Ok(session_user);
+    }
+
+    let key = api_key::table
+        .filter(api_key::token_hash.eq(&token_hash))
+        .first::<ApiKey>(conn)
+        .optional()?;
+
+    match key {
+        Some(key) => {
+            diesel::update(api_key::table.find(key.id))
+                .set(api_key::last_used.eq(now))
+                .execute(conn)?;
+
+            Ok(Some(key.user_id))
+        }
+        None => Ok(None),
+    }
+}
+
+/// Creates a token which streams the songs and the artwork of their albums for a while, to put
+/// into the stream URLs of an exported playlist. Unlike a session token or an API key it can't be
+/// used for anything else, so sharing the playlist doesn't share the user's access.
+pub fn create_stream_token(
+    conn: &SqliteConnection,
+    user_id: UUID,
+    song_ids: &[UUID],
+    now: NaiveDateTime,
+) -> QueryResult<String> {
+    let token = new_token();
+    let token_hash = hash_token(&token);
+    let unique: HashSet<UUID> = song_ids.iter().copied().collect();
+
+    conn.transaction(|| {
+        let expired = stream_token::table
+            .filter(stream_token::expires.le(now))
+            .select(stream_token::token_hash);
+        diesel::delete(
+            stream_token_song::table.filter(stream_token_song::token_hash.eq_any(expired)),
+        )
+        .execute(conn)?;
+        diesel::delete(stream_token::table.filter(stream_token::expires.le(now))).execute(conn)?;
+
+        diesel::insert_into(stream_token::table)
+            .values(&StreamToken {
+                token_hash: token_hash.clone(),
+                user_id,
+                time_added: now,
+                expires: now + Duration::hours(STREAM_TOKEN_HOURS),
+            })
+            .execute(conn)?;
+
+        let songs: Vec<StreamTokenSong> = unique
+            .into_iter()
+            .map(|song_id| StreamTokenSong {
+                token_hash: &token_hash,
+                song_id,
+            })
+            .collect();
+        diesel::insert_into(stream_token_song::table)
+            .values(&songs)
+            .execute(conn)?;
+
+        Ok(token)
+    })
+}
+
+/// The user a stream token belongs to, if it's valid and streams `streamed`.
+pub fn authenticate_stream(
+    conn: &SqliteConnection,
+    token: &str,
+    streamed: Streamed,
+    now: NaiveDateTime,
+) -> QueryResult<Option<UUID>> {
+    let token_hash = hash_token(token);
+    let songs = stream_token_song::table
+        .inner_join(song::table)
+        .filter(stream_token_song::token_hash.eq(&token_hash));
+
+    let streams: i64 = match streamed {
+        Streamed::Song(id) => songs.filter(song::id.eq(id)).count().get_result(conn)?,
+        Streamed::Artwork(id) => songs
+            .filter(song::album_id.eq(id))
+            .count()
+            .get_result(conn)?,
+    };
+    if streams == 0 {
+        return Ok(None);
+    }
+
+    stream_token::table
+        .find(&token_hash)
+        .filter(stream_token::expires.gt(now))
+        .select(stream_token::user_id)
+        .first(conn)
+        .optional()
+}
+
+impl ApiKey {
+    /// Creates a key for the user. The token is returned with it, since only its hash is stored.
+    pub fn create(
+        conn: &SqliteConnection,
+        user_id: UUID,
+        name: &str,
+        now: NaiveDateTime,
+    ) -> QueryResult<(String, ApiKey)> {
+        let token = new_token();
+        let key = ApiKey {
+            id: UUID::new(),
+            user_id,
+            name: name.to_string(),
+            token_hash: hash_token(&token),
+            time_added: now,
+            last_used: None,
+        };
+
+        key.clone().insert_into(api_key::table).execute(conn)?;
+        Ok((token, key))
+    }
+
+    pub fn list(conn: &SqliteConnection) -> QueryResult<Vec<(ApiKey, String)>> {
+        api_key::table
+            .inner_join(user::table)
+            .select((api_key::all_columns, user::name))
+            .order_by(user::name.asc())
+            .then_order_by(api_key::time_added.asc())
+            .load(conn)
+    }
+
+    /// Deletes a key, returning whether it existed.
+    pub fn revoke(conn: &SqliteConnection, id: UUID) -> QueryResult<bool> {
+        let deleted = diesel::delete(api_key::table.find(id)).execute(conn)?;
+        Ok(deleted > 0)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{hash_password, hash_token, new_token, verify_password, DUMMY_PASSWORD_HASH};
+    use argon2::password_hash::PasswordHash;
+
+    #[test]
+    fn passwords_verify() {
+        let hash = hash_password("hunter2").unwrap();
+
+        assert!(hash.starts_with("$argon2"));
+        assert!(verify_password(&hash, "hunter2"));
+        assert!(!verify_password(&hash, "hunter3"));
+        assert!(!verify_password("not a hash", "hunter2"));
+    }
+
+    #[test]
+    fn dummy_hash_is_made_like_others() {
+        let hash = PasswordHash::new(DUMMY_PASSWORD_HASH).unwrap();
+        let real = hash_password("hunter2").unwrap();
+
+        assert_eq!(hash.params, PasswordHash::new(&real).unwrap().params);
+        assert!(!verify_password(DUMMY_PASSWORD_HASH, ""));
+        assert!(!verify_password(DUMMY_PASSWORD_HASH, "hunter2"));
+    }
+
+    #[test]
+    fn tokens_are_random() {
+        let token = new_token();
+
+        assert_eq!(token.len(), 64);
+        assert_ne!(token, new_token());
+        assert_eq!(hash_token(&token), hash_token(&token));
+        assert_ne!(hash_token(&token), token);
+    }
+}
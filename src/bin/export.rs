@@ -0,0 +1,164 @@
+use chrono::{NaiveDate, NaiveDateTime};
+use diesel::sqlite::SqliteConnection;
+use diesel::OptionalExtension;
+use forte_core::context;
+use forte_core::export::listens;
+use forte_core::export::listens::{Incremental, ListensFormat};
+use forte_core::export::playlist;
+use forte_core::export::playlist::{Locations, PlaylistFormat};
+use forte_core::models::{Playlist, UUID};
+use std::fs::File;
+use std::io;
+use std::io::BufWriter;
+use std::path::PathBuf;
+use structopt::StructOpt;
+
+#[derive(StructOpt, Debug)]
+pub enum Command {
+    /// Writes the listening history in a format ListenBrainz or Last.fm importers accept.
+    #[structopt(name = "listens")]
+    Listens {
+        /// Either `listenbrainz` (JSON lines) or `lastfm-csv`.
+        #[structopt(long = "format", default_value = "listenbrainz")]
+        format: ListensFormat,
+
+        /// Only export plays at or after this time in UTC, formatted as `2020-01-31` or
+        /// `2020-01-31T21:15:00`.
+        #[structopt(long = "since", parse(try_from_str = parse_time))]
+        since: Option<NaiveDateTime>,
+
+        /// Only export plays before this time in UTC.
+        #[structopt(long = "until", parse(try_from_str = parse_time))]
+        until: Option<NaiveDateTime>,
+
+        /// Only export plays added after the ones written by the user's last incremental export
+        /// in the same format.
+        #[structopt(long = "incremental")]
+        incremental: bool,
+
+        /// The file to write to. By default, the listens are written to stdout.
+        #[structopt(short = "o", long = "output", parse(from_os_str))]
+        output: Option<PathBuf>,
+
+        /// The user whose plays to export. By default, the first user added.
+        #[structopt(long = "user")]
+        user: Option<String>,
+    },
+
+    /// Writes a playlist as a file other players open. Songs are located by the paths of their
+    /// files.
+    #[structopt(name = "playlist")]
+    Playlist {
+        /// The name or id of the playlist.
+        #[structopt(name = "playlist")]
+        playlist: String,
+
+        /// Either `m3u8`, `xspf` or `jspf`.
+        #[structopt(long = "format", default_value = "m3u8")]
+        format: PlaylistFormat,
+
+        /// The file to write to. By default, the playlist is written to stdout.
+        #[structopt(short = "o", long = "output", parse(from_os_str))]
+        output: Option<PathBuf>,
+
+        /// The user who made the playlist. By default, the first user added.
+        #[structopt(long = "user")]
+        user: Option<String>,
+    },
+}
+
+fn parse_time(value: &str) -> Result<NaiveDateTime, String> {
+    value
+        .parse::<NaiveDateTime>()
+        .or_else(|_| value.parse::<NaiveDate>().map(|date| date.and_hms(0, 0, 0)))
+        .map_err(|_| format!("expected a date or a date and time, got '{}'", value))
+}
+
+pub fn run(pool: context::Pool, command: Command) -> Result<(), crate::Error> {
+    let conn = pool.get()?;
+
+    match command {
+        Command::Listens {
+            format,
+            since,
+            until,
+            incremental,
+            output,
+            user,
+        } => {
+            let user = crate::user::find(&conn, user.as_deref())?;
+            let incremental = if incremental {
+                Incremental::Advance
+            } else {
+                Incremental::Off
+            };
+
+            let count = match output {
+                Some(path) => listens::export(
+                    &conn,
+                    user.id,
+                    format,
+                    since,
+                    until,
+                    incremental,
+                    BufWriter::new(File::create(path)?),
+                )?,
+                None => listens::export(
+                    &conn,
+                    user.id,
+                    format,
+                    since,
+                    until,
+                    incremental,
+                    io::stdout().lock(),
+                )?,
+            };
+
+            eprintln!("Exported {} listens.", count);
+        }
+        Command::Playlist {
+            playlist,
+            format,
+            output,
+            user,
+        } => {
+            let user = crate::user::find(&conn, user.as_deref())?;
+            let playlist = find_playlist(&conn, user.id, &playlist)?;
+
+            match output {
+                Some(path) => playlist::export(
+                    &conn,
+                    &playlist,
+                    format,
+                    &Locations::Paths,
+                    BufWriter::new(File::create(path)?),
+                )?,
+                None => playlist::export(
+                    &conn,
+                    &playlist,
+                    format,
+                    &Locations::Paths,
+                    io::stdout().lock(),
+                )?,
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// The playlist with the id, or the user's playlist with the name.
+fn find_playlist(
+    conn: &SqliteConnection,
+    user_id: UUID,
+    name_or_id: &str,
+) -> Result<Playlist, crate::Error> {
+    if let Ok(id) = UUID::parse_str(name_or_id) {
+        if let Some(playlist) = Playlist::from_id(conn, id).optional()? {
+            return Ok(playlist);
+        }
+    }
+
+    Playlist::by_name(conn, user_id, name_or_id)?
+        .ok_or_else(|| crate::Error::UnknownPlaylist(name_or_id.to_string()))
+}
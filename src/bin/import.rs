@@ -0,0 +1,189 @@
+use crate::userdata::describe;
+use chrono::Utc;
+use forte_core::context;
+use forte_core::import::itunes;
+use forte_core::import::itunes::PrefixReplacement;
+use forte_core::import::listens;
+use forte_core::import::playlist;
+use forte_core::matching::SongMatcher;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+use structopt::StructOpt;
+
+#[derive(StructOpt, Debug)]
+pub enum Command {
+    /// Merges play counts, last played times, loved tracks and ratings from an iTunes or
+    /// Music.app library exported as XML.
+    #[structopt(name = "itunes")]
+    Itunes {
+        /// The exported library, usually `Library.xml`.
+        #[structopt(name = "library", parse(from_os_str))]
+        library: PathBuf,
+
+        /// Replaces the start of the paths of the tracks in the library, for libraries which
+        /// were moved since they were used with iTunes. Formatted as `FROM=TO`.
+        #[structopt(long = "replace-prefix", parse(try_from_str = parse_prefix))]
+        replace_prefix: Option<PrefixReplacement>,
+
+        /// The user whose stats to merge into. By default, the first user added.
+        #[structopt(long = "user")]
+        user: Option<String>,
+    },
+
+    /// Adds listens exported from ListenBrainz (JSON) or Last.fm (CSV) to the listening history.
+    #[structopt(name = "listens")]
+    Listens {
+        /// The exported listens. Files ending in `.csv` are read as Last.fm exports, anything
+        /// else as ListenBrainz exports.
+        #[structopt(name = "file", parse(from_os_str))]
+        file: PathBuf,
+
+        /// Where to write the listens of songs which aren't in the library, one JSON object per
+        /// line. Defaults to the file name with `.unmatched.jsonl` appended.
+        #[structopt(long = "unmatched", parse(from_os_str))]
+        unmatched: Option<PathBuf>,
+
+        /// The user whose listening history to add to. By default, the first user added.
+        #[structopt(long = "user")]
+        user: Option<String>,
+    },
+
+    /// Adds a playlist from an M3U, M3U8, XSPF or JSPF file. Entries are matched to songs by
+    /// their locations, then by their artist, title and album.
+    #[structopt(name = "playlist")]
+    Playlist {
+        /// The playlist file. Its format is picked by its extension.
+        #[structopt(name = "file", parse(from_os_str))]
+        file: PathBuf,
+
+        /// The name of the playlist. By default, the title in the file or the file's name.
+        #[structopt(long = "name")]
+        name: Option<String>,
+
+        /// Lets other users see and play the playlist.
+        #[structopt(long = "shared")]
+        shared: bool,
+
+        /// The user who the playlist belongs to. By default, the first user added.
+        #[structopt(long = "user")]
+        user: Option<String>,
+    },
+}
+
+fn parse_prefix(value: &str) -> Result<PrefixReplacement, String> {
+    let mut parts = value.splitn(2, '=');
+    match (parts.next(), parts.next()) {
+        (Some(from), Some(to)) if !from.is_empty() => Ok(PrefixReplacement {
+            from: PathBuf::from(from),
+            to: PathBuf::from(to),
+        }),
+        _ => Err(format!("expected FROM=TO, got '{}'", value)),
+    }
+}
+
+pub fn run(pool: context::Pool, command: Command) -> Result<(), crate::Error> {
+    let conn = pool.get()?;
+
+    match command {
+        Command::Itunes {
+            library,
+            replace_prefix,
+            user,
+        } => {
+            let user = crate::user::find(&conn, user.as_deref())?;
+            let library = itunes::read_library(&library)?;
+            let report = itunes::merge(&conn, user.id, &library, replace_prefix.as_ref())?;
+
+            println!(
+                "Merged {} tracks, {} tracks aren't in the library.",
+                report.matched,
+                report.unmatched.len()
+            );
+
+            for key in &report.unmatched {
+                println!("Not in the library: {}", describe(key));
+            }
+        }
+        Command::Listens {
+            file,
+            unmatched,
+            user,
+        } => {
+            let user = crate::user::find(&conn, user.as_deref())?;
+            let is_csv = file
+                .extension()
+                .map_or(false, |extension| extension.eq_ignore_ascii_case("csv"));
+
+            let (listens, client) = if is_csv {
+                (listens::read_last_fm(&file)?, "Last.fm")
+            } else {
+                (listens::read_listenbrainz(&file)?, "ListenBrainz")
+            };
+
+            let report = listens::import(&conn, user.id, listens, client)?;
+
+            println!(
+                "Imported {} plays, skipped {} plays already in the history, {} listens aren't in the library.",
+                report.imported,
+                report.duplicates,
+                report.unmatched.len()
+            );
+
+            if !report.unmatched.is_empty() {
+                let unmatched = unmatched.unwrap_or_else(|| unmatched_path(&file));
+                write_unmatched(&unmatched, &report.unmatched)?;
+                println!("Wrote the unmatched listens to {}", unmatched.display());
+            }
+        }
+        Command::Playlist {
+            file,
+            name,
+            shared,
+            user,
+        } => {
+            let user = crate::user::find(&conn, user.as_deref())?;
+            let contents = playlist::read_file(&file)?;
+            let name = name.or_else(|| contents.title.clone()).unwrap_or_else(|| {
+                file.file_stem()
+                    .unwrap_or_default()
+                    .to_string_lossy()
+                    .into_owned()
+            });
+
+            let imported = playlist::import(
+                &conn,
+                user.id,
+                &contents,
+                &name,
+                shared,
+                &SongMatcher::load(&conn)?,
+                Utc::now().naive_utc(),
+            )?;
+
+            println!(
+                "Imported the playlist '{}', {} songs aren't in the library.",
+                imported.playlist.name, imported.missing
+            );
+        }
+    }
+
+    Ok(())
+}
+
+fn unmatched_path(file: &Path) -> PathBuf {
+    let mut name = file.file_name().unwrap_or_default().to_os_string();
+    name.push(".unmatched.jsonl");
+    file.with_file_name(name)
+}
+
+fn write_unmatched(path: &Path, unmatched: &[listens::Listen]) -> Result<(), crate::Error> {
+    let mut writer = BufWriter::new(File::create(path)?);
+    for listen in unmatched {
+        serde_json::to_writer(&mut writer, listen)?;
+        writer.write_all(b"\n")?;
+    }
+
+    writer.flush()?;
+    Ok(())
+}
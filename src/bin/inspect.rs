@@ -0,0 +1,95 @@
+use crate::sync::FORMAT_EXTENSIONS;
+use forte_core::import;
+use forte_core::import::artwork::ArtworkSource;
+use forte_core::import::ImportPlan;
+use serde::Serialize;
+use std::borrow::Cow;
+use std::path::{Path, PathBuf};
+use taglib2_sys::SongProperties;
+use walkdir::WalkDir;
+
+/// What forte reads from a file and what it would import from it, printed as JSON.
+#[derive(Serialize)]
+struct Inspection<'a> {
+    path: Cow<'a, str>,
+
+    /// Everything read from the tags, including the full property map.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    tags: Option<&'a SongProperties>,
+
+    /// The song, artists and album the file would be imported as.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    import: Option<ImportPlan>,
+
+    /// The artwork which would be chosen for the file's album.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    artwork: Option<ArtworkSource>,
+
+    /// Why the file couldn't be read or wouldn't be imported.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    errors: Vec<String>,
+}
+
+/// Prints an inspection of each path as JSON, one object per line unless `pretty` is set. When
+/// `recursive` is set, audio files inside directories are inspected too.
+pub fn inspect(paths: &[PathBuf], recursive: bool, pretty: bool) {
+    for path in paths {
+        if recursive && path.is_dir() {
+            WalkDir::new(path)
+                .follow_links(true)
+                .into_iter()
+                .filter_map(|entry| entry.ok())
+                .filter(|entry| has_audio_extension(entry.path()))
+                .for_each(|entry| print_inspection(entry.path(), pretty));
+        } else {
+            print_inspection(path, pretty);
+        }
+    }
+}
+
+fn has_audio_extension(path: &Path) -> bool {
+    path.extension()
+        .and_then(|e| e.to_str())
+        .map_or(false, |extension| {
+            FORMAT_EXTENSIONS.contains(&extension.to_lowercase().as_ref())
+        })
+}
+
+fn print_inspection(path: &Path, pretty: bool) {
+    let props = SongProperties::read(path);
+    let mut inspection = Inspection {
+        path: path.to_string_lossy(),
+        tags: None,
+        import: None,
+        artwork: None,
+        errors: Vec::new(),
+    };
+
+    match &props {
+        Ok(props) => {
+            inspection.tags = Some(props);
+
+            match ImportPlan::new(path, props) {
+                Ok(plan) => inspection.import = Some(plan),
+                Err(e) => inspection.errors.push(e.to_string()),
+            }
+
+            match import::artwork::get_best_artwork_source(path, props) {
+                Ok(artwork) => inspection.artwork = artwork,
+                Err(e) => inspection.errors.push(e.to_string()),
+            }
+        }
+        Err(e) => inspection.errors.push(e.to_string()),
+    };
+
+    let json = if pretty {
+        serde_json::to_string_pretty(&inspection)
+    } else {
+        serde_json::to_string(&inspection)
+    };
+
+    match json {
+        Ok(json) => println!("{}", json),
+        Err(e) => eprintln!("Error inspecting '{}': {}", path.display(), e),
+    }
+}
@@ -0,0 +1,22 @@
+use forte_core::context;
+use forte_core::models::ImportIssue;
+
+/// Prints the files which failed to import, most recently seen first.
+pub fn list(pool: context::Pool, kind: Option<&str>) -> Result<(), crate::Error> {
+    let conn = pool.get()?;
+    let issues = ImportIssue::list(&conn, kind)?;
+
+    for issue in &issues {
+        println!(
+            "{}\t{}\t{}\t{}",
+            issue.last_seen.format("%Y-%m-%d %H:%M:%S"),
+            issue.kind,
+            issue.path.display(),
+            issue.message
+        );
+    }
+
+    println!("{} import issues", issues.len());
+
+    Ok(())
+}
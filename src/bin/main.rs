@@ -5,13 +5,21 @@ extern crate diesel_migrations;
 #[macro_use]
 extern crate rust_embed;
 
+pub mod export;
+pub mod import;
+pub mod inspect;
+pub mod issues;
+pub mod scrobbler;
 pub mod server;
 pub mod sync;
+pub mod user;
+pub mod userdata;
 
 use crate::server::temp::TemporaryFiles;
 use app_dirs::app_root;
 use app_dirs::AppDataType;
 use app_dirs::AppInfo;
+use forte_core::auth;
 use forte_core::context;
 use lru_disk_cache::LruDiskCache;
 use std::ops::Deref;
@@ -22,7 +30,7 @@ use structopt::StructOpt;
 embed_migrations!("./migrations");
 
 #[derive(thiserror::Error, Debug)]
-enum Error {
+pub enum Error {
     #[error(transparent)]
     R2d2(#[from] r2d2::Error),
 
@@ -40,6 +48,48 @@ enum Error {
 
     #[error(transparent)]
     Sync(#[from] sync::Error),
+
+    #[error(transparent)]
+    Diesel(#[from] diesel::result::Error),
+
+    #[error(transparent)]
+    Json(#[from] serde_json::Error),
+
+    #[error(transparent)]
+    Plist(#[from] plist::Error),
+
+    #[error(transparent)]
+    Listens(#[from] forte_core::import::listens::Error),
+
+    #[error(transparent)]
+    ListensExport(#[from] forte_core::export::listens::Error),
+
+    #[error(transparent)]
+    PlaylistImport(#[from] forte_core::import::errors::Error),
+
+    #[error(transparent)]
+    PlaylistExport(#[from] forte_core::export::playlist::Error),
+
+    #[error(transparent)]
+    Auth(#[from] forte_core::auth::Error),
+
+    #[error("the file was written by a newer version of forte (format version {0})")]
+    UnsupportedVersion(u32),
+
+    #[error("there is no user named '{0}'")]
+    UnknownUser(String),
+
+    #[error("there already is a user named '{0}'")]
+    UserExists(String),
+
+    #[error("there is no playlist named '{0}'")]
+    UnknownPlaylist(String),
+
+    #[error("the password can't be empty")]
+    EmptyPassword,
+
+    #[error("the passwords don't match")]
+    PasswordMismatch,
 }
 
 #[derive(StructOpt, Debug)]
@@ -58,6 +108,11 @@ enum Command {
         /// The address at which to serve the backend.
         #[structopt(long = "host", default_value = "0.0.0.0:8080")]
         host: String,
+
+        /// Writes ratings into the tags of the rated songs' files, so other players see them.
+        /// The files hold the most recent rating of any user.
+        #[structopt(long = "write-ratings")]
+        write_ratings: bool,
     },
 
     #[structopt(name = "sync")]
@@ -65,6 +120,59 @@ enum Command {
         /// The directory to sync.
         #[structopt(name = "sync-dir", parse(from_os_str))]
         directory: PathBuf,
+
+        /// Imports the ratings other players stored in the tags (POPM, FMPS_RATING or RATING)
+        /// of songs the user didn't rate yet. Already imported songs are read again for this.
+        #[structopt(long = "import-ratings")]
+        import_ratings: bool,
+
+        /// The user to import ratings for. By default, the first user added.
+        #[structopt(long = "user")]
+        user: Option<String>,
+    },
+
+    /// Lists the files which failed to import during sync.
+    #[structopt(name = "issues")]
+    Issues {
+        /// Only list issues of this kind, like `missing_tag` or `no_album`.
+        #[structopt(long = "kind")]
+        kind: Option<String>,
+    },
+
+    /// Imports listening data and playlists from other music players.
+    #[structopt(name = "import")]
+    Import(import::Command),
+
+    /// Exports listening data and playlists for other services and players.
+    #[structopt(name = "export")]
+    Export(export::Command),
+
+    /// Manages the services plays are submitted to.
+    #[structopt(name = "scrobbler")]
+    Scrobbler(scrobbler::Command),
+
+    /// Manages the users, who each have their own likes, plays and stats, and how they sign in.
+    #[structopt(name = "user")]
+    User(user::Command),
+
+    /// Exports or imports likes, play counts and listening history.
+    #[structopt(name = "userdata")]
+    Userdata(userdata::Command),
+
+    /// Prints the tags of audio files and what would be imported from them as JSON.
+    #[structopt(name = "inspect")]
+    Inspect {
+        /// Inspect the audio files inside directories.
+        #[structopt(short = "r", long = "recursive")]
+        recursive: bool,
+
+        /// Pretty print the JSON instead of printing one object per line.
+        #[structopt(long = "pretty")]
+        pretty: bool,
+
+        /// The files or directories to inspect.
+        #[structopt(name = "paths", parse(from_os_str), required = true)]
+        paths: Vec<PathBuf>,
     },
 }
 
@@ -93,6 +201,18 @@ fn main() {
 
 fn run() -> Result<(), Error> {
     let opt: Opt = Opt::from_args();
+
+    // Inspecting files doesn't touch the application directory.
+    if let Command::Inspect {
+        recursive,
+        pretty,
+        paths,
+    } = &opt.command
+    {
+        inspect::inspect(paths, *recursive, *pretty);
+        return Ok(());
+    }
+
     let app_dir: PathBuf = opt.common.app_dir.map_or_else(
         || {
             app_root(
@@ -117,19 +237,58 @@ fn run() -> Result<(), Error> {
     embedded_migrations::run(pool.get()?.deref())?;
 
     match opt.command {
-        Command::Serve { host } => {
+        Command::Serve {
+            host,
+            write_ratings,
+        } => {
+            if !auth::has_credentials(&pool.get()?)? {
+                eprintln!(
+                    "Nobody can sign in yet. Set a password with `forte user set-password` or \
+                     create an API key with `forte user add-key`."
+                );
+            }
+
             let transcode_cache = make_transcode_cache(app_dir)?;
             let temporary_files = TemporaryFiles::new("forte")?;
 
-            server::serve(pool, &host, transcode_cache, temporary_files)?;
+            server::serve(pool, &host, transcode_cache, temporary_files, write_ratings)?;
         }
-        Command::Sync { directory } => {
+        Command::Sync {
+            directory,
+            import_ratings,
+            user,
+        } => {
             let mut artwork_directory = app_dir;
             artwork_directory.push("artwork");
             fs::create_dir_all(&artwork_directory)?;
 
-            sync::sync(pool, &directory, &artwork_directory)?;
+            let ratings_user = if import_ratings {
+                Some(user::find(&pool.get()?, user.as_deref())?.id)
+            } else {
+                None
+            };
+
+            sync::sync(pool, &directory, &artwork_directory, ratings_user)?;
+        }
+        Command::Issues { kind } => {
+            issues::list(pool, kind.as_deref())?;
+        }
+        Command::Import(command) => {
+            import::run(pool, command)?;
+        }
+        Command::Export(command) => {
+            export::run(pool, command)?;
+        }
+        Command::Scrobbler(command) => {
+            scrobbler::run(pool, command)?;
+        }
+        Command::User(command) => {
+            user::run(pool, command)?;
+        }
+        Command::Userdata(command) => {
+            userdata::run(pool, command)?;
         }
+        Command::Inspect { .. } => unreachable!("handled before the database is opened"),
     }
 
     Ok(())
@@ -0,0 +1,85 @@
+use chrono::Utc;
+use forte_core::context;
+use forte_core::models::UUID;
+use forte_core::scrobbler::Scrobbler;
+use structopt::StructOpt;
+
+#[derive(StructOpt, Debug)]
+pub enum Command {
+    /// Submits a user's plays to a service with the ListenBrainz API from now on.
+    #[structopt(name = "add")]
+    Add {
+        /// A name to recognize the scrobbler by.
+        #[structopt(name = "name")]
+        name: String,
+
+        /// The root of the API, like `https://api.listenbrainz.org`.
+        #[structopt(name = "api-url")]
+        api_url: String,
+
+        /// The user token to submit with.
+        #[structopt(long = "token")]
+        token: String,
+
+        /// The user whose plays to submit. By default, the first user added.
+        #[structopt(long = "user")]
+        user: Option<String>,
+    },
+
+    /// Lists the scrobblers and how many submissions are waiting for each.
+    #[structopt(name = "list")]
+    List,
+
+    /// Stops submitting to a scrobbler and drops the submissions still waiting for it.
+    #[structopt(name = "remove")]
+    Remove {
+        #[structopt(name = "id", parse(try_from_str = UUID::parse_str))]
+        id: UUID,
+    },
+}
+
+pub fn run(pool: context::Pool, command: Command) -> Result<(), crate::Error> {
+    let conn = pool.get()?;
+
+    match command {
+        Command::Add {
+            name,
+            api_url,
+            token,
+            user,
+        } => {
+            let user = crate::user::find(&conn, user.as_deref())?;
+            let scrobbler = Scrobbler::add(
+                &conn,
+                user.id,
+                &name,
+                &api_url,
+                &token,
+                Utc::now().naive_utc(),
+            )?;
+            println!("Added scrobbler {} ({})", scrobbler.name, scrobbler.id);
+        }
+        Command::List => {
+            for scrobbler in Scrobbler::list(&conn)? {
+                let (queued, rejected, last_error) = scrobbler.queue_status(&conn)?;
+                println!(
+                    "{}\t{}\t{}\t{} queued\t{} rejected",
+                    scrobbler.id, scrobbler.name, scrobbler.api_url, queued, rejected
+                );
+
+                if let Some(error) = last_error {
+                    println!("\tlast error: {}", error);
+                }
+            }
+        }
+        Command::Remove { id } => {
+            if Scrobbler::remove(&conn, id)? {
+                println!("Removed scrobbler {}", id);
+            } else {
+                println!("No scrobbler {}", id);
+            }
+        }
+    }
+
+    Ok(())
+}
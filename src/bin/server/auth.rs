@@ -0,0 +1,163 @@
+use crate::server::graphql::AppState;
+use actix_web::dev::{Service, ServiceRequest, ServiceResponse};
+use actix_web::http::{header, Method};
+use actix_web::web::{Data, Json};
+use actix_web::{error, post, HttpMessage, HttpRequest, HttpResponse};
+use chrono::{NaiveDateTime, Utc};
+use forte_core::auth;
+use forte_core::auth::Streamed;
+use forte_core::models::{Album, Song, UUID};
+use futures::future::{err, Either, Ready};
+use serde::{Deserialize, Serialize};
+use url::form_urlencoded;
+
+/// The user a request was authenticated as, kept in the extensions of the request.
+#[derive(Clone, Copy)]
+pub struct CurrentUser(pub UUID);
+
+/// Whether a path can only be used after signing in.
+fn is_protected(path: &str) -> bool {
+    path == "/graphql" || path.starts_with("/files/")
+}
+
+/// The token sent with a request, either as an `Authorization: Bearer` header or as a `token`
+/// query parameter for clients which can't set headers, like `<audio>` elements.
+pub fn request_token(request: &HttpRequest) -> Option<String> {
+    let bearer = request
+        .headers()
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "))
+        .map(|token| token.trim().to_string());
+
+    bearer.or_else(|| {
+        form_urlencoded::parse(request.query_string().as_bytes())
+            .find(|(key, _)| key == "token")
+            .map(|(_, token)| token.into_owned())
+    })
+}
+
+fn authenticate(request: &ServiceRequest) -> actix_web::Result<UUID> {
+    let token = request_token(request.request())
+        .ok_or_else(|| error::ErrorUnauthorized("sign in with a session token or an API key"))?;
+
+    let state = request
+        .app_data::<Data<AppState>>()
+        .ok_or_else(|| error::ErrorInternalServerError("the app state is missing"))?;
+    let conn = state
+        .connection_pool
+        .get()
+        .map_err(error::ErrorInternalServerError)?;
+
+    let now = Utc::now().naive_utc();
+    let user_id =
+        match auth::authenticate(&conn, &token, now).map_err(error::ErrorInternalServerError)? {
+            Some(user_id) => Some(user_id),
+            None => match streamed(request) {
+                Some(streamed) => auth::authenticate_stream(&conn, &token, streamed, now)
+                    .map_err(error::ErrorInternalServerError)?,
+                None => None,
+            },
+        };
+
+    user_id.ok_or_else(|| error::ErrorUnauthorized("the token is invalid or expired"))
+}
+
+/// The song or artwork a request streams, which stream tokens can be used for.
+fn streamed(request: &ServiceRequest) -> Option<Streamed> {
+    if request.method() != Method::GET {
+        return None;
+    }
+
+    let path = request.path();
+    path_id(path, &Song::get_raw_stream_url("{id}"))
+        .map(Streamed::Song)
+        .or_else(|| path_id(path, &Album::get_artwork_url("{id}")).map(Streamed::Artwork))
+}
+
+/// The id in `path`, if it matches `pattern` with the id as `{id}`.
+fn path_id(path: &str, pattern: &str) -> Option<UUID> {
+    let mut parts = pattern.splitn(2, "{id}");
+    let (prefix, suffix) = (parts.next()?, parts.next()?);
+    let id = path.strip_prefix(prefix)?.strip_suffix(suffix)?;
+
+    UUID::parse_str(id).ok()
+}
+
+/// Middleware which rejects requests to protected paths without a valid token, and remembers
+/// the user of the ones with a valid token as a [`CurrentUser`].
+pub fn require_user<S, B>(
+    request: ServiceRequest,
+    service: &mut S,
+) -> Either<S::Future, Ready<Result<ServiceResponse<B>, actix_web::Error>>>
+where
+    S: Service<Request = ServiceRequest, Response = ServiceResponse<B>, Error = actix_web::Error>,
+{
+    if !is_protected(request.path()) {
+        return Either::Left(service.call(request));
+    }
+
+    match authenticate(&request) {
+        Ok(user_id) => {
+            request.extensions_mut().insert(CurrentUser(user_id));
+            Either::Left(service.call(request))
+        }
+        Err(error) => Either::Right(err(error)),
+    }
+}
+
+#[derive(Deserialize)]
+pub struct LoginParams {
+    name: String,
+    password: String,
+}
+
+#[derive(Serialize)]
+struct Login {
+    token: String,
+    expires: NaiveDateTime,
+}
+
+/// Starts a session for a user, returning the token to send with every request.
+#[post("/auth/login")]
+pub async fn login(
+    state: Data<AppState>,
+    Json(params): Json<LoginParams>,
+) -> actix_web::Result<HttpResponse> {
+    let conn = state
+        .connection_pool
+        .get()
+        .map_err(error::ErrorInternalServerError)?;
+
+    let (token, session) = auth::login(
+        &conn,
+        &params.name,
+        &params.password,
+        Utc::now().naive_utc(),
+    )
+    .map_err(error::ErrorInternalServerError)?
+    .ok_or_else(|| error::ErrorUnauthorized("wrong name or password"))?;
+
+    Ok(HttpResponse::Ok().json(Login {
+        token,
+        expires: session.expires,
+    }))
+}
+
+/// Ends the session whose token is sent with the request.
+#[post("/auth/logout")]
+pub async fn logout(
+    request: HttpRequest,
+    state: Data<AppState>,
+) -> actix_web::Result<HttpResponse> {
+    let token =
+        request_token(&request).ok_or_else(|| error::ErrorUnauthorized("no session token"))?;
+    let conn = state
+        .connection_pool
+        .get()
+        .map_err(error::ErrorInternalServerError)?;
+
+    auth::logout(&conn, &token).map_err(error::ErrorInternalServerError)?;
+
+    Ok(HttpResponse::NoContent().finish())
+}
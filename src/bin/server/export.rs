@@ -0,0 +1,140 @@
+use crate::server::graphql::AppState;
+use crate::server::streaming::convert_diesel_err;
+use actix_web::http::{header, Method};
+use actix_web::web::{Data, Path, Query};
+use actix_web::{error, HttpRequest, HttpResponse};
+use chrono::{NaiveDateTime, Utc};
+use forte_core::auth;
+use forte_core::export::listens::{Incremental, ListensFormat};
+use forte_core::export::playlist::{Locations, PlaylistFormat};
+use forte_core::export::{listens, playlist};
+use forte_core::models::Playlist;
+use serde::Deserialize;
+use uuid::Uuid;
+
+#[derive(Deserialize)]
+pub struct ListensParams {
+    since: Option<NaiveDateTime>,
+    until: Option<NaiveDateTime>,
+
+    /// Only export plays added after the user's last incremental export in the same format.
+    /// POST requests also remember the newest play exported, GET requests never change it.
+    #[serde(default)]
+    incremental: bool,
+}
+
+pub async fn listens_handler(
+    request: HttpRequest,
+    state: Data<AppState>,
+    Path((format,)): Path<(String,)>,
+    Query(params): Query<ListensParams>,
+) -> actix_web::Result<HttpResponse> {
+    let format: ListensFormat = format.parse().map_err(error::ErrorNotFound)?;
+    let context = state.build_context(&request)?;
+    let incremental = match (params.incremental, request.method()) {
+        (false, _) => Incremental::Off,
+        (true, &Method::POST) => Incremental::Advance,
+        (true, _) => Incremental::Peek,
+    };
+
+    let mut body = Vec::new();
+    listens::export(
+        &context.connection(),
+        context.user_id(),
+        format,
+        params.since,
+        params.until,
+        incremental,
+        &mut body,
+    )
+    .map_err(error::ErrorInternalServerError)?;
+
+    Ok(HttpResponse::Ok()
+        .content_type(format.content_type())
+        .header(
+            header::CONTENT_DISPOSITION,
+            format!(
+                "attachment; filename=\"listens.{}\"",
+                format.file_extension()
+            ),
+        )
+        .body(body))
+}
+
+/// How the songs of an exported playlist are located.
+#[derive(Deserialize, Clone, Copy)]
+#[serde(rename_all = "lowercase")]
+pub enum LocationsParam {
+    /// URLs to stream the songs from this server, for players on other devices.
+    Streams,
+
+    /// The absolute paths of the songs' files, for players on the same machine as the library.
+    Paths,
+}
+
+impl Default for LocationsParam {
+    fn default() -> Self {
+        LocationsParam::Streams
+    }
+}
+
+#[derive(Deserialize)]
+pub struct PlaylistParams {
+    #[serde(default)]
+    locations: LocationsParam,
+}
+
+pub async fn playlist_handler(
+    request: HttpRequest,
+    state: Data<AppState>,
+    Path((playlist_id, format)): Path<(Uuid, String)>,
+    Query(params): Query<PlaylistParams>,
+) -> actix_web::Result<HttpResponse> {
+    let format: PlaylistFormat = format.parse().map_err(error::ErrorNotFound)?;
+    let context = state.build_context(&request)?;
+    let conn = context.connection();
+
+    let playlist = Playlist::from_id(conn, playlist_id.into()).map_err(convert_diesel_err)?;
+    if !playlist.is_visible_to(context.user_id()) {
+        return Err(error::ErrorNotFound("no such playlist"));
+    }
+
+    let locations = match params.locations {
+        LocationsParam::Paths => Locations::Paths,
+        LocationsParam::Streams => {
+            let connection_info = request.connection_info();
+
+            // Players can't sign in, so the URLs carry a token which only streams these songs.
+            let song_ids: Vec<_> = playlist
+                .songs(conn)
+                .map_err(error::ErrorInternalServerError)?
+                .iter()
+                .map(|song| song.id)
+                .collect();
+            let token = auth::create_stream_token(
+                conn,
+                context.user_id(),
+                &song_ids,
+                Utc::now().naive_utc(),
+            )
+            .map_err(error::ErrorInternalServerError)?;
+
+            Locations::Streams {
+                base_url: format!("{}://{}", connection_info.scheme(), connection_info.host()),
+                token: Some(token),
+            }
+        }
+    };
+
+    let mut body = Vec::new();
+    playlist::export(conn, &playlist, format, &locations, &mut body)
+        .map_err(error::ErrorInternalServerError)?;
+
+    Ok(HttpResponse::Ok()
+        .content_type(format.content_type())
+        .header(
+            header::CONTENT_DISPOSITION,
+            format!("attachment; filename=\"playlist.{}\"", format.name()),
+        )
+        .body(body))
+}
@@ -1,21 +1,41 @@
+use crate::server::auth::CurrentUser;
 use crate::server::transcoder::Transcoder;
 use actix_web::web::{Data, Payload};
-use actix_web::{error, get, post, HttpRequest, HttpResponse};
+use actix_web::{error, get, post, HttpMessage, HttpRequest, HttpResponse};
 use forte_core::context;
 use forte_core::context::GraphQLContext;
-use forte_core::models::Schema;
+use forte_core::models::{Schema, User};
 use juniper_actix::{graphiql_handler, graphql_handler};
 
 pub struct AppState {
     pub schema: Schema,
     pub connection_pool: context::Pool,
     pub transcoder: Transcoder,
+
+    /// Whether ratings are written into the tags of the rated songs' files.
+    pub write_ratings: bool,
 }
 
 impl AppState {
-    pub fn build_context(&self) -> Result<GraphQLContext, r2d2::Error> {
-        let connection = self.connection_pool.get()?;
-        Ok(GraphQLContext::new(connection))
+    /// Builds the context of a request which passed [`crate::server::auth::require_user`].
+    pub fn build_context(&self, request: &HttpRequest) -> actix_web::Result<GraphQLContext> {
+        let CurrentUser(user_id) = *request
+            .extensions()
+            .get::<CurrentUser>()
+            .ok_or_else(|| error::ErrorUnauthorized("not signed in"))?;
+
+        let connection = self
+            .connection_pool
+            .get()
+            .map_err(error::ErrorInternalServerError)?;
+        let user = User::from_id(&connection, user_id).map_err(error::ErrorInternalServerError)?;
+
+        Ok(GraphQLContext::new(
+            connection,
+            user.id,
+            user.role,
+            self.write_ratings,
+        ))
     }
 }
 
@@ -25,9 +45,7 @@ pub async fn graphql(
     payload: Payload,
     state: Data<AppState>,
 ) -> actix_web::Result<HttpResponse> {
-    let context = state
-        .build_context()
-        .map_err(error::ErrorInternalServerError)?;
+    let context = state.build_context(&request)?;
     graphql_handler(&state.schema, &context, request, payload).await
 }
 
@@ -1,4 +1,7 @@
+mod auth;
+mod export;
 mod graphql;
+mod scrobbler;
 mod streaming;
 pub mod temp;
 mod transcoder;
@@ -14,7 +17,9 @@ use crate::server::transcoding::transcode_handler;
 use actix_web::rt::System;
 use actix_web::{web, App, HttpServer};
 use forte_core::context;
-use forte_core::models::{create_schema, Album, Song};
+use forte_core::export::listens::ListensFormat;
+use forte_core::export::playlist::PlaylistFormat;
+use forte_core::models::{create_schema, Album, AlbumPicture, Song};
 use lru_disk_cache::LruDiskCache;
 
 #[cfg(feature = "embed_web")]
@@ -31,9 +36,11 @@ pub fn serve(
     host: &str,
     transcode_cache: LruDiskCache,
     temp_files: TemporaryFiles,
+    write_ratings: bool,
 ) -> std::io::Result<()> {
     let mut sys = System::new("forte");
     let transcoder = Transcoder::new(transcode_cache, temp_files);
+    let submitter_pool = pool.clone();
 
     let server = HttpServer::new(move || {
         App::new()
@@ -41,9 +48,13 @@ pub fn serve(
                 schema: create_schema(),
                 transcoder: transcoder.clone(),
                 connection_pool: pool.clone(),
+                write_ratings,
             })
+            .wrap_fn(auth::require_user)
             .service(graphql_handler)
             .service(graphiql)
+            .service(auth::login)
+            .service(auth::logout)
             .route(
                 &Song::get_raw_stream_url("{id}"),
                 web::get().to(streaming::song_handler),
@@ -52,6 +63,22 @@ pub fn serve(
                 &Album::get_artwork_url("{id}"),
                 web::get().to(streaming::artwork_handler),
             )
+            .route(
+                &AlbumPicture::get_url("{album_id}", "{picture_id}"),
+                web::get().to(streaming::album_picture_handler),
+            )
+            .route(
+                &ListensFormat::get_export_url("{format}"),
+                web::get().to(export::listens_handler),
+            )
+            .route(
+                &ListensFormat::get_export_url("{format}"),
+                web::post().to(export::listens_handler),
+            )
+            .route(
+                &PlaylistFormat::get_export_url("{id}", "{format}"),
+                web::get().to(export::playlist_handler),
+            )
             .service(transcode_handler)
             .configure(register_web_interface_handler)
     })
@@ -60,5 +87,8 @@ pub fn serve(
 
     println!("Starting Server on {}", host);
 
-    sys.block_on(server.run())
+    sys.block_on(async move {
+        actix_web::rt::spawn(scrobbler::submit_forever(submitter_pool));
+        server.run().await
+    })
 }
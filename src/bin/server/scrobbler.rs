@@ -0,0 +1,99 @@
+use actix_web::client::Client;
+use actix_web::http::{header, StatusCode};
+use actix_web::rt::time::delay_for;
+use chrono::Utc;
+use forte_core::context;
+use forte_core::scrobbler;
+use forte_core::scrobbler::Batch;
+use std::time::Duration;
+
+/// How often the queue is checked for submissions which are due.
+const POLL_INTERVAL: Duration = Duration::from_secs(10);
+
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Submits queued listens and songs playing now to the scrobblers until the server stops.
+pub async fn submit_forever(pool: context::Pool) {
+    let client = Client::builder().timeout(REQUEST_TIMEOUT).finish();
+
+    loop {
+        if let Err(err) = submit_due(&pool, &client).await {
+            eprintln!("Failed to submit listens: {}", err);
+        }
+
+        delay_for(POLL_INTERVAL).await;
+    }
+}
+
+/// Why a submission wasn't accepted.
+enum SendError {
+    /// The scrobbler rejected the submission as invalid, so sending it again won't help.
+    Rejected(String),
+
+    /// The scrobbler couldn't be reached or failed, or the token was refused. Retried later.
+    Failed(String),
+}
+
+async fn submit_due(pool: &context::Pool, client: &Client) -> Result<(), crate::Error> {
+    // Batches are sent oldest first, which the halves of rejected batches keep.
+    let mut batches = scrobbler::due_batches(&*pool.get()?, Utc::now().naive_utc())?;
+    batches.reverse();
+
+    while let Some(batch) = batches.pop() {
+        let result = send(client, &batch).await;
+
+        let conn = pool.get()?;
+        match result {
+            Ok(()) => scrobbler::submitted(&conn, &batch)?,
+            Err(SendError::Rejected(_)) if batch.entries.len() > 1 => {
+                let (older, newer) = batch.split();
+                batches.push(newer);
+                batches.push(older);
+            }
+            Err(SendError::Rejected(message)) => scrobbler::rejected(&conn, &batch, &message)?,
+            Err(SendError::Failed(message)) => {
+                scrobbler::failed(&conn, &batch, &message, Utc::now().naive_utc())?;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+async fn send(client: &Client, batch: &Batch) -> Result<(), SendError> {
+    let mut response = client
+        .post(batch.scrobbler.submit_url())
+        .header(
+            header::AUTHORIZATION,
+            format!("Token {}", batch.scrobbler.token),
+        )
+        .content_type("application/json")
+        .send_body(batch.body())
+        .await
+        .map_err(|err| SendError::Failed(err.to_string()))?;
+
+    let status = response.status();
+    if status.is_success() {
+        return Ok(());
+    }
+
+    let body = response
+        .body()
+        .await
+        .map(|body| String::from_utf8_lossy(&body).into_owned())
+        .unwrap_or_default();
+
+    let message = format!("{}: {}", status, body);
+    let retryable = [
+        StatusCode::UNAUTHORIZED,
+        StatusCode::FORBIDDEN,
+        StatusCode::REQUEST_TIMEOUT,
+        StatusCode::TOO_MANY_REQUESTS,
+    ];
+
+    if status.is_client_error() && !retryable.contains(&status) {
+        Err(SendError::Rejected(message))
+    } else {
+        Err(SendError::Failed(message))
+    }
+}
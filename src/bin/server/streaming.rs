@@ -1,8 +1,9 @@
 use crate::server::graphql::AppState;
 use actix_files::NamedFile;
-use actix_web::error;
 use actix_web::web::{Data, Path};
+use actix_web::{error, HttpRequest};
 use forte_core::models::album::Album;
+use forte_core::models::album_picture::AlbumPicture;
 use forte_core::models::song::Song;
 use uuid::Uuid;
 
@@ -14,12 +15,11 @@ pub fn convert_diesel_err(err: diesel::result::Error) -> actix_web::Error {
 }
 
 pub async fn song_handler(
+    request: HttpRequest,
     state: Data<AppState>,
     Path((song_id,)): Path<(Uuid,)>,
 ) -> actix_web::Result<NamedFile> {
-    let context = state
-        .build_context()
-        .map_err(error::ErrorInternalServerError)?;
+    let context = state.build_context(&request)?;
 
     let song = Song::from_id(&context.connection(), song_id.into()).map_err(convert_diesel_err)?;
 
@@ -27,12 +27,11 @@ pub async fn song_handler(
 }
 
 pub async fn artwork_handler(
+    request: HttpRequest,
     state: Data<AppState>,
     Path((album_id,)): Path<(Uuid,)>,
 ) -> actix_web::Result<NamedFile> {
-    let context = state
-        .build_context()
-        .map_err(error::ErrorInternalServerError)?;
+    let context = state.build_context(&request)?;
 
     let album =
         Album::from_id(&context.connection(), album_id.into()).map_err(convert_diesel_err)?;
@@ -42,3 +41,19 @@ pub async fn artwork_handler(
 
     Ok(NamedFile::open(artwork_path.as_path())?)
 }
+
+pub async fn album_picture_handler(
+    request: HttpRequest,
+    state: Data<AppState>,
+    Path((album_id, picture_id)): Path<(Uuid, Uuid)>,
+) -> actix_web::Result<NamedFile> {
+    let context = state.build_context(&request)?;
+
+    let picture = AlbumPicture::from_id(&context.connection(), picture_id.into())
+        .map_err(convert_diesel_err)?;
+    if picture.album_id != album_id.into() {
+        return Err(error::ErrorNotFound("no such picture"));
+    }
+
+    Ok(NamedFile::open(picture.path.as_path())?)
+}
@@ -1,9 +1,16 @@
+use chrono::Utc;
 use diesel::sqlite::SqliteConnection;
 use forte_core::context;
 use forte_core::import;
+use forte_core::import::playlist;
+use forte_core::import::ratings;
+use forte_core::matching::SongMatcher;
+use forte_core::models::{ImportIssue, Playlist, User, UUID};
 use indicatif::ProgressBar;
 use indicatif::ProgressStyle;
+use std::collections::HashSet;
 use std::path::Path;
+use std::path::PathBuf;
 use taglib2_sys::SongProperties;
 use walkdir::DirEntry;
 use walkdir::WalkDir;
@@ -24,16 +31,39 @@ pub enum Error {
     #[error(transparent)]
     Import(#[from] import::errors::Error),
 
-    #[error("This audio file doesn't have a tag")]
-    MissingSongProperties,
+    #[error(transparent)]
+    Diesel(#[from] diesel::result::Error),
+}
+
+impl Error {
+    /// A short identifier of the kind of error, stored with import issues.
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Error::R2d2(_) => "database",
+            Error::WalkdirError(_) => "io",
+            Error::Taglib(e) => e.kind(),
+            Error::Import(e) => e.kind(),
+            Error::Diesel(_) => "database",
+        }
+    }
 }
 
-const FORMAT_EXTENSIONS: [&str; 3] = ["flac", "mp3", "m4a"];
+/// The extensions of audio files which are imported.
+pub const FORMAT_EXTENSIONS: [&str; 3] = ["flac", "mp3", "m4a"];
 
-pub fn sync(pool: context::Pool, path: &Path, artwork_directory: &Path) -> Result<()> {
+/// Imports the songs under `path`, then the playlist files under it as shared playlists of the
+/// first user. When `ratings_user` is set, the ratings in the tags of every song, including
+/// already imported ones, are imported for that user unless they rated the song. Songs imported
+/// by older versions have the values those versions missed or didn't import precisely read again.
+pub fn sync(
+    pool: context::Pool,
+    path: &Path,
+    artwork_directory: &Path,
+    ratings_user: Option<UUID>,
+) -> Result<()> {
     let conn = pool.get()?;
 
-    let entries: Vec<DirEntry> = WalkDir::new(path)
+    let (playlist_entries, mut entries): (Vec<DirEntry>, Vec<DirEntry>) = WalkDir::new(path)
         .follow_links(true)
         .into_iter()
         .filter_map(|d| d.ok())
@@ -45,8 +75,13 @@ pub fn sync(pool: context::Pool, path: &Path, artwork_directory: &Path) -> Resul
                 .map_or(false, |extension| {
                     FORMAT_EXTENSIONS.contains(&extension.to_lowercase().as_ref())
                 })
+                || playlist::is_playlist_file(entry.path())
         })
-        .collect();
+        .partition(|entry| playlist::is_playlist_file(entry.path()));
+
+    // Playlists are imported after the songs, so that they can refer to songs added by this sync.
+    entries.extend(playlist_entries);
+    let mut matcher: Option<SongMatcher> = None;
 
     let bar = ProgressBar::new(entries.len() as u64);
     bar.set_style(
@@ -55,6 +90,13 @@ pub fn sync(pool: context::Pool, path: &Path, artwork_directory: &Path) -> Resul
             .progress_chars("#>-"),
     );
 
+    // Only files which had issues need their issues cleared.
+    let mut issue_paths: HashSet<PathBuf> = ImportIssue::list(&conn, None)?
+        .into_iter()
+        .map(|issue| issue.path.into())
+        .collect();
+    let mut failed = 0;
+
     bar.wrap_iter(entries.iter()).for_each(|dir_entry| {
         let path = dir_entry.path();
         let path_string = path.display().to_string();
@@ -62,13 +104,52 @@ pub fn sync(pool: context::Pool, path: &Path, artwork_directory: &Path) -> Resul
         let message = format!("Importing {}", path_string);
         bar.set_message(message.as_str());
 
-        if let Err(e) = handle_entry(path, artwork_directory, &conn) {
-            bar.println(format!("Error importing '{}': {}", path_string, e));
+        let handled = if playlist::is_playlist_file(path) {
+            handle_playlist(path, &mut matcher, &bar, &conn)
+        } else {
+            handle_entry(path, artwork_directory, ratings_user, &conn)
+        };
+
+        let result = match handled {
+            Ok(_) if issue_paths.remove(path) => ImportIssue::clear(&conn, path),
+            Ok(_) => Ok(()),
+            Err(e) => {
+                failed += 1;
+                bar.println(format!("Error importing '{}': {}", path_string, e));
+                ImportIssue::record(&conn, path, e.kind(), &e.to_string())
+            }
+        };
+
+        if let Err(e) = result {
+            bar.println(format!(
+                "Error recording import issue for '{}': {}",
+                path_string, e
+            ));
         }
     });
 
     bar.finish();
 
+    // Files which were deleted or moved away since they failed to import aren't issues anymore.
+    for issue_path in issue_paths {
+        if issue_path.starts_with(path) && !issue_path.exists() {
+            ImportIssue::clear(&conn, &issue_path)?;
+        }
+    }
+
+    // Songs which moved were imported again at their new path, so playlists are pointed there.
+    let relinked = Playlist::relink(&conn)?;
+    if relinked > 0 {
+        println!("Relinked {} playlist items to moved songs.", relinked);
+    }
+
+    if failed > 0 {
+        println!(
+            "{} files couldn't be imported. Run `forte issues` to list them.",
+            failed
+        );
+    }
+
     Ok(())
 }
 
@@ -84,16 +165,62 @@ enum EntryResult {
 fn handle_entry(
     path: &Path,
     artwork_directory: &Path,
+    ratings_user: Option<UUID>,
     conn: &SqliteConnection,
 ) -> Result<EntryResult> {
-    if import::is_imported(path, conn)? {
+    let imported = import::is_imported(path, conn)?;
+    let rescan = imported && import::needs_rescan(path, conn)?;
+    if imported && !rescan && ratings_user.is_none() {
         return Ok(EntryResult::Skipped);
     }
 
     let props = SongProperties::read(path)?;
-    let props = props.ok_or(Error::MissingSongProperties)?;
+    let stars = ratings::stars_from_tags(&props);
 
-    import::add_song(path, artwork_directory, props, conn)?;
+    let result = if imported {
+        if rescan {
+            import::rescan_song(path, &props, conn)?;
+        }
+
+        EntryResult::Skipped
+    } else {
+        import::add_song(path, artwork_directory, props, conn)?;
+        EntryResult::Imported
+    };
+
+    if let (Some(user_id), Some(stars)) = (ratings_user, stars) {
+        ratings::import(conn, user_id, path, stars)?;
+    }
+
+    Ok(result)
+}
+
+/// Imports or refreshes the playlist from the playlist file at `path`. The songs are matched
+/// against the library once all songs were imported, when the first playlist is handled.
+fn handle_playlist(
+    path: &Path,
+    matcher: &mut Option<SongMatcher>,
+    bar: &ProgressBar,
+    conn: &SqliteConnection,
+) -> Result<EntryResult> {
+    let matcher = match matcher {
+        Some(matcher) => matcher,
+        None => matcher.get_or_insert(SongMatcher::load(conn)?),
+    };
+    let user = User::default_user(conn)?;
+
+    let imported =
+        match playlist::import_file(conn, path, user.id, matcher, Utc::now().naive_utc())? {
+            Some(imported) => imported,
+            None => return Ok(EntryResult::Skipped),
+        };
+
+    if imported.missing > 0 {
+        bar.println(format!(
+            "{} songs of the playlist '{}' aren't in the library",
+            imported.missing, imported.playlist.name
+        ));
+    }
 
     Ok(EntryResult::Imported)
 }
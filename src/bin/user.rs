@@ -0,0 +1,180 @@
+use chrono::Utc;
+use diesel::sqlite::SqliteConnection;
+use forte_core::auth;
+use forte_core::auth::ApiKey;
+use forte_core::context;
+use forte_core::models::{Role, User, UUID};
+use structopt::StructOpt;
+
+#[derive(StructOpt, Debug)]
+pub enum Command {
+    /// Adds a user with their own likes, plays and stats.
+    #[structopt(name = "add")]
+    Add {
+        #[structopt(name = "name")]
+        name: String,
+
+        /// Asks for a password to sign in with.
+        #[structopt(long = "password")]
+        password: bool,
+
+        /// What the user is allowed to do: admin, listener or guest.
+        #[structopt(long = "role", default_value = "listener")]
+        role: Role,
+    },
+
+    /// Lists the users.
+    #[structopt(name = "list")]
+    List,
+
+    /// Asks for a new password for a user, signing them out everywhere.
+    #[structopt(name = "set-password")]
+    SetPassword {
+        #[structopt(name = "name")]
+        name: String,
+    },
+
+    /// Changes what a user is allowed to do.
+    #[structopt(name = "set-role")]
+    SetRole {
+        #[structopt(name = "name")]
+        name: String,
+
+        /// admin, listener or guest.
+        #[structopt(name = "role")]
+        role: Role,
+    },
+
+    /// Creates an API key for a client which can't sign in with a password. The key is only
+    /// printed once.
+    #[structopt(name = "add-key")]
+    AddKey {
+        /// The user the client acts as.
+        #[structopt(name = "user")]
+        user: String,
+
+        /// A name to recognize the key by, like the client using it.
+        #[structopt(name = "name")]
+        name: String,
+    },
+
+    /// Lists the API keys.
+    #[structopt(name = "list-keys")]
+    ListKeys,
+
+    /// Deletes an API key so it can't be used anymore.
+    #[structopt(name = "revoke-key")]
+    RevokeKey {
+        #[structopt(name = "id", parse(try_from_str = UUID::parse_str))]
+        id: UUID,
+    },
+}
+
+pub fn run(pool: context::Pool, command: Command) -> Result<(), crate::Error> {
+    let conn = pool.get()?;
+
+    match command {
+        Command::Add {
+            name,
+            password,
+            role,
+        } => {
+            if User::by_name(&conn, &name)?.is_some() {
+                return Err(crate::Error::UserExists(name));
+            }
+
+            let password = if password {
+                Some(read_password()?)
+            } else {
+                None
+            };
+
+            let user = User::add(&conn, &name, role, Utc::now().naive_utc())?;
+            if let Some(password) = password {
+                auth::set_password(&conn, user.id, &password)?;
+            }
+
+            println!("Added {} {} ({})", user.role.name(), user.name, user.id);
+        }
+        Command::List => {
+            let default_user = User::default_user(&conn)?;
+            for user in User::list(&conn)? {
+                if user.id == default_user.id {
+                    println!(
+                        "{}\t{}\t{}\t(default)",
+                        user.id,
+                        user.name,
+                        user.role.name()
+                    );
+                } else {
+                    println!("{}\t{}\t{}", user.id, user.name, user.role.name());
+                }
+            }
+        }
+        Command::SetPassword { name } => {
+            let user = find(&conn, Some(&name))?;
+            let password = read_password()?;
+
+            auth::set_password(&conn, user.id, &password)?;
+            println!("Changed the password of {}", user.name);
+        }
+        Command::SetRole { name, role } => {
+            let user = find(&conn, Some(&name))?;
+
+            User::set_role(&conn, user.id, role)?;
+            println!("{} is now a {}", user.name, role.name());
+        }
+        Command::AddKey { user, name } => {
+            let user = find(&conn, Some(&user))?;
+            let (token, key) = ApiKey::create(&conn, user.id, &name, Utc::now().naive_utc())?;
+
+            println!(
+                "Created API key {} ({}) for {}:",
+                key.name, key.id, user.name
+            );
+            println!("{}", token);
+        }
+        Command::ListKeys => {
+            for (key, user_name) in ApiKey::list(&conn)? {
+                let last_used = key
+                    .last_used
+                    .map_or_else(|| "never used".to_string(), |time| time.to_string());
+                println!("{}\t{}\t{}\t{}", key.id, user_name, key.name, last_used);
+            }
+        }
+        Command::RevokeKey { id } => {
+            if ApiKey::revoke(&conn, id)? {
+                println!("Revoked API key {}", id);
+            } else {
+                println!("No API key {}", id);
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// The user named `name`, or the default user when no name is given.
+pub fn find(conn: &SqliteConnection, name: Option<&str>) -> Result<User, crate::Error> {
+    match name {
+        Some(name) => {
+            User::by_name(conn, name)?.ok_or_else(|| crate::Error::UnknownUser(name.to_string()))
+        }
+        None => Ok(User::default_user(conn)?),
+    }
+}
+
+/// Asks for a password twice on the terminal without echoing it.
+fn read_password() -> Result<String, crate::Error> {
+    let password = rpassword::read_password_from_tty(Some("Password: "))?;
+    if password.is_empty() {
+        return Err(crate::Error::EmptyPassword);
+    }
+
+    let repeated = rpassword::read_password_from_tty(Some("Repeat the password: "))?;
+    if password != repeated {
+        return Err(crate::Error::PasswordMismatch);
+    }
+
+    Ok(password)
+}
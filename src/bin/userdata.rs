@@ -0,0 +1,101 @@
+use forte_core::context;
+use forte_core::matching::SongKey;
+use forte_core::userdata;
+use forte_core::userdata::UserData;
+use std::fs::File;
+use std::io;
+use std::io::{BufReader, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use structopt::StructOpt;
+
+#[derive(StructOpt, Debug)]
+pub enum Command {
+    /// Writes likes, ratings, play counts and listening history to a JSON file.
+    #[structopt(name = "export")]
+    Export {
+        /// The file to write to. By default, the data is written to stdout.
+        #[structopt(short = "o", long = "output", parse(from_os_str))]
+        output: Option<PathBuf>,
+
+        /// The user whose data to export. By default, the first user added.
+        #[structopt(long = "user")]
+        user: Option<String>,
+    },
+
+    /// Restores data written by `forte userdata export` onto the songs in the library.
+    #[structopt(name = "import")]
+    Import {
+        /// The file to read from.
+        #[structopt(name = "file", parse(from_os_str))]
+        file: PathBuf,
+
+        /// The user whose data to restore. By default, the first user added.
+        #[structopt(long = "user")]
+        user: Option<String>,
+    },
+}
+
+pub fn run(pool: context::Pool, command: Command) -> Result<(), crate::Error> {
+    let conn = pool.get()?;
+
+    match command {
+        Command::Export { output, user } => {
+            let user = crate::user::find(&conn, user.as_deref())?;
+            let data = userdata::export(&conn, user.id)?;
+            match output {
+                Some(path) => write(BufWriter::new(File::create(path)?), &data)?,
+                None => write(io::stdout().lock(), &data)?,
+            }
+        }
+        Command::Import { file, user } => {
+            let user = crate::user::find(&conn, user.as_deref())?;
+            let data = read(&file)?;
+            let report = userdata::import(&conn, user.id, &data)?;
+
+            println!(
+                "Matched {} of {} songs, {} of {} albums and {} of {} artists.",
+                report.matched_songs,
+                data.songs.len(),
+                report.matched_albums,
+                data.albums.len(),
+                report.matched_artists,
+                data.artists.len()
+            );
+            println!(
+                "Imported {} plays, skipped {} already imported.",
+                report.imported_plays, report.duplicate_plays
+            );
+
+            for key in &report.unmatched_songs {
+                println!("Not in the library: {}", describe(key));
+            }
+        }
+    }
+
+    Ok(())
+}
+
+fn write(mut writer: impl Write, data: &UserData) -> Result<(), crate::Error> {
+    serde_json::to_writer_pretty(&mut writer, data)?;
+    writeln!(writer)?;
+
+    Ok(())
+}
+
+fn read(path: &Path) -> Result<UserData, crate::Error> {
+    let data: UserData = serde_json::from_reader(BufReader::new(File::open(path)?))?;
+    if data.version > userdata::VERSION {
+        return Err(crate::Error::UnsupportedVersion(data.version));
+    }
+
+    Ok(data)
+}
+
+/// Describes a song for people, by its path if it has one.
+pub fn describe(key: &SongKey) -> String {
+    match &key.path {
+        Some(path) => path.display().to_string(),
+        None if key.artists.is_empty() => key.title.clone(),
+        None => format!("{} - {}", key.artists.join(", "), key.title),
+    }
+}
@@ -1,4 +1,6 @@
+use crate::models::{Role, UUID};
 use diesel::sqlite::SqliteConnection;
+use juniper::{graphql_value, FieldError, FieldResult};
 use send_wrapper::SendWrapper;
 
 pub type ConnectionManager = r2d2_diesel::ConnectionManager<SqliteConnection>;
@@ -14,18 +16,68 @@ pub struct GraphQLContext {
     // Wrap the connection in SendWrapper since actix ensures futures don't move
     // across threads. SendWrapper makes the context Send + Sync.
     connection: SendWrapper<PooledConnection>,
+
+    /// The user whose likes, plays and stats are read and written.
+    user_id: UUID,
+
+    /// What the user is allowed to do.
+    role: Role,
+
+    /// Whether ratings are written into the tags of the rated songs' files.
+    write_ratings: bool,
 }
 
 impl GraphQLContext {
-    pub fn new(connection: PooledConnection) -> GraphQLContext {
+    pub fn new(
+        connection: PooledConnection,
+        user_id: UUID,
+        role: Role,
+        write_ratings: bool,
+    ) -> GraphQLContext {
         GraphQLContext {
             connection: SendWrapper::new(connection),
+            user_id,
+            role,
+            write_ratings,
         }
     }
 
     pub fn connection(&self) -> &SqliteConnection {
         &self.connection
     }
+
+    pub fn user_id(&self) -> UUID {
+        self.user_id
+    }
+
+    pub fn role(&self) -> Role {
+        self.role
+    }
+
+    pub fn write_ratings(&self) -> bool {
+        self.write_ratings
+    }
+
+    /// Fails with a `FORBIDDEN` error code unless the user has at least the `role`.
+    pub fn require_role(&self, role: Role) -> FieldResult<()> {
+        if self.role >= role {
+            return Ok(());
+        }
+
+        Err(FieldError::new(
+            format!("this needs the {} role", role.name()),
+            graphql_value!({
+                "code": "FORBIDDEN",
+                "requiredRole": (role.name()),
+            }),
+        ))
+    }
 }
 
 impl juniper::Context for GraphQLContext {}
+
+/// An error with the `FORBIDDEN` code, for things the user isn't allowed to do regardless of their
+/// role, like changing someone else's playlist.
+pub fn forbidden(message: &str) -> FieldError {
+    FieldError::new(message, graphql_value!({ "code": "FORBIDDEN" }))
+}
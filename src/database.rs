@@ -6,7 +6,32 @@ table! {
         artist_id -> Binary,
         release_year -> Nullable<Integer>,
         time_added -> Timestamp,
-        last_played -> Nullable<Timestamp>,
+        mbid -> Nullable<Text>,
+        directory -> Nullable<Binary>,
+    }
+}
+
+table! {
+    album_picture (id) {
+        id -> Binary,
+        album_id -> Binary,
+        picture_type -> Integer,
+        description -> Nullable<Text>,
+        path -> Binary,
+        byte_size -> Integer,
+        width -> Nullable<Integer>,
+        height -> Nullable<Integer>,
+    }
+}
+
+table! {
+    api_key (id) {
+        id -> Binary,
+        user_id -> Binary,
+        name -> Text,
+        token_hash -> Text,
+        time_added -> Timestamp,
+        last_used -> Nullable<Timestamp>,
     }
 }
 
@@ -15,7 +40,121 @@ table! {
         id -> Binary,
         name -> Text,
         time_added -> Timestamp,
-        last_played -> Nullable<Timestamp>,
+    }
+}
+
+table! {
+    import_issue (path) {
+        path -> Binary,
+        kind -> Text,
+        message -> Text,
+        last_seen -> Timestamp,
+    }
+}
+
+table! {
+    listens_export (user_id, format) {
+        user_id -> Binary,
+        format -> Text,
+        last_sequence -> Integer,
+    }
+}
+
+table! {
+    play (id) {
+        id -> Binary,
+        user_id -> Binary,
+        song_id -> Binary,
+        time -> Timestamp,
+        album_id -> Nullable<Binary>,
+        artist_id -> Nullable<Binary>,
+        client -> Nullable<Text>,
+        duration -> Nullable<Integer>,
+    }
+}
+
+table! {
+    play_queue (user_id) {
+        user_id -> Binary,
+        current_index -> Integer,
+        elapsed -> Integer,
+        shuffle -> Bool,
+        repeat_mode -> Text,
+        time_modified -> Timestamp,
+    }
+}
+
+table! {
+    play_queue_item (user_id, position) {
+        user_id -> Binary,
+        position -> Integer,
+        song_id -> Binary,
+    }
+}
+
+table! {
+    play_sequence (sequence) {
+        sequence -> Integer,
+        play_id -> Binary,
+    }
+}
+
+table! {
+    playlist (id) {
+        id -> Binary,
+        user_id -> Binary,
+        name -> Text,
+        shared -> Bool,
+        time_added -> Timestamp,
+        time_modified -> Timestamp,
+        source_path -> Nullable<Binary>,
+        source_modified -> Nullable<Timestamp>,
+        rules -> Nullable<Text>,
+    }
+}
+
+table! {
+    playlist_item (id) {
+        id -> Binary,
+        playlist_id -> Binary,
+        song_id -> Binary,
+        position -> Text,
+        time_added -> Timestamp,
+    }
+}
+
+table! {
+    scrobble_queue (id) {
+        id -> Binary,
+        scrobbler_id -> Binary,
+        play_id -> Nullable<Binary>,
+        listen_type -> Text,
+        payload -> Text,
+        time_added -> Timestamp,
+        attempts -> Integer,
+        next_attempt -> Timestamp,
+        last_error -> Nullable<Text>,
+        rejected -> Bool,
+    }
+}
+
+table! {
+    scrobbler (id) {
+        id -> Binary,
+        user_id -> Binary,
+        name -> Text,
+        api_url -> Text,
+        token -> Text,
+        time_added -> Timestamp,
+    }
+}
+
+table! {
+    session (token_hash) {
+        token_hash -> Text,
+        user_id -> Binary,
+        time_added -> Timestamp,
+        expires -> Timestamp,
     }
 }
 
@@ -28,10 +167,9 @@ table! {
         disk_number -> Integer,
         duration -> Integer,
         time_added -> Timestamp,
-        play_count -> Integer,
-        last_played -> Nullable<Timestamp>,
-        liked -> Bool,
         path -> Binary,
+        mbid -> Nullable<Text>,
+        genre -> Nullable<Text>,
     }
 }
 
@@ -42,9 +180,134 @@ table! {
     }
 }
 
+table! {
+    song_rescan (song_id) {
+        song_id -> Binary,
+    }
+}
+
+table! {
+    stream_token (token_hash) {
+        token_hash -> Text,
+        user_id -> Binary,
+        time_added -> Timestamp,
+        expires -> Timestamp,
+    }
+}
+
+table! {
+    stream_token_song (token_hash, song_id) {
+        token_hash -> Text,
+        song_id -> Binary,
+    }
+}
+
+table! {
+    user (id) {
+        id -> Binary,
+        name -> Text,
+        time_added -> Timestamp,
+        password_hash -> Nullable<Text>,
+        role -> Text,
+    }
+}
+
+table! {
+    user_album_stats (user_id, album_id) {
+        user_id -> Binary,
+        album_id -> Binary,
+        last_played -> Nullable<Timestamp>,
+        rating -> Nullable<Integer>,
+        time_liked -> Nullable<Timestamp>,
+    }
+}
+
+table! {
+    user_artist_stats (user_id, artist_id) {
+        user_id -> Binary,
+        artist_id -> Binary,
+        last_played -> Nullable<Timestamp>,
+        time_followed -> Nullable<Timestamp>,
+    }
+}
+
+table! {
+    user_playlist_stats (user_id, playlist_id) {
+        user_id -> Binary,
+        playlist_id -> Binary,
+        last_played -> Nullable<Timestamp>,
+    }
+}
+
+table! {
+    user_song_stats (user_id, song_id) {
+        user_id -> Binary,
+        song_id -> Binary,
+        play_count -> Integer,
+        last_played -> Nullable<Timestamp>,
+        liked -> Bool,
+        rating -> Nullable<Integer>,
+    }
+}
+
 joinable!(album -> artist (artist_id));
+joinable!(album_picture -> album (album_id));
+joinable!(api_key -> user (user_id));
+joinable!(listens_export -> user (user_id));
+joinable!(play -> album (album_id));
+joinable!(play -> artist (artist_id));
+joinable!(play -> song (song_id));
+joinable!(play -> user (user_id));
+joinable!(play_queue -> user (user_id));
+joinable!(play_queue_item -> song (song_id));
+joinable!(play_queue_item -> user (user_id));
+joinable!(play_sequence -> play (play_id));
+joinable!(playlist -> user (user_id));
+joinable!(playlist_item -> playlist (playlist_id));
+joinable!(playlist_item -> song (song_id));
+joinable!(scrobble_queue -> scrobbler (scrobbler_id));
+joinable!(scrobbler -> user (user_id));
+joinable!(session -> user (user_id));
 joinable!(song -> album (album_id));
 joinable!(song_artist -> artist (artist_id));
 joinable!(song_artist -> song (song_id));
+joinable!(song_rescan -> song (song_id));
+joinable!(stream_token -> user (user_id));
+joinable!(stream_token_song -> song (song_id));
+joinable!(stream_token_song -> stream_token (token_hash));
+joinable!(user_album_stats -> album (album_id));
+joinable!(user_album_stats -> user (user_id));
+joinable!(user_artist_stats -> artist (artist_id));
+joinable!(user_artist_stats -> user (user_id));
+joinable!(user_playlist_stats -> playlist (playlist_id));
+joinable!(user_playlist_stats -> user (user_id));
+joinable!(user_song_stats -> song (song_id));
+joinable!(user_song_stats -> user (user_id));
 
-allow_tables_to_appear_in_same_query!(album, artist, song, song_artist,);
+allow_tables_to_appear_in_same_query!(
+    album,
+    album_picture,
+    api_key,
+    artist,
+    import_issue,
+    listens_export,
+    play,
+    play_queue,
+    play_queue_item,
+    play_sequence,
+    playlist,
+    playlist_item,
+    scrobble_queue,
+    scrobbler,
+    session,
+    song,
+    song_artist,
+    song_rescan,
+    stream_token,
+    stream_token_song,
+    user,
+    user_album_stats,
+    user_artist_stats,
+    user_playlist_stats,
+    user_song_stats,
+);
@@ -0,0 +1,481 @@
+//! Writes the play history of a user in formats which ListenBrainz and Last.fm importers accept.
+
+use crate::database::{album, artist, listens_export, play, play_sequence, song, song_artist};
+use crate::models::*;
+use diesel::prelude::*;
+use juniper::GraphQLEnum;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::io;
+use std::io::Write;
+use std::str::FromStr;
+use url::form_urlencoded;
+
+/// How times are written in export URLs, which is how serde reads them back.
+const URL_TIME_FORMAT: &str = "%Y-%m-%dT%H:%M:%S";
+
+#[derive(thiserror::Error, Debug)]
+pub enum Error {
+    #[error(transparent)]
+    Io(#[from] io::Error),
+
+    #[error(transparent)]
+    Json(#[from] serde_json::Error),
+
+    #[error(transparent)]
+    Csv(#[from] csv::Error),
+
+    #[error(transparent)]
+    Diesel(#[from] diesel::result::Error),
+}
+
+#[derive(GraphQLEnum, Clone, Copy, Debug, PartialEq)]
+pub enum ListensFormat {
+    /// One JSON listen per line, like the listens exported from ListenBrainz.
+    #[graphql(name = "LISTENBRAINZ")]
+    ListenBrainz,
+
+    /// CSV with a header row and one scrobble per row, like the exports of lastfm-to-csv.
+    #[graphql(name = "LASTFM_CSV")]
+    LastFmCsv,
+}
+
+impl ListensFormat {
+    /// The name of the format on the command line and in export URLs.
+    pub fn name(self) -> &'static str {
+        match self {
+            ListensFormat::ListenBrainz => "listenbrainz",
+            ListensFormat::LastFmCsv => "lastfm-csv",
+        }
+    }
+
+    pub fn file_extension(self) -> &'static str {
+        match self {
+            ListensFormat::ListenBrainz => "jsonl",
+            ListensFormat::LastFmCsv => "csv",
+        }
+    }
+
+    pub fn content_type(self) -> &'static str {
+        match self {
+            ListensFormat::ListenBrainz => "application/x-ndjson",
+            ListensFormat::LastFmCsv => "text/csv",
+        }
+    }
+
+    pub fn get_export_url(format: &str) -> String {
+        format!("/files/listens/{}", format)
+    }
+
+    /// The URL to download the plays in `[since, until)` in this format from.
+    pub fn export_url(
+        self,
+        since: Option<NaiveDateTime>,
+        until: Option<NaiveDateTime>,
+        incremental: bool,
+    ) -> String {
+        let mut params = form_urlencoded::Serializer::new(String::new());
+        if let Some(since) = since {
+            params.append_pair("since", &since.format(URL_TIME_FORMAT).to_string());
+        }
+
+        if let Some(until) = until {
+            params.append_pair("until", &until.format(URL_TIME_FORMAT).to_string());
+        }
+
+        if incremental {
+            params.append_pair("incremental", "true");
+        }
+
+        let url = ListensFormat::get_export_url(self.name());
+        match params.finish() {
+            params if params.is_empty() => url,
+            params => format!("{}?{}", url, params),
+        }
+    }
+}
+
+impl FromStr for ListensFormat {
+    type Err = String;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        match name {
+            "listenbrainz" => Ok(ListensFormat::ListenBrainz),
+            "lastfm-csv" => Ok(ListensFormat::LastFmCsv),
+            _ => Err(format!(
+                "unknown format '{}', expected listenbrainz or lastfm-csv",
+                name
+            )),
+        }
+    }
+}
+
+/// A play with the metadata of the song which was played.
+pub struct ExportedListen {
+    pub time: NaiveDateTime,
+
+    /// The artists of the song separated by commas, starting with the album's artist.
+    pub artist: String,
+    pub title: String,
+    pub album: String,
+
+    /// The MusicBrainz recording ID of the song.
+    pub recording_mbid: Option<String>,
+
+    /// The MusicBrainz release ID of the album.
+    pub release_mbid: Option<String>,
+
+    /// The length of the song in milliseconds.
+    pub duration: i32,
+    pub track_number: i32,
+    pub disk_number: i32,
+
+    /// The client the song was played with.
+    pub client: Option<String>,
+}
+
+/// The metadata of a song, selected by [`SONG_COLUMNS`].
+type SongRow = (
+    UUID,
+    String,
+    Option<String>,
+    i32,
+    i32,
+    i32,
+    String,
+    Option<String>,
+    UUID,
+);
+
+type SongColumns = (
+    song::id,
+    song::name,
+    song::mbid,
+    song::duration,
+    song::track_number,
+    song::disk_number,
+    album::name,
+    album::mbid,
+    album::artist_id,
+);
+
+const SONG_COLUMNS: SongColumns = (
+    song::id,
+    song::name,
+    song::mbid,
+    song::duration,
+    song::track_number,
+    song::disk_number,
+    album::name,
+    album::mbid,
+    album::artist_id,
+);
+
+/// Loads the user's plays in `[since, until)` which were added after the play with the sequence
+/// `after`, oldest first, with their sequences. Missing bounds include every play on that side.
+pub fn load(
+    conn: &SqliteConnection,
+    user_id: UUID,
+    since: Option<NaiveDateTime>,
+    until: Option<NaiveDateTime>,
+    after: Option<i32>,
+) -> QueryResult<Vec<(i32, ExportedListen)>> {
+    let mut query = play::table
+        .inner_join(song::table.inner_join(album::table))
+        .inner_join(play_sequence::table)
+        .filter(play::user_id.eq(user_id))
+        .select((
+            play_sequence::sequence,
+            play::time,
+            play::client,
+            SONG_COLUMNS,
+        ))
+        .order_by(play::time.asc())
+        .then_order_by(play::id.asc())
+        .into_boxed();
+
+    if let Some(since) = since {
+        query = query.filter(play::time.ge(since));
+    }
+
+    if let Some(until) = until {
+        query = query.filter(play::time.lt(until));
+    }
+
+    if let Some(after) = after {
+        query = query.filter(play_sequence::sequence.gt(after));
+    }
+
+    let rows: Vec<(i32, NaiveDateTime, Option<String>, SongRow)> = query.load(conn)?;
+    let credits = load_credits(conn, None)?;
+
+    Ok(rows
+        .into_iter()
+        .map(|(sequence, time, client, song)| (sequence, to_listen(time, client, song, &credits)))
+        .collect())
+}
+
+/// Loads a single play.
+pub fn load_play(conn: &SqliteConnection, play_id: UUID) -> QueryResult<ExportedListen> {
+    let (time, client, song): (NaiveDateTime, Option<String>, SongRow) = play::table
+        .inner_join(song::table.inner_join(album::table))
+        .filter(play::id.eq(play_id))
+        .select((play::time, play::client, SONG_COLUMNS))
+        .first(conn)?;
+
+    let credits = load_credits(conn, Some(song.0))?;
+    Ok(to_listen(time, client, song, &credits))
+}
+
+/// Loads a song as if it was listened to at `time` without recording a play, like the song which
+/// is playing now.
+pub fn load_song(
+    conn: &SqliteConnection,
+    song_id: UUID,
+    time: NaiveDateTime,
+    client: Option<String>,
+) -> QueryResult<ExportedListen> {
+    let song: SongRow = song::table
+        .inner_join(album::table)
+        .filter(song::id.eq(song_id))
+        .select(SONG_COLUMNS)
+        .first(conn)?;
+
+    let credits = load_credits(conn, Some(song_id))?;
+    Ok(to_listen(time, client, song, &credits))
+}
+
+/// Loads the ids and names of the artists of every song, or of one song.
+fn load_credits(
+    conn: &SqliteConnection,
+    song_id: Option<UUID>,
+) -> QueryResult<HashMap<UUID, Vec<(UUID, String)>>> {
+    let mut query = song_artist::table
+        .inner_join(artist::table)
+        .select((song_artist::song_id, artist::id, artist::name))
+        .order_by(artist::name)
+        .into_boxed();
+
+    if let Some(song_id) = song_id {
+        query = query.filter(song_artist::song_id.eq(song_id));
+    }
+
+    let mut credits: HashMap<UUID, Vec<(UUID, String)>> = HashMap::new();
+    for (song_id, artist_id, name) in query.load::<(UUID, UUID, String)>(conn)? {
+        credits.entry(song_id).or_default().push((artist_id, name));
+    }
+
+    Ok(credits)
+}
+
+fn to_listen(
+    time: NaiveDateTime,
+    client: Option<String>,
+    (
+        song_id,
+        title,
+        recording_mbid,
+        duration,
+        track_number,
+        disk_number,
+        album,
+        release_mbid,
+        album_artist_id,
+    ): SongRow,
+    credits: &HashMap<UUID, Vec<(UUID, String)>>,
+) -> ExportedListen {
+    ExportedListen {
+        time,
+        artist: credits
+            .get(&song_id)
+            .map(|artists| credit(artists, album_artist_id))
+            .unwrap_or_default(),
+        title,
+        album,
+        recording_mbid,
+        release_mbid,
+        duration,
+        track_number,
+        disk_number,
+        client,
+    }
+}
+
+/// Joins the names of the artists of a song, putting the album's artist first.
+fn credit(artists: &[(UUID, String)], album_artist_id: UUID) -> String {
+    let (album_artist, others): (Vec<_>, Vec<_>) = artists
+        .iter()
+        .partition(|(artist_id, _)| *artist_id == album_artist_id);
+
+    album_artist
+        .into_iter()
+        .chain(others)
+        .map(|(_, name)| name.as_str())
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+#[derive(Serialize)]
+struct ListenBrainzListen<'a> {
+    /// Left out for songs which are playing now.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    listened_at: Option<i64>,
+    track_metadata: TrackMetadata<'a>,
+}
+
+#[derive(Serialize)]
+struct TrackMetadata<'a> {
+    artist_name: &'a str,
+    track_name: &'a str,
+    release_name: &'a str,
+    additional_info: AdditionalInfo<'a>,
+}
+
+#[derive(Serialize)]
+struct AdditionalInfo<'a> {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    recording_mbid: Option<&'a str>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    release_mbid: Option<&'a str>,
+    duration_ms: i32,
+    tracknumber: i32,
+    discnumber: i32,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    media_player: Option<&'a str>,
+    submission_client: &'static str,
+}
+
+impl ExportedListen {
+    fn to_listenbrainz(&self, playing_now: bool) -> ListenBrainzListen<'_> {
+        ListenBrainzListen {
+            listened_at: if playing_now {
+                None
+            } else {
+                Some(self.time.timestamp())
+            },
+            track_metadata: TrackMetadata {
+                artist_name: &self.artist,
+                track_name: &self.title,
+                release_name: &self.album,
+                additional_info: AdditionalInfo {
+                    recording_mbid: self.recording_mbid.as_deref(),
+                    release_mbid: self.release_mbid.as_deref(),
+                    duration_ms: self.duration,
+                    tracknumber: self.track_number,
+                    discnumber: self.disk_number,
+                    media_player: self.client.as_deref(),
+                    submission_client: "forte",
+                },
+            },
+        }
+    }
+
+    /// The listen as JSON in the payload format of the ListenBrainz API. Songs which are playing
+    /// now are submitted without the time they were listened at.
+    pub fn to_listenbrainz_json(&self, playing_now: bool) -> serde_json::Result<String> {
+        serde_json::to_string(&self.to_listenbrainz(playing_now))
+    }
+}
+
+#[derive(Serialize)]
+struct LastFmScrobble<'a> {
+    uts: i64,
+    utc_time: String,
+    artist: &'a str,
+    artist_mbid: &'a str,
+    album: &'a str,
+    album_mbid: &'a str,
+    track: &'a str,
+    track_mbid: &'a str,
+}
+
+/// Writes listens in a format.
+pub fn write(
+    format: ListensFormat,
+    listens: &[ExportedListen],
+    mut writer: impl Write,
+) -> Result<(), Error> {
+    match format {
+        ListensFormat::ListenBrainz => {
+            for listen in listens {
+                serde_json::to_writer(&mut writer, &listen.to_listenbrainz(false))?;
+                writer.write_all(b"\n")?;
+            }
+        }
+        ListensFormat::LastFmCsv => {
+            let mut writer = csv::Writer::from_writer(&mut writer);
+            for listen in listens {
+                writer.serialize(LastFmScrobble {
+                    uts: listen.time.timestamp(),
+                    utc_time: listen.time.format("%d %b %Y, %H:%M").to_string(),
+                    artist: &listen.artist,
+                    artist_mbid: "",
+                    album: &listen.album,
+                    album_mbid: listen.release_mbid.as_deref().unwrap_or_default(),
+                    track: &listen.title,
+                    track_mbid: listen.recording_mbid.as_deref().unwrap_or_default(),
+                })?;
+            }
+
+            writer.flush()?;
+        }
+    }
+
+    writer.flush()?;
+    Ok(())
+}
+
+/// Which plays an export writes, and whether it's remembered for later incremental exports.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Incremental {
+    /// Every play.
+    Off,
+
+    /// The plays added after the newest play written by the user's last remembered incremental
+    /// export in the same format.
+    Peek,
+
+    /// Like `Peek`, remembering the newest play written.
+    Advance,
+}
+
+/// Writes the user's plays in `[since, until)` to `writer`, returning how many were written.
+pub fn export(
+    conn: &SqliteConnection,
+    user_id: UUID,
+    format: ListensFormat,
+    since: Option<NaiveDateTime>,
+    until: Option<NaiveDateTime>,
+    incremental: Incremental,
+    writer: impl Write,
+) -> Result<usize, Error> {
+    let after = if incremental == Incremental::Off {
+        None
+    } else {
+        listens_export::table
+            .find((user_id, format.name()))
+            .select(listens_export::last_sequence)
+            .first(conn)
+            .optional()?
+    };
+
+    let listens = load(conn, user_id, since, until, after)?;
+    let (sequences, listens): (Vec<i32>, Vec<ExportedListen>) = listens.into_iter().unzip();
+    write(format, &listens, writer)?;
+
+    if incremental == Incremental::Advance {
+        if let Some(last) = sequences.into_iter().max() {
+            diesel::replace_into(listens_export::table)
+                .values((
+                    listens_export::user_id.eq(user_id),
+                    listens_export::format.eq(format.name()),
+                    listens_export::last_sequence.eq(last),
+                ))
+                .execute(conn)?;
+        }
+    }
+
+    Ok(listens.len())
+}
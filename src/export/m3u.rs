@@ -0,0 +1,92 @@
+//! Writes playlists as extended M3U8 files.
+
+use crate::export::playlist::ExportedSong;
+use crate::models::*;
+use std::io;
+use std::io::Write;
+
+/// Writes the songs of a playlist named `name` in order. `location` gives the path or URL each
+/// song is played from.
+pub fn write<W, F>(name: &str, songs: &[ExportedSong], location: F, out: &mut W) -> io::Result<()>
+where
+    W: Write,
+    F: Fn(&Song) -> String,
+{
+    writeln!(out, "#EXTM3U")?;
+    writeln!(out, "#PLAYLIST:{}", single_line(name))?;
+
+    for exported in songs {
+        writeln!(
+            out,
+            "#EXTINF:{},{} - {}",
+            milliseconds_to_seconds(exported.song.duration as i64),
+            single_line(&exported.artists.join(", ")),
+            single_line(&exported.song.name)
+        )?;
+        writeln!(out, "{}", location(&exported.song))?;
+    }
+
+    Ok(())
+}
+
+/// Line breaks in tags would end the line of an entry early.
+fn single_line(text: &str) -> String {
+    text.replace(|c| c == '\r' || c == '\n', " ")
+}
+
+#[cfg(test)]
+mod test {
+    use super::write;
+    use crate::export::playlist::ExportedSong;
+    use crate::models::*;
+    use chrono::NaiveDate;
+    use std::path::PathBuf;
+
+    #[test]
+    fn writes_extinf_lines() {
+        let time_added = NaiveDate::from_ymd(2026, 10, 20).and_hms(0, 0, 0);
+        let album_id = UUID::new();
+        let songs = vec![ExportedSong {
+            song: Song {
+                id: UUID::new(),
+                name: "Come\nTogether".to_string(),
+                album_id,
+                track_number: 1,
+                disk_number: 1,
+                duration: 259_400,
+                time_added,
+                path: PathBuf::from("/music/01 Come Together.flac").into(),
+                mbid: None,
+                genre: None,
+            },
+            album: Album {
+                id: album_id,
+                artwork_path: None,
+                name: "Abbey Road".to_string(),
+                artist_id: UUID::new(),
+                release_year: Some(1969),
+                time_added,
+                mbid: None,
+                directory: None,
+            },
+            artists: vec!["The Beatles".to_string()],
+        }];
+
+        let mut out = Vec::new();
+        write(
+            "Mix",
+            &songs,
+            |song| song.path.display().to_string(),
+            &mut out,
+        )
+        .unwrap();
+
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "#EXTM3U\n\
+             #PLAYLIST:Mix\n\
+             #EXTINF:259,The Beatles - Come Together\n\
+             /music/01 Come Together.flac\n"
+        );
+    }
+}
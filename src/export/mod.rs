@@ -0,0 +1,4 @@
+pub mod listens;
+pub mod m3u;
+pub mod playlist;
+pub mod xspf;
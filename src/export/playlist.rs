@@ -0,0 +1,249 @@
+//! Writes playlists as files other players open, as M3U8, XSPF or JSPF.
+
+use crate::database::{album, artist, song_artist};
+use crate::export::{m3u, xspf};
+use crate::models::*;
+use diesel::prelude::*;
+use juniper::GraphQLEnum;
+use std::collections::{HashMap, HashSet};
+use std::io;
+use std::io::Write;
+use std::str::FromStr;
+use url::{form_urlencoded, Url};
+
+#[derive(thiserror::Error, Debug)]
+pub enum Error {
+    #[error(transparent)]
+    Io(#[from] io::Error),
+
+    #[error(transparent)]
+    Xml(#[from] quick_xml::Error),
+
+    #[error(transparent)]
+    Json(#[from] serde_json::Error),
+
+    #[error(transparent)]
+    Diesel(#[from] diesel::result::Error),
+}
+
+#[derive(GraphQLEnum, Clone, Copy, Debug, PartialEq)]
+pub enum PlaylistFormat {
+    /// Extended M3U in UTF-8, which almost every player opens.
+    #[graphql(name = "M3U8")]
+    M3u8,
+
+    /// The XML Shareable Playlist Format.
+    #[graphql(name = "XSPF")]
+    Xspf,
+
+    /// XSPF written as JSON, like the playlists of ListenBrainz.
+    #[graphql(name = "JSPF")]
+    Jspf,
+}
+
+impl PlaylistFormat {
+    /// The name of the format on the command line, which is also its file extension.
+    pub fn name(self) -> &'static str {
+        match self {
+            PlaylistFormat::M3u8 => "m3u8",
+            PlaylistFormat::Xspf => "xspf",
+            PlaylistFormat::Jspf => "jspf",
+        }
+    }
+
+    pub fn content_type(self) -> &'static str {
+        match self {
+            PlaylistFormat::M3u8 => "audio/x-mpegurl; charset=utf-8",
+            PlaylistFormat::Xspf => "application/xspf+xml",
+            PlaylistFormat::Jspf => "application/json",
+        }
+    }
+
+    pub fn get_export_url(id: &str, extension: &str) -> String {
+        format!("/files/playlist/{}.{}", id, extension)
+    }
+
+    /// The URL to download the playlist in this format from. With `paths`, songs are located by
+    /// the paths of their files rather than by stream URLs.
+    pub fn export_url(self, playlist_id: UUID, paths: bool) -> String {
+        let url = PlaylistFormat::get_export_url(&playlist_id.to_string(), self.name());
+        if paths {
+            format!("{}?locations=paths", url)
+        } else {
+            url
+        }
+    }
+}
+
+impl FromStr for PlaylistFormat {
+    type Err = String;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        match name.to_lowercase().as_str() {
+            "m3u8" | "m3u" => Ok(PlaylistFormat::M3u8),
+            "xspf" => Ok(PlaylistFormat::Xspf),
+            "jspf" => Ok(PlaylistFormat::Jspf),
+            _ => Err(format!(
+                "unknown format '{}', expected m3u8, xspf or jspf",
+                name
+            )),
+        }
+    }
+}
+
+/// Where the players an exported playlist is for find its songs and artwork.
+pub enum Locations {
+    /// The files in the library, for players on the same machine.
+    Paths,
+
+    /// The server at `base_url` (like `http://music.local:8080`), for players on other devices.
+    /// Players can't sign in, so the URLs carry `token`, which should only be able to stream the
+    /// playlist's songs.
+    Streams {
+        base_url: String,
+        token: Option<String>,
+    },
+}
+
+impl Locations {
+    /// Where the song is played from, as a path or a URL.
+    pub fn song(&self, song: &Song) -> String {
+        match self {
+            Locations::Paths => song.path.display().to_string(),
+            Locations::Streams { .. } => self.url(&Song::get_raw_stream_url(&song.id.to_string())),
+        }
+    }
+
+    /// Where the song is played from as a URI, for formats which only have URIs.
+    pub fn song_uri(&self, song: &Song) -> String {
+        match self {
+            Locations::Paths => Url::from_file_path(song.path.as_path())
+                .map_or_else(|_| song.path.display().to_string(), String::from),
+            Locations::Streams { .. } => self.song(song),
+        }
+    }
+
+    /// The URI of the album's artwork, if it has artwork.
+    pub fn artwork_uri(&self, album: &Album) -> Option<String> {
+        let artwork_path = album.artwork_path.as_ref()?;
+
+        match self {
+            Locations::Paths => Url::from_file_path(artwork_path.as_path())
+                .ok()
+                .map(String::from),
+            Locations::Streams { .. } => {
+                Some(self.url(&Album::get_artwork_url(&album.id.to_string())))
+            }
+        }
+    }
+
+    fn url(&self, path: &str) -> String {
+        match self {
+            Locations::Paths => path.to_string(),
+            Locations::Streams { base_url, token } => {
+                let mut url = format!("{}{}", base_url.trim_end_matches('/'), path);
+                if let Some(token) = token {
+                    url.push('?');
+                    url.push_str(
+                        &form_urlencoded::Serializer::new(String::new())
+                            .append_pair("token", token)
+                            .finish(),
+                    );
+                }
+
+                url
+            }
+        }
+    }
+}
+
+/// A song of an exported playlist.
+pub struct ExportedSong {
+    pub song: Song,
+    pub album: Album,
+
+    /// The names of the song's artists, in alphabetical order.
+    pub artists: Vec<String>,
+}
+
+/// Writes the playlist in the format.
+pub fn export<W: Write>(
+    conn: &SqliteConnection,
+    playlist: &Playlist,
+    format: PlaylistFormat,
+    locations: &Locations,
+    mut out: W,
+) -> Result<(), Error> {
+    let songs = load(conn, playlist)?;
+
+    match format {
+        PlaylistFormat::M3u8 => m3u::write(
+            &playlist.name,
+            &songs,
+            |song| locations.song(song),
+            &mut out,
+        )?,
+        PlaylistFormat::Xspf => {
+            xspf::write_xspf(&xspf::to_jspf(&playlist.name, &songs, locations), &mut out)?
+        }
+        PlaylistFormat::Jspf => {
+            xspf::write_jspf(&xspf::to_jspf(&playlist.name, &songs, locations), &mut out)?
+        }
+    }
+
+    out.flush()?;
+    Ok(())
+}
+
+/// The songs of the playlist in order.
+pub fn load(conn: &SqliteConnection, playlist: &Playlist) -> QueryResult<Vec<ExportedSong>> {
+    let songs = playlist.songs(conn)?;
+
+    // Playlists can be longer than SQLite binds values in a query, so albums and artists are
+    // loaded a chunk at a time.
+    let album_ids: Vec<UUID> = songs
+        .iter()
+        .map(|song| song.album_id)
+        .collect::<HashSet<UUID>>()
+        .into_iter()
+        .collect();
+    let mut albums: HashMap<UUID, Album> = HashMap::new();
+    for chunk in album_ids.chunks(MAX_BOUND_IDS) {
+        for album in album::table
+            .filter(album::id.eq_any(chunk))
+            .load::<Album>(conn)?
+        {
+            albums.insert(album.id, album);
+        }
+    }
+
+    let song_ids: Vec<UUID> = songs
+        .iter()
+        .map(|song| song.id)
+        .collect::<HashSet<UUID>>()
+        .into_iter()
+        .collect();
+    let mut artists: HashMap<UUID, Vec<String>> = HashMap::new();
+    for chunk in song_ids.chunks(MAX_BOUND_IDS) {
+        for (song_id, name) in song_artist::table
+            .inner_join(artist::table)
+            .filter(song_artist::song_id.eq_any(chunk))
+            .order_by(artist::name)
+            .select((song_artist::song_id, artist::name))
+            .load::<(UUID, String)>(conn)?
+        {
+            artists.entry(song_id).or_default().push(name);
+        }
+    }
+
+    Ok(songs
+        .into_iter()
+        .filter_map(|song| {
+            Some(ExportedSong {
+                album: albums.get(&song.album_id)?.clone(),
+                artists: artists.get(&song.id).cloned().unwrap_or_default(),
+                song,
+            })
+        })
+        .collect())
+}
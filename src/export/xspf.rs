@@ -0,0 +1,188 @@
+//! Writes playlists as XSPF, or as JSPF, its JSON form.
+
+use crate::export::playlist::{ExportedSong, Locations};
+use crate::import::xspf::{Jspf, JspfPlaylist, JspfTrack, MUSICBRAINZ_RECORDING_URL};
+use quick_xml::events::{BytesDecl, BytesEnd, BytesStart, BytesText, Event};
+use quick_xml::Writer;
+use std::io::Write;
+
+/// The songs of a playlist named `name`, in the fields both formats share.
+pub fn to_jspf(name: &str, songs: &[ExportedSong], locations: &Locations) -> Jspf {
+    let track = songs
+        .iter()
+        .map(|exported| JspfTrack {
+            location: vec![locations.song_uri(&exported.song)],
+            identifier: exported
+                .song
+                .mbid
+                .iter()
+                .map(|mbid| format!("{}{}", MUSICBRAINZ_RECORDING_URL, mbid))
+                .collect(),
+            title: Some(exported.song.name.clone()),
+            creator: Some(exported.artists.join(", ")).filter(|artists| !artists.is_empty()),
+            album: Some(exported.album.name.clone()),
+            track_num: Some(exported.song.track_number),
+            duration: Some(exported.song.duration),
+            image: locations.artwork_uri(&exported.album),
+        })
+        .collect();
+
+    Jspf {
+        playlist: JspfPlaylist {
+            title: Some(name.to_string()),
+            track,
+        },
+    }
+}
+
+pub fn write_jspf<W: Write>(jspf: &Jspf, out: W) -> serde_json::Result<()> {
+    serde_json::to_writer_pretty(out, jspf)
+}
+
+pub fn write_xspf<W: Write>(jspf: &Jspf, out: W) -> quick_xml::Result<()> {
+    let mut writer = Writer::new_with_indent(out, b' ', 2);
+    writer.write_event(Event::Decl(BytesDecl::new(b"1.0", Some(b"UTF-8"), None)))?;
+
+    let mut playlist = BytesStart::borrowed_name(b"playlist");
+    playlist.push_attribute(("version", "1"));
+    playlist.push_attribute(("xmlns", "http://xspf.org/ns/0/"));
+    writer.write_event(Event::Start(playlist))?;
+
+    if let Some(title) = &jspf.playlist.title {
+        write_element(&mut writer, b"title", title)?;
+    }
+
+    writer.write_event(Event::Start(BytesStart::borrowed_name(b"trackList")))?;
+    for track in &jspf.playlist.track {
+        writer.write_event(Event::Start(BytesStart::borrowed_name(b"track")))?;
+
+        for location in &track.location {
+            write_element(&mut writer, b"location", location)?;
+        }
+        for identifier in &track.identifier {
+            write_element(&mut writer, b"identifier", identifier)?;
+        }
+
+        let fields: [(&[u8], Option<String>); 6] = [
+            (b"title", track.title.clone()),
+            (b"creator", track.creator.clone()),
+            (b"album", track.album.clone()),
+            (
+                b"trackNum",
+                track.track_num.map(|number| number.to_string()),
+            ),
+            (
+                b"duration",
+                track.duration.map(|duration| duration.to_string()),
+            ),
+            (b"image", track.image.clone()),
+        ];
+        for (name, value) in &fields {
+            if let Some(value) = value {
+                write_element(&mut writer, name, value)?;
+            }
+        }
+
+        writer.write_event(Event::End(BytesEnd::borrowed(b"track")))?;
+    }
+    writer.write_event(Event::End(BytesEnd::borrowed(b"trackList")))?;
+
+    writer.write_event(Event::End(BytesEnd::borrowed(b"playlist")))?;
+    Ok(())
+}
+
+fn write_element<W: Write>(
+    writer: &mut Writer<W>,
+    name: &[u8],
+    text: &str,
+) -> quick_xml::Result<()> {
+    writer.write_event(Event::Start(BytesStart::borrowed_name(name)))?;
+    writer.write_event(Event::Text(BytesText::from_plain_str(text)))?;
+    writer.write_event(Event::End(BytesEnd::borrowed(name)))?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::{to_jspf, write_jspf, write_xspf};
+    use crate::export::playlist::{ExportedSong, Locations};
+    use crate::import::xspf::{parse_jspf, parse_xspf};
+    use crate::models::*;
+    use chrono::NaiveDate;
+    use std::path::{Path, PathBuf};
+
+    fn songs() -> Vec<ExportedSong> {
+        let time_added = NaiveDate::from_ymd(2026, 10, 20).and_hms(0, 0, 0);
+        let album_id = UUID::new();
+
+        vec![ExportedSong {
+            song: Song {
+                id: UUID::new(),
+                name: "Come Together".to_string(),
+                album_id,
+                track_number: 1,
+                disk_number: 1,
+                duration: 259_400,
+                time_added,
+                path: PathBuf::from("/music/Abbey Road/01 Come Together.flac").into(),
+                mbid: Some("1234".to_string()),
+                genre: None,
+            },
+            album: Album {
+                id: album_id,
+                artwork_path: Some(PathBuf::from("/artwork/abbey.jpg").into()),
+                name: "Abbey Road & more".to_string(),
+                artist_id: UUID::new(),
+                release_year: Some(1969),
+                time_added,
+                mbid: None,
+                directory: None,
+            },
+            artists: vec!["The Beatles".to_string()],
+        }]
+    }
+
+    #[test]
+    fn xspf_reads_back() {
+        let jspf = to_jspf("Mix", &songs(), &Locations::Paths);
+        let mut out = Vec::new();
+        write_xspf(&jspf, &mut out).unwrap();
+
+        let contents = String::from_utf8(out).unwrap();
+        assert!(contents.contains("<image>file:///artwork/abbey.jpg</image>"));
+        assert!(contents.contains("<album>Abbey Road &amp; more</album>"));
+
+        let file = parse_xspf(&contents, Path::new("/")).unwrap();
+        assert_eq!(file.title.as_deref(), Some("Mix"));
+        assert_eq!(
+            file.entries[0].path,
+            Some(PathBuf::from("/music/Abbey Road/01 Come Together.flac"))
+        );
+        assert_eq!(file.entries[0].mbid.as_deref(), Some("1234"));
+        assert_eq!(file.entries[0].album.as_deref(), Some("Abbey Road & more"));
+        assert_eq!(file.entries[0].duration, Some(259_400));
+    }
+
+    #[test]
+    fn jspf_with_stream_urls() {
+        let songs = songs();
+        let locations = Locations::Streams {
+            base_url: "http://music.local:8080".to_string(),
+            token: Some("a b".to_string()),
+        };
+        let mut out = Vec::new();
+        write_jspf(&to_jspf("Mix", &songs, &locations), &mut out).unwrap();
+
+        let contents = String::from_utf8(out).unwrap();
+        let stream_url = format!(
+            "http://music.local:8080/files/music/{}/raw?token=a+b",
+            songs[0].song.id
+        );
+        assert!(contents.contains(&stream_url));
+
+        let file = parse_jspf(&contents, Path::new("/")).unwrap();
+        assert_eq!(file.entries[0].path, None);
+        assert_eq!(file.entries[0].artists, vec!["The Beatles".to_string()]);
+    }
+}
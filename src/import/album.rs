@@ -1,5 +1,6 @@
 use super::artwork;
 use super::errors;
+use super::plan::ImportPlan;
 use crate::database::album;
 use crate::models::*;
 use chrono::prelude::*;
@@ -8,22 +9,22 @@ use std::path::Path;
 use std::path::PathBuf;
 use taglib2_sys::SongProperties;
 
+/// Finds the album a song belongs to, creating it if it doesn't exist yet.
+///
+/// Songs with a MusicBrainz release ID are grouped by it. Otherwise, albums with the same name
+/// and artist are considered different editions when their release years, release IDs or
+/// directories differ. Albums missing a release ID or directory (for example, ones imported
+/// before those were recorded) match any song, and are updated with the song's values.
 pub fn add_or_get_album(
     path: &Path,
     artwork_dir: &Path,
     props: &SongProperties,
+    plan: &ImportPlan,
     artist_id: UUID,
     conn: &SqliteConnection,
 ) -> errors::Result<Album> {
-    let name = props.album.as_ref().ok_or(errors::Error::NoAlbumError)?;
-
-    let album: Option<Album> = album::table
-        .filter(album::name.eq(name))
-        .filter(album::artist_id.eq(artist_id))
-        .first(conn)
-        .optional()?;
-
-    if let Some(album) = album {
+    if let Some(album) = find_album(plan, artist_id, conn)? {
+        fill_missing_identifiers(&album, plan, conn)?;
         return Ok(album);
     }
 
@@ -35,14 +36,83 @@ pub fn add_or_get_album(
     let album = Album {
         id,
         artwork_path: artwork_path.map(|p| p.into()),
-        name: name.to_string(),
+        name: plan.album.clone(),
         artist_id,
-        release_year: props.year.map(|year| year as i32),
+        release_year: plan.release_year,
         time_added: Utc::now().naive_utc(),
-        last_played: None,
+        mbid: plan.album_mbid.clone(),
+        directory: plan.album_directory.clone().map(PathWrapper::from),
     };
 
     album.clone().insert_into(album::table).execute(conn)?;
 
     Ok(album)
 }
+
+fn find_album(
+    plan: &ImportPlan,
+    artist_id: UUID,
+    conn: &SqliteConnection,
+) -> QueryResult<Option<Album>> {
+    if let Some(mbid) = &plan.album_mbid {
+        let album: Option<Album> = album::table
+            .filter(album::mbid.eq(mbid))
+            .first(conn)
+            .optional()?;
+
+        if album.is_some() {
+            return Ok(album);
+        }
+    }
+
+    let candidates: Vec<Album> = album::table
+        .filter(album::name.eq(&plan.album))
+        .filter(album::artist_id.eq(artist_id))
+        .load(conn)?;
+
+    Ok(candidates
+        .into_iter()
+        .find(|album| is_same_edition(album, plan)))
+}
+
+/// Checks whether a song belongs to an album with the same name and artist. Values missing on
+/// either side don't tell editions apart.
+fn is_same_edition(album: &Album, plan: &ImportPlan) -> bool {
+    fn conflicts<T: PartialEq>(a: Option<T>, b: Option<T>) -> bool {
+        match (a, b) {
+            (Some(a), Some(b)) => a != b,
+            _ => false,
+        }
+    }
+
+    !conflicts(album.release_year, plan.release_year)
+        && !conflicts(album.mbid.as_ref(), plan.album_mbid.as_ref())
+        && !conflicts(
+            album.directory.as_ref().map(|d| d.as_path()),
+            plan.album_directory.as_deref(),
+        )
+}
+
+fn fill_missing_identifiers(
+    album: &Album,
+    plan: &ImportPlan,
+    conn: &SqliteConnection,
+) -> QueryResult<()> {
+    let target = album::table.find(album.id);
+
+    if album.mbid.is_none() && plan.album_mbid.is_some() {
+        diesel::update(target)
+            .set(album::mbid.eq(&plan.album_mbid))
+            .execute(conn)?;
+    }
+
+    if album.directory.is_none() {
+        if let Some(directory) = &plan.album_directory {
+            diesel::update(target)
+                .set(album::directory.eq(PathWrapper::from(directory.as_path())))
+                .execute(conn)?;
+        }
+    }
+
+    Ok(())
+}
@@ -17,7 +17,6 @@ pub fn add_or_get_artist(name: &str, conn: &SqliteConnection) -> QueryResult<Art
         id: UUID::new(),
         name: name.to_string(),
         time_added: Utc::now().naive_utc(),
-        last_played: None,
     };
 
     artist.clone().insert_into(artist::table).execute(conn)?;
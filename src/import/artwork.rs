@@ -1,11 +1,12 @@
 use image::GenericImageView;
 use mime_guess::Mime;
+use serde::Serialize;
 use std::fs::File;
 use std::io::Write;
 use std::path::Path;
 use std::path::PathBuf;
 use std::{io, result};
-use taglib2_sys::{Picture, SongProperties};
+use taglib2_sys::{Picture, PictureType, SongProperties};
 
 type Result<T> = std::result::Result<T, Error>;
 
@@ -27,7 +28,7 @@ pub enum Error {
 /// Gets the path of the best artwork for the file at `path`. It looks in two places for possible
 /// artwork.
 ///
-/// 1. The artwork embedded in the file's tags.
+/// 1. The artwork embedded in the file's tags (see `SongProperties::cover_artwork`).
 ///
 /// 2. PNG and JPEG files in the same directory as the song.
 ///
@@ -49,6 +50,40 @@ pub fn get_best_artwork_path(
     })
 }
 
+/// Describes where the artwork chosen by `get_best_artwork_path` comes from.
+#[derive(Serialize, Debug)]
+#[serde(tag = "source", rename_all = "snake_case")]
+pub enum ArtworkSource {
+    /// A picture embedded in the song's tags.
+    Embedded {
+        picture_type: PictureType,
+        size: u32,
+    },
+
+    /// An image file near the song.
+    Linked { path: PathBuf, size: u32 },
+}
+
+/// Describes the artwork `get_best_artwork_path` would choose for the file at `path` without
+/// writing anything to disk.
+pub fn get_best_artwork_source(
+    path: &Path,
+    props: &SongProperties,
+) -> Result<Option<ArtworkSource>> {
+    let source = find_best_artwork(path, props)?.map(|info| match info.image_type {
+        ImageType::Embedded(picture) => ArtworkSource::Embedded {
+            picture_type: picture.picture_type,
+            size: info.size,
+        },
+        ImageType::Linked(path) => ArtworkSource::Linked {
+            path,
+            size: info.size,
+        },
+    });
+
+    Ok(source)
+}
+
 /// Holds information about the location of an image.
 enum ImageType<'a> {
     /// Artwork embedded in the tag of a song.
@@ -72,8 +107,10 @@ impl<'a> ImageInfo<'a> {
     fn from_embedded(
         picture: &Picture,
     ) -> result::Result<Option<ImageInfo<'_>>, image::ImageError> {
-        let img = image::load_from_memory(&picture.data)?;
-        let (width, height) = img.dimensions();
+        let (width, height) = match picture.dimensions {
+            Some(dimensions) => dimensions,
+            None => image::load_from_memory(&picture.data)?.dimensions(),
+        };
 
         if width != height {
             return Ok(None);
@@ -103,23 +140,7 @@ impl<'a> ImageInfo<'a> {
     /// Gets path to image, creating a file in `artwork_dir` if the image is embedded.
     fn make_and_get_path(self, artwork_dir: &Path, new_artwork_name: &str) -> Result<PathBuf> {
         let artwork_path = match self.image_type {
-            ImageType::Embedded(picture) => {
-                let extensions = mime_guess::get_mime_extensions(&picture.mime)
-                    .ok_or_else(|| Error::UnknownExtension(picture.mime.clone()))?;
-
-                let extension = extensions
-                    .get(0)
-                    .ok_or_else(|| Error::NoExtensions(picture.mime.clone()))?;
-
-                let mut artwork_path = artwork_dir.to_owned();
-                artwork_path.push(format!("{}.{}", new_artwork_name, extension));
-
-                // Write Picture
-                let mut artwork_file = File::create(&artwork_path)?;
-                artwork_file.write_all(&picture.data)?;
-
-                artwork_path
-            }
+            ImageType::Embedded(picture) => write_embedded(picture, artwork_dir, new_artwork_name)?,
             ImageType::Linked(path) => path,
         };
 
@@ -127,6 +148,26 @@ impl<'a> ImageInfo<'a> {
     }
 }
 
+/// Writes an embedded picture to a file named `name` inside `artwork_dir`, choosing the file
+/// extension from the picture's mime type. Returns the path of the new file.
+pub fn write_embedded(picture: &Picture, artwork_dir: &Path, name: &str) -> Result<PathBuf> {
+    let extensions = mime_guess::get_mime_extensions(&picture.mime)
+        .ok_or_else(|| Error::UnknownExtension(picture.mime.clone()))?;
+
+    let extension = extensions
+        .get(0)
+        .ok_or_else(|| Error::NoExtensions(picture.mime.clone()))?;
+
+    let mut artwork_path = artwork_dir.to_owned();
+    artwork_path.push(format!("{}.{}", name, extension));
+
+    // Write Picture
+    let mut artwork_file = File::create(&artwork_path)?;
+    artwork_file.write_all(&picture.data)?;
+
+    Ok(artwork_path)
+}
+
 /// Finds PNGs and JPEGs which are squares in the directory at `path`.
 fn find_covers_in_path(path: &Path) -> Result<Vec<ImageInfo<'static>>> {
     let images = path
@@ -159,8 +200,7 @@ fn find_covers_in_path(path: &Path) -> Result<Vec<ImageInfo<'static>>> {
 /// the one with the highest resolution.
 fn find_best_artwork<'a>(path: &Path, props: &'a SongProperties) -> Result<Option<ImageInfo<'a>>> {
     let embedded_artwork = props
-        .cover_artwork
-        .as_ref()
+        .cover_artwork()
         .map_or(Ok(None), ImageInfo::from_embedded)?;
 
     let linked_artwork = find_covers_in_path(path.parent().unwrap())?;
@@ -14,6 +14,12 @@ pub enum Error {
     #[error(transparent)]
     Artwork(#[from] artwork::Error),
 
+    #[error("the playlist isn't valid XML: {0}")]
+    Xml(#[from] quick_xml::Error),
+
+    #[error("the playlist isn't valid JSON: {0}")]
+    Json(#[from] serde_json::Error),
+
     #[error("either the tag's album artist or artist needs to be set, neither is")]
     NoArtistError,
 
@@ -23,3 +29,18 @@ pub enum Error {
     #[error("the title wasn't specified in the tag")]
     NoTitleError,
 }
+
+impl Error {
+    /// A short, stable identifier of the kind of error, suitable for grouping errors.
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Error::Diesel(_) => "database",
+            Error::Io(_) => "io",
+            Error::Artwork(_) => "artwork",
+            Error::Xml(_) | Error::Json(_) => "invalid_playlist",
+            Error::NoArtistError => "no_artist",
+            Error::NoAlbumError => "no_album",
+            Error::NoTitleError => "no_title",
+        }
+    }
+}
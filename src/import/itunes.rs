@@ -0,0 +1,259 @@
+//! Merges the play counts, likes and ratings from an iTunes or Music.app library, exported as
+//! `Library.xml`, into the stats of a user.
+
+use crate::database::user_song_stats;
+use crate::matching::{SongKey, SongMatcher};
+use crate::models::{SongStats, UUID};
+use chrono::{DateTime, NaiveDateTime, Utc};
+use diesel::prelude::*;
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+use url::Url;
+
+#[derive(Deserialize)]
+pub struct Library {
+    #[serde(rename = "Tracks", default)]
+    pub tracks: BTreeMap<String, Track>,
+}
+
+#[derive(Deserialize)]
+pub struct Track {
+    #[serde(rename = "Name")]
+    pub name: Option<String>,
+
+    #[serde(rename = "Artist")]
+    pub artist: Option<String>,
+
+    #[serde(rename = "Album Artist")]
+    pub album_artist: Option<String>,
+
+    #[serde(rename = "Album")]
+    pub album: Option<String>,
+
+    #[serde(rename = "Track Number")]
+    pub track_number: Option<i32>,
+
+    #[serde(rename = "Disc Number")]
+    pub disk_number: Option<i32>,
+
+    /// A `file://` URL of the track's file.
+    #[serde(rename = "Location")]
+    pub location: Option<String>,
+
+    #[serde(rename = "Play Count", default)]
+    pub play_count: i32,
+
+    #[serde(rename = "Play Date UTC")]
+    pub play_date: Option<plist::Date>,
+
+    #[serde(rename = "Loved", default)]
+    pub loved: bool,
+
+    /// The rating from 0 to 100, with 20 for each star.
+    #[serde(rename = "Rating")]
+    pub rating: Option<i32>,
+
+    /// Whether the rating was derived from the album's rating rather than set on the track.
+    #[serde(rename = "Rating Computed", default)]
+    pub rating_computed: bool,
+}
+
+impl Track {
+    fn has_user_data(&self) -> bool {
+        self.play_count > 0 || self.play_date.is_some() || self.loved || self.stars().is_some()
+    }
+
+    /// The rating of the track from 1 to 5 stars, if it was rated.
+    pub fn stars(&self) -> Option<i32> {
+        if self.rating_computed {
+            return None;
+        }
+
+        self.rating.and_then(rating_to_stars)
+    }
+
+    fn last_played(&self) -> Option<NaiveDateTime> {
+        self.play_date
+            .map(|date| DateTime::<Utc>::from(SystemTime::from(date)).naive_utc())
+    }
+
+    fn key(&self, prefix: Option<&PrefixReplacement>) -> SongKey {
+        let path = self
+            .location
+            .as_ref()
+            .and_then(|location| location_to_path(location))
+            .map(|path| match prefix {
+                Some(prefix) => prefix.apply(&path),
+                None => path,
+            });
+
+        SongKey {
+            path,
+            mbid: None,
+            title: self.name.clone().unwrap_or_default(),
+            artists: self
+                .artist
+                .iter()
+                .chain(self.album_artist.iter())
+                .cloned()
+                .collect(),
+            album: self.album.clone(),
+            track_number: self.track_number,
+            disk_number: self.disk_number,
+        }
+    }
+}
+
+/// Converts a rating from 0 to 100 into 1 to 5 stars. Unrated tracks have a rating of 0.
+pub(crate) fn rating_to_stars(rating: i32) -> Option<i32> {
+    let stars = (rating + 10) / 20;
+    if stars > 0 {
+        Some(stars.min(5))
+    } else {
+        None
+    }
+}
+
+fn location_to_path(location: &str) -> Option<PathBuf> {
+    Url::parse(location).ok()?.to_file_path().ok()
+}
+
+/// Moves the paths in the library export from where the music was on the machine running iTunes
+/// to where it is now.
+#[derive(Debug)]
+pub struct PrefixReplacement {
+    pub from: PathBuf,
+    pub to: PathBuf,
+}
+
+impl PrefixReplacement {
+    fn apply(&self, path: &Path) -> PathBuf {
+        match path.strip_prefix(&self.from) {
+            Ok(rest) => self.to.join(rest),
+            Err(_) => path.to_path_buf(),
+        }
+    }
+}
+
+pub struct MergeReport {
+    pub matched: usize,
+
+    /// Tracks with play counts, likes or ratings which aren't in the library.
+    pub unmatched: Vec<SongKey>,
+}
+
+pub fn read_library(path: &Path) -> Result<Library, plist::Error> {
+    plist::from_file(path)
+}
+
+/// Merges the user data of every track in the iTunes library into the user's stats of the
+/// matching songs. Play counts and last played times are only raised, loved tracks are liked and
+/// ratings are only set on songs which aren't rated yet, so merging the same library twice
+/// changes nothing.
+pub fn merge(
+    conn: &SqliteConnection,
+    user_id: UUID,
+    library: &Library,
+    prefix: Option<&PrefixReplacement>,
+) -> QueryResult<MergeReport> {
+    conn.transaction(|| {
+        let matcher = SongMatcher::load(conn)?;
+        let mut report = MergeReport {
+            matched: 0,
+            unmatched: Vec::new(),
+        };
+
+        for track in library
+            .tracks
+            .values()
+            .filter(|track| track.has_user_data())
+        {
+            let key = track.key(prefix);
+            let id = match matcher.find(&key) {
+                Some(id) => id,
+                None => {
+                    report.unmatched.push(key);
+                    continue;
+                }
+            };
+
+            report.matched += 1;
+            SongStats::ensure(conn, user_id, id)?;
+            let target = user_song_stats::table.find((user_id, id));
+
+            diesel::update(target)
+                .filter(user_song_stats::play_count.lt(track.play_count))
+                .set(user_song_stats::play_count.eq(track.play_count))
+                .execute(conn)?;
+
+            if let Some(last_played) = track.last_played() {
+                diesel::update(target)
+                    .filter(
+                        user_song_stats::last_played
+                            .is_null()
+                            .or(user_song_stats::last_played.lt(last_played)),
+                    )
+                    .set(user_song_stats::last_played.eq(last_played))
+                    .execute(conn)?;
+            }
+
+            if track.loved {
+                diesel::update(target)
+                    .set(user_song_stats::liked.eq(true))
+                    .execute(conn)?;
+            }
+
+            if let Some(stars) = track.stars() {
+                diesel::update(target)
+                    .filter(user_song_stats::rating.is_null())
+                    .set(user_song_stats::rating.eq(stars))
+                    .execute(conn)?;
+            }
+        }
+
+        Ok(report)
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::{location_to_path, rating_to_stars, PrefixReplacement};
+    use std::path::{Path, PathBuf};
+
+    #[test]
+    fn rating_to_stars_rounds() {
+        assert_eq!(rating_to_stars(0), None);
+        assert_eq!(rating_to_stars(20), Some(1));
+        assert_eq!(rating_to_stars(50), Some(3));
+        assert_eq!(rating_to_stars(100), Some(5));
+    }
+
+    #[test]
+    fn location_to_path_decodes() {
+        assert_eq!(
+            location_to_path("file://localhost/Users/me/Music/Bj%C3%B6rk/01%20Army%20of%20Me.m4a"),
+            Some(PathBuf::from("/Users/me/Music/Björk/01 Army of Me.m4a"))
+        );
+    }
+
+    #[test]
+    fn prefix_replacement_moves_paths() {
+        let prefix = PrefixReplacement {
+            from: PathBuf::from("/Users/me/Music/iTunes/iTunes Media/Music"),
+            to: PathBuf::from("/srv/music"),
+        };
+
+        assert_eq!(
+            prefix.apply(Path::new(
+                "/Users/me/Music/iTunes/iTunes Media/Music/Björk/Post/01 Army of Me.m4a"
+            )),
+            PathBuf::from("/srv/music/Björk/Post/01 Army of Me.m4a")
+        );
+        assert_eq!(
+            prefix.apply(Path::new("/Volumes/Other/song.mp3")),
+            PathBuf::from("/Volumes/Other/song.mp3")
+        );
+    }
+}
@@ -0,0 +1,362 @@
+//! Imports scrobbles exported from ListenBrainz (JSON) and Last.fm (CSV) into the play history.
+
+use crate::database::{play, song, song_artist};
+use crate::matching::{SongKey, SongMatcher};
+use crate::models::*;
+use chrono::{Duration, NaiveDateTime};
+use diesel::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+/// Plays of the same song closer together than this are considered to be the same play, since
+/// services record slightly different times for the same scrobble.
+const DUPLICATE_WINDOW_SECONDS: i64 = 60;
+
+#[derive(thiserror::Error, Debug)]
+pub enum Error {
+    #[error(transparent)]
+    Io(#[from] io::Error),
+
+    #[error(transparent)]
+    Json(#[from] serde_json::Error),
+
+    #[error(transparent)]
+    Csv(#[from] csv::Error),
+
+    #[error("line {line}: {message}")]
+    InvalidListen { line: usize, message: String },
+}
+
+/// A song which was listened to according to a scrobbling service.
+#[derive(Serialize, Debug)]
+pub struct Listen {
+    pub time: NaiveDateTime,
+    pub artist: String,
+    pub title: String,
+    pub album: Option<String>,
+
+    /// The MusicBrainz recording ID of the song.
+    pub recording_mbid: Option<String>,
+
+    /// The length of the song in milliseconds.
+    pub duration: Option<i32>,
+}
+
+impl Listen {
+    fn key(&self) -> SongKey {
+        SongKey {
+            mbid: self.recording_mbid.clone(),
+            title: self.title.clone(),
+            artists: vec![self.artist.clone()],
+            album: self.album.clone(),
+            ..SongKey::default()
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct ListenBrainzListen {
+    listened_at: i64,
+    track_metadata: TrackMetadata,
+}
+
+#[derive(Deserialize)]
+struct TrackMetadata {
+    artist_name: String,
+    track_name: String,
+    release_name: Option<String>,
+
+    #[serde(default)]
+    additional_info: AdditionalInfo,
+
+    /// The recording MusicBrainz matched the listen to, when the client didn't send one.
+    mbid_mapping: Option<MbidMapping>,
+}
+
+#[derive(Deserialize, Default)]
+struct AdditionalInfo {
+    recording_mbid: Option<String>,
+    duration_ms: Option<i32>,
+}
+
+#[derive(Deserialize)]
+struct MbidMapping {
+    recording_mbid: Option<String>,
+}
+
+impl From<ListenBrainzListen> for Option<Listen> {
+    fn from(listen: ListenBrainzListen) -> Option<Listen> {
+        let metadata = listen.track_metadata;
+        let info = metadata.additional_info;
+        let mapping = metadata.mbid_mapping;
+        let recording_mbid = info
+            .recording_mbid
+            .or_else(|| mapping.and_then(|mapping| mapping.recording_mbid));
+
+        Some(Listen {
+            time: NaiveDateTime::from_timestamp_opt(listen.listened_at, 0)?,
+            artist: metadata.artist_name,
+            title: metadata.track_name,
+            album: metadata.release_name,
+            recording_mbid,
+            duration: info.duration_ms,
+        })
+    }
+}
+
+/// Reads a ListenBrainz export, either a JSON array of listens or one listen per line.
+pub fn read_listenbrainz(path: &Path) -> Result<Vec<Listen>, Error> {
+    let contents = fs::read_to_string(path)?;
+
+    let listens: Vec<ListenBrainzListen> = if contents.trim_start().starts_with('[') {
+        serde_json::from_str(&contents)?
+    } else {
+        contents
+            .lines()
+            .filter(|line| !line.trim().is_empty())
+            .map(serde_json::from_str)
+            .collect::<Result<_, _>>()?
+    };
+
+    listens
+        .into_iter()
+        .enumerate()
+        .map(|(index, listen)| {
+            Option::<Listen>::from(listen).ok_or_else(|| Error::InvalidListen {
+                line: index + 1,
+                message: "the time the song was listened at is out of range".to_string(),
+            })
+        })
+        .collect()
+}
+
+/// The columns of a Last.fm export. Exports without a header row are `artist, album, title,
+/// date`.
+struct LastFmColumns {
+    artist: usize,
+    album: Option<usize>,
+    title: usize,
+    time: usize,
+    recording_mbid: Option<usize>,
+}
+
+impl LastFmColumns {
+    fn from_header(header: &csv::StringRecord) -> Option<LastFmColumns> {
+        let find = |names: &[&str]| {
+            header
+                .iter()
+                .position(|field| names.contains(&field.trim().to_lowercase().as_str()))
+        };
+
+        Some(LastFmColumns {
+            artist: find(&["artist", "artist_name"])?,
+            album: find(&["album", "album_name"]),
+            title: find(&["track", "title", "track_name", "name"])?,
+            time: find(&["uts", "date", "timestamp", "utc_time"])?,
+            recording_mbid: find(&["track_mbid", "mbid"]),
+        })
+    }
+}
+
+const DEFAULT_LAST_FM_COLUMNS: LastFmColumns = LastFmColumns {
+    artist: 0,
+    album: Some(1),
+    title: 2,
+    time: 3,
+    recording_mbid: None,
+};
+
+/// Parses the time of a scrobble, either in seconds since the epoch or formatted like
+/// `31 Jan 2020 21:15` in UTC.
+fn parse_last_fm_time(time: &str) -> Option<NaiveDateTime> {
+    let time = time.trim();
+    if let Ok(seconds) = time.parse::<i64>() {
+        return NaiveDateTime::from_timestamp_opt(seconds, 0);
+    }
+
+    [
+        "%d %b %Y %H:%M",
+        "%d %b %Y, %H:%M",
+        "%Y-%m-%d %H:%M:%S",
+        "%Y-%m-%dT%H:%M:%S",
+    ]
+    .iter()
+    .find_map(|format| NaiveDateTime::parse_from_str(time, format).ok())
+}
+
+/// Reads a Last.fm export in CSV.
+pub fn read_last_fm(path: &Path) -> Result<Vec<Listen>, Error> {
+    let mut reader = csv::ReaderBuilder::new()
+        .has_headers(false)
+        .flexible(true)
+        .from_path(path)?;
+
+    let mut records = reader.records().enumerate().peekable();
+    let columns = match records.peek() {
+        Some((_, Ok(first))) => match LastFmColumns::from_header(first) {
+            Some(columns) => {
+                records.next();
+                columns
+            }
+            None => DEFAULT_LAST_FM_COLUMNS,
+        },
+        _ => DEFAULT_LAST_FM_COLUMNS,
+    };
+
+    let mut listens = Vec::new();
+    for (index, record) in records {
+        let record = record?;
+        let line = index + 1;
+        let field = |column: usize| record.get(column).map(str::trim).unwrap_or_default();
+        let optional_field = |column: Option<usize>| {
+            column
+                .map(field)
+                .filter(|value| !value.is_empty())
+                .map(str::to_string)
+        };
+
+        let time = parse_last_fm_time(field(columns.time)).ok_or_else(|| Error::InvalidListen {
+            line,
+            message: format!("couldn't parse the time '{}'", field(columns.time)),
+        })?;
+
+        listens.push(Listen {
+            time,
+            artist: field(columns.artist).to_string(),
+            title: field(columns.title).to_string(),
+            album: optional_field(columns.album),
+            recording_mbid: optional_field(columns.recording_mbid),
+            duration: None,
+        });
+    }
+
+    Ok(listens)
+}
+
+pub struct ListensReport {
+    pub imported: usize,
+
+    /// Listens which were already in the play history.
+    pub duplicates: usize,
+
+    /// Listens of songs which aren't in the library.
+    pub unmatched: Vec<Listen>,
+}
+
+/// Adds listens of songs in the library to the user's play history, recorded as played by
+/// `client`. The user's play counts of the songs and last played times of the songs, their albums
+/// and their artists are updated with the new plays.
+pub fn import(
+    conn: &SqliteConnection,
+    user_id: UUID,
+    listens: Vec<Listen>,
+    client: &str,
+) -> QueryResult<ListensReport> {
+    conn.transaction(|| {
+        let matcher = SongMatcher::load(conn)?;
+        let mut report = ListensReport {
+            imported: 0,
+            duplicates: 0,
+            unmatched: Vec::new(),
+        };
+
+        // The number of new plays and the time of the last one of each song.
+        let mut played: HashMap<UUID, (i32, NaiveDateTime)> = HashMap::new();
+
+        for listen in listens {
+            let song_id = match matcher.find_fuzzy(&listen.key()) {
+                Some(song_id) => song_id,
+                None => {
+                    report.unmatched.push(listen);
+                    continue;
+                }
+            };
+
+            let window = Duration::seconds(DUPLICATE_WINDOW_SECONDS);
+            let duplicate: Option<UUID> = play::table
+                .filter(play::user_id.eq(user_id))
+                .filter(play::song_id.eq(song_id))
+                .filter(play::time.between(listen.time - window, listen.time + window))
+                .select(play::id)
+                .first(conn)
+                .optional()?;
+
+            if duplicate.is_some() {
+                report.duplicates += 1;
+                continue;
+            }
+
+            Play {
+                id: UUID::new(),
+                user_id,
+                song_id,
+                time: listen.time,
+                album_id: None,
+                artist_id: None,
+                client: Some(client.to_string()),
+                duration: listen.duration,
+            }
+            .insert_into(play::table)
+            .execute(conn)?;
+
+            report.imported += 1;
+
+            let entry = played.entry(song_id).or_insert((0, listen.time));
+            entry.0 += 1;
+            entry.1 = entry.1.max(listen.time);
+        }
+
+        for (song_id, (count, last_played)) in played {
+            update_aggregates(conn, user_id, song_id, count, last_played)?;
+        }
+
+        Ok(report)
+    })
+}
+
+fn update_aggregates(
+    conn: &SqliteConnection,
+    user_id: UUID,
+    song_id: UUID,
+    new_plays: i32,
+    last_played: NaiveDateTime,
+) -> QueryResult<()> {
+    SongStats::played(conn, user_id, song_id, new_plays, last_played)?;
+
+    let album_id: UUID = song::table
+        .find(song_id)
+        .select(song::album_id)
+        .first(conn)?;
+
+    UserStats::album_played(conn, user_id, album_id, last_played)?;
+
+    let artist_ids: Vec<UUID> = song_artist::table
+        .filter(song_artist::song_id.eq(song_id))
+        .select(song_artist::artist_id)
+        .load(conn)?;
+
+    for artist_id in artist_ids {
+        UserStats::artist_played(conn, user_id, artist_id, last_played)?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::parse_last_fm_time;
+    use chrono::NaiveDate;
+
+    #[test]
+    fn parses_last_fm_times() {
+        let expected = Some(NaiveDate::from_ymd(2020, 1, 31).and_hms(21, 15, 0));
+
+        assert_eq!(parse_last_fm_time("31 Jan 2020 21:15"), expected);
+        assert_eq!(parse_last_fm_time("31 Jan 2020, 21:15"), expected);
+        assert_eq!(parse_last_fm_time("1580505300"), expected);
+        assert_eq!(parse_last_fm_time("yesterday"), None);
+    }
+}
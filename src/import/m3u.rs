@@ -0,0 +1,199 @@
+//! Reads M3U and M3U8 playlist files, including the `#EXTINF` lines of extended M3U.
+
+use crate::import::playlist::{PlaylistEntry, PlaylistFile};
+use std::fs;
+use std::io;
+use std::path::{Component, Path, PathBuf};
+use url::Url;
+
+/// Reads the playlist file at `path`. M3U8 files are UTF-8, while M3U files are
+/// read as Latin-1 unless they are valid UTF-8.
+pub fn read(path: &Path) -> io::Result<PlaylistFile> {
+    let bytes = fs::read(path)?;
+    let is_m3u8 = path
+        .extension()
+        .map_or(false, |extension| extension.eq_ignore_ascii_case("m3u8"));
+
+    let contents = match String::from_utf8(bytes) {
+        Ok(contents) => contents,
+        Err(e) if is_m3u8 => String::from_utf8_lossy(e.as_bytes()).into_owned(),
+        Err(e) => e.into_bytes().into_iter().map(char::from).collect(),
+    };
+
+    let directory = path.parent().unwrap_or_else(|| Path::new(""));
+    Ok(parse(&contents, directory))
+}
+
+/// Parses the contents of a playlist file. Relative locations are resolved against `directory`,
+/// the directory the file is in. Locations which aren't files, like stream URLs, are skipped.
+pub fn parse(contents: &str, directory: &Path) -> PlaylistFile {
+    let mut file = PlaylistFile::default();
+    let mut info: Option<PlaylistEntry> = None;
+
+    for line in contents.trim_start_matches('\u{feff}').lines() {
+        let line = line.trim();
+
+        if let Some(title) = line.strip_prefix("#PLAYLIST:") {
+            file.title = Some(title.trim().to_string()).filter(|title| !title.is_empty());
+            continue;
+        }
+
+        if let Some(extinf) = line.strip_prefix("#EXTINF:") {
+            info = Some(parse_extinf(extinf));
+            continue;
+        }
+
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        let mut entry = info.take().unwrap_or_default();
+        if let Some(path) = resolve(line, directory) {
+            entry.path = Some(path);
+            file.entries.push(entry);
+        }
+    }
+
+    file
+}
+
+/// Parses what follows `#EXTINF:`, like `123,Artist - Title`. The duration can be followed by
+/// attributes, like `-1 tvg-id="x",Title`, and is -1 when unknown.
+fn parse_extinf(extinf: &str) -> PlaylistEntry {
+    // The display name starts after the first comma which isn't in a quoted attribute.
+    let mut in_quotes = false;
+    let comma = extinf.char_indices().find(|(_, c)| {
+        if *c == '"' {
+            in_quotes = !in_quotes;
+        }
+        *c == ',' && !in_quotes
+    });
+    let (attributes, name) = match comma {
+        Some((index, _)) => (&extinf[..index], Some(extinf[index + 1..].trim())),
+        None => (extinf, None),
+    };
+
+    let duration = attributes
+        .split_whitespace()
+        .next()
+        .and_then(|seconds| seconds.parse::<f64>().ok())
+        .filter(|seconds| *seconds >= 0.0)
+        .map(|seconds| (seconds * 1000.0).round() as i32);
+
+    let (artist, title) = match name.filter(|name| !name.is_empty()) {
+        Some(name) => match name.find(" - ") {
+            Some(index) => (Some(name[..index].trim()), Some(name[index + 3..].trim())),
+            None => (None, Some(name)),
+        },
+        None => (None, None),
+    };
+
+    PlaylistEntry {
+        title: title.map(str::to_string),
+        artists: artist.map(str::to_string).into_iter().collect(),
+        duration,
+        ..PlaylistEntry::default()
+    }
+}
+
+/// The path of the file at `location`, a path relative to `directory`, an absolute path or a
+/// `file://` URL.
+fn resolve(location: &str, directory: &Path) -> Option<PathBuf> {
+    if let Ok(url) = Url::parse(location) {
+        // Single letters are drive letters of Windows paths rather than URL schemes.
+        if url.scheme().len() > 1 {
+            return url.to_file_path().ok();
+        }
+    }
+
+    // Playlists written on Windows separate directories with backslashes.
+    let location = if location.contains('/') {
+        location.to_string()
+    } else {
+        location.replace('\\', "/")
+    };
+
+    Some(normalize(&directory.join(location)))
+}
+
+/// Removes the `.` and `..` components of `path` without following symbolic links, so paths
+/// compare equal to the paths songs were imported from.
+fn normalize(path: &Path) -> PathBuf {
+    let mut normalized = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                normalized.pop();
+            }
+            component => normalized.push(component),
+        }
+    }
+
+    normalized
+}
+
+#[cfg(test)]
+mod test {
+    use super::parse;
+    use std::path::{Path, PathBuf};
+
+    #[test]
+    fn extended_m3u() {
+        let contents = "\u{feff}#EXTM3U\n\
+            #PLAYLIST:Abbey Road picks\n\
+            #EXTINF:259,The Beatles - Come Together\n\
+            01 Come Together.flac\n\
+            \n\
+            #EXTINF:-1 tvg-name=\"a, b\",Something\r\n\
+            ../Abbey Road/./02 Something.flac\r\n";
+
+        let file = parse(contents, Path::new("/music/Beatles/Lists"));
+        let entries = &file.entries;
+
+        assert_eq!(file.title.as_deref(), Some("Abbey Road picks"));
+        assert_eq!(entries.len(), 2);
+        assert_eq!(
+            entries[0].path,
+            Some(PathBuf::from("/music/Beatles/Lists/01 Come Together.flac"))
+        );
+        assert_eq!(entries[0].title.as_deref(), Some("Come Together"));
+        assert_eq!(entries[0].artists, vec!["The Beatles".to_string()]);
+        assert_eq!(entries[0].duration, Some(259_000));
+
+        assert_eq!(
+            entries[1].path,
+            Some(PathBuf::from("/music/Beatles/Abbey Road/02 Something.flac"))
+        );
+        assert_eq!(entries[1].title.as_deref(), Some("Something"));
+        assert!(entries[1].artists.is_empty());
+        assert_eq!(entries[1].duration, None);
+    }
+
+    #[test]
+    fn plain_m3u() {
+        let contents = "/music/a.mp3\n# a comment\nfile:///music/b%20c.mp3\nhttp://radio/stream\n";
+
+        let entries = parse(contents, Path::new("/lists")).entries;
+        let paths: Vec<_> = entries.iter().map(|entry| entry.path.clone()).collect();
+
+        assert_eq!(
+            paths,
+            vec![
+                Some(PathBuf::from("/music/a.mp3")),
+                Some(PathBuf::from("/music/b c.mp3"))
+            ]
+        );
+        assert!(entries.iter().all(|entry| entry.title.is_none()));
+    }
+
+    #[test]
+    fn windows_separators() {
+        let entries = parse("..\\Album\\song.mp3\n", Path::new("/music/Lists")).entries;
+
+        assert_eq!(
+            entries[0].path,
+            Some(PathBuf::from("/music/Album/song.mp3"))
+        );
+    }
+}
@@ -2,7 +2,18 @@ mod album;
 mod artist;
 pub mod artwork;
 pub mod errors;
+pub mod itunes;
+pub mod listens;
+pub mod m3u;
+mod pictures;
+mod plan;
+pub mod playlist;
+pub mod ratings;
 mod song;
+pub mod xspf;
 
+pub use self::plan::ImportPlan;
 pub use self::song::add_song;
 pub use self::song::is_imported;
+pub use self::song::needs_rescan;
+pub use self::song::rescan_song;
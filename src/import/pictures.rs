@@ -0,0 +1,68 @@
+use super::artwork;
+use super::errors;
+use crate::database::album_picture;
+use crate::models::*;
+use diesel::prelude::*;
+use std::path::Path;
+use std::ptr;
+use taglib2_sys::SongProperties;
+
+/// Stores the pictures embedded in a song's tags, other than the one used as its cover artwork,
+/// in the gallery of its album. Every song of an album usually embeds the same pictures, so
+/// pictures with the same type, description and size as one already in the gallery are skipped.
+pub fn add_album_pictures(
+    album_id: UUID,
+    artwork_dir: &Path,
+    props: &SongProperties,
+    conn: &SqliteConnection,
+) -> errors::Result<()> {
+    let mut existing: Vec<AlbumPicture> = album_picture::table
+        .filter(album_picture::album_id.eq(album_id))
+        .load(conn)?;
+
+    let cover_artwork = props.cover_artwork();
+
+    for picture in &props.pictures {
+        if cover_artwork.map_or(false, |cover| ptr::eq(cover, picture)) {
+            continue;
+        }
+
+        let byte_size = picture.data.len() as i32;
+        let is_duplicate = existing.iter().any(|other| {
+            other.picture_type == picture.picture_type.as_raw()
+                && other.description == picture.description
+                && other.byte_size == byte_size
+        });
+
+        if is_duplicate {
+            continue;
+        }
+
+        let id = UUID::new();
+        let path = artwork::write_embedded(
+            picture,
+            artwork_dir,
+            &format!("{}-{}", album_id.to_string(), id.to_string()),
+        )?;
+
+        let album_picture = AlbumPicture {
+            id,
+            album_id,
+            picture_type: picture.picture_type.as_raw(),
+            description: picture.description.clone(),
+            path: path.into(),
+            byte_size,
+            width: picture.dimensions.map(|(width, _)| width as i32),
+            height: picture.dimensions.map(|(_, height)| height as i32),
+        };
+
+        album_picture
+            .clone()
+            .insert_into(album_picture::table)
+            .execute(conn)?;
+
+        existing.push(album_picture);
+    }
+
+    Ok(())
+}
@@ -0,0 +1,156 @@
+use super::errors;
+use super::ratings;
+use serde::Serialize;
+use std::path::{Path, PathBuf};
+use taglib2_sys::SongProperties;
+
+/// The property holding the MusicBrainz release ID of the song's album.
+const ALBUM_MBID_PROPERTY: &str = "MUSICBRAINZ_ALBUMID";
+
+/// The property holding the MusicBrainz recording ID of the song.
+const MBID_PROPERTY: &str = "MUSICBRAINZ_TRACKID";
+
+const GENRE_PROPERTY: &str = "GENRE";
+
+/// The values forte imports from a song's tags after applying the fallbacks for missing tags.
+#[derive(Serialize, Debug)]
+pub struct ImportPlan {
+    pub title: String,
+
+    /// The artist credited on the song. Falls back to the album artist.
+    pub artist: String,
+
+    /// The artist the album is filed under. Falls back to the song's artist.
+    pub album_artist: String,
+    pub album: String,
+    pub track_number: i32,
+
+    /// The disk the song is on. Songs without a disk number are on the first disk.
+    pub disk_number: i32,
+    pub release_year: Option<i32>,
+
+    /// The length of the song in milliseconds.
+    pub duration: i32,
+
+    /// The MusicBrainz recording ID of the song.
+    pub mbid: Option<String>,
+
+    /// The MusicBrainz release ID of the album.
+    pub album_mbid: Option<String>,
+    pub genre: Option<String>,
+
+    /// The rating from 1 to 5 stars stored by other players, imported with `--import-ratings`.
+    pub rating: Option<i32>,
+
+    /// The directory holding the album's songs. Per-disk directories like `CD1` or `Disc 2` are
+    /// skipped so every disk of an album ends up in the same album.
+    pub album_directory: Option<PathBuf>,
+}
+
+impl ImportPlan {
+    /// Determines what would be imported from `props`, the tags of the file at `path`.
+    pub fn new(path: &Path, props: &SongProperties) -> errors::Result<ImportPlan> {
+        let artist = props
+            .artist
+            .as_ref()
+            .or_else(|| props.album_artist.as_ref())
+            .ok_or(errors::Error::NoArtistError)?;
+
+        let album_artist = props
+            .album_artist
+            .as_ref()
+            .or_else(|| props.artist.as_ref())
+            .ok_or(errors::Error::NoArtistError)?;
+
+        let album = props.album.as_ref().ok_or(errors::Error::NoAlbumError)?;
+        let title = props.title.as_ref().ok_or(errors::Error::NoTitleError)?;
+
+        Ok(ImportPlan {
+            title: title.to_string(),
+            artist: artist.to_string(),
+            album_artist: album_artist.to_string(),
+            album: album.to_string(),
+            track_number: props.track_number as i32,
+            disk_number: props.disk_number.map_or(1, |n| n as i32),
+            release_year: props.year.map(|year| year as i32),
+            duration: props.duration,
+            mbid: non_empty_property(props, MBID_PROPERTY),
+            album_mbid: non_empty_property(props, ALBUM_MBID_PROPERTY),
+            genre: non_empty_property(props, GENRE_PROPERTY),
+            rating: ratings::stars_from_tags(props),
+            album_directory: album_directory(path),
+        })
+    }
+}
+
+fn non_empty_property(props: &SongProperties, key: &str) -> Option<String> {
+    props
+        .property(key)
+        .map(str::trim)
+        .filter(|value| !value.is_empty())
+        .map(str::to_string)
+}
+
+/// Gets the directory holding an album from the path of one of its songs.
+fn album_directory(path: &Path) -> Option<PathBuf> {
+    let parent = path.parent()?;
+    let is_disk_directory = parent.file_name().map_or(false, |name| {
+        is_disk_directory_name(&name.to_string_lossy())
+    });
+
+    if is_disk_directory {
+        if let Some(grandparent) = parent.parent() {
+            return Some(grandparent.to_path_buf());
+        }
+    }
+
+    Some(parent.to_path_buf())
+}
+
+/// Checks whether a directory name looks like it holds a single disk of an album, like `CD1`,
+/// `Disc 2` or `disk-03`.
+fn is_disk_directory_name(name: &str) -> bool {
+    let name = name.to_lowercase();
+    let number = ["disc", "disk", "cd"]
+        .iter()
+        .find_map(|prefix| name.strip_prefix(prefix))
+        .map(|rest| rest.trim_start_matches(|c: char| c == ' ' || c == '-' || c == '_'));
+
+    number.map_or(false, |number| {
+        !number.is_empty() && number.chars().all(|c| c.is_ascii_digit())
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::album_directory;
+    use std::path::Path;
+
+    #[test]
+    fn album_directory_is_parent() {
+        let directory = album_directory(Path::new("/music/Artist/Album/01 Song.flac"));
+        assert_eq!(directory.as_deref(), Some(Path::new("/music/Artist/Album")));
+    }
+
+    #[test]
+    fn album_directory_skips_disk_directories() {
+        for disk in &["CD1", "Disc 2", "disk-03", "cd_4"] {
+            let path = Path::new("/music/Album").join(disk).join("01 Song.flac");
+            assert_eq!(
+                album_directory(&path).as_deref(),
+                Some(Path::new("/music/Album"))
+            );
+        }
+    }
+
+    #[test]
+    fn album_directory_keeps_other_directories() {
+        for name in &["CD", "Discography", "cdx1"] {
+            let path = Path::new("/music").join(name).join("01 Song.flac");
+            assert_eq!(
+                album_directory(&path).as_deref(),
+                Some(path.parent().unwrap())
+            );
+        }
+    }
+}
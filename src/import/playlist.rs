@@ -0,0 +1,191 @@
+//! Imports playlist files as playlists, matching their entries to the songs in the library.
+
+use crate::database::{playlist, playlist_item};
+use crate::export::playlist::PlaylistFormat;
+use crate::import::errors;
+use crate::import::{m3u, xspf};
+use crate::matching::{SongKey, SongMatcher};
+use crate::models::*;
+use chrono::{DateTime, Utc};
+use diesel::prelude::*;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// The extensions of playlist files which are imported.
+pub const PLAYLIST_EXTENSIONS: [&str; 4] = ["m3u", "m3u8", "xspf", "jspf"];
+
+/// A song in a playlist file, described by whatever the file knows about it.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct PlaylistEntry {
+    pub path: Option<PathBuf>,
+
+    /// The MusicBrainz recording ID of the song.
+    pub mbid: Option<String>,
+    pub title: Option<String>,
+    pub artists: Vec<String>,
+    pub album: Option<String>,
+
+    /// The length of the song in milliseconds.
+    pub duration: Option<i32>,
+}
+
+/// The title and entries of a playlist file.
+#[derive(Debug, Default)]
+pub struct PlaylistFile {
+    pub title: Option<String>,
+    pub entries: Vec<PlaylistEntry>,
+}
+
+impl PlaylistEntry {
+    pub fn key(&self) -> SongKey {
+        SongKey {
+            path: self.path.clone(),
+            mbid: self.mbid.clone(),
+            title: self.title.clone().unwrap_or_default(),
+            artists: self.artists.clone(),
+            album: self.album.clone(),
+            ..SongKey::default()
+        }
+    }
+}
+
+/// A playlist imported from a file.
+pub struct ImportedPlaylist {
+    pub playlist: Playlist,
+
+    /// The number of entries which aren't songs in the library.
+    pub missing: usize,
+}
+
+pub fn is_playlist_file(path: &Path) -> bool {
+    path.extension()
+        .and_then(|extension| extension.to_str())
+        .map_or(false, |extension| {
+            PLAYLIST_EXTENSIONS.contains(&extension.to_lowercase().as_ref())
+        })
+}
+
+/// Reads the playlist file at `path` in the format its extension stands for.
+pub fn read_file(path: &Path) -> errors::Result<PlaylistFile> {
+    let format = path
+        .extension()
+        .and_then(|extension| extension.to_str())
+        .and_then(|extension| extension.parse().ok());
+
+    match format {
+        Some(PlaylistFormat::Xspf) => xspf::read_xspf(path),
+        Some(PlaylistFormat::Jspf) => xspf::read_jspf(path),
+        _ => Ok(m3u::read(path)?),
+    }
+}
+
+/// Parses the contents of an uploaded playlist file. Only absolute locations can be files, other
+/// entries are matched by what the file knows about their songs.
+pub fn parse(format: PlaylistFormat, contents: &str) -> errors::Result<PlaylistFile> {
+    let root = Path::new("/");
+
+    match format {
+        PlaylistFormat::M3u8 => Ok(m3u::parse(contents, root)),
+        PlaylistFormat::Xspf => xspf::parse_xspf(contents, root),
+        PlaylistFormat::Jspf => xspf::parse_jspf(contents, root),
+    }
+}
+
+/// The songs of the entries which are in the library, in order, and the number of entries which
+/// aren't.
+fn match_entries(file: &PlaylistFile, matcher: &SongMatcher) -> (Vec<UUID>, usize) {
+    let song_ids: Vec<UUID> = file
+        .entries
+        .iter()
+        .filter_map(|entry| matcher.find(&entry.key()))
+        .collect();
+    let missing = file.entries.len() - song_ids.len();
+
+    (song_ids, missing)
+}
+
+/// Adds a playlist of `user_id` with the songs of the file's entries which are in the library.
+pub fn import(
+    conn: &SqliteConnection,
+    user_id: UUID,
+    file: &PlaylistFile,
+    name: &str,
+    shared: bool,
+    matcher: &SongMatcher,
+    now: NaiveDateTime,
+) -> QueryResult<ImportedPlaylist> {
+    let (song_ids, missing) = match_entries(file, matcher);
+
+    let playlist = conn.transaction::<_, diesel::result::Error, _>(|| {
+        let playlist = Playlist::create(conn, user_id, name, shared, None, now)?;
+        Playlist::add_songs(conn, playlist.id, &song_ids, None, now)?;
+
+        Ok(playlist)
+    })?;
+
+    Ok(ImportedPlaylist { playlist, missing })
+}
+
+/// Imports the playlist file at `path` in the library as a shared playlist of `user_id`. A
+/// playlist which was imported from the file before is refreshed if the file was modified
+/// since, otherwise nothing is imported and `None` is returned.
+pub fn import_file(
+    conn: &SqliteConnection,
+    path: &Path,
+    user_id: UUID,
+    matcher: &SongMatcher,
+    now: NaiveDateTime,
+) -> errors::Result<Option<ImportedPlaylist>> {
+    let modified = DateTime::<Utc>::from(fs::metadata(path)?.modified()?).naive_utc();
+
+    let existing: Option<Playlist> = playlist::table
+        .filter(playlist::source_path.eq(PathWrapper::from(path)))
+        .first(conn)
+        .optional()?;
+    if let Some(existing) = &existing {
+        if existing.source_modified == Some(modified) {
+            return Ok(None);
+        }
+    }
+
+    let file = read_file(path)?;
+
+    let playlist = conn.transaction::<_, diesel::result::Error, _>(|| {
+        let imported = match existing {
+            Some(existing) => {
+                diesel::delete(
+                    playlist_item::table.filter(playlist_item::playlist_id.eq(existing.id)),
+                )
+                .execute(conn)?;
+
+                let (song_ids, missing) = match_entries(&file, matcher);
+                Playlist::add_songs(conn, existing.id, &song_ids, None, now)?;
+                ImportedPlaylist {
+                    playlist: existing,
+                    missing,
+                }
+            }
+            None => {
+                let name = file.title.clone().unwrap_or_else(|| {
+                    path.file_stem()
+                        .map_or_else(String::new, |stem| stem.to_string_lossy().into_owned())
+                });
+                import(conn, user_id, &file, &name, true, matcher, now)?
+            }
+        };
+
+        diesel::update(playlist::table.find(imported.playlist.id))
+            .set((
+                playlist::source_path.eq(PathWrapper::from(path)),
+                playlist::source_modified.eq(modified),
+            ))
+            .execute(conn)?;
+
+        Ok(ImportedPlaylist {
+            playlist: Playlist::from_id(conn, imported.playlist.id)?,
+            missing: imported.missing,
+        })
+    })?;
+
+    Ok(Some(playlist))
+}
@@ -0,0 +1,133 @@
+//! Reads the star ratings other players store in the tags of audio files, and writes ratings
+//! back so those players see them.
+
+use crate::database::{song, user_song_stats};
+use crate::import::itunes::rating_to_stars;
+use crate::models::*;
+use diesel::prelude::*;
+use std::path::Path;
+use taglib2_sys::SongProperties;
+
+/// The property holding the rating from 0.0 to 1.0 in the freedesktop.org metadata spec.
+const FMPS_RATING_PROPERTY: &str = "FMPS_RATING";
+
+/// The property Vorbis comment players store ratings in, either from 1 to 5 or from 0 to 100.
+const RATING_PROPERTY: &str = "RATING";
+
+/// The rating of a song from 1 to 5 stars read from its tags. A POPM frame is preferred over the
+/// `FMPS_RATING` property, which is preferred over the `RATING` property.
+pub fn stars_from_tags(props: &SongProperties) -> Option<i32> {
+    props
+        .popularimeter
+        .and_then(popularimeter_to_stars)
+        .or_else(|| props.property(FMPS_RATING_PROPERTY).and_then(fmps_to_stars))
+        .or_else(|| {
+            props
+                .property(RATING_PROPERTY)
+                .and_then(rating_property_to_stars)
+        })
+}
+
+/// Converts a POPM rating from 1 to 255 into stars, using the ranges Windows uses. A rating of 0
+/// means the song wasn't rated.
+fn popularimeter_to_stars(rating: u8) -> Option<i32> {
+    match rating {
+        0 => None,
+        1..=31 => Some(1),
+        32..=95 => Some(2),
+        96..=159 => Some(3),
+        160..=223 => Some(4),
+        _ => Some(5),
+    }
+}
+
+fn fmps_to_stars(value: &str) -> Option<i32> {
+    let rating: f64 = value.trim().parse().ok()?;
+    if !(0.0..=1.0).contains(&rating) {
+        return None;
+    }
+
+    let stars = (rating * 5.0).round() as i32;
+    if stars > 0 {
+        Some(stars)
+    } else {
+        None
+    }
+}
+
+/// Converts a `RATING` property into stars. Values up to 5 are stars, larger ones are out of 100.
+fn rating_property_to_stars(value: &str) -> Option<i32> {
+    match value.trim().parse::<i32>().ok()? {
+        stars @ 1..=5 => Some(stars),
+        rating @ 6..=100 => rating_to_stars(rating),
+        _ => None,
+    }
+}
+
+/// Sets the user's rating of the song at `path` to `stars`, unless they already rated it.
+/// Returns whether the rating was set.
+pub fn import(
+    conn: &SqliteConnection,
+    user_id: UUID,
+    path: &Path,
+    stars: i32,
+) -> QueryResult<bool> {
+    let song_id = song::table
+        .filter(song::path.eq(PathWrapper::from(path)))
+        .select(song::id)
+        .first::<UUID>(conn)
+        .optional()?;
+
+    let song_id = match song_id {
+        Some(song_id) => song_id,
+        None => return Ok(false),
+    };
+
+    SongStats::ensure(conn, user_id, song_id)?;
+    let updated = diesel::update(user_song_stats::table.find((user_id, song_id)))
+        .filter(user_song_stats::rating.is_null())
+        .set(user_song_stats::rating.eq(stars))
+        .execute(conn)?;
+
+    Ok(updated > 0)
+}
+
+/// Writes a rating from 1 to 5 stars into the tags of the file at `path`, or removes the rating
+/// when `stars` is `None`.
+pub fn write(path: &Path, stars: Option<i32>) -> Result<(), taglib2_sys::Error> {
+    taglib2_sys::write_rating_tags(path, stars.map(|stars| stars.max(0).min(5) as u8))
+}
+
+#[cfg(test)]
+mod test {
+    use super::{fmps_to_stars, popularimeter_to_stars, rating_property_to_stars};
+
+    #[test]
+    fn popularimeter_to_stars_uses_windows_ranges() {
+        assert_eq!(popularimeter_to_stars(0), None);
+        assert_eq!(popularimeter_to_stars(1), Some(1));
+        assert_eq!(popularimeter_to_stars(64), Some(2));
+        assert_eq!(popularimeter_to_stars(128), Some(3));
+        assert_eq!(popularimeter_to_stars(196), Some(4));
+        assert_eq!(popularimeter_to_stars(255), Some(5));
+    }
+
+    #[test]
+    fn fmps_to_stars_rounds() {
+        assert_eq!(fmps_to_stars("0.0"), None);
+        assert_eq!(fmps_to_stars("0.2"), Some(1));
+        assert_eq!(fmps_to_stars("0.5"), Some(3));
+        assert_eq!(fmps_to_stars("1"), Some(5));
+        assert_eq!(fmps_to_stars("1.5"), None);
+        assert_eq!(fmps_to_stars("great"), None);
+    }
+
+    #[test]
+    fn rating_property_accepts_stars_and_percentages() {
+        assert_eq!(rating_property_to_stars("0"), None);
+        assert_eq!(rating_property_to_stars("4"), Some(4));
+        assert_eq!(rating_property_to_stars("60"), Some(3));
+        assert_eq!(rating_property_to_stars("100"), Some(5));
+        assert_eq!(rating_property_to_stars("255"), None);
+    }
+}
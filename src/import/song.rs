@@ -1,8 +1,11 @@
 use super::errors;
 use crate::database::song;
 use crate::database::song_artist;
+use crate::database::song_rescan;
 use crate::import::album::add_or_get_album;
 use crate::import::artist::add_or_get_artist;
+use crate::import::pictures::add_album_pictures;
+use crate::import::plan::ImportPlan;
 use crate::models::*;
 use chrono::prelude::*;
 use diesel::prelude::*;
@@ -22,6 +25,52 @@ pub fn is_imported(path: &Path, conn: &SqliteConnection) -> errors::Result<bool>
     Ok(does_exist.is_some())
 }
 
+/// Checks whether the already imported file at the path has to have its tags read again because
+/// it was imported by an older version.
+pub fn needs_rescan(path: &Path, conn: &SqliteConnection) -> errors::Result<bool> {
+    let song_ids = song::table
+        .select(song::id)
+        .filter(song::path.eq(PathWrapper::from(path)));
+
+    let count: i64 = song_rescan::table
+        .filter(song_rescan::song_id.eq_any(song_ids))
+        .count()
+        .get_result(conn)?;
+
+    Ok(count > 0)
+}
+
+/// Updates the values of the already imported song at the path which older versions didn't
+/// import precisely or at all, like durations and genres, from its tags.
+pub fn rescan_song(
+    path: &Path,
+    props: &SongProperties,
+    conn: &SqliteConnection,
+) -> errors::Result<()> {
+    let plan = ImportPlan::new(path, props)?;
+    let path = PathWrapper::from(path);
+
+    conn.transaction::<(), result::Error, _>(|| {
+        let song_id: UUID = song::table
+            .select(song::id)
+            .filter(song::path.eq(&path))
+            .first(conn)?;
+
+        diesel::update(song::table.find(song_id))
+            .set((
+                song::duration.eq(plan.duration),
+                song::genre.eq(&plan.genre),
+            ))
+            .execute(conn)?;
+
+        diesel::delete(song_rescan::table.find(song_id)).execute(conn)?;
+
+        Ok(())
+    })?;
+
+    Ok(())
+}
+
 /// Takes information about a song read from tags and adds it to the database.
 pub fn add_song(
     path: &Path,
@@ -29,42 +78,33 @@ pub fn add_song(
     props: SongProperties,
     conn: &SqliteConnection,
 ) -> super::errors::Result<()> {
-    let artist: Option<Artist> = props
-        .artist
-        .as_ref()
-        .map_or(Ok(None), |name| add_or_get_artist(name, conn).map(Some))?;
-
-    let album_artist: Option<Artist> = props
-        .album_artist
-        .as_ref()
-        .map_or(Ok(None), |name| add_or_get_artist(name, conn).map(Some))?;
-
-    let (artist, album_artist) = (
-        artist
-            .as_ref()
-            .or_else(|| album_artist.as_ref())
-            .ok_or(errors::Error::NoArtistError)?,
-        album_artist
-            .as_ref()
-            .or_else(|| artist.as_ref())
-            .ok_or(errors::Error::NoArtistError)?,
-    );
-
-    let album = add_or_get_album(path, artwork_directory, &props, album_artist.id, conn)?;
+    let plan = ImportPlan::new(path, &props)?;
+
+    let artist = add_or_get_artist(&plan.artist, conn)?;
+    let album_artist = add_or_get_artist(&plan.album_artist, conn)?;
+
+    let album = add_or_get_album(
+        path,
+        artwork_directory,
+        &props,
+        &plan,
+        album_artist.id,
+        conn,
+    )?;
+    add_album_pictures(album.id, artwork_directory, &props, conn)?;
 
     let song_id = UUID::new();
     let song = Song {
         id: song_id,
-        name: props.title.ok_or(errors::Error::NoTitleError)?,
+        name: plan.title,
         album_id: album.id,
-        track_number: props.track_number as i32,
-        disk_number: props.disk_number.map_or(1, |n| n as i32),
-        duration: props.duration,
+        track_number: plan.track_number,
+        disk_number: plan.disk_number,
+        duration: plan.duration,
         time_added: Utc::now().naive_utc(),
-        play_count: 0,
-        last_played: None,
-        liked: false,
         path: path.into(),
+        mbid: plan.mbid,
+        genre: plan.genre,
     };
 
     conn.transaction::<(), result::Error, _>(|| {
@@ -0,0 +1,275 @@
+//! Reads XSPF playlists and JSPF, the same playlists written as JSON.
+
+use crate::import::errors;
+use crate::import::playlist::{PlaylistEntry, PlaylistFile};
+use quick_xml::events::Event;
+use quick_xml::Reader;
+use serde::{Deserialize, Deserializer, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+use url::Url;
+
+/// Identifiers of songs which start with this are followed by their MusicBrainz recording ID.
+pub const MUSICBRAINZ_RECORDING_URL: &str = "https://musicbrainz.org/recording/";
+
+/// A JSPF file, shared with the exporter so both read and write the same fields.
+#[derive(Serialize, Deserialize, Default)]
+pub struct Jspf {
+    pub playlist: JspfPlaylist,
+}
+
+#[derive(Serialize, Deserialize, Default)]
+pub struct JspfPlaylist {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub title: Option<String>,
+
+    #[serde(default)]
+    pub track: Vec<JspfTrack>,
+}
+
+#[derive(Serialize, Deserialize, Default)]
+pub struct JspfTrack {
+    /// URIs of the song. JSPF has lists here, but some writers only write one string.
+    #[serde(
+        default,
+        deserialize_with = "one_or_many",
+        skip_serializing_if = "Vec::is_empty"
+    )]
+    pub location: Vec<String>,
+
+    #[serde(
+        default,
+        deserialize_with = "one_or_many",
+        skip_serializing_if = "Vec::is_empty"
+    )]
+    pub identifier: Vec<String>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub title: Option<String>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub creator: Option<String>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub album: Option<String>,
+
+    #[serde(default, rename = "trackNum", skip_serializing_if = "Option::is_none")]
+    pub track_num: Option<i32>,
+
+    /// The length of the song in milliseconds.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub duration: Option<i32>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub image: Option<String>,
+}
+
+fn one_or_many<'de, D>(deserializer: D) -> Result<Vec<String>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum OneOrMany {
+        One(String),
+        Many(Vec<String>),
+    }
+
+    Ok(match OneOrMany::deserialize(deserializer)? {
+        OneOrMany::One(value) => vec![value],
+        OneOrMany::Many(values) => values,
+    })
+}
+
+pub fn read_xspf(path: &Path) -> errors::Result<PlaylistFile> {
+    let contents = fs::read_to_string(path)?;
+    parse_xspf(&contents, path.parent().unwrap_or_else(|| Path::new("/")))
+}
+
+pub fn read_jspf(path: &Path) -> errors::Result<PlaylistFile> {
+    let contents = fs::read_to_string(path)?;
+    parse_jspf(&contents, path.parent().unwrap_or_else(|| Path::new("/")))
+}
+
+/// Parses an XSPF playlist. Relative locations are resolved against `directory`.
+pub fn parse_xspf(contents: &str, directory: &Path) -> errors::Result<PlaylistFile> {
+    let mut reader = Reader::from_str(contents);
+    reader.trim_text(true);
+
+    let mut file = PlaylistFile::default();
+    let mut track: Option<PlaylistEntry> = None;
+    let mut elements: Vec<Vec<u8>> = Vec::new();
+    let mut buf = Vec::new();
+
+    loop {
+        let text = match reader.read_event(&mut buf)? {
+            Event::Start(start) => {
+                if start.local_name() == b"track" {
+                    track = Some(PlaylistEntry::default());
+                }
+                elements.push(start.local_name().to_vec());
+                None
+            }
+            Event::End(_) => {
+                if elements.pop().as_deref() == Some(b"track") {
+                    file.entries.extend(track.take());
+                }
+                None
+            }
+            Event::Text(text) => Some(text.unescape_and_decode(&reader)?),
+            Event::CData(text) => Some(String::from_utf8_lossy(text.escaped()).into_owned()),
+            Event::Eof => break,
+            _ => None,
+        };
+
+        if let Some(text) = text {
+            let parent = elements.len().checked_sub(2).map(|index| &elements[index]);
+            match (
+                parent.map(Vec::as_slice),
+                elements.last().map(Vec::as_slice),
+            ) {
+                (Some(b"playlist"), Some(b"title")) => file.title = Some(text),
+                (Some(b"track"), Some(element)) => {
+                    if let Some(track) = &mut track {
+                        read_track_element(track, element, text, directory);
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        buf.clear();
+    }
+
+    Ok(file)
+}
+
+fn read_track_element(track: &mut PlaylistEntry, element: &[u8], text: String, directory: &Path) {
+    match element {
+        // Tracks can have several locations, the first one which is a file is used.
+        b"location" if track.path.is_none() => track.path = resolve(&text, directory),
+        b"identifier" => {
+            if let Some(mbid) = text.strip_prefix(MUSICBRAINZ_RECORDING_URL) {
+                track.mbid = Some(mbid.trim_end_matches('/').to_string());
+            }
+        }
+        b"title" => track.title = Some(text),
+        b"creator" => track.artists = vec![text],
+        b"album" => track.album = Some(text),
+        b"duration" => track.duration = text.parse().ok(),
+        _ => {}
+    }
+}
+
+/// Parses a JSPF playlist. Relative locations are resolved against `directory`.
+pub fn parse_jspf(contents: &str, directory: &Path) -> errors::Result<PlaylistFile> {
+    let jspf: Jspf = serde_json::from_str(contents)?;
+
+    let entries = jspf
+        .playlist
+        .track
+        .into_iter()
+        .map(|track| PlaylistEntry {
+            path: track
+                .location
+                .iter()
+                .find_map(|location| resolve(location, directory)),
+            mbid: track.identifier.iter().find_map(|identifier| {
+                identifier
+                    .strip_prefix(MUSICBRAINZ_RECORDING_URL)
+                    .map(|mbid| mbid.trim_end_matches('/').to_string())
+            }),
+            title: track.title,
+            artists: track.creator.into_iter().collect(),
+            album: track.album,
+            duration: track.duration,
+        })
+        .collect();
+
+    Ok(PlaylistFile {
+        title: jspf.playlist.title,
+        entries,
+    })
+}
+
+/// The path of the file at `location`, a URI which can be relative to `directory`. Locations
+/// which aren't files, like stream URLs, have no path.
+fn resolve(location: &str, directory: &Path) -> Option<PathBuf> {
+    let base = Url::from_directory_path(directory).ok()?;
+    base.join(location.trim()).ok()?.to_file_path().ok()
+}
+
+#[cfg(test)]
+mod test {
+    use super::{parse_jspf, parse_xspf};
+    use std::path::{Path, PathBuf};
+
+    #[test]
+    fn xspf() {
+        let contents = r#"<?xml version="1.0" encoding="UTF-8"?>
+            <playlist version="1" xmlns="http://xspf.org/ns/0/">
+              <title>Abbey Road &amp; more</title>
+              <trackList>
+                <track>
+                  <location>http://example.com/stream</location>
+                  <location>Abbey%20Road/01%20Come%20Together.flac</location>
+                  <identifier>https://musicbrainz.org/recording/1234</identifier>
+                  <title>Come Together</title>
+                  <creator>The Beatles</creator>
+                  <album>Abbey Road</album>
+                  <duration>259400</duration>
+                </track>
+                <track>
+                  <title><![CDATA[Something]]></title>
+                </track>
+              </trackList>
+            </playlist>"#;
+
+        let file = parse_xspf(contents, Path::new("/music/Beatles")).unwrap();
+
+        assert_eq!(file.title.as_deref(), Some("Abbey Road & more"));
+        assert_eq!(file.entries.len(), 2);
+
+        let first = &file.entries[0];
+        assert_eq!(
+            first.path,
+            Some(PathBuf::from(
+                "/music/Beatles/Abbey Road/01 Come Together.flac"
+            ))
+        );
+        assert_eq!(first.mbid.as_deref(), Some("1234"));
+        assert_eq!(first.title.as_deref(), Some("Come Together"));
+        assert_eq!(first.artists, vec!["The Beatles".to_string()]);
+        assert_eq!(first.album.as_deref(), Some("Abbey Road"));
+        assert_eq!(first.duration, Some(259_400));
+
+        assert_eq!(file.entries[1].title.as_deref(), Some("Something"));
+        assert_eq!(file.entries[1].path, None);
+    }
+
+    #[test]
+    fn jspf() {
+        let contents = r#"{
+            "playlist": {
+                "title": "Mix",
+                "track": [
+                    {
+                        "location": ["file:///music/a.flac"],
+                        "identifier": "https://musicbrainz.org/recording/5678",
+                        "title": "A",
+                        "creator": "B"
+                    },
+                    { "title": "C" }
+                ]
+            }
+        }"#;
+
+        let file = parse_jspf(contents, Path::new("/")).unwrap();
+
+        assert_eq!(file.title.as_deref(), Some("Mix"));
+        assert_eq!(file.entries[0].path, Some(PathBuf::from("/music/a.flac")));
+        assert_eq!(file.entries[0].mbid.as_deref(), Some("5678"));
+        assert_eq!(file.entries[0].artists, vec!["B".to_string()]);
+        assert_eq!(file.entries[1].title.as_deref(), Some("C"));
+    }
+}
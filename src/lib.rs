@@ -1,7 +1,22 @@
 #[macro_use]
 extern crate diesel;
 
+#[cfg(test)]
+#[macro_use]
+extern crate diesel_migrations;
+
+pub mod auth;
 pub mod context;
 pub mod database;
+pub mod export;
 pub mod import;
+pub mod matching;
 pub mod models;
+pub mod ordering;
+pub mod radio;
+pub mod rules;
+pub mod scrobbler;
+pub mod userdata;
+
+#[cfg(test)]
+mod test_database;
@@ -0,0 +1,494 @@
+//! Finds songs in the library from what other databases know about them, like a file path,
+//! MusicBrainz ID or their tags. Used to bring data from outside the library (exports, other
+//! players, scrobbling services) back onto the songs it's about.
+
+use crate::database::album;
+use crate::database::artist;
+use crate::database::song;
+use crate::database::song_artist;
+use crate::models::*;
+use diesel::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::path::PathBuf;
+
+/// Identifies a song without its database id, which changes whenever the library is rebuilt.
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct SongKey {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub path: Option<PathBuf>,
+
+    /// The MusicBrainz recording ID of the song.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub mbid: Option<String>,
+
+    pub title: String,
+
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub artists: Vec<String>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub album: Option<String>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub track_number: Option<i32>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub disk_number: Option<i32>,
+}
+
+impl SongKey {
+    /// Loads the keys of every song in the library.
+    pub fn load_all(conn: &SqliteConnection) -> QueryResult<HashMap<UUID, SongKey>> {
+        let songs: Vec<(UUID, PathWrapper, Option<String>, String, String, i32, i32)> = song::table
+            .inner_join(album::table)
+            .select((
+                song::id,
+                song::path,
+                song::mbid,
+                song::name,
+                album::name,
+                song::track_number,
+                song::disk_number,
+            ))
+            .load(conn)?;
+
+        let mut keys: HashMap<UUID, SongKey> = songs
+            .into_iter()
+            .map(
+                |(id, path, mbid, title, album, track_number, disk_number)| {
+                    let key = SongKey {
+                        path: Some(path.into()),
+                        mbid,
+                        title,
+                        artists: Vec::new(),
+                        album: Some(album),
+                        track_number: Some(track_number),
+                        disk_number: Some(disk_number),
+                    };
+
+                    (id, key)
+                },
+            )
+            .collect();
+
+        let artists: Vec<(UUID, String)> = song_artist::table
+            .inner_join(artist::table)
+            .select((song_artist::song_id, artist::name))
+            .order_by(artist::name)
+            .load(conn)?;
+
+        for (song_id, artist) in artists {
+            if let Some(key) = keys.get_mut(&song_id) {
+                key.artists.push(artist);
+            }
+        }
+
+        Ok(keys)
+    }
+}
+
+/// Simplifies a name for comparison, ignoring case, punctuation and spacing.
+pub fn normalize(name: &str) -> String {
+    name.split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .map(str::to_lowercase)
+        .collect::<Vec<String>>()
+        .join(" ")
+}
+
+/// Simplifies a title further than [`normalize`], dropping what's usually added to the titles
+/// of other versions of the same recording or release, like "(Remastered 2009)", "[Live]" or
+/// " - Single Version".
+pub fn simplify_title(title: &str) -> String {
+    let mut outside_brackets = String::with_capacity(title.len());
+    let mut depth = 0;
+    for c in title.chars() {
+        match c {
+            '(' | '[' => depth += 1,
+            ')' | ']' if depth > 0 => depth -= 1,
+            _ if depth == 0 => outside_brackets.push(c),
+            _ => {}
+        }
+    }
+
+    let simplified = normalize(outside_brackets.split(" - ").next().unwrap_or_default());
+    if simplified.is_empty() {
+        normalize(title)
+    } else {
+        simplified
+    }
+}
+
+/// Splits an artist credit like "Artist feat. Someone & Someone Else" into the normalized names
+/// of the artists credited, keeping the whole credit too since it can be a single artist's name
+/// (like "Simon & Garfunkel").
+pub fn split_artists(credit: &str) -> HashSet<String> {
+    let lowercase = credit.to_lowercase();
+    let mut parts: HashSet<&str> = HashSet::new();
+    parts.insert(&lowercase);
+
+    // Featured artists are split off first, so "A & B feat. C" keeps "A & B".
+    for separator in &[
+        " featuring ",
+        " feat. ",
+        " feat ",
+        " ft. ",
+        " & ",
+        ", ",
+        " and ",
+        " x ",
+    ] {
+        let split: Vec<&str> = parts
+            .iter()
+            .flat_map(|part| part.split(separator))
+            .collect();
+        parts.extend(split);
+    }
+
+    let mut artists: HashSet<String> = parts.into_iter().map(normalize).collect();
+    artists.remove("");
+
+    artists
+}
+
+struct Candidate {
+    id: UUID,
+    artists: HashSet<String>,
+    track_number: Option<i32>,
+    disk_number: Option<i32>,
+}
+
+impl Candidate {
+    fn has_any_artist(&self, artists: &[String]) -> bool {
+        artists.is_empty()
+            || artists
+                .iter()
+                .any(|artist| self.artists.contains(&normalize(artist)))
+    }
+
+    fn has_position(&self, key: &SongKey) -> bool {
+        key.track_number
+            .map_or(true, |n| self.track_number == Some(n))
+            && key
+                .disk_number
+                .map_or(true, |n| self.disk_number == Some(n))
+    }
+}
+
+/// A song which might match a key whose exact tags don't match any song.
+struct FuzzyCandidate {
+    id: UUID,
+
+    /// Every artist credited, see [`split_artists`].
+    artists: HashSet<String>,
+
+    /// The simplified name of the album.
+    album: Option<String>,
+}
+
+/// Finds songs in the library by their keys. Songs are matched by path, then by MusicBrainz ID,
+/// then by their title, album and artists, and finally by title and artists alone when only one
+/// song matches.
+pub struct SongMatcher {
+    by_path: HashMap<PathBuf, UUID>,
+    by_mbid: HashMap<String, UUID>,
+
+    /// Candidates by normalized title and album.
+    by_album: HashMap<(String, String), Vec<Candidate>>,
+
+    /// Candidates by normalized title.
+    by_title: HashMap<String, Vec<Candidate>>,
+
+    /// Candidates by simplified title.
+    by_simple_title: HashMap<String, Vec<FuzzyCandidate>>,
+}
+
+impl SongMatcher {
+    pub fn load(conn: &SqliteConnection) -> QueryResult<SongMatcher> {
+        Ok(SongMatcher::new(SongKey::load_all(conn)?))
+    }
+
+    pub fn new(keys: HashMap<UUID, SongKey>) -> SongMatcher {
+        let mut matcher = SongMatcher {
+            by_path: HashMap::new(),
+            by_mbid: HashMap::new(),
+            by_album: HashMap::new(),
+            by_title: HashMap::new(),
+            by_simple_title: HashMap::new(),
+        };
+
+        for (id, key) in keys {
+            if let Some(path) = key.path.clone() {
+                matcher.by_path.insert(path, id);
+            }
+
+            if let Some(mbid) = key.mbid.clone() {
+                matcher.by_mbid.insert(mbid, id);
+            }
+
+            let title = normalize(&key.title);
+            let candidate = || Candidate {
+                id,
+                artists: key.artists.iter().map(|artist| normalize(artist)).collect(),
+                track_number: key.track_number,
+                disk_number: key.disk_number,
+            };
+
+            if let Some(album) = &key.album {
+                matcher
+                    .by_album
+                    .entry((title.clone(), normalize(album)))
+                    .or_default()
+                    .push(candidate());
+            }
+
+            matcher.by_title.entry(title).or_default().push(candidate());
+
+            matcher
+                .by_simple_title
+                .entry(simplify_title(&key.title))
+                .or_default()
+                .push(FuzzyCandidate {
+                    id,
+                    artists: key
+                        .artists
+                        .iter()
+                        .flat_map(|artist| split_artists(artist))
+                        .collect(),
+                    album: key.album.as_deref().map(simplify_title),
+                });
+        }
+
+        matcher
+    }
+
+    /// Finds the song identified by `key`, if it's in the library.
+    pub fn find(&self, key: &SongKey) -> Option<UUID> {
+        if let Some(id) = key.path.as_ref().and_then(|path| self.by_path.get(path)) {
+            return Some(*id);
+        }
+
+        if let Some(id) = key.mbid.as_ref().and_then(|mbid| self.by_mbid.get(mbid)) {
+            return Some(*id);
+        }
+
+        let title = normalize(&key.title);
+
+        if let Some(album) = &key.album {
+            let candidates: Vec<&Candidate> = self
+                .by_album
+                .get(&(title.clone(), normalize(album)))
+                .into_iter()
+                .flatten()
+                .filter(|candidate| candidate.has_any_artist(&key.artists))
+                .collect();
+
+            // The same album can be in the library more than once, in different editions.
+            let best = candidates
+                .iter()
+                .find(|candidate| candidate.has_position(key))
+                .or_else(|| candidates.first());
+
+            if let Some(candidate) = best {
+                return Some(candidate.id);
+            }
+        }
+
+        // Without a matching album, the title and artists have to be unambiguous.
+        if key.artists.is_empty() {
+            return None;
+        }
+
+        let mut candidates = self
+            .by_title
+            .get(&title)
+            .into_iter()
+            .flatten()
+            .filter(|candidate| candidate.has_any_artist(&key.artists));
+
+        match (candidates.next(), candidates.next()) {
+            (Some(candidate), None) => Some(candidate.id),
+            _ => None,
+        }
+    }
+
+    /// Finds the song identified by `key` like [`find`](SongMatcher::find), falling back to
+    /// comparing simplified titles and the individual artists credited. For keys from sources
+    /// which spell tags differently than the files in the library, like scrobbling services.
+    pub fn find_fuzzy(&self, key: &SongKey) -> Option<UUID> {
+        if let Some(id) = self.find(key) {
+            return Some(id);
+        }
+
+        let artists: HashSet<String> = key
+            .artists
+            .iter()
+            .flat_map(|artist| split_artists(artist))
+            .collect();
+
+        let candidates: Vec<&FuzzyCandidate> = self
+            .by_simple_title
+            .get(&simplify_title(&key.title))
+            .into_iter()
+            .flatten()
+            .filter(|candidate| !candidate.artists.is_disjoint(&artists))
+            .collect();
+
+        if let Some(album) = key.album.as_deref().map(simplify_title) {
+            let on_album = candidates
+                .iter()
+                .find(|candidate| candidate.album.as_ref() == Some(&album));
+
+            if let Some(candidate) = on_album {
+                return Some(candidate.id);
+            }
+        }
+
+        match candidates.as_slice() {
+            [candidate] => Some(candidate.id),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{normalize, simplify_title, split_artists, SongKey, SongMatcher};
+    use crate::models::UUID;
+    use std::collections::HashMap;
+    use std::path::PathBuf;
+
+    fn key(title: &str, artist: &str, album: &str, track_number: i32) -> SongKey {
+        SongKey {
+            title: title.to_string(),
+            artists: vec![artist.to_string()],
+            album: Some(album.to_string()),
+            track_number: Some(track_number),
+            disk_number: Some(1),
+            ..SongKey::default()
+        }
+    }
+
+    fn matcher() -> SongMatcher {
+        let mut keys = HashMap::new();
+        keys.insert(
+            UUID::from_number(1),
+            SongKey {
+                path: Some(PathBuf::from("/music/Abbey Road/01 Come Together.flac")),
+                mbid: Some("mbid-1".to_string()),
+                ..key("Come Together", "The Beatles", "Abbey Road", 1)
+            },
+        );
+        keys.insert(
+            UUID::from_number(2),
+            key("Something", "The Beatles", "Abbey Road", 2),
+        );
+        keys.insert(
+            UUID::from_number(3),
+            key("Something", "The Beatles", "1", 16),
+        );
+        keys.insert(
+            UUID::from_number(4),
+            key("Help!", "The Beatles", "Help!", 1),
+        );
+
+        SongMatcher::new(keys)
+    }
+
+    #[test]
+    fn normalize_ignores_case_and_punctuation() {
+        assert_eq!(normalize("  Help!  (Remastered) "), "help remastered");
+        assert_eq!(normalize("AC/DC"), "ac dc");
+    }
+
+    #[test]
+    fn simplify_title_drops_versions() {
+        assert_eq!(simplify_title("Something (Remastered 2009)"), "something");
+        assert_eq!(simplify_title("Help! - Single Version"), "help");
+        assert_eq!(
+            simplify_title("Come Together [Live] (2019 Mix)"),
+            "come together"
+        );
+        assert_eq!(simplify_title("(What's the Story)"), "what s the story");
+    }
+
+    #[test]
+    fn split_artists_keeps_whole_credit() {
+        let artists = split_artists("Simon & Garfunkel feat. Someone");
+        for artist in &["simon garfunkel", "simon", "garfunkel", "someone"] {
+            assert!(artists.contains(*artist), "missing {}", artist);
+        }
+    }
+
+    #[test]
+    fn matches_path_and_mbid_first() {
+        let matcher = matcher();
+
+        let by_path = SongKey {
+            path: Some(PathBuf::from("/music/Abbey Road/01 Come Together.flac")),
+            title: "Something else".to_string(),
+            ..SongKey::default()
+        };
+        assert_eq!(matcher.find(&by_path), Some(UUID::from_number(1)));
+
+        let by_mbid = SongKey {
+            mbid: Some("mbid-1".to_string()),
+            ..SongKey::default()
+        };
+        assert_eq!(matcher.find(&by_mbid), Some(UUID::from_number(1)));
+    }
+
+    #[test]
+    fn matches_tags() {
+        let matcher = matcher();
+
+        assert_eq!(
+            matcher.find(&key("something", "the beatles", "ABBEY ROAD", 2)),
+            Some(UUID::from_number(2))
+        );
+        assert_eq!(
+            matcher.find(&key("Something", "Someone Else", "Abbey Road", 2)),
+            None
+        );
+    }
+
+    #[test]
+    fn matches_title_and_artist_only_when_unambiguous() {
+        let matcher = matcher();
+        let without_album = |title: &str| SongKey {
+            title: title.to_string(),
+            artists: vec!["The Beatles".to_string()],
+            ..SongKey::default()
+        };
+
+        assert_eq!(
+            matcher.find(&without_album("Help!")),
+            Some(UUID::from_number(4))
+        );
+        assert_eq!(matcher.find(&without_album("Something")), None);
+    }
+
+    #[test]
+    fn fuzzy_matches_other_spellings() {
+        let matcher = matcher();
+
+        assert_eq!(
+            matcher.find_fuzzy(&key(
+                "Something - 2019 Mix",
+                "The Beatles feat. Nobody",
+                "Abbey Road (Super Deluxe Edition)",
+                0
+            )),
+            Some(UUID::from_number(2))
+        );
+        assert_eq!(
+            matcher.find_fuzzy(&key("Help! (Remastered)", "The Beatles", "Hits", 0)),
+            Some(UUID::from_number(4))
+        );
+        assert_eq!(
+            matcher.find_fuzzy(&key("Something (Live)", "The Beatles", "Live", 0)),
+            None
+        );
+    }
+}
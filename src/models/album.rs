@@ -1,9 +1,12 @@
 use crate::context::GraphQLContext;
 use crate::database::album;
+use crate::database::album_picture;
 use crate::database::song;
+use crate::database::user_album_stats;
 use crate::models::*;
 use diesel::dsl;
 use diesel::prelude::*;
+use diesel::sql_types::Timestamp;
 use juniper::{FieldError, FieldResult};
 
 #[derive(Queryable, Identifiable, Insertable, Clone)]
@@ -15,7 +18,13 @@ pub struct Album {
     pub artist_id: UUID,
     pub release_year: Option<i32>,
     pub time_added: NaiveDateTime,
-    pub last_played: Option<NaiveDateTime>,
+
+    /// The MusicBrainz release ID of the album.
+    pub mbid: Option<String>,
+
+    /// The directory the album's songs were imported from. Used to tell apart different albums
+    /// with the same name and artist.
+    pub directory: Option<PathWrapper>,
 }
 
 impl Album {
@@ -27,18 +36,52 @@ impl Album {
         format!("/files/artwork/{}/raw", id)
     }
 
-    pub fn stats(&self) -> UserStats {
-        UserStats {
-            id: format!("stats:{}", self.id.to_string()),
-            last_played: self.last_played,
+    /// The total length of the album's songs in milliseconds.
+    pub fn duration_ms(&self, conn: &SqliteConnection) -> QueryResult<i64> {
+        let maybe_duration: Option<i64> = song::table
+            .filter(song::album_id.eq(&self.id))
+            .select(dsl::sum(song::duration))
+            .first::<Option<i64>>(conn)?;
+
+        Ok(maybe_duration.unwrap_or(0))
+    }
+
+    /// The albums the user likes, most recently liked first.
+    pub fn liked_connection(
+        context: &GraphQLContext,
+        page: Page,
+    ) -> FieldResult<Connection<Album>> {
+        let conn = &context.connection() as &SqliteConnection;
+        let user_id = context.user_id();
+
+        let liked = || {
+            album::table
+                .inner_join(user_album_stats::table)
+                .filter(user_album_stats::user_id.eq(user_id))
+                .filter(user_album_stats::time_liked.is_not_null())
+                .into_boxed()
+        };
+        let count: i64 = liked().select(dsl::count_star()).first(conn)?;
+
+        Keyset {
+            columns: album::all_columns,
+            key: || -> SortKey<_, Timestamp> { Box::new(user_album_stats::time_liked) },
+            id: album::id,
+            descending: true,
+            nullable: false,
         }
+        .load_page(conn, liked, &page, count)
     }
 }
 
 impl GetConnection<album::table> for Album {
     type Name = album::name;
     type TimeAdded = album::time_added;
-    type LastPlayed = album::last_played;
+    type Id = album::id;
+
+    fn id() -> Self::Id {
+        album::id
+    }
 
     fn name() -> Self::Name {
         album::name
@@ -48,8 +91,20 @@ impl GetConnection<album::table> for Album {
         album::time_added
     }
 
-    fn last_played() -> Self::LastPlayed {
-        album::last_played
+    fn last_played_query() -> &'static str {
+        "SELECT last_played FROM user_album_stats
+        WHERE user_album_stats.album_id = album.id AND user_album_stats.user_id = "
+    }
+
+    fn rating_query() -> Option<&'static str> {
+        Some(
+            "SELECT rating FROM user_album_stats
+            WHERE user_album_stats.album_id = album.id AND user_album_stats.user_id = ",
+        )
+    }
+
+    fn table_name() -> &'static str {
+        "album"
     }
 }
 
@@ -84,26 +139,52 @@ impl Album {
             .map_err(FieldError::from)
     }
 
-    fn duration(&self, context: &GraphQLContext) -> FieldResult<i32> {
+    /// Pictures embedded in the album's songs other than the cover artwork, such as back covers,
+    /// booklet pages and artist photos.
+    fn pictures(&self, context: &GraphQLContext) -> FieldResult<Vec<AlbumPicture>> {
         let conn = &context.connection() as &SqliteConnection;
-        let maybe_duration: Option<i64> = song::table
-            .filter(song::album_id.eq(&self.id))
-            .select(dsl::sum(song::duration))
-            .first::<Option<i64>>(conn)?;
-        let duration = maybe_duration.unwrap_or(0);
+        album_picture::table
+            .filter(album_picture::album_id.eq(&self.id))
+            .order_by(album_picture::picture_type.asc())
+            .load::<AlbumPicture>(conn)
+            .map_err(FieldError::from)
+    }
+
+    /// The total length of the album's songs in seconds. The lengths are summed in milliseconds
+    /// before rounding so rounding errors don't accumulate.
+    fn duration(&self, context: &GraphQLContext) -> FieldResult<i32> {
+        Ok(milliseconds_to_seconds(
+            self.duration_ms(context.connection())?,
+        ))
+    }
 
-        Ok(duration as i32)
+    /// The total length of the album's songs in milliseconds.
+    fn duration_ms(&self, context: &GraphQLContext) -> FieldResult<i32> {
+        Ok(self.duration_ms(context.connection())? as i32)
     }
 
     fn release_year(&self) -> Option<i32> {
         self.release_year
     }
 
-    fn stats(&self) -> UserStats {
-        self.stats()
+    fn stats(&self, context: &GraphQLContext) -> FieldResult<UserStats> {
+        UserStats::for_album(context.connection(), context.user_id(), self.id)
+            .map_err(FieldError::from)
     }
 
     fn time_added(&self) -> TimeWrapper {
         self.time_added.into()
     }
+
+    /// Whether the user saved the album to their library.
+    fn liked(&self, context: &GraphQLContext) -> FieldResult<bool> {
+        let time_liked = UserStats::album_liked(context.connection(), context.user_id(), self.id)?;
+        Ok(time_liked.is_some())
+    }
+
+    /// The rating of the album from 1 to 5 stars, if the user rated it.
+    fn rating(&self, context: &GraphQLContext) -> FieldResult<Option<i32>> {
+        UserStats::album_rating(context.connection(), context.user_id(), self.id)
+            .map_err(FieldError::from)
+    }
 }
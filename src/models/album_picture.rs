@@ -0,0 +1,140 @@
+use crate::context::GraphQLContext;
+use crate::database::album_picture;
+use crate::models::*;
+use diesel::prelude::*;
+use juniper::{FieldError, FieldResult, GraphQLEnum};
+use taglib2_sys::PictureType;
+
+#[derive(Queryable, Identifiable, Insertable, Clone)]
+#[table_name = "album_picture"]
+pub struct AlbumPicture {
+    pub id: UUID,
+    pub album_id: UUID,
+    pub picture_type: i32,
+    pub description: Option<String>,
+    pub path: PathWrapper,
+    pub byte_size: i32,
+    pub width: Option<i32>,
+    pub height: Option<i32>,
+}
+
+impl AlbumPicture {
+    pub fn from_id(conn: &SqliteConnection, id: UUID) -> QueryResult<Self> {
+        album_picture::table.find(id).first::<Self>(conn)
+    }
+
+    pub fn get_url(album_id: &str, id: &str) -> String {
+        format!("/files/artwork/{}/pictures/{}", album_id, id)
+    }
+
+    pub fn picture_type(&self) -> PictureType {
+        PictureType::from_raw(self.picture_type)
+    }
+}
+
+#[juniper::graphql_object(context = GraphQLContext)]
+impl AlbumPicture {
+    fn id(&self) -> UUID {
+        self.id
+    }
+
+    fn url(&self) -> String {
+        AlbumPicture::get_url(&self.album_id.to_string(), &self.id.to_string())
+    }
+
+    fn picture_type(&self) -> PictureKind {
+        self.picture_type().into()
+    }
+
+    fn description(&self) -> Option<&str> {
+        self.description.as_deref()
+    }
+
+    fn width(&self) -> Option<i32> {
+        self.width
+    }
+
+    fn height(&self) -> Option<i32> {
+        self.height
+    }
+
+    fn album(&self, context: &GraphQLContext) -> FieldResult<Album> {
+        Album::from_id(&context.connection(), self.album_id).map_err(FieldError::from)
+    }
+}
+
+/// The role of a picture embedded in a song's tags.
+#[derive(GraphQLEnum)]
+#[graphql(name = "PictureType")]
+pub enum PictureKind {
+    #[graphql(name = "OTHER")]
+    Other,
+    #[graphql(name = "FILE_ICON")]
+    FileIcon,
+    #[graphql(name = "OTHER_FILE_ICON")]
+    OtherFileIcon,
+    #[graphql(name = "FRONT_COVER")]
+    FrontCover,
+    #[graphql(name = "BACK_COVER")]
+    BackCover,
+    #[graphql(name = "LEAFLET_PAGE")]
+    LeafletPage,
+    #[graphql(name = "MEDIA")]
+    Media,
+    #[graphql(name = "LEAD_ARTIST")]
+    LeadArtist,
+    #[graphql(name = "ARTIST")]
+    Artist,
+    #[graphql(name = "CONDUCTOR")]
+    Conductor,
+    #[graphql(name = "BAND")]
+    Band,
+    #[graphql(name = "COMPOSER")]
+    Composer,
+    #[graphql(name = "LYRICIST")]
+    Lyricist,
+    #[graphql(name = "RECORDING_LOCATION")]
+    RecordingLocation,
+    #[graphql(name = "DURING_RECORDING")]
+    DuringRecording,
+    #[graphql(name = "DURING_PERFORMANCE")]
+    DuringPerformance,
+    #[graphql(name = "MOVIE_SCREEN_CAPTURE")]
+    MovieScreenCapture,
+    #[graphql(name = "COLOURED_FISH")]
+    ColouredFish,
+    #[graphql(name = "ILLUSTRATION")]
+    Illustration,
+    #[graphql(name = "BAND_LOGO")]
+    BandLogo,
+    #[graphql(name = "PUBLISHER_LOGO")]
+    PublisherLogo,
+}
+
+impl From<PictureType> for PictureKind {
+    fn from(picture_type: PictureType) -> Self {
+        match picture_type {
+            PictureType::Other => PictureKind::Other,
+            PictureType::FileIcon => PictureKind::FileIcon,
+            PictureType::OtherFileIcon => PictureKind::OtherFileIcon,
+            PictureType::FrontCover => PictureKind::FrontCover,
+            PictureType::BackCover => PictureKind::BackCover,
+            PictureType::LeafletPage => PictureKind::LeafletPage,
+            PictureType::Media => PictureKind::Media,
+            PictureType::LeadArtist => PictureKind::LeadArtist,
+            PictureType::Artist => PictureKind::Artist,
+            PictureType::Conductor => PictureKind::Conductor,
+            PictureType::Band => PictureKind::Band,
+            PictureType::Composer => PictureKind::Composer,
+            PictureType::Lyricist => PictureKind::Lyricist,
+            PictureType::RecordingLocation => PictureKind::RecordingLocation,
+            PictureType::DuringRecording => PictureKind::DuringRecording,
+            PictureType::DuringPerformance => PictureKind::DuringPerformance,
+            PictureType::MovieScreenCapture => PictureKind::MovieScreenCapture,
+            PictureType::ColouredFish => PictureKind::ColouredFish,
+            PictureType::Illustration => PictureKind::Illustration,
+            PictureType::BandLogo => PictureKind::BandLogo,
+            PictureType::PublisherLogo => PictureKind::PublisherLogo,
+        }
+    }
+}
@@ -1,9 +1,21 @@
 use crate::context::GraphQLContext;
 use crate::database::album;
+use crate::database::album_picture;
 use crate::database::artist;
+use crate::database::user_artist_stats;
 use crate::models::*;
+use diesel::dsl;
 use diesel::prelude::*;
+use diesel::sql_types::Timestamp;
 use juniper::{FieldError, FieldResult};
+use taglib2_sys::PictureType;
+
+/// The picture types which show the artist of an album.
+const ARTIST_PICTURE_TYPES: [PictureType; 3] = [
+    PictureType::LeadArtist,
+    PictureType::Artist,
+    PictureType::Band,
+];
 
 #[derive(Queryable, Identifiable, Insertable, Clone)]
 #[table_name = "artist"]
@@ -11,7 +23,6 @@ pub struct Artist {
     pub id: UUID,
     pub name: String,
     pub time_added: NaiveDateTime,
-    pub last_played: Option<NaiveDateTime>,
 }
 
 impl Artist {
@@ -19,18 +30,42 @@ impl Artist {
         artist::table.find(id).first::<Self>(conn)
     }
 
-    pub fn stats(&self) -> UserStats {
-        UserStats {
-            id: format!("stats:{}", self.id.to_string()),
-            last_played: self.last_played,
+    /// The artists the user follows, most recently followed first.
+    pub fn followed_connection(
+        context: &GraphQLContext,
+        page: Page,
+    ) -> FieldResult<Connection<Artist>> {
+        let conn = &context.connection() as &SqliteConnection;
+        let user_id = context.user_id();
+
+        let followed = || {
+            artist::table
+                .inner_join(user_artist_stats::table)
+                .filter(user_artist_stats::user_id.eq(user_id))
+                .filter(user_artist_stats::time_followed.is_not_null())
+                .into_boxed()
+        };
+        let count: i64 = followed().select(dsl::count_star()).first(conn)?;
+
+        Keyset {
+            columns: artist::all_columns,
+            key: || -> SortKey<_, Timestamp> { Box::new(user_artist_stats::time_followed) },
+            id: artist::id,
+            descending: true,
+            nullable: false,
         }
+        .load_page(conn, followed, &page, count)
     }
 }
 
 impl GetConnection<artist::table> for Artist {
     type Name = artist::name;
     type TimeAdded = artist::time_added;
-    type LastPlayed = artist::last_played;
+    type Id = artist::id;
+
+    fn id() -> Self::Id {
+        artist::id
+    }
 
     fn name() -> Self::Name {
         artist::name
@@ -40,8 +75,17 @@ impl GetConnection<artist::table> for Artist {
         artist::time_added
     }
 
-    fn last_played() -> Self::LastPlayed {
-        artist::last_played
+    fn last_played_query() -> &'static str {
+        "SELECT last_played FROM user_artist_stats
+        WHERE user_artist_stats.artist_id = artist.id AND user_artist_stats.user_id = "
+    }
+
+    fn rating_query() -> Option<&'static str> {
+        None
+    }
+
+    fn table_name() -> &'static str {
+        "artist"
     }
 }
 
@@ -64,8 +108,33 @@ impl Artist {
             .map_err(FieldError::from)
     }
 
-    fn stats(&self) -> UserStats {
-        self.stats()
+    /// Photos of the artist embedded in the songs of their albums.
+    fn photos(&self, context: &GraphQLContext) -> FieldResult<Vec<AlbumPicture>> {
+        let conn = &context.connection() as &SqliteConnection;
+        let picture_types: Vec<i32> = ARTIST_PICTURE_TYPES
+            .iter()
+            .map(|picture_type| picture_type.as_raw())
+            .collect();
+
+        album_picture::table
+            .inner_join(album::table)
+            .filter(album::artist_id.eq(&self.id))
+            .filter(album_picture::picture_type.eq_any(picture_types))
+            .select(album_picture::all_columns)
+            .load::<AlbumPicture>(conn)
+            .map_err(FieldError::from)
+    }
+
+    fn stats(&self, context: &GraphQLContext) -> FieldResult<UserStats> {
+        UserStats::for_artist(context.connection(), context.user_id(), self.id)
+            .map_err(FieldError::from)
+    }
+
+    /// Whether the user follows the artist.
+    fn followed(&self, context: &GraphQLContext) -> FieldResult<bool> {
+        let time_followed =
+            UserStats::artist_followed(context.connection(), context.user_id(), self.id)?;
+        Ok(time_followed.is_some())
     }
 
     fn time_added(&self) -> TimeWrapper {
@@ -2,18 +2,29 @@ use crate::context::GraphQLContext;
 use crate::models::*;
 use diesel::associations::HasTable;
 use diesel::dsl;
+use diesel::expression::AsExpression;
 use diesel::expression::NonAggregate;
 use diesel::prelude::*;
 use diesel::query_builder::AsQuery;
 use diesel::query_builder::BoxedSelectStatement;
 use diesel::query_builder::QueryFragment;
 use diesel::query_dsl::boxed_dsl::BoxedDsl;
+use diesel::query_dsl::LoadQuery;
+use diesel::sql_types::Binary;
+use diesel::sql_types::Bool;
 use diesel::sql_types::HasSqlType;
+use diesel::sql_types::Integer;
+use diesel::sql_types::NotNull;
 use diesel::sql_types::Nullable;
+use diesel::sql_types::SingleValue;
 use diesel::sql_types::Text;
 use diesel::sql_types::Timestamp;
 use diesel::sqlite::Sqlite;
-use juniper::{graphql_object, FieldResult, GraphQLEnum, GraphQLInputObject, GraphQLObject};
+use juniper::{
+    graphql_object, FieldError, FieldResult, GraphQLEnum, GraphQLInputObject, GraphQLObject,
+};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
 
 pub struct Edge<T> {
     pub cursor: String,
@@ -50,12 +61,97 @@ impl Edge<Song> {
     }
 }
 
+#[graphql_object(name = "ImportIssueEdge", context = GraphQLContext)]
+impl Edge<ImportIssue> {
+    fn cursor(&self) -> &str {
+        &self.cursor
+    }
+    fn node(&self) -> &ImportIssue {
+        &self.node
+    }
+}
+
+#[graphql_object(name = "PlayEdge", context = GraphQLContext)]
+impl Edge<Play> {
+    fn cursor(&self) -> &str {
+        &self.cursor
+    }
+    fn node(&self) -> &Play {
+        &self.node
+    }
+}
+
+#[graphql_object(name = "PlaylistEdge", context = GraphQLContext)]
+impl Edge<Playlist> {
+    fn cursor(&self) -> &str {
+        &self.cursor
+    }
+    fn node(&self) -> &Playlist {
+        &self.node
+    }
+}
+
+#[graphql_object(name = "PlaylistItemEdge", context = GraphQLContext)]
+impl Edge<PlaylistItem> {
+    fn cursor(&self) -> &str {
+        &self.cursor
+    }
+    fn node(&self) -> &PlaylistItem {
+        &self.node
+    }
+}
+
+#[graphql_object(name = "SearchResultEdge", context = GraphQLContext)]
+impl Edge<SearchResult> {
+    fn cursor(&self) -> &str {
+        &self.cursor
+    }
+    fn node(&self) -> &SearchResult {
+        &self.node
+    }
+}
+
 pub struct Connection<T> {
     pub count: usize,
     pub edges: Vec<Edge<T>>,
+    pub has_previous_page: bool,
     pub has_next_page: bool,
 }
 
+impl<T> Connection<T> {
+    /// Builds a connection from a page of `nodes` starting at `offset` in a list of `count`
+    /// items, for lists which can only be paged by offset.
+    pub fn from_offset(nodes: Vec<T>, offset: i64, count: i64) -> Connection<T> {
+        let has_next_page = offset + (nodes.len() as i64) < count;
+        let edges: Vec<Edge<T>> = nodes
+            .into_iter()
+            .enumerate()
+            .map(|(idx, node)| Edge {
+                cursor: encode_cursor(&(offset + idx as i64)),
+                node,
+            })
+            .collect();
+
+        Connection {
+            count: count as usize,
+            edges,
+            has_previous_page: offset > 0,
+            has_next_page,
+        }
+    }
+}
+
+impl<T> From<&Connection<T>> for PageInfo {
+    fn from(connection: &Connection<T>) -> Self {
+        PageInfo {
+            has_previous_page: connection.has_previous_page,
+            has_next_page: connection.has_next_page,
+            start_cursor: connection.edges.first().map(|edge| edge.cursor.clone()),
+            end_cursor: connection.edges.last().map(|edge| edge.cursor.clone()),
+        }
+    }
+}
+
 #[graphql_object(name = "AlbumConnection", context = GraphQLContext)]
 impl Connection<Album> {
     fn count(&self) -> i32 {
@@ -65,9 +161,7 @@ impl Connection<Album> {
         &self.edges
     }
     fn page_info(&self) -> PageInfo {
-        PageInfo {
-            has_next_page: self.has_next_page,
-        }
+        PageInfo::from(self)
     }
 }
 
@@ -80,9 +174,7 @@ impl Connection<Artist> {
         &self.edges
     }
     fn page_info(&self) -> PageInfo {
-        PageInfo {
-            has_next_page: self.has_next_page,
-        }
+        PageInfo::from(self)
     }
 }
 
@@ -95,15 +187,365 @@ impl Connection<Song> {
         &self.edges
     }
     fn page_info(&self) -> PageInfo {
-        PageInfo {
-            has_next_page: self.has_next_page,
-        }
+        PageInfo::from(self)
+    }
+}
+
+#[graphql_object(name = "ImportIssueConnection", context = GraphQLContext)]
+impl Connection<ImportIssue> {
+    fn count(&self) -> i32 {
+        self.count as i32
+    }
+    fn edges(&self) -> &[Edge<ImportIssue>] {
+        &self.edges
+    }
+    fn page_info(&self) -> PageInfo {
+        PageInfo::from(self)
+    }
+}
+
+#[graphql_object(name = "PlayConnection", context = GraphQLContext)]
+impl Connection<Play> {
+    fn count(&self) -> i32 {
+        self.count as i32
+    }
+    fn edges(&self) -> &[Edge<Play>] {
+        &self.edges
+    }
+    fn page_info(&self) -> PageInfo {
+        PageInfo::from(self)
+    }
+}
+
+#[graphql_object(name = "PlaylistConnection", context = GraphQLContext)]
+impl Connection<Playlist> {
+    fn count(&self) -> i32 {
+        self.count as i32
+    }
+    fn edges(&self) -> &[Edge<Playlist>] {
+        &self.edges
+    }
+    fn page_info(&self) -> PageInfo {
+        PageInfo::from(self)
+    }
+}
+
+#[graphql_object(name = "PlaylistItemConnection", context = GraphQLContext)]
+impl Connection<PlaylistItem> {
+    fn count(&self) -> i32 {
+        self.count as i32
+    }
+    fn edges(&self) -> &[Edge<PlaylistItem>] {
+        &self.edges
+    }
+    fn page_info(&self) -> PageInfo {
+        PageInfo::from(self)
+    }
+}
+
+#[graphql_object(name = "SearchResultConnection", context = GraphQLContext)]
+impl Connection<SearchResult> {
+    fn count(&self) -> i32 {
+        self.count as i32
+    }
+    fn edges(&self) -> &[Edge<SearchResult>] {
+        &self.edges
+    }
+    fn page_info(&self) -> PageInfo {
+        PageInfo::from(self)
     }
 }
 
 #[derive(GraphQLObject)]
 pub struct PageInfo {
+    pub has_previous_page: bool,
     pub has_next_page: bool,
+
+    /// The cursor of the first edge, null when there are no edges.
+    pub start_cursor: Option<String>,
+
+    /// The cursor of the last edge, null when there are no edges.
+    pub end_cursor: Option<String>,
+}
+
+/// The number of items on a page when neither `first` nor `last` is given.
+const DEFAULT_PAGE_SIZE: i32 = 25;
+
+/// Which page of a connection to get, from the `first`, `after`, `last` and `before` arguments.
+pub struct Page {
+    /// The number of items on the page.
+    pub size: i64,
+
+    /// Whether the page has the last `size` items before `before` instead of the first `size`
+    /// items after `after`.
+    pub backward: bool,
+    pub after: Option<String>,
+    pub before: Option<String>,
+}
+
+impl Page {
+    pub fn new(
+        first: Option<i32>,
+        after: Option<String>,
+        last: Option<i32>,
+        before: Option<String>,
+    ) -> FieldResult<Page> {
+        let (size, backward) = match (first, last) {
+            (Some(_), Some(_)) => return Err("first and last can't be used together".into()),
+            (Some(first), None) => (first, false),
+            (None, Some(last)) => (last, true),
+            (None, None) => (DEFAULT_PAGE_SIZE, false),
+        };
+        if size < 0 {
+            return Err(format!("page size can't be negative, got {}", size).into());
+        }
+
+        Ok(Page {
+            size: size as i64,
+            backward,
+            after,
+            before,
+        })
+    }
+
+    /// The offset and length of the page in a list of `count` items paged by offset.
+    pub fn offset_window(&self, count: i64) -> FieldResult<(i64, i64)> {
+        let start = match &self.after {
+            Some(after) => (decode_cursor::<i64>(after)? + 1).max(0).min(count),
+            None => 0,
+        };
+        let end = match &self.before {
+            Some(before) => decode_cursor::<i64>(before)?.max(start).min(count),
+            None => count,
+        };
+
+        if self.backward {
+            let offset = (end - self.size).max(start);
+            Ok((offset, end - offset))
+        } else {
+            Ok((start, self.size.min(end - start)))
+        }
+    }
+}
+
+/// Encodes the position of an item in a connection as a cursor. Cursors are opaque to clients,
+/// which only pass them back as `after` or `before`.
+pub fn encode_cursor<T: Serialize>(position: &T) -> String {
+    let json = serde_json::to_vec(position).expect("positions serialize to JSON");
+    base64::encode_config(&json, base64::URL_SAFE_NO_PAD)
+}
+
+pub fn decode_cursor<T: DeserializeOwned>(cursor: &str) -> FieldResult<T> {
+    base64::decode_config(cursor, base64::URL_SAFE_NO_PAD)
+        .ok()
+        .and_then(|json| serde_json::from_slice(&json).ok())
+        .ok_or_else(|| FieldError::from(format!("invalid cursor '{}'", cursor)))
+}
+
+/// A boxed sort key of a connection's items. Null keys sort before every other key.
+pub type SortKey<QS, ST> = Box<dyn BoxableExpression<QS, Sqlite, SqlType = Nullable<ST>>>;
+
+/// The SQL types connections can be sorted by, with the type of their values in cursors.
+pub trait SortKeyType: NotNull + SingleValue + 'static {
+    type Value: Serialize + DeserializeOwned + Clone;
+}
+
+impl SortKeyType for Text {
+    type Value = String;
+}
+
+impl SortKeyType for Timestamp {
+    type Value = NaiveDateTime;
+}
+
+impl SortKeyType for Integer {
+    type Value = i32;
+}
+
+type Predicate<QS> = Box<dyn BoxableExpression<QS, Sqlite, SqlType = Bool>>;
+
+/// The key and id of the item a cursor points at.
+type KeysetCursor<X> = (Option<<X as SortKeyType>::Value>, Vec<u8>);
+
+/// How the items of a connection are ordered: by a key, with ties broken by a unique binary id.
+///
+/// Pages are found by seeking past the key and id of the item in the cursor rather than skipping
+/// an offset, so items added or removed while paging don't shift the pages after them and deep
+/// pages are as fast as the first when there's an index on the key and id. Keys selected by a
+/// subquery, like when the user last played items and how they rated them, have no index, so
+/// every page of those sorts all the items again.
+pub struct Keyset<C, K, I> {
+    /// The columns of the items.
+    pub columns: C,
+    pub key: K,
+    pub id: I,
+    pub descending: bool,
+
+    /// Whether the key can be null.
+    pub nullable: bool,
+}
+
+impl<C, K, I> Keyset<C, K, I> {
+    /// Loads a page of the items of `query`, a connection of `count` items.
+    pub fn load_page<T, ST, QS: 'static, X>(
+        &self,
+        conn: &SqliteConnection,
+        query: impl Fn() -> BoxedSelectStatement<'static, ST, QS, Sqlite>,
+        page: &Page,
+        count: i64,
+    ) -> FieldResult<Connection<T>>
+    where
+        C: SelectableExpression<QS> + NonAggregate + QueryFragment<Sqlite> + Copy + 'static,
+        K: Fn() -> SortKey<QS, X>,
+        I: Expression<SqlType = Binary>
+            + BoxableExpression<QS, Sqlite, SqlType = Binary>
+            + Copy
+            + 'static,
+        X: SortKeyType,
+        Option<X::Value>: AsExpression<Nullable<X>>,
+        <Option<X::Value> as AsExpression<Nullable<X>>>::Expression:
+            BoxableExpression<QS, Sqlite, SqlType = Nullable<X>> + 'static,
+        BoxedSelectStatement<'static, (C::SqlType, Nullable<X>, Binary), QS, Sqlite>:
+            LoadQuery<SqliteConnection, (T, Option<X::Value>, Vec<u8>)>,
+    {
+        let after = match &page.after {
+            Some(after) => Some(decode_cursor::<KeysetCursor<X>>(after)?),
+            None => None,
+        };
+        let before = match &page.before {
+            Some(before) => Some(decode_cursor::<KeysetCursor<X>>(before)?),
+            None => None,
+        };
+
+        let between = || {
+            let mut rows = query().select((self.columns, (self.key)(), self.id));
+            if let Some(after) = &after {
+                rows = rows.filter(self.beyond(after, !self.descending, false));
+            }
+            if let Some(before) = &before {
+                rows = rows.filter(self.beyond(before, self.descending, false));
+            }
+
+            rows
+        };
+
+        // Backward pages are read in reverse from `before`, then turned around.
+        let mut results: Vec<(T, Option<X::Value>, Vec<u8>)> =
+            if self.descending == page.backward {
+                between()
+                    .order_by((self.key)().asc())
+                    .then_order_by(self.id.asc())
+            } else {
+                between()
+                    .order_by((self.key)().desc())
+                    .then_order_by(self.id.desc())
+            }
+            .limit(page.size + 1)
+            .load(conn)?;
+
+        let has_more = results.len() as i64 > page.size;
+        results.truncate(page.size as usize);
+        if page.backward {
+            results.reverse();
+        }
+
+        // Whether any items are on the other side of the cursor the page starts from, the item
+        // in the cursor included.
+        let any_beyond = |cursor: &KeysetCursor<X>, greater: bool| -> QueryResult<bool> {
+            let beyond: Vec<(T, Option<X::Value>, Vec<u8>)> = query()
+                .select((self.columns, (self.key)(), self.id))
+                .filter(self.beyond(cursor, greater, true))
+                .limit(1)
+                .load(conn)?;
+
+            Ok(!beyond.is_empty())
+        };
+
+        let (has_previous_page, has_next_page) = if page.backward {
+            let has_next_page = match &before {
+                Some(before) => any_beyond(before, !self.descending)?,
+                None => false,
+            };
+
+            (has_more, has_next_page)
+        } else {
+            let has_previous_page = match &after {
+                Some(after) => any_beyond(after, self.descending)?,
+                None => false,
+            };
+
+            (has_previous_page, has_more)
+        };
+
+        let edges = results
+            .into_iter()
+            .map(|(node, key, id)| Edge {
+                cursor: encode_cursor(&(key, id)),
+                node,
+            })
+            .collect();
+
+        Ok(Connection {
+            count: count as usize,
+            edges,
+            has_previous_page,
+            has_next_page,
+        })
+    }
+
+    /// Keeps the items whose key and id sort after the cursor's, or before it when `greater` is
+    /// false. The item in the cursor is kept too when `inclusive` is true.
+    fn beyond<QS: 'static, X>(
+        &self,
+        cursor: &KeysetCursor<X>,
+        greater: bool,
+        inclusive: bool,
+    ) -> Predicate<QS>
+    where
+        K: Fn() -> SortKey<QS, X>,
+        I: Expression<SqlType = Binary>
+            + BoxableExpression<QS, Sqlite, SqlType = Binary>
+            + Copy
+            + 'static,
+        X: SortKeyType,
+        Option<X::Value>: AsExpression<Nullable<X>>,
+        <Option<X::Value> as AsExpression<Nullable<X>>>::Expression:
+            BoxableExpression<QS, Sqlite, SqlType = Nullable<X>> + 'static,
+    {
+        let (key, id) = cursor.clone();
+        let key_column = &self.key;
+
+        let id_beyond: Predicate<QS> = match (greater, inclusive) {
+            (true, false) => Box::new(self.id.gt(id)),
+            (true, true) => Box::new(self.id.ge(id)),
+            (false, false) => Box::new(self.id.lt(id)),
+            (false, true) => Box::new(self.id.le(id)),
+        };
+
+        // Every comparison starts with a bound on the key alone so an index on it can be used.
+        match key {
+            Some(key) if greater => Box::new(
+                key_column()
+                    .ge(Some(key.clone()))
+                    .and(key_column().gt(Some(key)).or(id_beyond)),
+            ),
+            Some(key) => {
+                let lower: Predicate<QS> = Box::new(
+                    key_column()
+                        .le(Some(key.clone()))
+                        .and(key_column().lt(Some(key)).or(id_beyond)),
+                );
+
+                if self.nullable {
+                    Box::new(lower.or(key_column().is_null()))
+                } else {
+                    lower
+                }
+            }
+            None if greater => Box::new(key_column().is_not_null().or(id_beyond)),
+            None => Box::new(key_column().is_null().and(id_beyond)),
+        }
+    }
 }
 
 #[derive(GraphQLInputObject)]
@@ -134,103 +576,405 @@ pub enum SortBy {
     Lexicographically,
     #[graphql(name = "RECENTLY_PLAYED")]
     RecentlyPlayed,
+
+    /// Highest rated first, with unrated items sorted below every rating.
+    #[graphql(name = "RATING")]
+    Rating,
 }
 
 pub trait GetConnection<TB>
 where
-    Self: HasTable<Table = TB> + Queryable<TB::SqlType, Sqlite> + Sized,
+    Self: HasTable<Table = TB>
+        + Queryable<TB::SqlType, Sqlite>
+        + Queryable<<TB::AllColumns as Expression>::SqlType, Sqlite>
+        + Sized,
     TB: Table
         + BoxedDsl<
             'static,
             Sqlite,
             Output = BoxedSelectStatement<'static, <TB as AsQuery>::SqlType, TB, Sqlite>,
-        >,
+        > + 'static,
+    TB::AllColumns: SelectableExpression<TB> + NonAggregate + QueryFragment<Sqlite> + Copy,
     <TB as QuerySource>::FromClause: QueryFragment<Sqlite>,
-    Sqlite: HasSqlType<TB::SqlType>,
+    Sqlite: HasSqlType<TB::SqlType> + HasSqlType<<TB::AllColumns as Expression>::SqlType>,
 {
     type Name: Column<Table = TB, SqlType = Text>
-        + AppearsOnTable<TB>
+        + SelectableExpression<TB>
         + QueryFragment<Sqlite>
-        + NonAggregate;
+        + NonAggregate
+        + 'static;
     type TimeAdded: Column<Table = TB, SqlType = Timestamp>
-        + AppearsOnTable<TB>
-        + QueryFragment<Sqlite>;
-    type LastPlayed: Column<Table = TB, SqlType = Nullable<Timestamp>>
-        + AppearsOnTable<TB>
-        + QueryFragment<Sqlite>;
+        + SelectableExpression<TB>
+        + QueryFragment<Sqlite>
+        + NonAggregate
+        + 'static;
+    type Id: Column<Table = TB, SqlType = Binary>
+        + BoxableExpression<TB, Sqlite, SqlType = Binary>
+        + Copy
+        + 'static;
 
+    fn id() -> Self::Id;
     fn name() -> Self::Name;
     fn time_added() -> Self::TimeAdded;
-    fn last_played() -> Self::LastPlayed;
+
+    /// A query selecting the time a row was last played from the per-user stats, which the id
+    /// of the user is appended to.
+    fn last_played_query() -> &'static str;
+
+    /// The time the user last played each row, or null if they never did.
+    fn last_played(
+        user_id: UUID,
+    ) -> Box<dyn BoxableExpression<TB, Sqlite, SqlType = Nullable<Timestamp>>> {
+        Box::new(
+            dsl::sql::<Nullable<Timestamp>>(&format!("({}", Self::last_played_query()))
+                .bind::<Binary, _>(user_id)
+                .sql(")"),
+        )
+    }
+
+    /// A query selecting the user's rating of a row, which the id of the user is appended to.
+    /// `None` for tables which can't be rated.
+    fn rating_query() -> Option<&'static str>;
+
+    /// The user's rating of each row, or null if they didn't rate it.
+    fn rating(
+        user_id: UUID,
+    ) -> Option<Box<dyn BoxableExpression<TB, Sqlite, SqlType = Nullable<Integer>>>> {
+        let query = Self::rating_query()?;
+
+        Some(Box::new(
+            dsl::sql::<Nullable<Integer>>(&format!("({}", query))
+                .bind::<Binary, _>(user_id)
+                .sql(")"),
+        ))
+    }
+
+    /// The name of the table in SQL. Its full-text index is named `<name>_fts`.
+    fn table_name() -> &'static str;
+
+    /// Keeps the rows matching `filter`, searching the full-text index when the filter has words
+    /// in it and falling back to a substring match of the name otherwise.
+    fn filter_query<'a>(
+        query: BoxedSelectStatement<'a, <TB as AsQuery>::SqlType, TB, Sqlite>,
+        filter: &str,
+    ) -> BoxedSelectStatement<'a, <TB as AsQuery>::SqlType, TB, Sqlite>
+    where
+        TB: 'a,
+        Self::Name: 'a,
+    {
+        match search::match_query(filter) {
+            Some(match_query) => {
+                QueryDsl::filter(query, search::matches_fts(Self::table_name(), match_query))
+            }
+            None => QueryDsl::filter(query, Self::name().like(format!("%{}%", filter))),
+        }
+    }
 
     fn get_connection(
         context: &GraphQLContext,
-        first: i64,
-        after: Option<String>,
+        page: Page,
         sort: Option<SortParams>,
     ) -> FieldResult<Connection<Self>> {
         let conn = &context.connection() as &SqliteConnection;
+        let user_id = context.user_id();
         let sort = sort.unwrap_or_default();
-        let lower_bound = after.map_or(Ok(0), |offset| offset.parse())?;
-        let filter = sort.filter.map(|filter| format!("%{}%", filter));
 
-        let mut query: BoxedSelectStatement<'_, <TB as AsQuery>::SqlType, TB, Sqlite> =
-            Self::table().into_boxed();
-        if let Some(filter) = &filter {
-            query = QueryDsl::filter(query, Self::name().like(filter));
-        }
+        let filtered = || {
+            let mut query: BoxedSelectStatement<'static, <TB as AsQuery>::SqlType, TB, Sqlite> =
+                Self::table().into_boxed();
+            if let Some(filter) = &sort.filter {
+                query = Self::filter_query(query, filter);
+            }
 
-        query = match sort.sort_by {
-            SortBy::Lexicographically => {
-                if !sort.reverse {
-                    query.order_by(Self::name().asc())
-                } else {
-                    query.order_by(Self::name().desc())
-                }
+            query
+        };
+
+        let count: i64 = filtered().select(dsl::count_star()).first(conn)?;
+
+        match sort.sort_by {
+            SortBy::Lexicographically => Keyset {
+                columns: TB::all_columns(),
+                key: || -> SortKey<TB, Text> { Box::new(Self::name().nullable()) },
+                id: Self::id(),
+                descending: sort.reverse,
+                nullable: false,
             }
+            .load_page(conn, filtered, &page, count),
 
-            SortBy::RecentlyAdded => {
-                if !sort.reverse {
-                    query.order_by(Self::time_added().desc())
-                } else {
-                    query.order_by(Self::time_added().asc())
-                }
+            SortBy::RecentlyAdded => Keyset {
+                columns: TB::all_columns(),
+                key: || -> SortKey<TB, Timestamp> { Box::new(Self::time_added().nullable()) },
+                id: Self::id(),
+                descending: !sort.reverse,
+                nullable: false,
             }
+            .load_page(conn, filtered, &page, count),
 
-            SortBy::RecentlyPlayed => {
-                if !sort.reverse {
-                    query.order_by(Self::last_played().desc())
-                } else {
-                    query.order_by(Self::last_played().asc())
-                }
+            SortBy::RecentlyPlayed => Keyset {
+                columns: TB::all_columns(),
+                key: || Self::last_played(user_id),
+                id: Self::id(),
+                descending: !sort.reverse,
+                nullable: true,
             }
-        };
+            .load_page(conn, filtered, &page, count),
 
-        let results: Vec<Self> = query.limit(first).offset(lower_bound).load(conn)?;
+            SortBy::Rating => {
+                if Self::rating_query().is_none() {
+                    return Err("only songs and albums can be sorted by rating".into());
+                }
 
-        let mut count_query = Self::table().into_boxed();
-        if let Some(filter) = &filter {
-            count_query = QueryDsl::filter(count_query, Self::name().like(filter));
+                Keyset {
+                    columns: TB::all_columns(),
+                    key: || Self::rating(user_id).expect("the table has ratings"),
+                    id: Self::id(),
+                    descending: !sort.reverse,
+                    nullable: true,
+                }
+                .load_page(conn, filtered, &page, count)
+            }
         }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{decode_cursor, encode_cursor, Connection, GetConnection, Keyset, Page, SortKey};
+    use crate::database::{song, user_song_stats};
+    use crate::models::*;
+    use crate::test_database;
+    use chrono::NaiveDate;
+    use diesel::prelude::*;
+    use diesel::sql_types::Text;
 
-        let count: i64 = count_query.select(dsl::count_star()).first(conn)?;
+    fn page(
+        first: Option<i32>,
+        after: Option<i64>,
+        last: Option<i32>,
+        before: Option<i64>,
+    ) -> Page {
+        Page::new(
+            first,
+            after.map(|offset| encode_cursor(&offset)),
+            last,
+            before.map(|offset| encode_cursor(&offset)),
+        )
+        .unwrap()
+    }
 
-        // The exclusive upper bound of the window into the data.
-        let upper_bound = lower_bound + if first < 0 { count } else { first };
+    #[test]
+    fn offset_windows() {
+        assert_eq!(
+            page(None, None, None, None).offset_window(100).unwrap(),
+            (0, 25)
+        );
+        assert_eq!(
+            page(Some(2), Some(1), None, None).offset_window(6).unwrap(),
+            (2, 2)
+        );
+        assert_eq!(
+            page(Some(10), Some(3), None, None)
+                .offset_window(6)
+                .unwrap(),
+            (4, 2)
+        );
+        assert_eq!(
+            page(None, None, Some(2), None).offset_window(6).unwrap(),
+            (4, 2)
+        );
+        assert_eq!(
+            page(None, None, Some(5), Some(2)).offset_window(6).unwrap(),
+            (0, 2)
+        );
+        assert_eq!(
+            page(Some(5), Some(3), None, Some(2))
+                .offset_window(6)
+                .unwrap(),
+            (4, 0)
+        );
+    }
 
-        let edges: Vec<Edge<Self>> = results
-            .into_iter()
+    #[test]
+    fn rejects_bad_pages() {
+        assert!(Page::new(Some(1), None, Some(1), None).is_err());
+        assert!(Page::new(Some(-1), None, None, None).is_err());
+        assert!(decode_cursor::<i64>("not a cursor").is_err());
+        assert_eq!(decode_cursor::<i64>(&encode_cursor(&42i64)).unwrap(), 42);
+    }
+
+    /// Songs with duplicate names, and stats with duplicate and missing play times and ratings.
+    /// Returns the songs with the day each was last played on and their ratings.
+    fn songs_with_stats(
+        conn: &SqliteConnection,
+        user_id: UUID,
+    ) -> Vec<(Song, Option<NaiveDateTime>, Option<i32>)> {
+        let stats = [
+            ("B", Some(3), Some(4)),
+            ("A", None, None),
+            ("B", Some(3), Some(4)),
+            ("C", Some(1), None),
+            ("B", None, Some(2)),
+            ("A", Some(5), Some(4)),
+            ("C", None, None),
+        ];
+
+        stats
+            .iter()
             .enumerate()
-            .map(|(idx, album)| Edge {
-                cursor: (lower_bound + idx as i64 + 1).to_string(),
-                node: album,
+            .map(|(index, (name, day, rating))| {
+                let song = test_database::add_song(conn, name, index as u32 + 1);
+                let last_played =
+                    day.map(|day| NaiveDate::from_ymd(2026, 10, day).and_hms(0, 0, 0));
+
+                diesel::insert_into(user_song_stats::table)
+                    .values((
+                        user_song_stats::user_id.eq(user_id),
+                        user_song_stats::song_id.eq(song.id),
+                        user_song_stats::play_count.eq(day.map_or(0, |_| 1)),
+                        user_song_stats::last_played.eq(last_played),
+                        user_song_stats::liked.eq(false),
+                        user_song_stats::rating.eq(rating),
+                    ))
+                    .execute(conn)
+                    .unwrap();
+
+                (song, last_played, *rating)
             })
+            .collect()
+    }
+
+    /// The ids of the songs sorted by `key` then by id, with null keys first.
+    fn sorted<K: Ord + Clone>(
+        songs: &[(Song, Option<NaiveDateTime>, Option<i32>)],
+        key: impl Fn(&(Song, Option<NaiveDateTime>, Option<i32>)) -> K,
+        descending: bool,
+    ) -> Vec<UUID> {
+        let mut keyed: Vec<(K, String, UUID)> = songs
+            .iter()
+            .map(|song| (key(song), song.0.id.to_string(), song.0.id))
             .collect();
+        keyed.sort_by(|a, b| (&a.0, &a.1).cmp(&(&b.0, &b.1)));
+        if descending {
+            keyed.reverse();
+        }
 
-        Ok(Connection {
-            count: count as usize,
-            edges,
-            has_next_page: upper_bound < count,
-        })
+        keyed.into_iter().map(|(_, _, id)| id).collect()
+    }
+
+    fn ids(connection: &Connection<Song>) -> Vec<UUID> {
+        connection.edges.iter().map(|edge| edge.node.id).collect()
+    }
+
+    /// Pages through every song two at a time, forward with `first` and `after`, then backward
+    /// with `last` and `before`, checking both find the songs in `expected` order.
+    fn check_pages(load: impl Fn(&Page) -> Connection<Song>, expected: &[UUID]) {
+        let mut forward = Vec::new();
+        let mut after = None;
+        loop {
+            let connection = load(&Page::new(Some(2), after.clone(), None, None).unwrap());
+            assert_eq!(connection.count, expected.len());
+            assert_eq!(connection.has_previous_page, after.is_some());
+
+            forward.extend(ids(&connection));
+            if !connection.has_next_page {
+                break;
+            }
+            after = connection.edges.last().map(|edge| edge.cursor.clone());
+        }
+        assert_eq!(forward, expected);
+
+        let mut backward = Vec::new();
+        let mut before = None;
+        loop {
+            let connection = load(&Page::new(None, None, Some(2), before.clone()).unwrap());
+            assert_eq!(connection.has_next_page, before.is_some());
+
+            backward.splice(0..0, ids(&connection));
+            if !connection.has_previous_page {
+                break;
+            }
+            before = connection.edges.first().map(|edge| edge.cursor.clone());
+        }
+        assert_eq!(backward, expected);
+
+        // Going back from a page reached going forward finds the songs before it.
+        let start = load(&Page::new(Some(3), None, None, None).unwrap());
+        let next =
+            load(&Page::new(Some(2), Some(start.edges[2].cursor.clone()), None, None).unwrap());
+        let back =
+            load(&Page::new(None, None, Some(3), Some(next.edges[0].cursor.clone())).unwrap());
+        assert_eq!(ids(&next), &expected[3..5]);
+        assert_eq!(ids(&back), ids(&start));
+        assert!(!back.has_previous_page);
+        assert!(back.has_next_page);
+    }
+
+    #[test]
+    fn keyset_pages_with_duplicate_keys() {
+        let conn = test_database::connection();
+        let user_id = test_database::default_user(&conn);
+        let songs = songs_with_stats(&conn, user_id);
+
+        for descending in [false, true].iter().copied() {
+            let keyset = Keyset {
+                columns: song::all_columns,
+                key: || -> SortKey<_, Text> { Box::new(song::name.nullable()) },
+                id: song::id,
+                descending,
+                nullable: false,
+            };
+
+            check_pages(
+                |page| {
+                    keyset
+                        .load_page(&conn, || song::table.into_boxed(), page, songs.len() as i64)
+                        .unwrap()
+                },
+                &sorted(&songs, |song| song.0.name.clone(), descending),
+            );
+        }
+    }
+
+    #[test]
+    fn keyset_pages_with_null_keys() {
+        let conn = test_database::connection();
+        let user_id = test_database::default_user(&conn);
+        let songs = songs_with_stats(&conn, user_id);
+
+        for descending in [false, true].iter().copied() {
+            let recently_played = Keyset {
+                columns: song::all_columns,
+                key: || Song::last_played(user_id),
+                id: song::id,
+                descending,
+                nullable: true,
+            };
+
+            check_pages(
+                |page| {
+                    recently_played
+                        .load_page(&conn, || song::table.into_boxed(), page, songs.len() as i64)
+                        .unwrap()
+                },
+                &sorted(&songs, |song| song.1, descending),
+            );
+
+            let rating = Keyset {
+                columns: song::all_columns,
+                key: || Song::rating(user_id).unwrap(),
+                id: song::id,
+                descending,
+                nullable: true,
+            };
+
+            check_pages(
+                |page| {
+                    rating
+                        .load_page(&conn, || song::table.into_boxed(), page, songs.len() as i64)
+                        .unwrap()
+                },
+                &sorted(&songs, |song| song.2, descending),
+            );
+        }
     }
 }
@@ -0,0 +1,111 @@
+use crate::context::GraphQLContext;
+use crate::database::import_issue;
+use crate::models::*;
+use chrono::Utc;
+use diesel::dsl;
+use diesel::prelude::*;
+use diesel::sql_types::Timestamp;
+use juniper::FieldResult;
+use std::path::Path;
+
+/// A file which failed to import during the last sync which saw it.
+#[derive(Queryable, Insertable, Clone)]
+#[table_name = "import_issue"]
+pub struct ImportIssue {
+    pub path: PathWrapper,
+
+    /// A short identifier of the kind of error, like `missing_tag` or `no_album`.
+    pub kind: String,
+    pub message: String,
+    pub last_seen: NaiveDateTime,
+}
+
+impl ImportIssue {
+    /// Records that importing the file at `path` failed, replacing any earlier issue with it.
+    pub fn record(
+        conn: &SqliteConnection,
+        path: &Path,
+        kind: &str,
+        message: &str,
+    ) -> QueryResult<()> {
+        let issue = ImportIssue {
+            path: path.into(),
+            kind: kind.to_string(),
+            message: message.to_string(),
+            last_seen: Utc::now().naive_utc(),
+        };
+
+        diesel::replace_into(import_issue::table)
+            .values(&issue)
+            .execute(conn)?;
+
+        Ok(())
+    }
+
+    /// Removes the issue with the file at `path`, if there is one.
+    pub fn clear(conn: &SqliteConnection, path: &Path) -> QueryResult<()> {
+        diesel::delete(import_issue::table.find(PathWrapper::from(path))).execute(conn)?;
+
+        Ok(())
+    }
+
+    /// Lists issues, most recently seen first, optionally only those of one kind.
+    pub fn list(conn: &SqliteConnection, kind: Option<&str>) -> QueryResult<Vec<ImportIssue>> {
+        let mut query = import_issue::table.into_boxed();
+        if let Some(kind) = kind {
+            query = query.filter(import_issue::kind.eq(kind));
+        }
+
+        query
+            .order_by(import_issue::last_seen.desc())
+            .then_order_by(import_issue::path.asc())
+            .load(conn)
+    }
+
+    pub fn get_connection(
+        context: &GraphQLContext,
+        page: Page,
+        kind: Option<String>,
+    ) -> FieldResult<Connection<ImportIssue>> {
+        let conn = context.connection();
+
+        let filtered = || {
+            let mut query = import_issue::table.into_boxed();
+            if let Some(kind) = &kind {
+                query = query.filter(import_issue::kind.eq(kind.clone()));
+            }
+
+            query
+        };
+
+        let count: i64 = filtered().select(dsl::count_star()).first(conn)?;
+
+        Keyset {
+            columns: import_issue::all_columns,
+            key: || -> SortKey<_, Timestamp> { Box::new(import_issue::last_seen.nullable()) },
+            id: import_issue::path,
+            descending: true,
+            nullable: false,
+        }
+        .load_page(conn, filtered, &page, count)
+    }
+}
+
+#[juniper::graphql_object(context = GraphQLContext)]
+impl ImportIssue {
+    fn path(&self) -> String {
+        self.path.to_string_lossy().into_owned()
+    }
+
+    fn kind(&self) -> &str {
+        &self.kind
+    }
+
+    fn message(&self) -> &str {
+        &self.message
+    }
+
+    fn last_seen(&self) -> TimeWrapper {
+        self.last_seen.into()
+    }
+}
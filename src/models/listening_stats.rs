@@ -0,0 +1,293 @@
+use crate::context::GraphQLContext;
+use crate::database::album;
+use crate::database::artist;
+use crate::database::song;
+use crate::models::*;
+use chrono::{NaiveDate, NaiveDateTime};
+use diesel::prelude::*;
+use diesel::sql_types::{BigInt, Binary, Date, Timestamp};
+use juniper::{graphql_object, FieldResult, GraphQLEnum};
+use std::collections::HashMap;
+
+/// Something which was played, with how often and how long it was listened to in a window of
+/// time.
+pub struct Top<T> {
+    pub node: T,
+    pub play_count: i64,
+
+    /// The time spent listening in milliseconds.
+    pub listening_time: i64,
+}
+
+#[graphql_object(name = "TopSong", context = GraphQLContext)]
+impl Top<Song> {
+    fn song(&self) -> &Song {
+        &self.node
+    }
+    fn play_count(&self) -> i32 {
+        self.play_count as i32
+    }
+    /// The time spent listening in seconds.
+    fn listening_time(&self) -> i32 {
+        milliseconds_to_seconds(self.listening_time)
+    }
+}
+
+#[graphql_object(name = "TopAlbum", context = GraphQLContext)]
+impl Top<Album> {
+    fn album(&self) -> &Album {
+        &self.node
+    }
+    fn play_count(&self) -> i32 {
+        self.play_count as i32
+    }
+    /// The time spent listening in seconds.
+    fn listening_time(&self) -> i32 {
+        milliseconds_to_seconds(self.listening_time)
+    }
+}
+
+#[graphql_object(name = "TopArtist", context = GraphQLContext)]
+impl Top<Artist> {
+    fn artist(&self) -> &Artist {
+        &self.node
+    }
+    fn play_count(&self) -> i32 {
+        self.play_count as i32
+    }
+    /// The time spent listening in seconds.
+    fn listening_time(&self) -> i32 {
+        milliseconds_to_seconds(self.listening_time)
+    }
+}
+
+#[derive(GraphQLEnum, Clone, Copy)]
+pub enum TimelineBucket {
+    #[graphql(name = "DAY")]
+    Day,
+    #[graphql(name = "WEEK")]
+    Week,
+    #[graphql(name = "MONTH")]
+    Month,
+}
+
+impl TimelineBucket {
+    /// An SQL expression for the first day of the bucket holding a play. Weeks start on Monday.
+    fn start_expression(self) -> &'static str {
+        match self {
+            TimelineBucket::Day => "date(play.time)",
+            TimelineBucket::Week => "date(play.time, 'weekday 0', '-6 days')",
+            TimelineBucket::Month => "date(play.time, 'start of month')",
+        }
+    }
+}
+
+/// The plays in a day, week or month.
+#[derive(QueryableByName)]
+pub struct ListeningPeriod {
+    #[sql_type = "Date"]
+    pub start: NaiveDate,
+
+    #[sql_type = "BigInt"]
+    pub play_count: i64,
+
+    /// The time spent listening in milliseconds.
+    #[sql_type = "BigInt"]
+    pub listening_time: i64,
+}
+
+#[graphql_object(context = GraphQLContext)]
+impl ListeningPeriod {
+    /// The start of the period, at midnight UTC.
+    fn start(&self) -> TimeWrapper {
+        self.start.and_hms(0, 0, 0).into()
+    }
+    fn play_count(&self) -> i32 {
+        self.play_count as i32
+    }
+    /// The time spent listening in seconds.
+    fn listening_time(&self) -> i32 {
+        milliseconds_to_seconds(self.listening_time)
+    }
+}
+
+#[derive(QueryableByName)]
+struct Ranking {
+    #[sql_type = "Binary"]
+    id: UUID,
+
+    #[sql_type = "BigInt"]
+    play_count: i64,
+
+    #[sql_type = "BigInt"]
+    listening_time: i64,
+}
+
+/// The time spent listening to a play. Plays recorded without a duration count as listening to
+/// the whole song.
+const LISTENING_TIME: &str = "sum(coalesce(play.duration, song.duration))";
+
+/// The user and window of time plays are counted in. Missing bounds include every play on that
+/// side.
+struct Window {
+    user_id: UUID,
+    since: NaiveDateTime,
+    until: NaiveDateTime,
+}
+
+impl Window {
+    fn new(user_id: UUID, since: Option<NaiveDateTime>, until: Option<NaiveDateTime>) -> Window {
+        Window {
+            user_id,
+            since: since.unwrap_or_else(|| NaiveDate::from_ymd(1, 1, 1).and_hms(0, 0, 0)),
+            until: until.unwrap_or_else(|| NaiveDate::from_ymd(9999, 12, 31).and_hms(0, 0, 0)),
+        }
+    }
+}
+
+/// Ranks the ids selected by `id_column` from the plays in a window, most played first. `joins`
+/// are joined onto `play` and `song`.
+fn rank(
+    conn: &SqliteConnection,
+    id_column: &str,
+    joins: &str,
+    window: &Window,
+    first: i64,
+) -> QueryResult<Vec<Ranking>> {
+    let query = format!(
+        "SELECT {id} AS id, count(*) AS play_count, {time} AS listening_time
+        FROM play INNER JOIN song ON song.id = play.song_id {joins}
+        WHERE play.user_id = ? AND play.time >= ? AND play.time < ?
+        GROUP BY {id}
+        ORDER BY play_count DESC, listening_time DESC, {id}
+        LIMIT ?",
+        id = id_column,
+        time = LISTENING_TIME,
+        joins = joins,
+    );
+
+    diesel::sql_query(query)
+        .bind::<Binary, _>(window.user_id)
+        .bind::<Timestamp, _>(window.since)
+        .bind::<Timestamp, _>(window.until)
+        .bind::<BigInt, _>(first)
+        .load(conn)
+}
+
+/// Pairs rankings with the nodes they rank, keeping the order of the rankings.
+fn into_top<T>(rankings: Vec<Ranking>, nodes: Vec<T>, id: impl Fn(&T) -> UUID) -> Vec<Top<T>> {
+    let mut nodes: HashMap<UUID, T> = nodes.into_iter().map(|node| (id(&node), node)).collect();
+
+    rankings
+        .into_iter()
+        .filter_map(|ranking| {
+            nodes.remove(&ranking.id).map(|node| Top {
+                node,
+                play_count: ranking.play_count,
+                listening_time: ranking.listening_time,
+            })
+        })
+        .collect()
+}
+
+impl Top<Song> {
+    pub fn top_songs(
+        context: &GraphQLContext,
+        first: i64,
+        since: Option<NaiveDateTime>,
+        until: Option<NaiveDateTime>,
+    ) -> FieldResult<Vec<Top<Song>>> {
+        let conn = context.connection();
+        let rankings = rank(
+            conn,
+            "play.song_id",
+            "",
+            &Window::new(context.user_id(), since, until),
+            first,
+        )?;
+
+        let ids: Vec<UUID> = rankings.iter().map(|ranking| ranking.id).collect();
+        let songs: Vec<Song> = song::table.filter(song::id.eq_any(ids)).load(conn)?;
+
+        Ok(into_top(rankings, songs, |song| song.id))
+    }
+}
+
+impl Top<Album> {
+    /// Ranks the albums of the songs played. The album a song was played from doesn't matter.
+    pub fn top_albums(
+        context: &GraphQLContext,
+        first: i64,
+        since: Option<NaiveDateTime>,
+        until: Option<NaiveDateTime>,
+    ) -> FieldResult<Vec<Top<Album>>> {
+        let conn = context.connection();
+        let rankings = rank(
+            conn,
+            "song.album_id",
+            "",
+            &Window::new(context.user_id(), since, until),
+            first,
+        )?;
+
+        let ids: Vec<UUID> = rankings.iter().map(|ranking| ranking.id).collect();
+        let albums: Vec<Album> = album::table.filter(album::id.eq_any(ids)).load(conn)?;
+
+        Ok(into_top(rankings, albums, |album| album.id))
+    }
+}
+
+impl Top<Artist> {
+    /// Ranks the artists of the songs played. A play of a song with several artists counts for
+    /// each of them.
+    pub fn top_artists(
+        context: &GraphQLContext,
+        first: i64,
+        since: Option<NaiveDateTime>,
+        until: Option<NaiveDateTime>,
+    ) -> FieldResult<Vec<Top<Artist>>> {
+        let conn = context.connection();
+        let rankings = rank(
+            conn,
+            "song_artist.artist_id",
+            "INNER JOIN song_artist ON song_artist.song_id = play.song_id",
+            &Window::new(context.user_id(), since, until),
+            first,
+        )?;
+
+        let ids: Vec<UUID> = rankings.iter().map(|ranking| ranking.id).collect();
+        let artists: Vec<Artist> = artist::table.filter(artist::id.eq_any(ids)).load(conn)?;
+
+        Ok(into_top(rankings, artists, |artist| artist.id))
+    }
+}
+
+impl ListeningPeriod {
+    /// Counts plays and listening time per day, week or month in UTC, oldest first. Periods
+    /// without plays are left out.
+    pub fn timeline(
+        context: &GraphQLContext,
+        bucket: TimelineBucket,
+        since: Option<NaiveDateTime>,
+        until: Option<NaiveDateTime>,
+    ) -> FieldResult<Vec<ListeningPeriod>> {
+        let window = Window::new(context.user_id(), since, until);
+        let query = format!(
+            "SELECT {start} AS start, count(*) AS play_count, {time} AS listening_time
+            FROM play INNER JOIN song ON song.id = play.song_id
+            WHERE play.user_id = ? AND play.time >= ? AND play.time < ?
+            GROUP BY start
+            ORDER BY start",
+            start = bucket.start_expression(),
+            time = LISTENING_TIME,
+        );
+
+        let periods = diesel::sql_query(query)
+            .bind::<Binary, _>(window.user_id)
+            .bind::<Timestamp, _>(window.since)
+            .bind::<Timestamp, _>(window.until)
+            .load(context.connection())?;
+
+        Ok(periods)
+    }
+}
@@ -1,35 +1,59 @@
 pub mod album;
+pub mod album_picture;
 pub mod artist;
 pub mod connection;
 pub mod id;
+pub mod import_issue;
+pub mod listening_stats;
 pub mod mutation;
 pub mod path;
+pub mod play;
+pub mod play_queue;
+pub mod playlist;
 pub mod query;
+pub mod radio;
 pub mod recents;
+pub mod role;
+pub mod search;
 pub mod song;
 pub mod song_user_stats;
 pub mod stats_collection;
 pub mod time;
+pub mod user;
 pub mod user_stats;
 
 pub use self::album::*;
+pub use self::album_picture::*;
 pub use self::artist::*;
 pub use self::connection::*;
 pub use self::id::*;
+pub use self::import_issue::*;
+pub use self::listening_stats::*;
 pub use self::mutation::*;
 pub use self::path::*;
+pub use self::play::*;
+pub use self::play_queue::*;
+pub use self::playlist::*;
 pub use self::query::*;
+pub use self::radio::*;
 pub use self::recents::*;
+pub use self::role::*;
+pub use self::search::*;
 pub use self::song::*;
 pub use self::song_user_stats::*;
 pub use self::stats_collection::*;
 pub use self::time::*;
+pub use self::user::*;
 pub use self::user_stats::*;
 pub use chrono::NaiveDateTime;
 
 use crate::context::GraphQLContext;
 use juniper::{EmptySubscription, RootNode};
 
+/// The most ids looked up in one query, below the 999 values older versions of SQLite bind in a
+/// query. Longer lists are looked up a chunk at a time.
+pub const MAX_BOUND_IDS: usize = 500;
+
 pub type Schema = RootNode<'static, Query, Mutation, EmptySubscription<GraphQLContext>>;
 
 pub fn create_schema() -> Schema {
@@ -1,29 +1,39 @@
 use crate::context::GraphQLContext;
-use crate::database::album;
-use crate::database::artist;
-use crate::database::song;
+use crate::database::{play, song};
+use crate::export::playlist::PlaylistFormat;
+use crate::import::{playlist, ratings};
+use crate::matching::SongMatcher;
 use crate::models::*;
+use crate::rules::SmartRules;
+use crate::scrobbler;
 use chrono::Utc;
-use diesel::expression::dsl::not;
 use diesel::prelude::*;
 use diesel::result;
 use diesel::Connection;
 use juniper::{FieldError, FieldResult};
+use std::collections::HashSet;
 
 pub struct Mutation;
 
 #[juniper::graphql_object(context = GraphQLContext)]
 impl Mutation {
+    /// Records that the user played a song. Clients which played songs while offline pass when
+    /// each song was played as `time`, which defaults to now. Times in the future count as now.
     fn play_song(
         &self,
         context: &GraphQLContext,
         song_id: UUID,
         artist_id: Option<UUID>,
         album_id: Option<UUID>,
+        playlist_id: Option<UUID>,
+        client: Option<String>,
+        duration_ms: Option<i32>,
+        time: Option<TimeWrapper>,
     ) -> FieldResult<StatsCollection> {
+        context.require_role(Role::Listener)?;
         let conn = &context.connection() as &SqliteConnection;
 
-        let valid_descriptors = vec![&artist_id, &album_id]
+        let valid_descriptors = vec![&artist_id, &album_id, &playlist_id]
             .into_iter()
             .filter(|option| option.is_some())
             .count();
@@ -32,45 +42,528 @@ impl Mutation {
             return Err("Multiple valid descriptors were passed. Only one should be passed".into());
         }
 
+        // Foreign keys aren't enforced, so plays and stats of rows which don't exist are refused
+        // here.
+        Song::from_id(conn, song_id)?;
+        if let Some(artist_id) = artist_id {
+            Artist::from_id(conn, artist_id)?;
+        }
+        if let Some(album_id) = album_id {
+            Album::from_id(conn, album_id)?;
+        }
+        if let Some(playlist_id) = playlist_id {
+            Playlist::visible(context, playlist_id)?;
+        }
+
         let now = Utc::now().naive_utc();
+        let time = time.map_or(now, |time| (*time).min(now));
+        let user_id = context.user_id();
 
         conn.transaction::<_, result::Error, _>(|| {
-            if let Some(ref artist_id) = artist_id {
-                diesel::update(artist::table.filter(artist::id.eq(artist_id)))
-                    .set(artist::last_played.eq(now))
-                    .execute(conn)?;
+            if let Some(artist_id) = artist_id {
+                UserStats::artist_played(conn, user_id, artist_id, time)?;
+            }
+
+            if let Some(album_id) = album_id {
+                UserStats::album_played(conn, user_id, album_id, time)?;
             }
 
-            if let Some(ref album_id) = album_id {
-                diesel::update(album::table.filter(album::id.eq(album_id)))
-                    .set(album::last_played.eq(now))
-                    .execute(conn)?;
+            if let Some(playlist_id) = playlist_id {
+                UserStats::playlist_played(conn, user_id, playlist_id, time)?;
             }
 
-            diesel::update(song::table.filter(song::id.eq(song_id)))
-                .set((
-                    song::play_count.eq(song::play_count + 1),
-                    song::last_played.eq(now),
-                ))
-                .execute(conn)?;
+            SongStats::played(conn, user_id, song_id, 1, time)?;
 
-            Ok(())
+            let play_id = UUID::new();
+            Play {
+                id: play_id,
+                user_id,
+                song_id,
+                time,
+                album_id,
+                artist_id,
+                client,
+                duration: duration_ms,
+            }
+            .insert_into(play::table)
+            .execute(conn)?;
+
+            // Queued in the same transaction so that recorded plays are always submitted.
+            scrobbler::enqueue_listen(conn, play_id)
         })?;
 
         Ok(StatsCollection {
             song_id,
             artist_id,
             album_id,
+            playlist_id,
         })
     }
 
-    fn toggle_like(&self, context: &GraphQLContext, song_id: UUID) -> FieldResult<Song> {
+    /// Tells the scrobblers which song started playing. Songs which finished playing are
+    /// submitted by `playSong`.
+    fn now_playing(
+        &self,
+        context: &GraphQLContext,
+        song_id: UUID,
+        client: Option<String>,
+    ) -> FieldResult<Song> {
+        context.require_role(Role::Listener)?;
         let conn = &context.connection() as &SqliteConnection;
 
-        diesel::update(song::table.filter(song::id.eq(song_id)))
-            .set(song::liked.eq(not(song::liked)))
-            .execute(conn)?;
+        scrobbler::enqueue_playing_now(
+            conn,
+            context.user_id(),
+            song_id,
+            client,
+            Utc::now().naive_utc(),
+        )?;
 
         Song::from_id(conn, song_id).map_err(FieldError::from)
     }
+
+    /// Removes plays from the user's listening history. Each removed play is taken off its song's
+    /// play count, the times its song, album and artist were last played go back to the latest
+    /// play left, and it's no longer submitted to scrobblers. Returns the number of plays removed.
+    fn delete_plays(&self, context: &GraphQLContext, ids: Vec<UUID>) -> FieldResult<i32> {
+        context.require_role(Role::Listener)?;
+        let conn = &context.connection() as &SqliteConnection;
+        let user_id = context.user_id();
+
+        let unique: Vec<UUID> = ids
+            .into_iter()
+            .collect::<HashSet<UUID>>()
+            .into_iter()
+            .collect();
+
+        let deleted = conn.transaction::<_, result::Error, _>(|| {
+            let mut deleted = 0;
+            let mut songs = HashSet::new();
+            let mut albums = HashSet::new();
+            let mut artists = HashSet::new();
+
+            for chunk in unique.chunks(MAX_BOUND_IDS) {
+                let plays = play::table
+                    .filter(play::id.eq_any(chunk))
+                    .filter(play::user_id.eq(user_id));
+                let removed: Vec<(UUID, UUID, Option<UUID>, Option<UUID>)> = plays
+                    .clone()
+                    .select((play::id, play::song_id, play::album_id, play::artist_id))
+                    .load(conn)?;
+
+                let mut play_ids = Vec::with_capacity(removed.len());
+                for (play_id, song_id, album_id, artist_id) in removed {
+                    SongStats::unplayed(conn, user_id, song_id)?;
+                    play_ids.push(play_id);
+                    songs.insert(song_id);
+                    albums.extend(album_id);
+                    artists.extend(artist_id);
+                }
+
+                scrobbler::forget_plays(conn, &play_ids)?;
+                deleted += diesel::delete(plays).execute(conn)?;
+            }
+
+            for song_id in songs {
+                SongStats::rewind(conn, user_id, song_id)?;
+            }
+            for album_id in albums {
+                UserStats::rewind_album(conn, user_id, album_id)?;
+            }
+            for artist_id in artists {
+                UserStats::rewind_artist(conn, user_id, artist_id)?;
+            }
+
+            Ok(deleted)
+        })?;
+
+        Ok(deleted as i32)
+    }
+
+    fn toggle_like(&self, context: &GraphQLContext, song_id: UUID) -> FieldResult<Song> {
+        context.require_role(Role::Listener)?;
+        let conn = &context.connection() as &SqliteConnection;
+
+        let song = Song::from_id(conn, song_id)?;
+        SongStats::toggle_like(conn, context.user_id(), song_id)?;
+
+        Ok(song)
+    }
+
+    /// Saves an album to the user's library, or removes it if it's already saved.
+    fn toggle_album_like(&self, context: &GraphQLContext, album_id: UUID) -> FieldResult<Album> {
+        context.require_role(Role::Listener)?;
+        let conn = &context.connection() as &SqliteConnection;
+
+        let album = Album::from_id(conn, album_id)?;
+        UserStats::toggle_album_like(conn, context.user_id(), album_id, Utc::now().naive_utc())?;
+
+        Ok(album)
+    }
+
+    /// Follows an artist, or unfollows them if the user already follows them.
+    fn toggle_artist_follow(
+        &self,
+        context: &GraphQLContext,
+        artist_id: UUID,
+    ) -> FieldResult<Artist> {
+        context.require_role(Role::Listener)?;
+        let conn = &context.connection() as &SqliteConnection;
+
+        let artist = Artist::from_id(conn, artist_id)?;
+        UserStats::toggle_artist_follow(
+            conn,
+            context.user_id(),
+            artist_id,
+            Utc::now().naive_utc(),
+        )?;
+
+        Ok(artist)
+    }
+
+    /// Rates a song from 1 to 5 stars, or clears its rating when `rating` is null. When the
+    /// server writes ratings into tags, the rating is saved even if writing the file fails, which is
+    /// only logged.
+    fn rate_song(
+        &self,
+        context: &GraphQLContext,
+        song_id: UUID,
+        rating: Option<i32>,
+    ) -> FieldResult<Song> {
+        context.require_role(Role::Listener)?;
+        let conn = &context.connection() as &SqliteConnection;
+        check_rating(rating)?;
+
+        let song = Song::from_id(conn, song_id)?;
+        SongStats::rate(conn, context.user_id(), song_id, rating)?;
+
+        if context.write_ratings() {
+            if let Err(e) = ratings::write(song.path.as_path(), rating) {
+                eprintln!(
+                    "Error writing the rating into '{}': {}",
+                    song.path.as_path().display(),
+                    e
+                );
+            }
+        }
+
+        Ok(song)
+    }
+
+    /// Rates an album from 1 to 5 stars, or clears its rating when `rating` is null.
+    fn rate_album(
+        &self,
+        context: &GraphQLContext,
+        album_id: UUID,
+        rating: Option<i32>,
+    ) -> FieldResult<Album> {
+        context.require_role(Role::Listener)?;
+        let conn = &context.connection() as &SqliteConnection;
+        check_rating(rating)?;
+
+        let album = Album::from_id(conn, album_id)?;
+        UserStats::rate_album(conn, context.user_id(), album_id, rating)?;
+
+        Ok(album)
+    }
+
+    /// Adds a playlist. With `rules`, it's a smart playlist whose songs are picked by the rules,
+    /// see `updatePlaylistRules`.
+    #[graphql(arguments(shared(default = false)))]
+    fn create_playlist(
+        &self,
+        context: &GraphQLContext,
+        name: String,
+        shared: bool,
+        rules: Option<String>,
+    ) -> FieldResult<Playlist> {
+        context.require_role(Role::Listener)?;
+        let rules = rules.as_deref().map(SmartRules::parse).transpose()?;
+
+        Playlist::create(
+            context.connection(),
+            context.user_id(),
+            &name,
+            shared,
+            rules.as_ref(),
+            Utc::now().naive_utc(),
+        )
+        .map_err(FieldError::from)
+    }
+
+    /// Renames the playlist or changes whether it's shared. Missing values are left alone.
+    fn update_playlist(
+        &self,
+        context: &GraphQLContext,
+        id: UUID,
+        name: Option<String>,
+        shared: Option<bool>,
+    ) -> FieldResult<Playlist> {
+        Playlist::owned(context, id)?;
+        let conn = &context.connection() as &SqliteConnection;
+
+        Playlist::update(conn, id, name.as_deref(), shared, Utc::now().naive_utc())?;
+
+        Playlist::from_id(conn, id).map_err(FieldError::from)
+    }
+
+    /// Replaces the rules of a smart playlist. Rules are JSON like
+    /// `{"match": {"all": [{"liked": true}, {"genre": "Jazz"}]}, "order": "random", "limit": 100}`.
+    /// Rules can check `liked`, `genre`, `artist`, `album`, `playCount`, `rating`, `year` and
+    /// `addedInLastDays`, and be combined with `all`, `any` and `not`.
+    fn update_playlist_rules(
+        &self,
+        context: &GraphQLContext,
+        id: UUID,
+        rules: String,
+    ) -> FieldResult<Playlist> {
+        let playlist = Playlist::owned(context, id)?;
+        if playlist.rules.is_none() {
+            return Err(FieldError::from("only smart playlists have rules"));
+        }
+        let rules = SmartRules::parse(&rules)?;
+        let conn = &context.connection() as &SqliteConnection;
+
+        Playlist::set_rules(conn, id, &rules, Utc::now().naive_utc())?;
+
+        Playlist::from_id(conn, id).map_err(FieldError::from)
+    }
+
+    fn delete_playlist(&self, context: &GraphQLContext, id: UUID) -> FieldResult<bool> {
+        Playlist::owned(context, id)?;
+        Playlist::delete(context.connection(), id)?;
+
+        Ok(true)
+    }
+
+    /// Adds songs to the playlist in order, before the item `before` or at the end when it's
+    /// missing.
+    fn add_to_playlist(
+        &self,
+        context: &GraphQLContext,
+        playlist_id: UUID,
+        song_ids: Vec<UUID>,
+        before: Option<UUID>,
+    ) -> FieldResult<Playlist> {
+        let playlist = Playlist::editable_items(context, playlist_id)?;
+        let conn = &context.connection() as &SqliteConnection;
+        check_songs(conn, &song_ids)?;
+
+        Playlist::add_songs(conn, playlist.id, &song_ids, before, Utc::now().naive_utc())?;
+
+        Playlist::from_id(conn, playlist_id).map_err(FieldError::from)
+    }
+
+    /// Removes items from the playlist. Items are removed by their own id rather than their
+    /// song's, since a song can be in a playlist more than once.
+    fn remove_from_playlist(
+        &self,
+        context: &GraphQLContext,
+        playlist_id: UUID,
+        item_ids: Vec<UUID>,
+    ) -> FieldResult<Playlist> {
+        let playlist = Playlist::editable_items(context, playlist_id)?;
+        let conn = &context.connection() as &SqliteConnection;
+
+        Playlist::remove_items(conn, playlist.id, &item_ids, Utc::now().naive_utc())?;
+
+        Playlist::from_id(conn, playlist_id).map_err(FieldError::from)
+    }
+
+    /// Moves an item before the item `before`, or to the end when it's missing.
+    fn move_playlist_item(
+        &self,
+        context: &GraphQLContext,
+        playlist_id: UUID,
+        item_id: UUID,
+        before: Option<UUID>,
+    ) -> FieldResult<Playlist> {
+        let playlist = Playlist::editable_items(context, playlist_id)?;
+        let conn = &context.connection() as &SqliteConnection;
+
+        Playlist::move_item(conn, playlist.id, item_id, before, Utc::now().naive_utc())?;
+
+        Playlist::from_id(conn, playlist_id).map_err(FieldError::from)
+    }
+
+    /// Adds a playlist from the contents of a playlist file. Entries are matched to songs by
+    /// their absolute locations, then by their artist, title and album. Entries which aren't in
+    /// the library are left out. The playlist is named after the file's title unless a `name`
+    /// is given.
+    #[graphql(arguments(shared(default = false)))]
+    fn import_playlist(
+        &self,
+        context: &GraphQLContext,
+        format: PlaylistFormat,
+        contents: String,
+        name: Option<String>,
+        shared: bool,
+    ) -> FieldResult<Playlist> {
+        context.require_role(Role::Listener)?;
+        let conn = &context.connection() as &SqliteConnection;
+
+        let file = playlist::parse(format, &contents)?;
+        let name = name
+            .or_else(|| file.title.clone())
+            .unwrap_or_else(|| "Imported playlist".to_string());
+
+        let imported = playlist::import(
+            conn,
+            context.user_id(),
+            &file,
+            &name,
+            shared,
+            &SongMatcher::load(conn)?,
+            Utc::now().naive_utc(),
+        )?;
+
+        Ok(imported.playlist)
+    }
+
+    /// A URL to download the playlist in the format from. With `paths`, songs are located by
+    /// the paths of their files rather than by stream URLs.
+    #[graphql(arguments(paths(default = false)))]
+    fn export_playlist(
+        &self,
+        context: &GraphQLContext,
+        id: UUID,
+        format: PlaylistFormat,
+        paths: bool,
+    ) -> FieldResult<String> {
+        let playlist = Playlist::visible(context, id)?;
+
+        Ok(format.export_url(playlist.id, paths))
+    }
+
+    /// Replaces the songs of the play queue, playing the song at `currentIndex` from `elapsedMs`
+    /// milliseconds in. Shuffle and repeat are left alone.
+    #[graphql(arguments(current_index(default = 0), elapsed_ms(default = 0)))]
+    fn replace_play_queue(
+        &self,
+        context: &GraphQLContext,
+        song_ids: Vec<UUID>,
+        current_index: i32,
+        elapsed_ms: i32,
+    ) -> FieldResult<PlayQueue> {
+        context.require_role(Role::Listener)?;
+        let conn = &context.connection() as &SqliteConnection;
+
+        check_songs(conn, &song_ids)?;
+        check_queue_index(current_index, song_ids.len() as i64)?;
+        check_elapsed(elapsed_ms)?;
+
+        PlayQueue::replace(
+            conn,
+            context.user_id(),
+            &song_ids,
+            current_index,
+            elapsed_ms,
+            Utc::now().naive_utc(),
+        )
+        .map_err(FieldError::from)
+    }
+
+    /// Adds songs to the end of the play queue.
+    fn append_to_play_queue(
+        &self,
+        context: &GraphQLContext,
+        song_ids: Vec<UUID>,
+    ) -> FieldResult<PlayQueue> {
+        context.require_role(Role::Listener)?;
+        let conn = &context.connection() as &SqliteConnection;
+
+        check_songs(conn, &song_ids)?;
+
+        PlayQueue::append(conn, context.user_id(), &song_ids, Utc::now().naive_utc())
+            .map_err(FieldError::from)
+    }
+
+    /// Moves the cursor of the play queue, saves how far into the current song playback got or
+    /// changes how the queue is played. Missing values are left alone. Moving the cursor plays
+    /// the song it moved to from the beginning unless `elapsedMs` is given.
+    fn update_play_queue(
+        &self,
+        context: &GraphQLContext,
+        current_index: Option<i32>,
+        elapsed_ms: Option<i32>,
+        shuffle: Option<bool>,
+        repeat_mode: Option<RepeatMode>,
+    ) -> FieldResult<PlayQueue> {
+        context.require_role(Role::Listener)?;
+        let conn = &context.connection() as &SqliteConnection;
+
+        if let Some(current_index) = current_index {
+            check_queue_index(current_index, PlayQueue::len(conn, context.user_id())?)?;
+        }
+        if let Some(elapsed_ms) = elapsed_ms {
+            check_elapsed(elapsed_ms)?;
+        }
+
+        PlayQueue::update(
+            conn,
+            context.user_id(),
+            current_index,
+            elapsed_ms,
+            shuffle,
+            repeat_mode,
+            Utc::now().naive_utc(),
+        )
+        .map_err(FieldError::from)
+    }
+}
+
+/// Checks that every id is of a song in the library. Songs which aren't would shift the
+/// positions of the songs after them in the play queue, and leave items in playlists which are
+/// never listed.
+fn check_songs(conn: &SqliteConnection, song_ids: &[UUID]) -> FieldResult<()> {
+    let unique: Vec<UUID> = song_ids
+        .iter()
+        .copied()
+        .collect::<HashSet<UUID>>()
+        .into_iter()
+        .collect();
+
+    // Long queues are checked a chunk at a time, since SQLite limits how many values a query
+    // binds.
+    let mut found = 0;
+    for chunk in unique.chunks(MAX_BOUND_IDS) {
+        let count: i64 = song::table
+            .filter(song::id.eq_any(chunk))
+            .count()
+            .get_result(conn)?;
+        found += count as usize;
+    }
+
+    if found != unique.len() {
+        return Err("some of the songs aren't in the library".into());
+    }
+
+    Ok(())
+}
+
+/// Checks that `index` is the position of a song in a play queue of `len` songs. The cursor of
+/// an empty queue is at 0.
+fn check_queue_index(index: i32, len: i64) -> FieldResult<()> {
+    if index < 0 || (index as i64 >= len && index != 0) {
+        return Err(format!("the queue has {} songs, there's no song at {}", len, index).into());
+    }
+
+    Ok(())
+}
+
+fn check_elapsed(elapsed_ms: i32) -> FieldResult<()> {
+    if elapsed_ms < 0 {
+        return Err(format!("elapsed time can't be negative, got {}", elapsed_ms).into());
+    }
+
+    Ok(())
+}
+
+fn check_rating(rating: Option<i32>) -> FieldResult<()> {
+    match rating {
+        Some(rating) if !(1..=5).contains(&rating) => {
+            Err(format!("ratings go from 1 to 5 stars, got {}", rating).into())
+        }
+        _ => Ok(()),
+    }
 }
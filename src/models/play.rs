@@ -0,0 +1,114 @@
+use crate::context::GraphQLContext;
+use crate::database::play;
+use crate::models::*;
+use diesel::dsl;
+use diesel::prelude::*;
+use diesel::sql_types::Timestamp;
+use juniper::{FieldError, FieldResult};
+
+/// A single play of a song.
+#[derive(Queryable, Identifiable, Insertable, Clone)]
+#[table_name = "play"]
+pub struct Play {
+    pub id: UUID,
+
+    /// The user who played the song.
+    pub user_id: UUID,
+    pub song_id: UUID,
+    pub time: NaiveDateTime,
+
+    /// The album the song was played from, if any.
+    pub album_id: Option<UUID>,
+
+    /// The artist the song was played from, if any.
+    pub artist_id: Option<UUID>,
+
+    /// The name of the client which played the song.
+    pub client: Option<String>,
+
+    /// How long the song was listened to in milliseconds.
+    pub duration: Option<i32>,
+}
+
+impl Play {
+    /// Gets a page of the user's plays, most recent first, optionally only those at or after
+    /// `since` and before `until`.
+    pub fn get_connection(
+        context: &GraphQLContext,
+        page: Page,
+        since: Option<NaiveDateTime>,
+        until: Option<NaiveDateTime>,
+    ) -> FieldResult<Connection<Play>> {
+        let conn = context.connection();
+        let user_id = context.user_id();
+
+        let filtered = || {
+            let mut query = play::table.filter(play::user_id.eq(user_id)).into_boxed();
+            if let Some(since) = since {
+                query = query.filter(play::time.ge(since));
+            }
+            if let Some(until) = until {
+                query = query.filter(play::time.lt(until));
+            }
+
+            query
+        };
+
+        let count: i64 = filtered().select(dsl::count_star()).first(conn)?;
+
+        Keyset {
+            columns: play::all_columns,
+            key: || -> SortKey<_, Timestamp> { Box::new(play::time.nullable()) },
+            id: play::id,
+            descending: true,
+            nullable: false,
+        }
+        .load_page(conn, filtered, &page, count)
+    }
+}
+
+#[juniper::graphql_object(context = GraphQLContext)]
+impl Play {
+    fn id(&self) -> UUID {
+        self.id
+    }
+
+    fn song(&self, context: &GraphQLContext) -> FieldResult<Song> {
+        Song::from_id(context.connection(), self.song_id).map_err(FieldError::from)
+    }
+
+    fn time(&self) -> TimeWrapper {
+        self.time.into()
+    }
+
+    /// The album the song was played from, if it was played from one.
+    fn album(&self, context: &GraphQLContext) -> FieldResult<Option<Album>> {
+        self.album_id
+            .map(|id| Album::from_id(context.connection(), id))
+            .transpose()
+            .map_err(FieldError::from)
+    }
+
+    /// The artist the song was played from, if it was played from one.
+    fn artist(&self, context: &GraphQLContext) -> FieldResult<Option<Artist>> {
+        self.artist_id
+            .map(|id| Artist::from_id(context.connection(), id))
+            .transpose()
+            .map_err(FieldError::from)
+    }
+
+    fn client(&self) -> Option<&str> {
+        self.client.as_deref()
+    }
+
+    /// How long the song was listened to in seconds, rounded to the nearest second.
+    fn duration(&self) -> Option<i32> {
+        self.duration
+            .map(|duration| milliseconds_to_seconds(duration as i64))
+    }
+
+    /// How long the song was listened to in milliseconds.
+    fn duration_ms(&self) -> Option<i32> {
+        self.duration
+    }
+}
@@ -0,0 +1,397 @@
+use crate::context::GraphQLContext;
+use crate::database::{play_queue, play_queue_item, song};
+use crate::models::*;
+use diesel::backend::Backend;
+use diesel::deserialize;
+use diesel::deserialize::FromSql;
+use diesel::prelude::*;
+use diesel::serialize;
+use diesel::serialize::Output;
+use diesel::sql_types::HasSqlType;
+use diesel::sql_types::Text;
+use diesel::types::ToSql;
+use juniper::{FieldError, FieldResult, GraphQLEnum};
+use std::io::Write;
+use std::str::FromStr;
+
+/// What is played after the current song finishes.
+#[derive(GraphQLEnum, AsExpression, FromSqlRow, Clone, Copy, Debug, PartialEq)]
+#[sql_type = "Text"]
+pub enum RepeatMode {
+    /// The next song, stopping after the last one.
+    #[graphql(name = "OFF")]
+    Off,
+
+    /// The next song, starting over after the last one.
+    #[graphql(name = "ALL")]
+    All,
+
+    /// The current song again.
+    #[graphql(name = "ONE")]
+    One,
+}
+
+impl RepeatMode {
+    /// The name of the mode in the database.
+    pub fn name(self) -> &'static str {
+        match self {
+            RepeatMode::Off => "off",
+            RepeatMode::All => "all",
+            RepeatMode::One => "one",
+        }
+    }
+}
+
+impl FromStr for RepeatMode {
+    type Err = String;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        match name {
+            "off" => Ok(RepeatMode::Off),
+            "all" => Ok(RepeatMode::All),
+            "one" => Ok(RepeatMode::One),
+            _ => Err(format!(
+                "unknown repeat mode '{}', expected off, all or one",
+                name
+            )),
+        }
+    }
+}
+
+impl<DB: Backend + HasSqlType<Text>> ToSql<Text, DB> for RepeatMode {
+    fn to_sql<W: Write>(&self, out: &mut Output<'_, W, DB>) -> serialize::Result {
+        <str as ToSql<Text, DB>>::to_sql(self.name(), out)
+    }
+}
+
+impl<DB> FromSql<Text, DB> for RepeatMode
+where
+    DB: Backend + HasSqlType<Text>,
+    String: FromSql<Text, DB>,
+{
+    fn from_sql(bytes: Option<&<DB as Backend>::RawValue>) -> deserialize::Result<Self> {
+        let name = String::from_sql(bytes)?;
+        Ok(name.parse()?)
+    }
+}
+
+/// The songs a user is listening to and how far they got, saved so they can carry on from
+/// another device. Each user has one queue.
+#[derive(Queryable, Identifiable, Insertable, Clone)]
+#[table_name = "play_queue"]
+#[primary_key(user_id)]
+pub struct PlayQueue {
+    pub user_id: UUID,
+
+    /// The position of the item being played.
+    pub current_index: i32,
+
+    /// How far into the current song playback got, in milliseconds.
+    pub elapsed: i32,
+
+    /// Whether the client plays the queue shuffled. Items are saved in the order they're played
+    /// in, so clients which shuffle the queue save it shuffled.
+    pub shuffle: bool,
+    pub repeat_mode: RepeatMode,
+    pub time_modified: NaiveDateTime,
+}
+
+#[derive(Queryable, Insertable)]
+#[table_name = "play_queue_item"]
+pub struct PlayQueueItem {
+    pub user_id: UUID,
+
+    /// The place of the song in the queue, counting from 0.
+    pub position: i32,
+    pub song_id: UUID,
+}
+
+impl PlayQueue {
+    /// The queue the user saved last, if they ever saved one.
+    pub fn find(conn: &SqliteConnection, user_id: UUID) -> QueryResult<Option<Self>> {
+        play_queue::table
+            .find(user_id)
+            .first::<Self>(conn)
+            .optional()
+    }
+
+    /// The queue the user saved last, or an empty queue if they never saved one.
+    fn find_or_empty(
+        conn: &SqliteConnection,
+        user_id: UUID,
+        now: NaiveDateTime,
+    ) -> QueryResult<Self> {
+        Ok(PlayQueue::find(conn, user_id)?.unwrap_or(PlayQueue {
+            user_id,
+            current_index: 0,
+            elapsed: 0,
+            shuffle: false,
+            repeat_mode: RepeatMode::Off,
+            time_modified: now,
+        }))
+    }
+
+    fn save(&self, conn: &SqliteConnection) -> QueryResult<()> {
+        diesel::replace_into(play_queue::table)
+            .values(self)
+            .execute(conn)?;
+
+        Ok(())
+    }
+
+    /// The number of songs in the user's queue.
+    pub fn len(conn: &SqliteConnection, user_id: UUID) -> QueryResult<i64> {
+        play_queue_item::table
+            .filter(play_queue_item::user_id.eq(user_id))
+            .count()
+            .get_result(conn)
+    }
+
+    /// Adds songs to the end of the queue from `position` on.
+    fn insert_songs(
+        conn: &SqliteConnection,
+        user_id: UUID,
+        song_ids: &[UUID],
+        position: i32,
+    ) -> QueryResult<()> {
+        let items: Vec<PlayQueueItem> = song_ids
+            .iter()
+            .enumerate()
+            .map(|(index, song_id)| PlayQueueItem {
+                user_id,
+                position: position + index as i32,
+                song_id: *song_id,
+            })
+            .collect();
+
+        diesel::insert_into(play_queue_item::table)
+            .values(&items)
+            .execute(conn)?;
+
+        Ok(())
+    }
+
+    /// Replaces the songs of the user's queue, playing the song at `current_index` from
+    /// `elapsed` milliseconds in. Shuffle and repeat are left alone.
+    pub fn replace(
+        conn: &SqliteConnection,
+        user_id: UUID,
+        song_ids: &[UUID],
+        current_index: i32,
+        elapsed: i32,
+        now: NaiveDateTime,
+    ) -> QueryResult<Self> {
+        conn.transaction(|| {
+            let mut queue = PlayQueue::find_or_empty(conn, user_id, now)?;
+
+            diesel::delete(play_queue_item::table.filter(play_queue_item::user_id.eq(user_id)))
+                .execute(conn)?;
+            PlayQueue::insert_songs(conn, user_id, song_ids, 0)?;
+
+            queue.current_index = current_index;
+            queue.elapsed = elapsed;
+            queue.time_modified = now;
+            queue.save(conn)?;
+
+            Ok(queue)
+        })
+    }
+
+    /// Adds songs to the end of the user's queue.
+    pub fn append(
+        conn: &SqliteConnection,
+        user_id: UUID,
+        song_ids: &[UUID],
+        now: NaiveDateTime,
+    ) -> QueryResult<Self> {
+        conn.transaction(|| {
+            let mut queue = PlayQueue::find_or_empty(conn, user_id, now)?;
+
+            let len = PlayQueue::len(conn, user_id)?;
+            PlayQueue::insert_songs(conn, user_id, song_ids, len as i32)?;
+
+            queue.time_modified = now;
+            queue.save(conn)?;
+
+            Ok(queue)
+        })
+    }
+
+    /// Moves the cursor of the user's queue, saves how far into the current song playback got
+    /// or changes how the queue is played. Missing values are left alone.
+    pub fn update(
+        conn: &SqliteConnection,
+        user_id: UUID,
+        current_index: Option<i32>,
+        elapsed: Option<i32>,
+        shuffle: Option<bool>,
+        repeat_mode: Option<RepeatMode>,
+        now: NaiveDateTime,
+    ) -> QueryResult<Self> {
+        conn.transaction(|| {
+            let mut queue = PlayQueue::find_or_empty(conn, user_id, now)?;
+
+            if let Some(current_index) = current_index {
+                queue.current_index = current_index;
+
+                // Playback starts at the beginning of a song the cursor moved to.
+                queue.elapsed = 0;
+            }
+            queue.elapsed = elapsed.unwrap_or(queue.elapsed);
+            queue.shuffle = shuffle.unwrap_or(queue.shuffle);
+            queue.repeat_mode = repeat_mode.unwrap_or(queue.repeat_mode);
+            queue.time_modified = now;
+            queue.save(conn)?;
+
+            Ok(queue)
+        })
+    }
+
+    /// The songs of the queue in order.
+    pub fn songs(&self, conn: &SqliteConnection) -> QueryResult<Vec<Song>> {
+        play_queue_item::table
+            .inner_join(song::table)
+            .filter(play_queue_item::user_id.eq(self.user_id))
+            .order_by(play_queue_item::position.asc())
+            .select(song::all_columns)
+            .load(conn)
+    }
+}
+
+#[juniper::graphql_object(context = GraphQLContext)]
+impl PlayQueue {
+    /// The songs of the queue in the order they're played in.
+    fn songs(&self, context: &GraphQLContext) -> FieldResult<Vec<Song>> {
+        self.songs(context.connection()).map_err(FieldError::from)
+    }
+
+    /// The position of the song being played in `songs`.
+    fn current_index(&self) -> i32 {
+        self.current_index
+    }
+
+    /// The song being played, or null when the queue is empty.
+    fn current_song(&self, context: &GraphQLContext) -> FieldResult<Option<Song>> {
+        play_queue_item::table
+            .inner_join(song::table)
+            .filter(play_queue_item::user_id.eq(self.user_id))
+            .filter(play_queue_item::position.eq(self.current_index))
+            .select(song::all_columns)
+            .first::<Song>(context.connection())
+            .optional()
+            .map_err(FieldError::from)
+    }
+
+    /// How far into the current song playback got in seconds.
+    fn elapsed(&self) -> i32 {
+        milliseconds_to_seconds(self.elapsed as i64)
+    }
+
+    /// How far into the current song playback got in milliseconds.
+    fn elapsed_ms(&self) -> i32 {
+        self.elapsed
+    }
+
+    /// Whether the queue is played shuffled. `songs` are already in the shuffled order.
+    fn shuffle(&self) -> bool {
+        self.shuffle
+    }
+
+    fn repeat_mode(&self) -> RepeatMode {
+        self.repeat_mode
+    }
+
+    /// When any device last changed the queue.
+    fn time_modified(&self) -> TimeWrapper {
+        self.time_modified.into()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{PlayQueue, RepeatMode};
+    use crate::models::*;
+    use crate::test_database;
+    use chrono::NaiveDate;
+    use diesel::prelude::*;
+
+    fn song_ids(queue: &PlayQueue, conn: &SqliteConnection) -> Vec<UUID> {
+        queue
+            .songs(conn)
+            .unwrap()
+            .into_iter()
+            .map(|song| song.id)
+            .collect()
+    }
+
+    #[test]
+    fn replace_and_append() {
+        let conn = test_database::connection();
+        let user_id = test_database::default_user(&conn);
+        let now = NaiveDate::from_ymd(2026, 10, 20).and_hms(0, 0, 0);
+        let songs: Vec<UUID> = (1..=4)
+            .map(|day| test_database::add_song(&conn, "Song", day).id)
+            .collect();
+
+        assert!(PlayQueue::find(&conn, user_id).unwrap().is_none());
+
+        let queue = PlayQueue::replace(&conn, user_id, &songs[..3], 1, 5000, now).unwrap();
+        assert_eq!(song_ids(&queue, &conn), &songs[..3]);
+        assert_eq!((queue.current_index, queue.elapsed), (1, 5000));
+
+        let queue = PlayQueue::append(&conn, user_id, &songs[3..], now).unwrap();
+        assert_eq!(song_ids(&queue, &conn), songs);
+        assert_eq!((queue.current_index, queue.elapsed), (1, 5000));
+
+        // Replacing the songs again drops the old ones, even the ones added by appending.
+        let queue = PlayQueue::replace(&conn, user_id, &[songs[2], songs[0]], 0, 0, now).unwrap();
+        assert_eq!(song_ids(&queue, &conn), vec![songs[2], songs[0]]);
+        assert_eq!(PlayQueue::len(&conn, user_id).unwrap(), 2);
+    }
+
+    #[test]
+    fn moving_the_cursor_resets_elapsed() {
+        let conn = test_database::connection();
+        let user_id = test_database::default_user(&conn);
+        let now = NaiveDate::from_ymd(2026, 10, 20).and_hms(0, 0, 0);
+        let songs: Vec<UUID> = (1..=3)
+            .map(|day| test_database::add_song(&conn, "Song", day).id)
+            .collect();
+
+        PlayQueue::replace(&conn, user_id, &songs, 0, 5000, now).unwrap();
+
+        let queue = PlayQueue::update(&conn, user_id, Some(2), None, None, None, now).unwrap();
+        assert_eq!((queue.current_index, queue.elapsed), (2, 0));
+
+        let queue =
+            PlayQueue::update(&conn, user_id, Some(1), Some(1200), None, None, now).unwrap();
+        assert_eq!((queue.current_index, queue.elapsed), (1, 1200));
+
+        // Saving the elapsed time or how the queue is played leaves the cursor alone.
+        let queue = PlayQueue::update(
+            &conn,
+            user_id,
+            None,
+            Some(3000),
+            Some(true),
+            Some(RepeatMode::All),
+            now,
+        )
+        .unwrap();
+        assert_eq!((queue.current_index, queue.elapsed), (1, 3000));
+        assert!(queue.shuffle);
+        assert_eq!(queue.repeat_mode, RepeatMode::All);
+
+        // Replacing the songs keeps how the queue is played.
+        let queue = PlayQueue::replace(&conn, user_id, &songs[..1], 0, 0, now).unwrap();
+        assert!(queue.shuffle);
+        assert_eq!(queue.repeat_mode, RepeatMode::All);
+        assert_eq!(
+            PlayQueue::find(&conn, user_id)
+                .unwrap()
+                .unwrap()
+                .current_index,
+            0
+        );
+    }
+}
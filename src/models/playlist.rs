@@ -0,0 +1,586 @@
+use crate::context;
+use crate::context::GraphQLContext;
+use crate::database::{playlist, playlist_item, song, user_playlist_stats};
+use crate::export::playlist::PlaylistFormat;
+use crate::matching::{SongKey, SongMatcher};
+use crate::models::*;
+use crate::ordering;
+use crate::rules::SmartRules;
+use chrono::Utc;
+use diesel::dsl;
+use diesel::prelude::*;
+use diesel::sql_types::{Text, Timestamp};
+use juniper::{FieldError, FieldResult};
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashSet;
+use std::hash::{Hash, Hasher};
+
+/// Songs a user put in an order. Playlists are private to the user who made them unless they're
+/// shared.
+#[derive(Queryable, Identifiable, Insertable, Clone)]
+#[table_name = "playlist"]
+pub struct Playlist {
+    pub id: UUID,
+
+    /// The user who made the playlist. Only they can change it.
+    pub user_id: UUID,
+    pub name: String,
+
+    /// Whether other users can see and play the playlist.
+    pub shared: bool,
+    pub time_added: NaiveDateTime,
+    pub time_modified: NaiveDateTime,
+
+    /// The playlist file in the library the playlist was imported from, if any. Imported
+    /// playlists are refreshed when their file changes, which replaces changes made in forte.
+    pub source_path: Option<PathWrapper>,
+
+    /// When the file was modified when the playlist was last imported from it.
+    pub source_modified: Option<NaiveDateTime>,
+
+    /// The rules picking the songs of a smart playlist as JSON, see [`crate::rules`]. Smart
+    /// playlists have no items, their songs are picked whenever they're listed.
+    pub rules: Option<String>,
+}
+
+#[derive(Queryable, Identifiable, Insertable, Clone)]
+#[table_name = "playlist_item"]
+pub struct PlaylistItem {
+    pub id: UUID,
+    pub playlist_id: UUID,
+    pub song_id: UUID,
+
+    /// Orders the items of a playlist, see [`crate::ordering`].
+    pub position: String,
+    pub time_added: NaiveDateTime,
+}
+
+impl Playlist {
+    pub fn from_id(conn: &SqliteConnection, id: UUID) -> QueryResult<Self> {
+        playlist::table.find(id).first::<Self>(conn)
+    }
+
+    /// The playlist of the user with the name, if they have one.
+    pub fn by_name(
+        conn: &SqliteConnection,
+        user_id: UUID,
+        name: &str,
+    ) -> QueryResult<Option<Self>> {
+        playlist::table
+            .filter(playlist::user_id.eq(user_id))
+            .filter(playlist::name.eq(name))
+            .order_by(playlist::time_added.asc())
+            .first::<Self>(conn)
+            .optional()
+    }
+
+    /// The playlist with the id, if the user of the context can see it.
+    pub fn visible(context: &GraphQLContext, id: UUID) -> FieldResult<Self> {
+        let playlist = Playlist::from_id(context.connection(), id)?;
+        if !playlist.is_visible_to(context.user_id()) {
+            return Err(context::forbidden("the playlist isn't shared"));
+        }
+
+        Ok(playlist)
+    }
+
+    /// The playlist with the id, if the user of the context made it and can change it.
+    pub fn owned(context: &GraphQLContext, id: UUID) -> FieldResult<Self> {
+        context.require_role(Role::Listener)?;
+
+        let playlist = Playlist::visible(context, id)?;
+        if playlist.user_id != context.user_id() {
+            return Err(context::forbidden("only the owner can change a playlist"));
+        }
+
+        Ok(playlist)
+    }
+
+    /// The playlist with the id, if the user of the context can add, remove and move its items.
+    pub fn editable_items(context: &GraphQLContext, id: UUID) -> FieldResult<Self> {
+        let playlist = Playlist::owned(context, id)?;
+        if playlist.rules.is_some() {
+            return Err(FieldError::from(
+                "the songs of a smart playlist are picked by its rules",
+            ));
+        }
+
+        Ok(playlist)
+    }
+
+    pub fn is_visible_to(&self, user_id: UUID) -> bool {
+        self.shared || self.user_id == user_id
+    }
+
+    pub fn create(
+        conn: &SqliteConnection,
+        user_id: UUID,
+        name: &str,
+        shared: bool,
+        rules: Option<&SmartRules>,
+        now: NaiveDateTime,
+    ) -> QueryResult<Self> {
+        let playlist = Playlist {
+            id: UUID::new(),
+            user_id,
+            name: name.to_string(),
+            shared,
+            time_added: now,
+            time_modified: now,
+            source_path: None,
+            source_modified: None,
+            rules: rules.map(SmartRules::to_json),
+        };
+
+        playlist
+            .clone()
+            .insert_into(playlist::table)
+            .execute(conn)?;
+        Ok(playlist)
+    }
+
+    /// Changes the name and whether the playlist is shared. Missing values are left alone.
+    pub fn update(
+        conn: &SqliteConnection,
+        id: UUID,
+        name: Option<&str>,
+        shared: Option<bool>,
+        now: NaiveDateTime,
+    ) -> QueryResult<()> {
+        if let Some(name) = name {
+            diesel::update(playlist::table.find(id))
+                .set(playlist::name.eq(name))
+                .execute(conn)?;
+        }
+
+        if let Some(shared) = shared {
+            diesel::update(playlist::table.find(id))
+                .set(playlist::shared.eq(shared))
+                .execute(conn)?;
+        }
+
+        Playlist::touch(conn, id, now)
+    }
+
+    /// Replaces the rules of a smart playlist.
+    pub fn set_rules(
+        conn: &SqliteConnection,
+        id: UUID,
+        rules: &SmartRules,
+        now: NaiveDateTime,
+    ) -> QueryResult<()> {
+        diesel::update(playlist::table.find(id))
+            .set(playlist::rules.eq(rules.to_json()))
+            .execute(conn)?;
+
+        Playlist::touch(conn, id, now)
+    }
+
+    /// The rules of the playlist if it's a smart playlist.
+    pub fn smart_rules(&self) -> QueryResult<Option<SmartRules>> {
+        self.rules
+            .as_deref()
+            .map(SmartRules::parse)
+            .transpose()
+            .map_err(|e| diesel::result::Error::DeserializationError(Box::new(e)))
+    }
+
+    pub fn delete(conn: &SqliteConnection, id: UUID) -> QueryResult<()> {
+        conn.transaction(|| {
+            diesel::delete(playlist_item::table.filter(playlist_item::playlist_id.eq(id)))
+                .execute(conn)?;
+            diesel::delete(
+                user_playlist_stats::table.filter(user_playlist_stats::playlist_id.eq(id)),
+            )
+            .execute(conn)?;
+            diesel::delete(playlist::table.find(id)).execute(conn)?;
+
+            Ok(())
+        })
+    }
+
+    fn touch(conn: &SqliteConnection, id: UUID, now: NaiveDateTime) -> QueryResult<()> {
+        diesel::update(playlist::table.find(id))
+            .set(playlist::time_modified.eq(now))
+            .execute(conn)?;
+
+        Ok(())
+    }
+
+    /// The positions an item placed before the item `before` would be between, or the position
+    /// of the last item when `before` is `None`. The item `moved` is left out, so that it can be
+    /// placed next to itself.
+    fn neighbours(
+        conn: &SqliteConnection,
+        id: UUID,
+        before: Option<UUID>,
+        moved: Option<UUID>,
+    ) -> QueryResult<(Option<String>, Option<String>)> {
+        let next: Option<String> = match before {
+            Some(before) => Some(
+                playlist_item::table
+                    .find(before)
+                    .filter(playlist_item::playlist_id.eq(id))
+                    .select(playlist_item::position)
+                    .first(conn)?,
+            ),
+            None => None,
+        };
+
+        let mut previous = playlist_item::table
+            .filter(playlist_item::playlist_id.eq(id))
+            .select(playlist_item::position)
+            .order_by(playlist_item::position.desc())
+            .into_boxed();
+        if let Some(next) = &next {
+            previous = previous.filter(playlist_item::position.lt(next.clone()));
+        }
+        if let Some(moved) = moved {
+            previous = previous.filter(playlist_item::id.ne(moved));
+        }
+
+        Ok((previous.first(conn).optional()?, next))
+    }
+
+    /// Adds songs to the playlist in order, before the item `before` or at the end when it's
+    /// `None`.
+    pub fn add_songs(
+        conn: &SqliteConnection,
+        id: UUID,
+        song_ids: &[UUID],
+        before: Option<UUID>,
+        now: NaiveDateTime,
+    ) -> QueryResult<Vec<PlaylistItem>> {
+        conn.transaction(|| {
+            let (previous, next) = Playlist::neighbours(conn, id, before, None)?;
+            let positions =
+                ordering::keys_between(previous.as_deref(), next.as_deref(), song_ids.len());
+
+            let items: Vec<PlaylistItem> = song_ids
+                .iter()
+                .zip(positions)
+                .map(|(song_id, position)| PlaylistItem {
+                    id: UUID::new(),
+                    playlist_id: id,
+                    song_id: *song_id,
+                    position,
+                    time_added: now,
+                })
+                .collect();
+
+            diesel::insert_into(playlist_item::table)
+                .values(&items)
+                .execute(conn)?;
+
+            Playlist::touch(conn, id, now)?;
+            Ok(items)
+        })
+    }
+
+    /// Removes items from the playlist, returning how many were removed.
+    pub fn remove_items(
+        conn: &SqliteConnection,
+        id: UUID,
+        item_ids: &[UUID],
+        now: NaiveDateTime,
+    ) -> QueryResult<usize> {
+        conn.transaction(|| {
+            let mut removed = 0;
+            for chunk in item_ids.chunks(MAX_BOUND_IDS) {
+                removed += diesel::delete(
+                    playlist_item::table
+                        .filter(playlist_item::playlist_id.eq(id))
+                        .filter(playlist_item::id.eq_any(chunk)),
+                )
+                .execute(conn)?;
+            }
+
+            Playlist::touch(conn, id, now)?;
+            Ok(removed)
+        })
+    }
+
+    /// Moves an item before the item `before`, or to the end when it's `None`. Only the moved
+    /// item's position changes.
+    pub fn move_item(
+        conn: &SqliteConnection,
+        id: UUID,
+        item_id: UUID,
+        before: Option<UUID>,
+        now: NaiveDateTime,
+    ) -> QueryResult<()> {
+        if before == Some(item_id) {
+            return Ok(());
+        }
+
+        conn.transaction(|| {
+            let (previous, next) = Playlist::neighbours(conn, id, before, Some(item_id))?;
+            let position = ordering::key_between(previous.as_deref(), next.as_deref());
+
+            let moved = diesel::update(
+                playlist_item::table
+                    .find(item_id)
+                    .filter(playlist_item::playlist_id.eq(id)),
+            )
+            .set(playlist_item::position.eq(position))
+            .execute(conn)?;
+            if moved == 0 {
+                return Err(diesel::result::Error::NotFound);
+            }
+
+            Playlist::touch(conn, id, now)
+        })
+    }
+
+    /// The songs of the playlist in order. The songs of smart playlists are picked by their
+    /// rules as of now. Smart playlists in a random order keep their shuffle until they change,
+    /// so that their pages fit together.
+    pub fn songs(&self, conn: &SqliteConnection) -> QueryResult<Vec<Song>> {
+        if let Some(rules) = self.smart_rules()? {
+            let mut hasher = DefaultHasher::new();
+            (self.id, self.time_modified).hash(&mut hasher);
+
+            return rules.songs(conn, self.user_id, Utc::now().naive_utc(), hasher.finish());
+        }
+
+        playlist_item::table
+            .inner_join(song::table)
+            .filter(playlist_item::playlist_id.eq(self.id))
+            .order_by(playlist_item::position.asc())
+            .select(song::all_columns)
+            .load(conn)
+    }
+
+    /// The total length of the playlist's songs in milliseconds.
+    pub fn duration_ms(&self, conn: &SqliteConnection) -> QueryResult<i64> {
+        if self.rules.is_some() {
+            let songs = self.songs(conn)?;
+            return Ok(songs.iter().map(|song| song.duration as i64).sum());
+        }
+
+        let maybe_duration: Option<i64> = playlist_item::table
+            .inner_join(song::table)
+            .filter(playlist_item::playlist_id.eq(self.id))
+            .select(dsl::sum(song::duration))
+            .first::<Option<i64>>(conn)?;
+
+        Ok(maybe_duration.unwrap_or(0))
+    }
+
+    /// The playlists the user made and the ones others shared, most recently changed first.
+    pub fn get_connection(
+        context: &GraphQLContext,
+        page: Page,
+    ) -> FieldResult<Connection<Playlist>> {
+        let conn = &context.connection() as &SqliteConnection;
+        let user_id = context.user_id();
+
+        let visible = || {
+            playlist::table
+                .filter(playlist::user_id.eq(user_id).or(playlist::shared.eq(true)))
+                .into_boxed()
+        };
+        let count: i64 = visible().select(dsl::count_star()).first(conn)?;
+
+        Keyset {
+            columns: playlist::all_columns,
+            key: || -> SortKey<_, Timestamp> { Box::new(playlist::time_modified.nullable()) },
+            id: playlist::id,
+            descending: true,
+            nullable: false,
+        }
+        .load_page(conn, visible, &page, count)
+    }
+
+    /// Points the items whose song's file is gone at a song in the library matching it, like
+    /// the same song at the path it was moved to and synced from. Returns how many items were
+    /// relinked.
+    pub fn relink(conn: &SqliteConnection) -> QueryResult<usize> {
+        let linked: HashSet<UUID> = playlist_item::table
+            .select(playlist_item::song_id)
+            .load::<UUID>(conn)?
+            .into_iter()
+            .collect();
+
+        let (existing, missing): (Vec<(UUID, SongKey)>, Vec<(UUID, SongKey)>) =
+            SongKey::load_all(conn)?
+                .into_iter()
+                .partition(|(_, key)| key.path.as_ref().map_or(false, |path| path.exists()));
+
+        let missing: Vec<(UUID, SongKey)> = missing
+            .into_iter()
+            .filter(|(id, _)| linked.contains(id))
+            .collect();
+        if missing.is_empty() {
+            return Ok(0);
+        }
+
+        let matcher = SongMatcher::new(existing.into_iter().collect());
+        let mut relinked = 0;
+        for (song_id, mut key) in missing {
+            // The path is where the song was, not where it is now.
+            key.path = None;
+
+            if let Some(new_song_id) = matcher.find(&key) {
+                relinked +=
+                    diesel::update(playlist_item::table.filter(playlist_item::song_id.eq(song_id)))
+                        .set(playlist_item::song_id.eq(new_song_id))
+                        .execute(conn)?;
+            }
+        }
+
+        Ok(relinked)
+    }
+}
+
+#[juniper::graphql_object(context = GraphQLContext)]
+impl Playlist {
+    fn id(&self) -> UUID {
+        self.id
+    }
+
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// The user who made the playlist.
+    fn owner(&self, context: &GraphQLContext) -> FieldResult<User> {
+        User::from_id(context.connection(), self.user_id).map_err(FieldError::from)
+    }
+
+    fn shared(&self) -> bool {
+        self.shared
+    }
+
+    /// Whether the songs of the playlist are picked by rules rather than added one by one.
+    fn smart(&self) -> bool {
+        self.rules.is_some()
+    }
+
+    /// The rules picking the songs of a smart playlist as JSON.
+    fn rules(&self) -> Option<&str> {
+        self.rules.as_deref()
+    }
+
+    /// The songs of the playlist in order.
+    fn items(
+        &self,
+        context: &GraphQLContext,
+        first: Option<i32>,
+        after: Option<String>,
+        last: Option<i32>,
+        before: Option<String>,
+    ) -> FieldResult<Connection<PlaylistItem>> {
+        PlaylistItem::get_connection(context, self, Page::new(first, after, last, before)?)
+    }
+
+    /// The total length of the playlist's songs in seconds.
+    fn duration(&self, context: &GraphQLContext) -> FieldResult<i32> {
+        Ok(milliseconds_to_seconds(
+            self.duration_ms(context.connection())?,
+        ))
+    }
+
+    /// The total length of the playlist's songs in milliseconds. It's a float because long
+    /// playlists outgrow the 32-bit integers of GraphQL in a few weeks of music.
+    fn duration_ms(&self, context: &GraphQLContext) -> FieldResult<f64> {
+        Ok(self.duration_ms(context.connection())? as f64)
+    }
+
+    fn stats(&self, context: &GraphQLContext) -> FieldResult<UserStats> {
+        UserStats::for_playlist(context.connection(), context.user_id(), self.id)
+            .map_err(FieldError::from)
+    }
+
+    /// A URL to download the playlist as an M3U8 file from. The songs are listed by their
+    /// stream URLs, or by the paths of their files with `?locations=paths`.
+    fn m3u_url(&self) -> String {
+        PlaylistFormat::M3u8.export_url(self.id, false)
+    }
+
+    /// Whether the playlist was imported from a playlist file in the library. Changes to it are
+    /// replaced when the file changes.
+    fn imported(&self) -> bool {
+        self.source_path.is_some()
+    }
+
+    fn time_added(&self) -> TimeWrapper {
+        self.time_added.into()
+    }
+
+    fn time_modified(&self) -> TimeWrapper {
+        self.time_modified.into()
+    }
+}
+
+impl PlaylistItem {
+    /// An item of a smart playlist, which only exists while the playlist is listed. It has the
+    /// id of its song, since smart playlists have each song once.
+    fn picked(playlist_id: UUID, song: &Song) -> Self {
+        PlaylistItem {
+            id: song.id,
+            playlist_id,
+            song_id: song.id,
+            position: String::new(),
+            time_added: song.time_added,
+        }
+    }
+
+    /// Gets a page of the items of a playlist in order. The songs of smart playlists are picked
+    /// for every page.
+    pub fn get_connection(
+        context: &GraphQLContext,
+        playlist: &Playlist,
+        page: Page,
+    ) -> FieldResult<Connection<PlaylistItem>> {
+        let conn = &context.connection() as &SqliteConnection;
+
+        // The songs of smart playlists aren't saved, so they can only be paged by offset.
+        if playlist.rules.is_some() {
+            let songs = playlist.songs(conn)?;
+            let count = songs.len() as i64;
+            let (offset, len) = page.offset_window(count)?;
+            let results = songs
+                .iter()
+                .skip(offset as usize)
+                .take(len as usize)
+                .map(|song| PlaylistItem::picked(playlist.id, song))
+                .collect();
+
+            return Ok(Connection::from_offset(results, offset, count));
+        }
+
+        let playlist_id = playlist.id;
+        let items = || {
+            playlist_item::table
+                .filter(playlist_item::playlist_id.eq(playlist_id))
+                .into_boxed()
+        };
+        let count: i64 = items().select(dsl::count_star()).first(conn)?;
+
+        Keyset {
+            columns: playlist_item::all_columns,
+            key: || -> SortKey<_, Text> { Box::new(playlist_item::position.nullable()) },
+            id: playlist_item::id,
+            descending: false,
+            nullable: false,
+        }
+        .load_page(conn, items, &page, count)
+    }
+}
+
+#[juniper::graphql_object(context = GraphQLContext)]
+impl PlaylistItem {
+    /// Identifies this entry of the song, since a song can be in a playlist more than once.
+    fn id(&self) -> UUID {
+        self.id
+    }
+
+    fn song(&self, context: &GraphQLContext) -> FieldResult<Song> {
+        Song::from_id(context.connection(), self.song_id).map_err(FieldError::from)
+    }
+
+    fn time_added(&self) -> TimeWrapper {
+        self.time_added.into()
+    }
+}
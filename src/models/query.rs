@@ -1,56 +1,294 @@
 use crate::context::GraphQLContext;
+use crate::export::listens::ListensFormat;
 use crate::models::*;
+use crate::radio::RadioSeed;
 use juniper::{FieldError, FieldResult};
 
 pub struct Query;
 
 #[juniper::graphql_object(context = GraphQLContext)]
 impl Query {
+    /// The user whose likes, plays and stats are returned.
+    fn current_user(context: &GraphQLContext) -> FieldResult<User> {
+        User::from_id(&context.connection(), context.user_id()).map_err(FieldError::from)
+    }
+
+    /// Everyone who can sign in. Only admins can list the users.
+    fn users(context: &GraphQLContext) -> FieldResult<Vec<User>> {
+        context.require_role(Role::Admin)?;
+        User::list(&context.connection()).map_err(FieldError::from)
+    }
+
     fn album(context: &GraphQLContext, id: UUID) -> FieldResult<Album> {
         Album::from_id(&context.connection(), id).map_err(FieldError::from)
     }
 
-    #[graphql(arguments(first(default = 25)))]
     fn albums(
         context: &GraphQLContext,
-        first: i32,
+        first: Option<i32>,
         after: Option<String>,
+        last: Option<i32>,
+        before: Option<String>,
         sort: Option<SortParams>,
     ) -> FieldResult<Connection<Album>> {
-        Album::get_connection(context, first as i64, after, sort)
+        Album::get_connection(context, Page::new(first, after, last, before)?, sort)
     }
 
     fn artist(context: &GraphQLContext, id: UUID) -> FieldResult<Artist> {
         Artist::from_id(&context.connection(), id).map_err(FieldError::from)
     }
 
-    #[graphql(arguments(first(default = 25)))]
     fn artists(
         context: &GraphQLContext,
-        first: i32,
+        first: Option<i32>,
         after: Option<String>,
+        last: Option<i32>,
+        before: Option<String>,
         sort: Option<SortParams>,
     ) -> FieldResult<Connection<Artist>> {
-        Artist::get_connection(context, first as i64, after, sort)
+        Artist::get_connection(context, Page::new(first, after, last, before)?, sort)
     }
 
     fn song(context: &GraphQLContext, id: UUID) -> FieldResult<Song> {
         Song::from_id(&context.connection(), id).map_err(FieldError::from)
     }
 
-    #[graphql(arguments(first(default = 25)))]
     fn songs(
         context: &GraphQLContext,
-        first: i32,
+        first: Option<i32>,
         after: Option<String>,
+        last: Option<i32>,
+        before: Option<String>,
         sort: Option<SortParams>,
     ) -> FieldResult<Connection<Song>> {
-        Song::get_connection(context, first as i64, after, sort)
+        Song::get_connection(context, Page::new(first, after, last, before)?, sort)
+    }
+
+    /// Songs the user played, most recent first. Optionally only plays at or after `since`
+    /// and before `until` are returned.
+    fn history(
+        context: &GraphQLContext,
+        first: Option<i32>,
+        after: Option<String>,
+        last: Option<i32>,
+        before: Option<String>,
+        since: Option<TimeWrapper>,
+        until: Option<TimeWrapper>,
+    ) -> FieldResult<Connection<Play>> {
+        Play::get_connection(
+            context,
+            Page::new(first, after, last, before)?,
+            since.map(|time| *time),
+            until.map(|time| *time),
+        )
+    }
+
+    /// The songs the user played the most, optionally only counting plays at or after `since`
+    /// and before `until`.
+    #[graphql(arguments(first(default = 10)))]
+    fn top_songs(
+        context: &GraphQLContext,
+        first: i32,
+        since: Option<TimeWrapper>,
+        until: Option<TimeWrapper>,
+    ) -> FieldResult<Vec<Top<Song>>> {
+        Top::<Song>::top_songs(
+            context,
+            first as i64,
+            since.map(|time| *time),
+            until.map(|time| *time),
+        )
+    }
+
+    /// The albums whose songs the user played the most, optionally only counting plays at or
+    /// after `since` and before `until`.
+    #[graphql(arguments(first(default = 10)))]
+    fn top_albums(
+        context: &GraphQLContext,
+        first: i32,
+        since: Option<TimeWrapper>,
+        until: Option<TimeWrapper>,
+    ) -> FieldResult<Vec<Top<Album>>> {
+        Top::<Album>::top_albums(
+            context,
+            first as i64,
+            since.map(|time| *time),
+            until.map(|time| *time),
+        )
+    }
+
+    /// The artists whose songs the user played the most, optionally only counting plays at or
+    /// after `since` and before `until`.
+    #[graphql(arguments(first(default = 10)))]
+    fn top_artists(
+        context: &GraphQLContext,
+        first: i32,
+        since: Option<TimeWrapper>,
+        until: Option<TimeWrapper>,
+    ) -> FieldResult<Vec<Top<Artist>>> {
+        Top::<Artist>::top_artists(
+            context,
+            first as i64,
+            since.map(|time| *time),
+            until.map(|time| *time),
+        )
+    }
+
+    /// The number of the user's plays and time spent listening per day, week or month, oldest
+    /// first.
+    fn listening_timeline(
+        context: &GraphQLContext,
+        bucket: TimelineBucket,
+        since: Option<TimeWrapper>,
+        until: Option<TimeWrapper>,
+    ) -> FieldResult<Vec<ListeningPeriod>> {
+        ListeningPeriod::timeline(
+            context,
+            bucket,
+            since.map(|time| *time),
+            until.map(|time| *time),
+        )
+    }
+
+    /// Searches the names of artists, albums and songs, most relevant results first. Every word
+    /// has to match, and words can be prefixes (e.g. "beatles abb").
+    fn search(
+        context: &GraphQLContext,
+        query: String,
+        first: Option<i32>,
+        after: Option<String>,
+        last: Option<i32>,
+        before: Option<String>,
+    ) -> FieldResult<Connection<SearchResult>> {
+        SearchResult::search(context, &query, Page::new(first, after, last, before)?)
+    }
+
+    /// A URL to download the user's plays at or after `since` and before `until` from, in a
+    /// format the importers of ListenBrainz or Last.fm accept. Incremental exports only include
+    /// plays added after the user's last incremental export in the same format. Downloading one
+    /// with a POST request moves that mark forward, GET requests leave it alone.
+    #[graphql(arguments(incremental(default = false)))]
+    fn listens_export_url(
+        format: ListensFormat,
+        since: Option<TimeWrapper>,
+        until: Option<TimeWrapper>,
+        incremental: bool,
+    ) -> String {
+        format.export_url(
+            since.map(|time| *time),
+            until.map(|time| *time),
+            incremental,
+        )
     }
 
+    /// Files which failed to import, most recently seen first. Optionally only issues of one
+    /// kind (e.g. `missing_tag`) are returned. Only admins can see import issues.
+    fn import_issues(
+        context: &GraphQLContext,
+        first: Option<i32>,
+        after: Option<String>,
+        last: Option<i32>,
+        before: Option<String>,
+        kind: Option<String>,
+    ) -> FieldResult<Connection<ImportIssue>> {
+        context.require_role(Role::Admin)?;
+        ImportIssue::get_connection(context, Page::new(first, after, last, before)?, kind)
+    }
+
+    /// The albums, artists and playlists added most recently. With `followedOnly`, only the albums of
+    /// artists the user follows are returned.
+    #[graphql(arguments(first(default = 25), followed_only(default = false)))]
+    fn recently_added(
+        context: &GraphQLContext,
+        first: i32,
+        followed_only: bool,
+    ) -> FieldResult<Vec<RecentItem>> {
+        if followed_only {
+            RecentItem::recently_added_followed(context, first as i64)
+        } else {
+            RecentItem::recently_added(context, first as i64)
+        }
+    }
+
+    /// The albums the user saved to their library, most recently saved first.
+    fn liked_albums(
+        context: &GraphQLContext,
+        first: Option<i32>,
+        after: Option<String>,
+        last: Option<i32>,
+        before: Option<String>,
+    ) -> FieldResult<Connection<Album>> {
+        Album::liked_connection(context, Page::new(first, after, last, before)?)
+    }
+
+    /// The artists the user follows, most recently followed first.
+    fn followed_artists(
+        context: &GraphQLContext,
+        first: Option<i32>,
+        after: Option<String>,
+        last: Option<i32>,
+        before: Option<String>,
+    ) -> FieldResult<Connection<Artist>> {
+        Artist::followed_connection(context, Page::new(first, after, last, before)?)
+    }
+
+    /// A playlist the user made or one someone shared.
+    fn playlist(context: &GraphQLContext, id: UUID) -> FieldResult<Playlist> {
+        Playlist::visible(context, id)
+    }
+
+    /// The playlists the user made and the ones others shared, most recently changed first.
+    fn playlists(
+        context: &GraphQLContext,
+        first: Option<i32>,
+        after: Option<String>,
+        last: Option<i32>,
+        before: Option<String>,
+    ) -> FieldResult<Connection<Playlist>> {
+        Playlist::get_connection(context, Page::new(first, after, last, before)?)
+    }
+
+    /// The queue the user saved last from any device, or null if they never saved one. Clients
+    /// restore it when they start.
+    fn play_queue(context: &GraphQLContext) -> FieldResult<Option<PlayQueue>> {
+        PlayQueue::find(context.connection(), context.user_id()).map_err(FieldError::from)
+    }
+
+    /// `first` songs picked at random, only from the songs matching `filter` when it's given.
+    /// The same `seed` picks the same songs as long as the library doesn't change. At most 500
+    /// songs are picked at once.
     #[graphql(arguments(first(default = 25)))]
-    fn recently_added(context: &GraphQLContext, first: i32) -> FieldResult<Vec<RecentItem>> {
-        RecentItem::recently_added(context, first as i64)
+    fn random_songs(
+        context: &GraphQLContext,
+        first: i32,
+        filter: Option<String>,
+        seed: Option<i32>,
+    ) -> FieldResult<Vec<Song>> {
+        random_songs(context, first as i64, filter, seed)
+    }
+
+    /// An endless list of songs like an artist, an album or a song, favoring songs the user
+    /// played alongside it, liked or didn't play lately. Exactly one of `artistId`, `albumId` or
+    /// `songId` is required. Pass the cursor of a page as `after` to get the next one. Pages
+    /// have at most 500 songs.
+    #[graphql(arguments(first(default = 25)))]
+    fn radio(
+        context: &GraphQLContext,
+        artist_id: Option<UUID>,
+        album_id: Option<UUID>,
+        song_id: Option<UUID>,
+        first: i32,
+        after: Option<String>,
+        seed: Option<i32>,
+    ) -> FieldResult<Radio> {
+        let radio_seed = match (artist_id, album_id, song_id) {
+            (Some(id), None, None) => RadioSeed::Artist(id),
+            (None, Some(id), None) => RadioSeed::Album(id),
+            (None, None, Some(id)) => RadioSeed::Song(id),
+            _ => return Err("exactly one of artistId, albumId or songId is required".into()),
+        };
+
+        Radio::get(context, radio_seed, first as i64, after, seed)
     }
 
     #[graphql(arguments(first(default = 25)))]
@@ -0,0 +1,150 @@
+use crate::context::GraphQLContext;
+use crate::database::song;
+use crate::models::*;
+use crate::radio;
+use crate::radio::RadioSeed;
+use chrono::Utc;
+use diesel::prelude::*;
+use juniper::{FieldError, FieldResult};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+
+/// A page of the songs of a radio. Radios never end, there's always a next page.
+pub struct Radio {
+    pub songs: Vec<Song>,
+
+    /// Gets the next page when passed as `after`.
+    pub cursor: String,
+}
+
+impl Radio {
+    /// A page of the songs of the radio seeded with `seed`, shuffled by `random_seed`, or a
+    /// random number when it's missing. Pages after the first continue from `after`.
+    pub fn get(
+        context: &GraphQLContext,
+        seed: RadioSeed,
+        first: i64,
+        after: Option<String>,
+        random_seed: Option<i32>,
+    ) -> FieldResult<Radio> {
+        check_page(first)?;
+
+        let conn = &context.connection() as &SqliteConnection;
+        let cursor = match after {
+            Some(after) => decode_cursor::<RadioCursor>(&after)?,
+            None => RadioCursor {
+                random_seed: random_seed.map_or_else(rand::random, |seed| seed as u64),
+                offset: 0,
+                started: Utc::now().naive_utc().timestamp(),
+            },
+        };
+        let started = NaiveDateTime::from_timestamp_opt(cursor.started, 0)
+            .ok_or_else(|| FieldError::from("invalid cursor"))?;
+        if cursor.offset > radio::MAX_RADIO_OFFSET {
+            return Err("the radio played too many songs, start it again".into());
+        }
+
+        let weights = radio::radio_songs(conn, context.user_id(), seed, started)?;
+        let song_ids =
+            radio::weighted_shuffle(&weights, cursor.random_seed, cursor.offset, first as usize);
+
+        Ok(Radio {
+            songs: songs_in_order(conn, &song_ids)?,
+            cursor: encode_cursor(&RadioCursor {
+                offset: cursor.offset + song_ids.len(),
+                ..cursor
+            }),
+        })
+    }
+}
+
+/// Where a page of a radio starts: the seed of its shuffle, the number of songs before the page
+/// and when the radio started, as seconds since the epoch.
+#[derive(Serialize, Deserialize)]
+struct RadioCursor {
+    random_seed: u64,
+    offset: usize,
+    started: i64,
+}
+
+/// `count` songs picked at random, only from the songs matching `filter` when it's given,
+/// shuffled by `random_seed`, or a random number when it's missing.
+pub fn random_songs(
+    context: &GraphQLContext,
+    count: i64,
+    filter: Option<String>,
+    random_seed: Option<i32>,
+) -> FieldResult<Vec<Song>> {
+    check_page(count)?;
+    let conn = &context.connection() as &SqliteConnection;
+
+    let mut query = song::table.into_boxed();
+    if let Some(filter) = &filter {
+        query = Song::filter_query(query, filter);
+    }
+    let mut song_ids: Vec<UUID> = query.select(song::id).load(conn)?;
+
+    // Songs are picked in the same order every time, so that the same seed picks the same songs.
+    song_ids.sort_by_key(|id| id.to_string());
+    let song_ids = radio::shuffle(
+        &song_ids,
+        random_seed.map_or_else(rand::random, |seed| seed as u64),
+        count as usize,
+    );
+
+    songs_in_order(conn, &song_ids).map_err(FieldError::from)
+}
+
+/// Checks that `first` songs fit on a page of at most `MAX_RADIO_PAGE` songs.
+fn check_page(first: i64) -> FieldResult<()> {
+    if first < 0 {
+        return Err(format!("first can't be negative, got {}", first).into());
+    }
+    if first as usize > radio::MAX_RADIO_PAGE {
+        return Err(format!(
+            "pages have at most {} songs, got {}",
+            radio::MAX_RADIO_PAGE,
+            first
+        )
+        .into());
+    }
+
+    Ok(())
+}
+
+/// The songs with the ids in the same order. Songs can be in there more than once.
+fn songs_in_order(conn: &SqliteConnection, song_ids: &[UUID]) -> QueryResult<Vec<Song>> {
+    let unique: Vec<UUID> = song_ids
+        .iter()
+        .copied()
+        .collect::<HashSet<UUID>>()
+        .into_iter()
+        .collect();
+
+    let mut songs: HashMap<UUID, Song> = HashMap::new();
+    for chunk in unique.chunks(MAX_BOUND_IDS) {
+        for song in song::table
+            .filter(song::id.eq_any(chunk))
+            .load::<Song>(conn)?
+        {
+            songs.insert(song.id, song);
+        }
+    }
+
+    Ok(song_ids
+        .iter()
+        .filter_map(|id| songs.get(id).cloned())
+        .collect())
+}
+
+#[juniper::graphql_object(context = GraphQLContext)]
+impl Radio {
+    fn songs(&self) -> &[Song] {
+        &self.songs
+    }
+
+    /// Pass as `after` to get the songs after these.
+    fn cursor(&self) -> &str {
+        &self.cursor
+    }
+}
@@ -1,6 +1,10 @@
 use crate::context::GraphQLContext;
 use crate::database::album;
 use crate::database::artist;
+use crate::database::playlist;
+use crate::database::user_album_stats;
+use crate::database::user_artist_stats;
+use crate::database::user_playlist_stats;
 use crate::models::*;
 use diesel::prelude::*;
 use juniper::{FieldResult, GraphQLUnion};
@@ -10,13 +14,22 @@ use juniper::{FieldResult, GraphQLUnion};
 pub enum RecentItem {
     Album(Album),
     Artist(Artist),
+    Playlist(Playlist),
 }
 
-fn merge_recents(albums: Vec<Album>, artists: Vec<Artist>) -> Vec<RecentItem> {
+fn merge_recents(
+    albums: Vec<Album>,
+    artists: Vec<Artist>,
+    playlists: Vec<Playlist>,
+) -> Vec<RecentItem> {
     let recent_albums = albums.into_iter().map(RecentItem::Album);
     let recent_artists = artists.into_iter().map(RecentItem::Artist);
+    let recent_playlists = playlists.into_iter().map(RecentItem::Playlist);
 
-    let recent: Vec<RecentItem> = recent_albums.chain(recent_artists).collect();
+    let recent: Vec<RecentItem> = recent_albums
+        .chain(recent_artists)
+        .chain(recent_playlists)
+        .collect();
 
     recent
 }
@@ -24,6 +37,7 @@ fn merge_recents(albums: Vec<Album>, artists: Vec<Artist>) -> Vec<RecentItem> {
 fn combine_and_truncate<F, O>(
     albums: Vec<Album>,
     artists: Vec<Artist>,
+    playlists: Vec<Playlist>,
     first: i64,
     compare_by: F,
 ) -> Vec<RecentItem>
@@ -31,7 +45,7 @@ where
     O: Ord,
     F: Fn(&RecentItem) -> O,
 {
-    let mut recents = merge_recents(albums, artists);
+    let mut recents = merge_recents(albums, artists, playlists);
 
     recents.sort_by(|a, b| Ord::cmp(&compare_by(b), &compare_by(a)));
     recents.truncate(first as usize);
@@ -53,48 +67,108 @@ impl RecentItem {
             .limit(first)
             .load(conn)?;
 
+        let playlists: Vec<Playlist> = playlist::table
+            .filter(
+                playlist::user_id
+                    .eq(context.user_id())
+                    .or(playlist::shared.eq(true)),
+            )
+            .order_by(playlist::time_added.desc())
+            .limit(first)
+            .load(conn)?;
+
         Ok(combine_and_truncate(
             albums,
             artists,
+            playlists,
             first,
             RecentItem::time_added,
         ))
     }
 
-    pub fn recently_played(context: &GraphQLContext, first: i64) -> FieldResult<Vec<RecentItem>> {
+    /// The albums of artists the user follows, added most recently first.
+    pub fn recently_added_followed(
+        context: &GraphQLContext,
+        first: i64,
+    ) -> FieldResult<Vec<RecentItem>> {
         let conn = &context.connection() as &SqliteConnection;
 
+        let followed_artists = user_artist_stats::table
+            .filter(user_artist_stats::user_id.eq(context.user_id()))
+            .filter(user_artist_stats::time_followed.is_not_null())
+            .select(user_artist_stats::artist_id);
+
         let albums: Vec<Album> = album::table
-            .filter(album::last_played.is_not_null())
-            .order_by(album::last_played.desc())
+            .filter(album::artist_id.eq_any(followed_artists))
+            .order_by(album::time_added.desc())
             .limit(first)
             .load(conn)?;
 
-        let artists: Vec<Artist> = artist::table
-            .filter(artist::last_played.is_not_null())
-            .order_by(artist::last_played.desc())
+        Ok(albums.into_iter().map(RecentItem::Album).collect())
+    }
+
+    pub fn recently_played(context: &GraphQLContext, first: i64) -> FieldResult<Vec<RecentItem>> {
+        let conn = &context.connection() as &SqliteConnection;
+
+        let albums: Vec<(Album, Option<NaiveDateTime>)> = album::table
+            .inner_join(user_album_stats::table)
+            .filter(user_album_stats::user_id.eq(context.user_id()))
+            .filter(user_album_stats::last_played.is_not_null())
+            .order_by(user_album_stats::last_played.desc())
             .limit(first)
+            .select((album::all_columns, user_album_stats::last_played))
             .load(conn)?;
 
-        Ok(combine_and_truncate(
-            albums,
-            artists,
-            first,
-            RecentItem::last_played,
-        ))
+        let artists: Vec<(Artist, Option<NaiveDateTime>)> = artist::table
+            .inner_join(user_artist_stats::table)
+            .filter(user_artist_stats::user_id.eq(context.user_id()))
+            .filter(user_artist_stats::last_played.is_not_null())
+            .order_by(user_artist_stats::last_played.desc())
+            .limit(first)
+            .select((artist::all_columns, user_artist_stats::last_played))
+            .load(conn)?;
+
+        // Playlists which stopped being shared since the user played them are left out.
+        let playlists: Vec<(Playlist, Option<NaiveDateTime>)> = playlist::table
+            .inner_join(user_playlist_stats::table)
+            .filter(user_playlist_stats::user_id.eq(context.user_id()))
+            .filter(user_playlist_stats::last_played.is_not_null())
+            .filter(
+                playlist::user_id
+                    .eq(context.user_id())
+                    .or(playlist::shared.eq(true)),
+            )
+            .order_by(user_playlist_stats::last_played.desc())
+            .limit(first)
+            .select((playlist::all_columns, user_playlist_stats::last_played))
+            .load(conn)?;
+
+        let mut recents: Vec<(RecentItem, Option<NaiveDateTime>)> = albums
+            .into_iter()
+            .map(|(album, last_played)| (RecentItem::Album(album), last_played))
+            .chain(
+                artists
+                    .into_iter()
+                    .map(|(artist, last_played)| (RecentItem::Artist(artist), last_played)),
+            )
+            .chain(
+                playlists
+                    .into_iter()
+                    .map(|(playlist, last_played)| (RecentItem::Playlist(playlist), last_played)),
+            )
+            .collect();
+
+        recents.sort_by(|(_, a), (_, b)| Ord::cmp(b, a));
+        recents.truncate(first as usize);
+
+        Ok(recents.into_iter().map(|(item, _)| item).collect())
     }
 
     fn time_added(&self) -> NaiveDateTime {
         match self {
             RecentItem::Album(album) => album.time_added,
             RecentItem::Artist(artist) => artist.time_added,
-        }
-    }
-
-    fn last_played(&self) -> NaiveDateTime {
-        match self {
-            RecentItem::Album(album) => album.last_played.unwrap(),
-            RecentItem::Artist(artist) => artist.last_played.unwrap(),
+            RecentItem::Playlist(playlist) => playlist.time_added,
         }
     }
 }
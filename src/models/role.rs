@@ -0,0 +1,74 @@
+use diesel::backend::Backend;
+use diesel::deserialize;
+use diesel::deserialize::FromSql;
+use diesel::serialize;
+use diesel::serialize::Output;
+use diesel::sql_types::HasSqlType;
+use diesel::sql_types::Text;
+use diesel::types::ToSql;
+use juniper::GraphQLEnum;
+use std::io::Write;
+use std::str::FromStr;
+
+/// What a user is allowed to do. Each role can do everything the roles before it can.
+#[derive(
+    GraphQLEnum, AsExpression, FromSqlRow, Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord,
+)]
+#[sql_type = "Text"]
+pub enum Role {
+    /// Browses and plays the library without anything being recorded.
+    #[graphql(name = "GUEST")]
+    Guest,
+
+    /// Records plays, likes and ratings of their own.
+    #[graphql(name = "LISTENER")]
+    Listener,
+
+    /// Manages the library and can see how it was imported.
+    #[graphql(name = "ADMIN")]
+    Admin,
+}
+
+impl Role {
+    /// The name of the role on the command line and in the database.
+    pub fn name(self) -> &'static str {
+        match self {
+            Role::Guest => "guest",
+            Role::Listener => "listener",
+            Role::Admin => "admin",
+        }
+    }
+}
+
+impl FromStr for Role {
+    type Err = String;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        match name {
+            "guest" => Ok(Role::Guest),
+            "listener" => Ok(Role::Listener),
+            "admin" => Ok(Role::Admin),
+            _ => Err(format!(
+                "unknown role '{}', expected admin, listener or guest",
+                name
+            )),
+        }
+    }
+}
+
+impl<DB: Backend + HasSqlType<Text>> ToSql<Text, DB> for Role {
+    fn to_sql<W: Write>(&self, out: &mut Output<'_, W, DB>) -> serialize::Result {
+        <str as ToSql<Text, DB>>::to_sql(self.name(), out)
+    }
+}
+
+impl<DB> FromSql<Text, DB> for Role
+where
+    DB: Backend + HasSqlType<Text>,
+    String: FromSql<Text, DB>,
+{
+    fn from_sql(bytes: Option<&<DB as Backend>::RawValue>) -> deserialize::Result<Self> {
+        let name = String::from_sql(bytes)?;
+        Ok(name.parse()?)
+    }
+}
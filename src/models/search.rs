@@ -0,0 +1,192 @@
+use crate::context::GraphQLContext;
+use crate::database::album;
+use crate::database::artist;
+use crate::database::song;
+use crate::models::*;
+use diesel::dsl;
+use diesel::prelude::*;
+use diesel::sql_types::{BigInt, Binary, Bool, Text};
+use diesel::sqlite::Sqlite;
+use juniper::{FieldResult, GraphQLUnion};
+use std::collections::HashMap;
+
+#[derive(GraphQLUnion)]
+#[graphql(Context = GraphQLContext)]
+pub enum SearchResult {
+    Song(Song),
+    Album(Album),
+    Artist(Artist),
+}
+
+/// Turns a search typed by a user into an FTS5 query matching rows which contain every word,
+/// with the last letters of each word allowed to be missing. Returns `None` when there are no
+/// words to search for.
+pub fn match_query(search: &str) -> Option<String> {
+    let terms: Vec<String> = search
+        .split_whitespace()
+        .filter(|word| word.chars().any(char::is_alphanumeric))
+        .map(|word| format!("\"{}\"*", word.replace('"', "\"\"")))
+        .collect();
+
+    if terms.is_empty() {
+        None
+    } else {
+        Some(terms.join(" "))
+    }
+}
+
+/// A filter keeping the rows of `table` whose row in its full-text index matches `match_query`.
+/// The index's rows are found through their keys in `<table>_fts_key`.
+pub fn matches_fts<QS>(
+    table: &str,
+    match_query: String,
+) -> Box<dyn BoxableExpression<QS, Sqlite, SqlType = Bool>> {
+    Box::new(
+        dsl::sql::<Bool>(&format!(
+            "{0}.id IN (SELECT id FROM {0}_fts_key WHERE key IN \
+             (SELECT rowid FROM {0}_fts WHERE {0}_fts MATCH ",
+            table
+        ))
+        .bind::<Text, _>(match_query)
+        .sql("))"),
+    )
+}
+
+#[derive(QueryableByName)]
+struct SearchHit {
+    #[sql_type = "Text"]
+    kind: String,
+
+    #[sql_type = "Binary"]
+    id: UUID,
+}
+
+#[derive(QueryableByName)]
+struct SearchCount {
+    #[sql_type = "BigInt"]
+    count: i64,
+}
+
+/// Matches from every index, best first. bm25 scores are lower for better matches.
+const SEARCH_QUERY: &str = "
+    SELECT kind, id FROM (
+        SELECT 'artist' AS kind, artist_fts_key.id AS id, bm25(artist_fts) AS rank
+        FROM artist_fts INNER JOIN artist_fts_key ON artist_fts_key.key = artist_fts.rowid
+        WHERE artist_fts MATCH ?
+        UNION ALL
+        SELECT 'album', album_fts_key.id, bm25(album_fts)
+        FROM album_fts INNER JOIN album_fts_key ON album_fts_key.key = album_fts.rowid
+        WHERE album_fts MATCH ?
+        UNION ALL
+        SELECT 'song', song_fts_key.id, bm25(song_fts)
+        FROM song_fts INNER JOIN song_fts_key ON song_fts_key.key = song_fts.rowid
+        WHERE song_fts MATCH ?
+    )
+    ORDER BY rank, kind, id
+    LIMIT ? OFFSET ?";
+
+const SEARCH_COUNT_QUERY: &str = "
+    SELECT
+        (SELECT count(*) FROM artist_fts WHERE artist_fts MATCH ?) +
+        (SELECT count(*) FROM album_fts WHERE album_fts MATCH ?) +
+        (SELECT count(*) FROM song_fts WHERE song_fts MATCH ?) AS count";
+
+impl SearchResult {
+    /// Searches the names of artists, albums and songs, returning the most relevant results
+    /// first. Songs also match the names of their album and artists.
+    pub fn search(
+        context: &GraphQLContext,
+        query: &str,
+        page: Page,
+    ) -> FieldResult<Connection<SearchResult>> {
+        let conn = &context.connection() as &SqliteConnection;
+
+        let match_query = match match_query(query) {
+            Some(match_query) => match_query,
+            None => return Ok(Connection::from_offset(Vec::new(), 0, 0)),
+        };
+
+        let SearchCount { count } = diesel::sql_query(SEARCH_COUNT_QUERY)
+            .bind::<Text, _>(&match_query)
+            .bind::<Text, _>(&match_query)
+            .bind::<Text, _>(&match_query)
+            .get_result(conn)?;
+
+        // Results are ranked by relevance, which has no stable key to seek past, so they're
+        // paged by offset.
+        let (offset, len) = page.offset_window(count)?;
+        let hits: Vec<SearchHit> = diesel::sql_query(SEARCH_QUERY)
+            .bind::<Text, _>(&match_query)
+            .bind::<Text, _>(&match_query)
+            .bind::<Text, _>(&match_query)
+            .bind::<BigInt, _>(len)
+            .bind::<BigInt, _>(offset)
+            .load(conn)?;
+
+        let ids_of = |kind: &str| -> Vec<UUID> {
+            hits.iter()
+                .filter(|hit| hit.kind == kind)
+                .map(|hit| hit.id)
+                .collect()
+        };
+
+        let mut artists: HashMap<UUID, Artist> = artist::table
+            .filter(artist::id.eq_any(ids_of("artist")))
+            .load::<Artist>(conn)?
+            .into_iter()
+            .map(|artist| (artist.id, artist))
+            .collect();
+
+        let mut albums: HashMap<UUID, Album> = album::table
+            .filter(album::id.eq_any(ids_of("album")))
+            .load::<Album>(conn)?
+            .into_iter()
+            .map(|album| (album.id, album))
+            .collect();
+
+        let mut songs: HashMap<UUID, Song> = song::table
+            .filter(song::id.eq_any(ids_of("song")))
+            .load::<Song>(conn)?
+            .into_iter()
+            .map(|song| (song.id, song))
+            .collect();
+
+        let results: Vec<SearchResult> = hits
+            .iter()
+            .filter_map(|hit| match hit.kind.as_str() {
+                "artist" => artists.remove(&hit.id).map(SearchResult::Artist),
+                "album" => albums.remove(&hit.id).map(SearchResult::Album),
+                _ => songs.remove(&hit.id).map(SearchResult::Song),
+            })
+            .collect();
+
+        Ok(Connection::from_offset(results, offset, count))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::match_query;
+
+    #[test]
+    fn match_query_requires_every_word() {
+        assert_eq!(
+            match_query("beatles  abbey").as_deref(),
+            Some("\"beatles\"* \"abbey\"*")
+        );
+    }
+
+    #[test]
+    fn match_query_escapes_quotes() {
+        assert_eq!(
+            match_query("\"heroes\" OR").as_deref(),
+            Some("\"\"\"heroes\"\"\"* \"OR\"*")
+        );
+    }
+
+    #[test]
+    fn match_query_skips_punctuation() {
+        assert_eq!(match_query("  - & "), None);
+        assert_eq!(match_query("AC/DC -").as_deref(), Some("\"AC/DC\"*"));
+    }
+}
@@ -6,7 +6,7 @@ use crate::models::*;
 use diesel::prelude::*;
 use juniper::{FieldError, FieldResult};
 
-#[derive(Queryable, Identifiable, Insertable)]
+#[derive(Queryable, Identifiable, Insertable, Clone)]
 #[table_name = "song"]
 pub struct Song {
     pub id: UUID,
@@ -15,13 +15,14 @@ pub struct Song {
     pub track_number: i32,
     pub disk_number: i32,
 
+    /// The length of the song in milliseconds.
     pub duration: i32,
     pub time_added: NaiveDateTime,
-
-    pub play_count: i32,
-    pub last_played: Option<NaiveDateTime>,
-    pub liked: bool,
     pub path: PathWrapper,
+
+    /// The MusicBrainz recording ID of the song.
+    pub mbid: Option<String>,
+    pub genre: Option<String>,
 }
 
 impl Song {
@@ -34,10 +35,19 @@ impl Song {
     }
 }
 
+/// Converts a duration stored in milliseconds into whole seconds, rounding to the nearest second.
+pub fn milliseconds_to_seconds(milliseconds: i64) -> i32 {
+    ((milliseconds + 500) / 1000) as i32
+}
+
 impl GetConnection<song::table> for Song {
     type Name = song::name;
     type TimeAdded = song::time_added;
-    type LastPlayed = song::last_played;
+    type Id = song::id;
+
+    fn id() -> Self::Id {
+        song::id
+    }
 
     fn name() -> Self::Name {
         song::name
@@ -47,8 +57,20 @@ impl GetConnection<song::table> for Song {
         song::time_added
     }
 
-    fn last_played() -> Self::LastPlayed {
-        song::last_played
+    fn last_played_query() -> &'static str {
+        "SELECT last_played FROM user_song_stats
+        WHERE user_song_stats.song_id = song.id AND user_song_stats.user_id = "
+    }
+
+    fn rating_query() -> Option<&'static str> {
+        Some(
+            "SELECT rating FROM user_song_stats
+            WHERE user_song_stats.song_id = song.id AND user_song_stats.user_id = ",
+        )
+    }
+
+    fn table_name() -> &'static str {
+        "song"
     }
 }
 
@@ -88,26 +110,38 @@ impl Song {
             .map_err(FieldError::from)
     }
 
-    fn stats(&self) -> UserStats {
-        UserStats {
-            id: format!("stats:{}", self.id.to_string()),
-            last_played: self.last_played,
-        }
+    fn stats(&self, context: &GraphQLContext) -> FieldResult<UserStats> {
+        UserStats::for_song(context.connection(), context.user_id(), self.id)
+            .map_err(FieldError::from)
     }
 
-    fn song_stats(&self) -> SongUserStats {
-        SongUserStats {
-            id: format!("song_stats:{}", self.id.to_string()),
-            play_count: self.play_count,
-            liked: self.liked,
-        }
+    fn song_stats(&self, context: &GraphQLContext) -> FieldResult<SongUserStats> {
+        let stats = SongStats::find(context.connection(), context.user_id(), self.id)?;
+        Ok(stats.into())
     }
 
+    /// The length of the song in seconds, rounded to the nearest second.
     fn duration(&self) -> i32 {
+        milliseconds_to_seconds(self.duration as i64)
+    }
+
+    /// The length of the song in milliseconds.
+    fn duration_ms(&self) -> i32 {
         self.duration
     }
 
+    /// The genre of the song from its tags.
+    fn genre(&self) -> Option<&str> {
+        self.genre.as_deref()
+    }
+
     fn time_added(&self) -> TimeWrapper {
         self.time_added.into()
     }
+
+    /// The rating of the song from 1 to 5 stars, if the user rated it.
+    fn rating(&self, context: &GraphQLContext) -> FieldResult<Option<i32>> {
+        let stats = SongStats::find(context.connection(), context.user_id(), self.id)?;
+        Ok(stats.rating)
+    }
 }
@@ -1,11 +1,160 @@
+use crate::database::{play, user_song_stats};
+use crate::models::*;
+use diesel::dsl;
+use diesel::expression::dsl::not;
+use diesel::prelude::*;
 use juniper::ID;
 
+/// What a user did with a song. Songs the user never played, liked or rated have no row.
+#[derive(Queryable, Insertable, Clone)]
+#[table_name = "user_song_stats"]
+pub struct SongStats {
+    pub user_id: UUID,
+    pub song_id: UUID,
+    pub play_count: i32,
+    pub last_played: Option<NaiveDateTime>,
+    pub liked: bool,
+
+    /// The rating of the song from 1 to 5 stars.
+    pub rating: Option<i32>,
+}
+
+impl SongStats {
+    /// The stats of a song the user never played, liked or rated.
+    pub fn empty(user_id: UUID, song_id: UUID) -> Self {
+        SongStats {
+            user_id,
+            song_id,
+            play_count: 0,
+            last_played: None,
+            liked: false,
+            rating: None,
+        }
+    }
+
+    /// The user's stats for a song, which are empty when there is no row for it.
+    pub fn find(conn: &SqliteConnection, user_id: UUID, song_id: UUID) -> QueryResult<Self> {
+        let stats = user_song_stats::table
+            .find((user_id, song_id))
+            .first::<Self>(conn)
+            .optional()?;
+
+        Ok(stats.unwrap_or_else(|| SongStats::empty(user_id, song_id)))
+    }
+
+    /// Adds the row for a song so it can be updated in place.
+    pub fn ensure(conn: &SqliteConnection, user_id: UUID, song_id: UUID) -> QueryResult<()> {
+        diesel::insert_or_ignore_into(user_song_stats::table)
+            .values((
+                user_song_stats::user_id.eq(user_id),
+                user_song_stats::song_id.eq(song_id),
+            ))
+            .execute(conn)?;
+
+        Ok(())
+    }
+
+    /// Counts `plays` more plays of a song, moving the time it was last played forward to
+    /// `time`.
+    pub fn played(
+        conn: &SqliteConnection,
+        user_id: UUID,
+        song_id: UUID,
+        plays: i32,
+        time: NaiveDateTime,
+    ) -> QueryResult<()> {
+        SongStats::ensure(conn, user_id, song_id)?;
+
+        let row = user_song_stats::table.find((user_id, song_id));
+        diesel::update(row)
+            .set(user_song_stats::play_count.eq(user_song_stats::play_count + plays))
+            .execute(conn)?;
+
+        diesel::update(row)
+            .filter(
+                user_song_stats::last_played
+                    .is_null()
+                    .or(user_song_stats::last_played.lt(time)),
+            )
+            .set(user_song_stats::last_played.eq(time))
+            .execute(conn)?;
+
+        Ok(())
+    }
+
+    /// Takes a removed play off a song's play count.
+    pub fn unplayed(conn: &SqliteConnection, user_id: UUID, song_id: UUID) -> QueryResult<()> {
+        diesel::update(user_song_stats::table.find((user_id, song_id)))
+            .filter(user_song_stats::play_count.gt(0))
+            .set(user_song_stats::play_count.eq(user_song_stats::play_count - 1))
+            .execute(conn)?;
+
+        Ok(())
+    }
+
+    /// Moves the time a song was last played back to its latest play left in the history, after
+    /// plays were removed.
+    pub fn rewind(conn: &SqliteConnection, user_id: UUID, song_id: UUID) -> QueryResult<()> {
+        let last_played: Option<NaiveDateTime> = play::table
+            .filter(play::user_id.eq(user_id))
+            .filter(play::song_id.eq(song_id))
+            .select(dsl::max(play::time))
+            .first(conn)?;
+
+        diesel::update(user_song_stats::table.find((user_id, song_id)))
+            .set(user_song_stats::last_played.eq(last_played))
+            .execute(conn)?;
+
+        Ok(())
+    }
+
+    /// Sets the user's rating of a song, or clears it when `rating` is `None`.
+    pub fn rate(
+        conn: &SqliteConnection,
+        user_id: UUID,
+        song_id: UUID,
+        rating: Option<i32>,
+    ) -> QueryResult<()> {
+        SongStats::ensure(conn, user_id, song_id)?;
+
+        diesel::update(user_song_stats::table.find((user_id, song_id)))
+            .set(user_song_stats::rating.eq(rating))
+            .execute(conn)?;
+
+        Ok(())
+    }
+
+    pub fn toggle_like(conn: &SqliteConnection, user_id: UUID, song_id: UUID) -> QueryResult<()> {
+        SongStats::ensure(conn, user_id, song_id)?;
+
+        diesel::update(user_song_stats::table.find((user_id, song_id)))
+            .set(user_song_stats::liked.eq(not(user_song_stats::liked)))
+            .execute(conn)?;
+
+        Ok(())
+    }
+}
+
 pub struct SongUserStats {
     pub id: String,
     pub play_count: i32,
     pub liked: bool,
 }
 
+impl From<SongStats> for SongUserStats {
+    fn from(stats: SongStats) -> Self {
+        SongUserStats {
+            id: format!(
+                "song_stats:{}:{}",
+                stats.user_id.to_string(),
+                stats.song_id.to_string()
+            ),
+            play_count: stats.play_count,
+            liked: stats.liked,
+        }
+    }
+}
+
 #[juniper::graphql_object]
 impl SongUserStats {
     fn id(&self) -> ID {
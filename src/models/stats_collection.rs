@@ -6,6 +6,7 @@ pub struct StatsCollection {
     pub song_id: UUID,
     pub album_id: Option<UUID>,
     pub artist_id: Option<UUID>,
+    pub playlist_id: Option<UUID>,
 }
 
 #[juniper::graphql_object(context = GraphQLContext)]
@@ -16,8 +17,7 @@ impl StatsCollection {
 
     fn album_stats(&self, context: &GraphQLContext) -> FieldResult<Option<UserStats>> {
         if let Some(album_id) = self.album_id {
-            let album = Album::from_id(&context.connection(), album_id)?;
-            let stats = album.stats();
+            let stats = UserStats::for_album(context.connection(), context.user_id(), album_id)?;
 
             return Ok(Some(stats));
         }
@@ -27,8 +27,18 @@ impl StatsCollection {
 
     fn artist_stats(&self, context: &GraphQLContext) -> FieldResult<Option<UserStats>> {
         if let Some(artist_id) = self.artist_id {
-            let artist = Artist::from_id(&context.connection(), artist_id)?;
-            let stats = artist.stats();
+            let stats = UserStats::for_artist(context.connection(), context.user_id(), artist_id)?;
+
+            return Ok(Some(stats));
+        }
+
+        Ok(None)
+    }
+
+    fn playlist_stats(&self, context: &GraphQLContext) -> FieldResult<Option<UserStats>> {
+        if let Some(playlist_id) = self.playlist_id {
+            let stats =
+                UserStats::for_playlist(context.connection(), context.user_id(), playlist_id)?;
 
             return Ok(Some(stats));
         }
@@ -0,0 +1,90 @@
+use crate::context::GraphQLContext;
+use crate::database::user;
+use crate::models::*;
+use diesel::prelude::*;
+
+/// Someone listening to the library. Likes, ratings, play counts and listening history are kept
+/// per user.
+#[derive(Queryable, Identifiable, Insertable, Clone)]
+#[table_name = "user"]
+pub struct User {
+    pub id: UUID,
+    pub name: String,
+    pub time_added: NaiveDateTime,
+
+    /// The argon2 hash of the user's password in PHC format. Users without a password can only
+    /// sign in with API keys.
+    pub password_hash: Option<String>,
+    pub role: Role,
+}
+
+impl User {
+    pub fn from_id(conn: &SqliteConnection, id: UUID) -> QueryResult<Self> {
+        user::table.find(id).first::<Self>(conn)
+    }
+
+    pub fn by_name(conn: &SqliteConnection, name: &str) -> QueryResult<Option<Self>> {
+        user::table
+            .filter(user::name.eq(name))
+            .first::<Self>(conn)
+            .optional()
+    }
+
+    /// The first user added, which owns the plays and likes from before there were users. Used
+    /// when no user is picked.
+    pub fn default_user(conn: &SqliteConnection) -> QueryResult<Self> {
+        user::table
+            .order_by(user::time_added.asc())
+            .then_order_by(user::name.asc())
+            .first::<Self>(conn)
+    }
+
+    pub fn add(
+        conn: &SqliteConnection,
+        name: &str,
+        role: Role,
+        now: NaiveDateTime,
+    ) -> QueryResult<Self> {
+        let user = User {
+            id: UUID::new(),
+            name: name.to_string(),
+            time_added: now,
+            password_hash: None,
+            role,
+        };
+
+        user.clone().insert_into(user::table).execute(conn)?;
+        Ok(user)
+    }
+
+    pub fn list(conn: &SqliteConnection) -> QueryResult<Vec<Self>> {
+        user::table.order_by(user::name.asc()).load(conn)
+    }
+
+    pub fn set_role(conn: &SqliteConnection, id: UUID, role: Role) -> QueryResult<()> {
+        diesel::update(user::table.find(id))
+            .set(user::role.eq(role))
+            .execute(conn)?;
+
+        Ok(())
+    }
+}
+
+#[juniper::graphql_object(context = GraphQLContext)]
+impl User {
+    fn id(&self) -> UUID {
+        self.id
+    }
+
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn time_added(&self) -> TimeWrapper {
+        self.time_added.into()
+    }
+
+    fn role(&self) -> Role {
+        self.role
+    }
+}
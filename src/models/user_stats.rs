@@ -1,4 +1,7 @@
+use crate::database::{play, user_album_stats, user_artist_stats, user_playlist_stats};
 use crate::models::*;
+use diesel::dsl;
+use diesel::prelude::*;
 use juniper::ID;
 
 pub struct UserStats {
@@ -6,6 +9,307 @@ pub struct UserStats {
     pub last_played: Option<NaiveDateTime>,
 }
 
+impl UserStats {
+    fn new(user_id: UUID, id: UUID, last_played: Option<NaiveDateTime>) -> UserStats {
+        UserStats {
+            id: format!("stats:{}:{}", user_id.to_string(), id.to_string()),
+            last_played,
+        }
+    }
+
+    /// Adds the row for an album so it can be updated in place.
+    fn ensure_album(conn: &SqliteConnection, user_id: UUID, album_id: UUID) -> QueryResult<()> {
+        diesel::insert_or_ignore_into(user_album_stats::table)
+            .values((
+                user_album_stats::user_id.eq(user_id),
+                user_album_stats::album_id.eq(album_id),
+            ))
+            .execute(conn)?;
+
+        Ok(())
+    }
+
+    /// Adds the row for an artist so it can be updated in place.
+    fn ensure_artist(conn: &SqliteConnection, user_id: UUID, artist_id: UUID) -> QueryResult<()> {
+        diesel::insert_or_ignore_into(user_artist_stats::table)
+            .values((
+                user_artist_stats::user_id.eq(user_id),
+                user_artist_stats::artist_id.eq(artist_id),
+            ))
+            .execute(conn)?;
+
+        Ok(())
+    }
+
+    pub fn for_song(conn: &SqliteConnection, user_id: UUID, song_id: UUID) -> QueryResult<Self> {
+        let stats = SongStats::find(conn, user_id, song_id)?;
+        Ok(UserStats::new(user_id, song_id, stats.last_played))
+    }
+
+    pub fn for_album(conn: &SqliteConnection, user_id: UUID, album_id: UUID) -> QueryResult<Self> {
+        let last_played = user_album_stats::table
+            .find((user_id, album_id))
+            .select(user_album_stats::last_played)
+            .first(conn)
+            .optional()?
+            .flatten();
+
+        Ok(UserStats::new(user_id, album_id, last_played))
+    }
+
+    pub fn for_artist(
+        conn: &SqliteConnection,
+        user_id: UUID,
+        artist_id: UUID,
+    ) -> QueryResult<Self> {
+        let last_played = user_artist_stats::table
+            .find((user_id, artist_id))
+            .select(user_artist_stats::last_played)
+            .first(conn)
+            .optional()?
+            .flatten();
+
+        Ok(UserStats::new(user_id, artist_id, last_played))
+    }
+
+    /// Moves the time the user last played an album forward to `time`.
+    pub fn album_played(
+        conn: &SqliteConnection,
+        user_id: UUID,
+        album_id: UUID,
+        time: NaiveDateTime,
+    ) -> QueryResult<()> {
+        UserStats::ensure_album(conn, user_id, album_id)?;
+
+        diesel::update(user_album_stats::table.find((user_id, album_id)))
+            .filter(
+                user_album_stats::last_played
+                    .is_null()
+                    .or(user_album_stats::last_played.lt(time)),
+            )
+            .set(user_album_stats::last_played.eq(time))
+            .execute(conn)?;
+
+        Ok(())
+    }
+
+    /// Moves the time the user last played an album back to their latest play from it left in
+    /// the history, after plays were removed.
+    pub fn rewind_album(conn: &SqliteConnection, user_id: UUID, album_id: UUID) -> QueryResult<()> {
+        let last_played: Option<NaiveDateTime> = play::table
+            .filter(play::user_id.eq(user_id))
+            .filter(play::album_id.eq(album_id))
+            .select(dsl::max(play::time))
+            .first(conn)?;
+
+        diesel::update(user_album_stats::table.find((user_id, album_id)))
+            .set(user_album_stats::last_played.eq(last_played))
+            .execute(conn)?;
+
+        Ok(())
+    }
+
+    pub fn for_playlist(
+        conn: &SqliteConnection,
+        user_id: UUID,
+        playlist_id: UUID,
+    ) -> QueryResult<Self> {
+        let last_played = user_playlist_stats::table
+            .find((user_id, playlist_id))
+            .select(user_playlist_stats::last_played)
+            .first(conn)
+            .optional()?
+            .flatten();
+
+        Ok(UserStats::new(user_id, playlist_id, last_played))
+    }
+
+    /// Moves the time the user last played a playlist forward to `time`.
+    pub fn playlist_played(
+        conn: &SqliteConnection,
+        user_id: UUID,
+        playlist_id: UUID,
+        time: NaiveDateTime,
+    ) -> QueryResult<()> {
+        diesel::insert_or_ignore_into(user_playlist_stats::table)
+            .values((
+                user_playlist_stats::user_id.eq(user_id),
+                user_playlist_stats::playlist_id.eq(playlist_id),
+            ))
+            .execute(conn)?;
+
+        diesel::update(user_playlist_stats::table.find((user_id, playlist_id)))
+            .filter(
+                user_playlist_stats::last_played
+                    .is_null()
+                    .or(user_playlist_stats::last_played.lt(time)),
+            )
+            .set(user_playlist_stats::last_played.eq(time))
+            .execute(conn)?;
+
+        Ok(())
+    }
+
+    /// The user's rating of an album from 1 to 5 stars, if they rated it.
+    pub fn album_rating(
+        conn: &SqliteConnection,
+        user_id: UUID,
+        album_id: UUID,
+    ) -> QueryResult<Option<i32>> {
+        Ok(user_album_stats::table
+            .find((user_id, album_id))
+            .select(user_album_stats::rating)
+            .first(conn)
+            .optional()?
+            .flatten())
+    }
+
+    /// Sets the user's rating of an album, or clears it when `rating` is `None`.
+    pub fn rate_album(
+        conn: &SqliteConnection,
+        user_id: UUID,
+        album_id: UUID,
+        rating: Option<i32>,
+    ) -> QueryResult<()> {
+        UserStats::ensure_album(conn, user_id, album_id)?;
+
+        diesel::update(user_album_stats::table.find((user_id, album_id)))
+            .set(user_album_stats::rating.eq(rating))
+            .execute(conn)?;
+
+        Ok(())
+    }
+
+    /// When the user liked an album, if they like it.
+    pub fn album_liked(
+        conn: &SqliteConnection,
+        user_id: UUID,
+        album_id: UUID,
+    ) -> QueryResult<Option<NaiveDateTime>> {
+        Ok(user_album_stats::table
+            .find((user_id, album_id))
+            .select(user_album_stats::time_liked)
+            .first(conn)
+            .optional()?
+            .flatten())
+    }
+
+    /// Likes an album at `now`, or unlikes it if the user already likes it.
+    pub fn toggle_album_like(
+        conn: &SqliteConnection,
+        user_id: UUID,
+        album_id: UUID,
+        now: NaiveDateTime,
+    ) -> QueryResult<()> {
+        let time_liked = match UserStats::album_liked(conn, user_id, album_id)? {
+            Some(_) => None,
+            None => Some(now),
+        };
+
+        UserStats::set_album_liked(conn, user_id, album_id, time_liked)
+    }
+
+    /// Sets when the user liked an album, unliking it when `time_liked` is `None`.
+    pub fn set_album_liked(
+        conn: &SqliteConnection,
+        user_id: UUID,
+        album_id: UUID,
+        time_liked: Option<NaiveDateTime>,
+    ) -> QueryResult<()> {
+        UserStats::ensure_album(conn, user_id, album_id)?;
+        diesel::update(user_album_stats::table.find((user_id, album_id)))
+            .set(user_album_stats::time_liked.eq(time_liked))
+            .execute(conn)?;
+
+        Ok(())
+    }
+
+    /// Moves the time the user last played an artist forward to `time`.
+    pub fn artist_played(
+        conn: &SqliteConnection,
+        user_id: UUID,
+        artist_id: UUID,
+        time: NaiveDateTime,
+    ) -> QueryResult<()> {
+        UserStats::ensure_artist(conn, user_id, artist_id)?;
+
+        diesel::update(user_artist_stats::table.find((user_id, artist_id)))
+            .filter(
+                user_artist_stats::last_played
+                    .is_null()
+                    .or(user_artist_stats::last_played.lt(time)),
+            )
+            .set(user_artist_stats::last_played.eq(time))
+            .execute(conn)?;
+
+        Ok(())
+    }
+
+    /// Moves the time the user last played an artist back to their latest play from them left in
+    /// the history, after plays were removed.
+    pub fn rewind_artist(
+        conn: &SqliteConnection,
+        user_id: UUID,
+        artist_id: UUID,
+    ) -> QueryResult<()> {
+        let last_played: Option<NaiveDateTime> = play::table
+            .filter(play::user_id.eq(user_id))
+            .filter(play::artist_id.eq(artist_id))
+            .select(dsl::max(play::time))
+            .first(conn)?;
+
+        diesel::update(user_artist_stats::table.find((user_id, artist_id)))
+            .set(user_artist_stats::last_played.eq(last_played))
+            .execute(conn)?;
+
+        Ok(())
+    }
+
+    /// When the user followed an artist, if they follow them.
+    pub fn artist_followed(
+        conn: &SqliteConnection,
+        user_id: UUID,
+        artist_id: UUID,
+    ) -> QueryResult<Option<NaiveDateTime>> {
+        Ok(user_artist_stats::table
+            .find((user_id, artist_id))
+            .select(user_artist_stats::time_followed)
+            .first(conn)
+            .optional()?
+            .flatten())
+    }
+
+    /// Follows an artist at `now`, or unfollows them if the user already follows them.
+    pub fn toggle_artist_follow(
+        conn: &SqliteConnection,
+        user_id: UUID,
+        artist_id: UUID,
+        now: NaiveDateTime,
+    ) -> QueryResult<()> {
+        let time_followed = match UserStats::artist_followed(conn, user_id, artist_id)? {
+            Some(_) => None,
+            None => Some(now),
+        };
+
+        UserStats::set_artist_followed(conn, user_id, artist_id, time_followed)
+    }
+
+    /// Sets when the user followed an artist, unfollowing them when `time_followed` is `None`.
+    pub fn set_artist_followed(
+        conn: &SqliteConnection,
+        user_id: UUID,
+        artist_id: UUID,
+        time_followed: Option<NaiveDateTime>,
+    ) -> QueryResult<()> {
+        UserStats::ensure_artist(conn, user_id, artist_id)?;
+        diesel::update(user_artist_stats::table.find((user_id, artist_id)))
+            .set(user_artist_stats::time_followed.eq(time_followed))
+            .execute(conn)?;
+
+        Ok(())
+    }
+}
+
 #[juniper::graphql_object]
 impl UserStats {
     fn id(&self) -> ID {
@@ -0,0 +1,181 @@
+//! Keys which order items by comparing them as strings, so an item can be put between two others
+//! by giving it a key between theirs without changing any other key.
+//!
+//! Keys are strings of base 62 digits which are read as fractions: `"V"` is about one half and
+//! `"V1"` is a bit more than that. Keys never end with the zero digit, so there is always room for
+//! a key before any other key.
+
+const DIGITS: &[u8] = b"0123456789ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz";
+
+fn digit_value(digit: u8) -> usize {
+    DIGITS
+        .iter()
+        .position(|candidate| *candidate == digit)
+        .expect("ordering keys only hold base 62 digits")
+}
+
+/// A key which sorts after `before` (or first when it's `None`) and before `after` (or last when
+/// it's `None`). `before` has to sort before `after`.
+pub fn key_between(before: Option<&str>, after: Option<&str>) -> String {
+    match (before, after) {
+        (Some(before), None) => key_after(before),
+        (before, after) => midpoint(before.unwrap_or("").as_bytes(), after.map(str::as_bytes)),
+    }
+}
+
+/// `count` keys in order between `before` and `after`, as `key_between` treats them. The keys are
+/// spread evenly by bisecting the range, so adding many items at once doesn't make each key
+/// longer than the one before it.
+pub fn keys_between(before: Option<&str>, after: Option<&str>, count: usize) -> Vec<String> {
+    let mut keys = Vec::with_capacity(count);
+    push_keys_between(before, after, count, &mut keys);
+    keys
+}
+
+fn push_keys_between(
+    before: Option<&str>,
+    after: Option<&str>,
+    count: usize,
+    keys: &mut Vec<String>,
+) {
+    if count == 0 {
+        return;
+    }
+
+    let middle = key_between(before, after);
+    let left = count / 2;
+    push_keys_between(before, Some(&middle), left, keys);
+    keys.push(middle.clone());
+    push_keys_between(Some(&middle), after, count - left - 1, keys);
+}
+
+/// A key after `key`, kept short by incrementing its first digit which isn't the largest, so
+/// that appending many items doesn't make keys grow quickly.
+fn key_after(key: &str) -> String {
+    let bytes = key.as_bytes();
+    match bytes
+        .iter()
+        .position(|digit| *digit != DIGITS[DIGITS.len() - 1])
+    {
+        Some(index) => {
+            let mut after = bytes[..index].to_vec();
+            after.push(DIGITS[digit_value(bytes[index]) + 1]);
+            String::from_utf8(after).unwrap()
+        }
+        None => midpoint(bytes, None),
+    }
+}
+
+/// A key between `a` and `b`, where a missing `b` is the end of the key space.
+fn midpoint(a: &[u8], b: Option<&[u8]>) -> String {
+    if let Some(b) = b {
+        // Keep the prefix both keys share, reading missing digits of `a` as zeros.
+        let common = b
+            .iter()
+            .enumerate()
+            .take_while(|(index, digit)| a.get(*index).copied().unwrap_or(DIGITS[0]) == **digit)
+            .count();
+
+        if common > 0 {
+            let mut key = String::from_utf8(b[..common].to_vec()).unwrap();
+            key.push_str(&midpoint(
+                a.get(common..).unwrap_or(&[]),
+                Some(&b[common..]),
+            ));
+            return key;
+        }
+    }
+
+    let digit_a = a.first().map_or(0, |digit| digit_value(*digit));
+    let digit_b = b.map_or(DIGITS.len(), |b| digit_value(b[0]));
+
+    if digit_b - digit_a > 1 {
+        let middle = (digit_a + digit_b + 1) / 2;
+        return (DIGITS[middle] as char).to_string();
+    }
+
+    match b {
+        // The first digit of `b` alone sorts before `b` and after `a`.
+        Some(b) if b.len() > 1 => (b[0] as char).to_string(),
+        _ => {
+            let mut key = (DIGITS[digit_a] as char).to_string();
+            key.push_str(&midpoint(a.get(1..).unwrap_or(&[]), None));
+            key
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{key_between, keys_between};
+
+    #[test]
+    fn first_key_is_in_the_middle() {
+        assert_eq!(key_between(None, None), "V");
+    }
+
+    #[test]
+    fn appending_keeps_keys_short() {
+        let mut keys = vec![key_between(None, None)];
+        for _ in 0..1000 {
+            let key = key_between(keys.last().map(String::as_str), None);
+            assert!(&key > keys.last().unwrap());
+            keys.push(key);
+        }
+
+        assert!(keys.iter().all(|key| key.len() <= 40));
+    }
+
+    #[test]
+    fn prepending_sorts_first() {
+        let mut first = key_between(None, None);
+        for _ in 0..100 {
+            let key = key_between(None, Some(&first));
+            assert!(key < first);
+            assert!(!key.ends_with('0'));
+            first = key;
+        }
+    }
+
+    #[test]
+    fn inserting_between_neighbours() {
+        let mut low = key_between(None, None);
+        let high = key_between(Some(&low), None);
+
+        // Repeatedly inserting right after the same item always finds room.
+        for _ in 0..100 {
+            let key = key_between(Some(&low), Some(&high));
+            assert!(low < key && key < high, "{} < {} < {}", low, key, high);
+            assert!(!key.ends_with('0'));
+            low = key;
+        }
+    }
+
+    #[test]
+    fn between_keys_of_different_lengths() {
+        assert_eq!(key_between(Some("V"), Some("W")), "VV");
+        assert_eq!(key_between(Some("V"), Some("V1")), "V0V");
+        assert_eq!(key_between(Some("Vz"), Some("W")), "VzV");
+        assert_eq!(key_between(Some("A"), Some("z")), "a");
+    }
+
+    #[test]
+    fn many_keys_at_once_are_ordered_and_short() {
+        let low = key_between(None, None);
+        let high = key_between(Some(&low), None);
+
+        for (before, after) in &[
+            (None, None),
+            (Some(low.as_str()), None),
+            (None, Some(low.as_str())),
+            (Some(low.as_str()), Some(high.as_str())),
+        ] {
+            let keys = keys_between(*before, *after, 1000);
+            assert_eq!(keys.len(), 1000);
+            assert!(keys.windows(2).all(|pair| pair[0] < pair[1]));
+            assert!(before.map_or(true, |before| before < keys[0].as_str()));
+            assert!(after.map_or(true, |after| keys[999].as_str() < after));
+            assert!(keys.iter().all(|key| key.len() <= 6 && !key.ends_with('0')));
+        }
+    }
+}
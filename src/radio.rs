@@ -0,0 +1,472 @@
+//! Picks songs to play when the user doesn't say which: shuffling the library and radios, endless
+//! streams of songs like an artist, album or song.
+//!
+//! Radios weigh every song in the library by how it relates to the songs it's seeded with, then
+//! play them in a weighted shuffle. Songs sharing artists or genres with the seed, played in the
+//! same listening sessions or put in the same playlists count the most. Liked songs count more
+//! and songs played in the last few days count less. Once every song was played the shuffle
+//! starts over, so radios never end.
+//!
+//! Both are shuffled by a random number generator seeded with a number, so the same seed picks
+//! the same songs in the same order.
+
+use crate::database::{album, play, playlist, playlist_item, song, song_artist, user_song_stats};
+use crate::models::*;
+use chrono::Duration;
+use diesel::prelude::*;
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
+use rand::{Rng, SeedableRng};
+use std::collections::{HashMap, HashSet};
+
+/// Every song has this weight before it's compared to the seed, so that radios drift away from
+/// the seed after the related songs were played.
+const BASE_WEIGHT: f64 = 0.1;
+const ARTIST_WEIGHT: f64 = 3.0;
+const GENRE_WEIGHT: f64 = 2.0;
+
+/// Weights of co-occurrences, which are counted logarithmically so a few sessions or playlists
+/// count almost as much as many.
+const SESSION_WEIGHT: f64 = 1.5;
+const PLAYLIST_WEIGHT: f64 = 1.0;
+
+const LIKED_FACTOR: f64 = 1.5;
+
+/// A song played this many hours ago has about two thirds of its weight, and its weight grows back
+/// over the next few days.
+const RECENCY_HOURS: f64 = 24.0;
+
+/// The least a recently played song's weight is multiplied by.
+const MIN_RECENCY_FACTOR: f64 = 0.05;
+
+/// Plays less than this many minutes apart are in the same listening session.
+const SESSION_GAP_MINUTES: i64 = 30;
+
+/// The most songs on a page of a radio.
+pub const MAX_RADIO_PAGE: usize = 500;
+
+/// The most songs of a radio which can be played before its pages end. Every page shuffles the
+/// songs before it again, so this bounds the work of getting a page.
+pub const MAX_RADIO_OFFSET: usize = 100_000;
+
+/// What a radio plays songs like.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum RadioSeed {
+    Artist(UUID),
+    Album(UUID),
+    Song(UUID),
+}
+
+/// How a song relates to the seed of a radio and to the user listening.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Signals {
+    /// Whether any of the song's artists is an artist of the seed.
+    pub shares_artist: bool,
+
+    /// Whether the song's genre is a genre of the seed.
+    pub shares_genre: bool,
+
+    /// The number of the user's listening sessions the song was played in along with the seed.
+    pub sessions: u32,
+
+    /// The number of playlists the song is in along with the seed.
+    pub playlists: u32,
+    pub liked: bool,
+
+    /// How many hours ago the user last played the song, if they ever did.
+    pub hours_since_played: Option<f64>,
+}
+
+impl Signals {
+    /// How likely the song is to be picked next, relative to other songs. Always positive.
+    pub fn weight(&self) -> f64 {
+        let mut weight = BASE_WEIGHT;
+        if self.shares_artist {
+            weight += ARTIST_WEIGHT;
+        }
+        if self.shares_genre {
+            weight += GENRE_WEIGHT;
+        }
+        weight += SESSION_WEIGHT * f64::from(self.sessions).ln_1p();
+        weight += PLAYLIST_WEIGHT * f64::from(self.playlists).ln_1p();
+
+        if self.liked {
+            weight *= LIKED_FACTOR;
+        }
+
+        if let Some(hours) = self.hours_since_played {
+            let recency = 1.0 - (-hours.max(0.0) / RECENCY_HOURS).exp();
+            weight *= recency.max(MIN_RECENCY_FACTOR);
+        }
+
+        weight
+    }
+}
+
+/// `count` songs from `offset` on of the endless weighted shuffle of the songs. Songs with more
+/// weight tend to come earlier. Every song is played once before any is played again.
+pub fn weighted_shuffle(
+    songs: &[(UUID, f64)],
+    seed: u64,
+    offset: usize,
+    count: usize,
+) -> Vec<UUID> {
+    if songs.is_empty() {
+        return Vec::new();
+    }
+
+    let mut rng = StdRng::seed_from_u64(seed);
+    let mut skipped = 0;
+    let mut page = Vec::with_capacity(count.min(MAX_RADIO_PAGE));
+    while page.len() < count {
+        // Sorting by u^(1 / weight) for uniform u picks in proportion to the weights without
+        // picking any song twice. Comparing logarithms keeps small weights apart.
+        let mut keyed: Vec<(f64, UUID)> = songs
+            .iter()
+            .map(|(id, weight)| (rng.gen::<f64>().ln() / weight, *id))
+            .collect();
+        keyed.sort_by(|a, b| b.0.partial_cmp(&a.0).unwrap());
+
+        // Only the songs of the page are kept, the ones before it are shuffled and dropped.
+        let skip = (offset - skipped).min(keyed.len());
+        skipped += skip;
+        page.extend(
+            keyed
+                .into_iter()
+                .skip(skip)
+                .take(count - page.len())
+                .map(|(_, id)| id),
+        );
+    }
+
+    page
+}
+
+/// `count` of the songs picked uniformly, in a random order.
+pub fn shuffle(song_ids: &[UUID], seed: u64, count: usize) -> Vec<UUID> {
+    let mut rng = StdRng::seed_from_u64(seed);
+    song_ids.choose_multiple(&mut rng, count).copied().collect()
+}
+
+/// Every song in the library which a radio seeded with `seed` plays, with its weight. The likes,
+/// plays and playlists are those of `user_id`, and only plays until `now` count, so that a radio
+/// started at `now` keeps its weights while its songs are played. The seed song itself isn't
+/// played by its radio.
+pub fn radio_songs(
+    conn: &SqliteConnection,
+    user_id: UUID,
+    seed: RadioSeed,
+    now: NaiveDateTime,
+) -> QueryResult<Vec<(UUID, f64)>> {
+    // Songs are weighed in the same order every time, so that the same seed picks the same songs.
+    let songs: Vec<(UUID, UUID, Option<String>)> = song::table
+        .select((song::id, song::album_id, song::genre))
+        .order_by(song::id.asc())
+        .load(conn)?;
+
+    let mut artists: HashMap<UUID, Vec<UUID>> = HashMap::new();
+    for (song_id, artist_id) in song_artist::table.load::<(UUID, UUID)>(conn)? {
+        artists.entry(song_id).or_default().push(artist_id);
+    }
+
+    let seed_songs: HashSet<UUID> = match seed {
+        RadioSeed::Song(id) => songs
+            .iter()
+            .filter(|(song_id, _, _)| *song_id == id)
+            .map(|(song_id, _, _)| *song_id)
+            .collect(),
+        RadioSeed::Album(id) => songs
+            .iter()
+            .filter(|(_, album_id, _)| *album_id == id)
+            .map(|(song_id, _, _)| *song_id)
+            .collect(),
+        RadioSeed::Artist(id) => {
+            let albums: HashSet<UUID> = album::table
+                .filter(album::artist_id.eq(id))
+                .select(album::id)
+                .load::<UUID>(conn)?
+                .into_iter()
+                .collect();
+
+            songs
+                .iter()
+                .filter(|(song_id, album_id, _)| {
+                    albums.contains(album_id)
+                        || artists.get(song_id).map_or(false, |ids| ids.contains(&id))
+                })
+                .map(|(song_id, _, _)| *song_id)
+                .collect()
+        }
+    };
+    if seed_songs.is_empty() {
+        return Err(diesel::result::Error::NotFound);
+    }
+
+    let mut seed_artists: HashSet<UUID> = seed_songs
+        .iter()
+        .flat_map(|song_id| artists.get(song_id).into_iter().flatten().copied())
+        .collect();
+    if let RadioSeed::Artist(id) = seed {
+        seed_artists.insert(id);
+    }
+
+    let seed_genres: HashSet<String> = songs
+        .iter()
+        .filter(|(song_id, _, _)| seed_songs.contains(song_id))
+        .filter_map(|(_, _, genre)| genre.as_deref().map(str::to_lowercase))
+        .collect();
+
+    let plays: Vec<(UUID, NaiveDateTime)> = play::table
+        .filter(play::user_id.eq(user_id))
+        .filter(play::time.le(now))
+        .order_by(play::time.asc())
+        .select((play::song_id, play::time))
+        .load(conn)?;
+    let last_played: HashMap<UUID, NaiveDateTime> = plays.iter().copied().collect();
+
+    let sessions = count_cooccurrences(sessions(&plays), &seed_songs);
+    let playlists = playlist_cooccurrences(conn, user_id, &seed_songs)?;
+
+    let liked: HashSet<UUID> = user_song_stats::table
+        .filter(user_song_stats::user_id.eq(user_id))
+        .filter(user_song_stats::liked.eq(true))
+        .select(user_song_stats::song_id)
+        .load::<UUID>(conn)?
+        .into_iter()
+        .collect();
+
+    Ok(songs
+        .iter()
+        .filter(|(song_id, _, _)| seed != RadioSeed::Song(*song_id))
+        .map(|(song_id, _, genre)| {
+            let signals = Signals {
+                shares_artist: artists
+                    .get(song_id)
+                    .map_or(false, |ids| ids.iter().any(|id| seed_artists.contains(id))),
+                shares_genre: genre
+                    .as_ref()
+                    .map_or(false, |genre| seed_genres.contains(&genre.to_lowercase())),
+                sessions: sessions.get(song_id).copied().unwrap_or(0),
+                playlists: playlists.get(song_id).copied().unwrap_or(0),
+                liked: liked.contains(song_id),
+                hours_since_played: last_played
+                    .get(song_id)
+                    .map(|time| (now - *time).num_minutes() as f64 / 60.0),
+            };
+
+            (*song_id, signals.weight())
+        })
+        .collect())
+}
+
+/// The songs played in each listening session, from plays in the order they were played in.
+fn sessions(plays: &[(UUID, NaiveDateTime)]) -> Vec<HashSet<UUID>> {
+    let mut sessions: Vec<HashSet<UUID>> = Vec::new();
+    let mut last_time: Option<NaiveDateTime> = None;
+    for (song_id, time) in plays {
+        let gap = last_time.map(|last_time| *time - last_time);
+        if gap.map_or(true, |gap| gap > Duration::minutes(SESSION_GAP_MINUTES)) {
+            sessions.push(HashSet::new());
+        }
+
+        sessions.last_mut().unwrap().insert(*song_id);
+        last_time = Some(*time);
+    }
+
+    sessions
+}
+
+/// The number of playlists the user can see each song is in along with any of the seed songs.
+fn playlist_cooccurrences(
+    conn: &SqliteConnection,
+    user_id: UUID,
+    seed_songs: &HashSet<UUID>,
+) -> QueryResult<HashMap<UUID, u32>> {
+    let items: Vec<(UUID, UUID)> = playlist_item::table
+        .inner_join(playlist::table)
+        .filter(playlist::user_id.eq(user_id).or(playlist::shared.eq(true)))
+        .select((playlist_item::playlist_id, playlist_item::song_id))
+        .load(conn)?;
+
+    let mut playlists: HashMap<UUID, HashSet<UUID>> = HashMap::new();
+    for (playlist_id, song_id) in items {
+        playlists.entry(playlist_id).or_default().insert(song_id);
+    }
+
+    Ok(count_cooccurrences(
+        playlists.into_iter().map(|(_, songs)| songs),
+        seed_songs,
+    ))
+}
+
+/// The number of groups each song is in along with any of the seed songs.
+fn count_cooccurrences(
+    groups: impl IntoIterator<Item = HashSet<UUID>>,
+    seed_songs: &HashSet<UUID>,
+) -> HashMap<UUID, u32> {
+    let mut counts = HashMap::new();
+    for group in groups {
+        if group.is_disjoint(seed_songs) {
+            continue;
+        }
+
+        for song_id in group {
+            *counts.entry(song_id).or_insert(0) += 1;
+        }
+    }
+
+    counts
+}
+
+#[cfg(test)]
+mod test {
+    use super::{
+        count_cooccurrences, radio_songs, sessions, shuffle, weighted_shuffle, RadioSeed, Signals,
+    };
+    use crate::models::UUID;
+    use crate::test_database;
+    use chrono::NaiveDate;
+    use std::collections::HashSet;
+
+    fn ids(count: usize) -> Vec<UUID> {
+        (0..count).map(|_| UUID::new()).collect()
+    }
+
+    #[test]
+    fn weights() {
+        let unrelated = Signals::default();
+        let same_artist = Signals {
+            shares_artist: true,
+            ..Signals::default()
+        };
+        let liked = Signals {
+            liked: true,
+            ..same_artist.clone()
+        };
+        let just_played = Signals {
+            hours_since_played: Some(0.5),
+            ..same_artist.clone()
+        };
+        let played_last_month = Signals {
+            hours_since_played: Some(24.0 * 30.0),
+            ..same_artist.clone()
+        };
+
+        assert!(unrelated.weight() > 0.0);
+        assert!(same_artist.weight() > unrelated.weight());
+        assert!(liked.weight() > same_artist.weight());
+        assert!(just_played.weight() < same_artist.weight() / 10.0);
+        assert!(just_played.weight() > 0.0);
+        assert!((played_last_month.weight() - same_artist.weight()).abs() < 0.01);
+
+        let more_sessions = Signals {
+            sessions: 10,
+            ..Signals::default()
+        };
+        let fewer_sessions = Signals {
+            sessions: 1,
+            ..Signals::default()
+        };
+        assert!(more_sessions.weight() > fewer_sessions.weight());
+        assert!(fewer_sessions.weight() > unrelated.weight());
+    }
+
+    #[test]
+    fn same_seed_same_songs() {
+        let songs: Vec<(UUID, f64)> = ids(50).into_iter().map(|id| (id, 1.0)).collect();
+
+        assert_eq!(
+            weighted_shuffle(&songs, 7, 0, 20),
+            weighted_shuffle(&songs, 7, 0, 20)
+        );
+        assert_ne!(
+            weighted_shuffle(&songs, 7, 0, 20),
+            weighted_shuffle(&songs, 8, 0, 20)
+        );
+
+        let ids: Vec<UUID> = songs.iter().map(|(id, _)| *id).collect();
+        assert_eq!(shuffle(&ids, 7, 10), shuffle(&ids, 7, 10));
+        assert_eq!(shuffle(&ids, 7, 100).len(), 50);
+    }
+
+    #[test]
+    fn pages_continue_the_shuffle() {
+        let songs: Vec<(UUID, f64)> = ids(10).into_iter().map(|id| (id, 1.0)).collect();
+
+        let all = weighted_shuffle(&songs, 3, 0, 25);
+        let mut pages = weighted_shuffle(&songs, 3, 0, 12);
+        pages.extend(weighted_shuffle(&songs, 3, 12, 13));
+        assert_eq!(all, pages);
+
+        // Radios never end, and play every song before playing any again.
+        assert_eq!(all.len(), 25);
+        let first_round: HashSet<UUID> = all[..10].iter().copied().collect();
+        let second_round: HashSet<UUID> = all[10..20].iter().copied().collect();
+        assert_eq!(first_round.len(), 10);
+        assert_eq!(second_round.len(), 10);
+    }
+
+    #[test]
+    fn heavier_songs_come_first() {
+        let heavy = UUID::new();
+        let mut songs: Vec<(UUID, f64)> = ids(9).into_iter().map(|id| (id, 0.1)).collect();
+        songs.push((heavy, 10.0));
+
+        let first = (0..100)
+            .filter(|seed| weighted_shuffle(&songs, *seed, 0, 1) == vec![heavy])
+            .count();
+        assert!(first > 80, "the heavy song came first {} times", first);
+    }
+
+    #[test]
+    fn cooccurrences() {
+        let songs = ids(4);
+        let seeds: HashSet<UUID> = vec![songs[0]].into_iter().collect();
+        let groups = vec![
+            vec![songs[0], songs[1]].into_iter().collect(),
+            vec![songs[0], songs[1], songs[2]].into_iter().collect(),
+            vec![songs[2], songs[3]].into_iter().collect(),
+        ];
+
+        let counts = count_cooccurrences(groups, &seeds);
+        assert_eq!(counts.get(&songs[1]), Some(&2));
+        assert_eq!(counts.get(&songs[2]), Some(&1));
+        assert_eq!(counts.get(&songs[3]), None);
+    }
+
+    #[test]
+    fn sessions_split_on_gaps() {
+        let songs = ids(3);
+        let at = |hour, minute| NaiveDate::from_ymd(2026, 10, 20).and_hms(hour, minute, 0);
+        let plays = vec![
+            (songs[0], at(10, 0)),
+            (songs[1], at(10, 25)),
+            (songs[2], at(10, 50)),
+            (songs[0], at(12, 0)),
+        ];
+
+        let sessions = sessions(&plays);
+        assert_eq!(sessions.len(), 2);
+        assert_eq!(sessions[0].len(), 3);
+        assert!(sessions[1].contains(&songs[0]));
+    }
+
+    #[test]
+    fn radio_songs_are_in_order() {
+        let conn = test_database::connection();
+        let user_id = test_database::default_user(&conn);
+        let now = NaiveDate::from_ymd(2026, 10, 20).and_hms(0, 0, 0);
+        let seed = test_database::add_song(&conn, "Seed", 1);
+        for day in 2..=20 {
+            test_database::add_song(&conn, "Song", day);
+        }
+
+        let weights = radio_songs(&conn, user_id, RadioSeed::Song(seed.id), now).unwrap();
+        let song_ids: Vec<String> = weights.iter().map(|(id, _)| id.to_string()).collect();
+        let mut sorted = song_ids.clone();
+        sorted.sort();
+
+        assert_eq!(song_ids.len(), 19);
+        assert_eq!(song_ids, sorted);
+    }
+}
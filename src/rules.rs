@@ -0,0 +1,556 @@
+//! The rules of smart playlists, which pick their songs from the library whenever they're listed
+//! rather than holding songs added one by one.
+//!
+//! Rules are stored as JSON. A rule is an object with one key naming what it checks:
+//!
+//! ```json
+//! {
+//!   "match": { "all": [{ "liked": true }, { "genre": "Jazz" }] },
+//!   "order": "random",
+//!   "limit": 100
+//! }
+//! ```
+//!
+//! - `{"all": [...]}`, `{"any": [...]}` and `{"not": {...}}` combine other rules.
+//! - `{"liked": true}` keeps the songs the user likes, or doesn't like with `false`.
+//! - `{"genre": "Jazz"}`, `{"artist": "Björk"}` and `{"album": "Homogenic"}` compare names,
+//!   ignoring case.
+//! - `{"playCount": 0}`, `{"rating": {"min": 4}}` and `{"year": {"min": 1990, "max": 1999}}`
+//!   keep the songs whose value is in the range. Both ends of ranges are included and either can
+//!   be left out.
+//! - `{"addedInLastDays": 30}` keeps the songs added to the library in the last 30 days, up to
+//!   `MAX_DAYS`.
+//!
+//! Likes, play counts and ratings are the ones of the user the playlist belongs to.
+
+use crate::database::{album, song, user_song_stats};
+use crate::models::*;
+use chrono::Duration;
+use diesel::dsl;
+use diesel::prelude::*;
+use diesel::sql_types::{Binary, Bool, Integer, Nullable, Text};
+use diesel::sqlite::Sqlite;
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
+use rand::SeedableRng;
+use serde::de::Error as _;
+use serde::{Deserialize, Deserializer, Serialize};
+
+/// The most days `addedInLastDays` looks back, about a thousand years.
+pub const MAX_DAYS: u32 = 365_250;
+
+/// Songs are compared to names by appending the name and ` COLLATE NOCASE)` to these. Genres are
+/// compared with `IS` so that songs without one don't match, rather than making the comparison
+/// NULL, which `not` would drop as well.
+const GENRE_QUERY: &str = "(song.genre IS ";
+const ARTIST_QUERY: &str = "song.id IN (SELECT song_artist.song_id FROM song_artist
+    INNER JOIN artist ON artist.id = song_artist.artist_id WHERE artist.name = ";
+const ALBUM_QUERY: &str = "song.album_id IN (SELECT album.id FROM album WHERE album.name = ";
+
+/// The name of each song's album, for sorting songs the way albums are.
+const ALBUM_NAME_QUERY: &str = "(SELECT album.name FROM album WHERE album.id = song.album_id)";
+
+/// A query selecting the number of times a user played each song, which the id of the user is
+/// appended to.
+const PLAY_COUNT_QUERY: &str = "(SELECT play_count FROM user_song_stats
+    WHERE user_song_stats.song_id = song.id AND user_song_stats.user_id = ";
+
+type SongFilter = Box<dyn BoxableExpression<song::table, Sqlite, SqlType = Bool>>;
+
+/// The rules of a smart playlist.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct SmartRules {
+    /// The rule songs have to match to be in the playlist.
+    #[serde(rename = "match")]
+    pub rule: Rule,
+
+    #[serde(default)]
+    pub order: SmartOrder,
+
+    /// The most songs the playlist has, picked in its order.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub limit: Option<u32>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub enum Rule {
+    /// Matches songs matching every rule, or every song when there are none.
+    All(Vec<Rule>),
+
+    /// Matches songs matching any of the rules, or no song when there are none.
+    Any(Vec<Rule>),
+    Not(Box<Rule>),
+    Liked(bool),
+    Genre(String),
+
+    /// Matches the songs any of whose artists has the name.
+    Artist(String),
+    Album(String),
+    PlayCount(Range),
+
+    /// Matches the songs rated in the range. Unrated songs never match.
+    Rating(Range),
+
+    /// Matches the songs released in the range. Songs without a release year never match.
+    Year(Range),
+
+    #[serde(deserialize_with = "days")]
+    AddedInLastDays(u32),
+}
+
+/// The order of the songs of a smart playlist.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub enum SmartOrder {
+    /// By album, then by where the songs are on the album.
+    Album,
+    Name,
+    RecentlyAdded,
+    MostPlayed,
+
+    /// Shuffled by a seed, so that every page of the songs is cut from the same shuffle.
+    Random,
+}
+
+impl Default for SmartOrder {
+    fn default() -> Self {
+        SmartOrder::Album
+    }
+}
+
+/// A range of numbers including both of its ends. Ranges are written as a number when they hold
+/// only that number.
+#[derive(Serialize, Clone, Debug, Default, PartialEq)]
+pub struct Range {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub min: Option<i32>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max: Option<i32>,
+}
+
+impl<'de> Deserialize<'de> for Range {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        #[derive(Deserialize)]
+        #[serde(deny_unknown_fields)]
+        struct Bounds {
+            #[serde(default)]
+            min: Option<i32>,
+
+            #[serde(default)]
+            max: Option<i32>,
+        }
+
+        #[derive(Deserialize)]
+        #[serde(untagged)]
+        enum ExactOrBounds {
+            Exact(i32),
+            Bounds(Bounds),
+        }
+
+        Ok(match ExactOrBounds::deserialize(deserializer)? {
+            ExactOrBounds::Exact(value) => Range {
+                min: Some(value),
+                max: Some(value),
+            },
+            ExactOrBounds::Bounds(Bounds { min, max }) => Range { min, max },
+        })
+    }
+}
+
+impl Range {
+    pub fn contains(&self, value: i32) -> bool {
+        self.min.map_or(true, |min| min <= value) && self.max.map_or(true, |max| value <= max)
+    }
+
+    /// The ends of the range, with missing ends replaced by the smallest and largest numbers.
+    fn bounds(&self) -> (i32, i32) {
+        (self.min.unwrap_or(i32::MIN), self.max.unwrap_or(i32::MAX))
+    }
+}
+
+impl SmartRules {
+    pub fn parse(json: &str) -> serde_json::Result<Self> {
+        serde_json::from_str(json)
+    }
+
+    pub fn to_json(&self) -> String {
+        serde_json::to_string(self).expect("rules can always be written as JSON")
+    }
+
+    /// The songs matching the rules in order, picked with the likes, play counts and ratings of
+    /// `user_id`. A random order is the shuffle of `seed`.
+    pub fn songs(
+        &self,
+        conn: &SqliteConnection,
+        user_id: UUID,
+        now: NaiveDateTime,
+        seed: u64,
+    ) -> QueryResult<Vec<Song>> {
+        let mut query = song::table
+            .into_boxed()
+            .filter(self.rule.filter(user_id, now));
+
+        query = match self.order {
+            SmartOrder::Album => query
+                .order_by(dsl::sql::<Text>(ALBUM_NAME_QUERY))
+                .then_order_by(song::album_id)
+                .then_order_by(song::disk_number)
+                .then_order_by(song::track_number),
+            SmartOrder::Name => query.order_by(song::name.asc()),
+            SmartOrder::RecentlyAdded => query.order_by(song::time_added.desc()),
+            SmartOrder::MostPlayed => query.order_by(
+                dsl::sql::<Nullable<Integer>>(PLAY_COUNT_QUERY)
+                    .bind::<Binary, _>(user_id)
+                    .sql(")")
+                    .desc(),
+            ),
+            // Songs are shuffled once they're loaded in the same order every time, since SQLite
+            // can't seed RANDOM().
+            SmartOrder::Random => query,
+        };
+        query = query.then_order_by(song::id);
+
+        if self.order == SmartOrder::Random {
+            let mut songs: Vec<Song> = query.load(conn)?;
+            songs.shuffle(&mut StdRng::seed_from_u64(seed));
+            if let Some(limit) = self.limit {
+                songs.truncate(limit as usize);
+            }
+
+            return Ok(songs);
+        }
+
+        if let Some(limit) = self.limit {
+            query = query.limit(limit as i64);
+        }
+
+        query.load(conn)
+    }
+}
+
+impl Rule {
+    /// A filter keeping the songs matching the rule.
+    pub fn filter(&self, user_id: UUID, now: NaiveDateTime) -> SongFilter {
+        let stats = || {
+            user_song_stats::table
+                .filter(user_song_stats::user_id.eq(user_id))
+                .select(user_song_stats::song_id)
+        };
+
+        match self {
+            Rule::All(rules) => join(rules, user_id, now, "1", |all, filter| {
+                Box::new(all.and(filter))
+            }),
+            Rule::Any(rules) => join(rules, user_id, now, "0", |any, filter| {
+                Box::new(any.or(filter))
+            }),
+            Rule::Not(rule) => Box::new(dsl::not(rule.filter(user_id, now))),
+            Rule::Liked(true) => {
+                Box::new(song::id.eq_any(stats().filter(user_song_stats::liked.eq(true))))
+            }
+            Rule::Liked(false) => {
+                Box::new(song::id.ne_all(stats().filter(user_song_stats::liked.eq(true))))
+            }
+            Rule::Genre(genre) => named(GENRE_QUERY, genre),
+            Rule::Artist(artist) => named(ARTIST_QUERY, artist),
+            Rule::Album(album) => named(ALBUM_QUERY, album),
+            Rule::PlayCount(range) => {
+                let (min, max) = range.bounds();
+
+                // Songs the user never played have no stats, so they're kept by leaving out the
+                // songs played a number of times outside the range.
+                if range.contains(0) {
+                    Box::new(song::id.ne_all(
+                        stats().filter(dsl::not(user_song_stats::play_count.between(min, max))),
+                    ))
+                } else {
+                    Box::new(
+                        song::id
+                            .eq_any(stats().filter(user_song_stats::play_count.between(min, max))),
+                    )
+                }
+            }
+            Rule::Rating(range) => {
+                let (min, max) = range.bounds();
+                Box::new(song::id.eq_any(stats().filter(user_song_stats::rating.between(min, max))))
+            }
+            Rule::Year(range) => {
+                let (min, max) = range.bounds();
+                Box::new(
+                    song::album_id.eq_any(
+                        album::table
+                            .filter(album::release_year.between(min, max))
+                            .select(album::id),
+                    ),
+                )
+            }
+            // Rules are parsed with at most `MAX_DAYS`, but going back past the earliest date
+            // keeps every song anyway.
+            Rule::AddedInLastDays(days) => {
+                match now.checked_sub_signed(Duration::days(*days as i64)) {
+                    Some(since) => Box::new(song::time_added.ge(since)),
+                    None => Box::new(dsl::sql::<Bool>("1")),
+                }
+            }
+        }
+    }
+}
+
+/// Reads the number of days of `addedInLastDays`, refusing more than `MAX_DAYS`.
+fn days<'de, D>(deserializer: D) -> Result<u32, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let days = u32::deserialize(deserializer)?;
+    if days > MAX_DAYS {
+        return Err(D::Error::custom(format!(
+            "addedInLastDays can be at most {}, got {}",
+            MAX_DAYS, days
+        )));
+    }
+
+    Ok(days)
+}
+
+/// The filters of the rules combined by `combine`, or the SQL `empty` when there are no rules.
+fn join(
+    rules: &[Rule],
+    user_id: UUID,
+    now: NaiveDateTime,
+    empty: &str,
+    combine: fn(SongFilter, SongFilter) -> SongFilter,
+) -> SongFilter {
+    let mut filters = rules.iter().map(|rule| rule.filter(user_id, now));
+    match filters.next() {
+        Some(first) => filters.fold(first, combine),
+        None => Box::new(dsl::sql::<Bool>(empty)),
+    }
+}
+
+/// A filter comparing `name` to the name `query` selects, ignoring case.
+fn named(query: &str, name: &str) -> SongFilter {
+    Box::new(
+        dsl::sql::<Bool>(query)
+            .bind::<Text, _>(name.to_string())
+            .sql(" COLLATE NOCASE)"),
+    )
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Range, Rule, SmartOrder, SmartRules};
+    use crate::database::{album, song};
+    use crate::models::*;
+    use crate::test_database;
+    use chrono::NaiveDate;
+    use diesel::debug_query;
+    use diesel::prelude::*;
+    use diesel::sqlite::Sqlite;
+
+    #[test]
+    fn parses_rules() {
+        let rules = SmartRules::parse(
+            r#"{
+                "match": {
+                    "all": [
+                        { "liked": true },
+                        { "not": { "genre": "Jazz" } },
+                        { "year": { "min": 1990, "max": 1999 } }
+                    ]
+                },
+                "order": "random",
+                "limit": 100
+            }"#,
+        )
+        .unwrap();
+
+        assert_eq!(
+            rules,
+            SmartRules {
+                rule: Rule::All(vec![
+                    Rule::Liked(true),
+                    Rule::Not(Box::new(Rule::Genre("Jazz".to_string()))),
+                    Rule::Year(Range {
+                        min: Some(1990),
+                        max: Some(1999)
+                    }),
+                ]),
+                order: SmartOrder::Random,
+                limit: Some(100),
+            }
+        );
+        assert_eq!(SmartRules::parse(&rules.to_json()).unwrap(), rules);
+    }
+
+    #[test]
+    fn exact_ranges() {
+        let rules = SmartRules::parse(r#"{ "match": { "playCount": 0 } }"#).unwrap();
+
+        assert_eq!(
+            rules.rule,
+            Rule::PlayCount(Range {
+                min: Some(0),
+                max: Some(0)
+            })
+        );
+        assert_eq!(rules.order, SmartOrder::Album);
+        assert_eq!(rules.limit, None);
+    }
+
+    #[test]
+    fn rejects_unknown_rules() {
+        assert!(SmartRules::parse(r#"{ "match": { "mood": "happy" } }"#).is_err());
+        assert!(SmartRules::parse(r#"{ "match": { "year": { "from": 1990 } } }"#).is_err());
+        assert!(SmartRules::parse(r#"{ "match": { "all": [] }, "sort": "name" }"#).is_err());
+    }
+
+    #[test]
+    fn rejects_days_out_of_range() {
+        assert!(SmartRules::parse(r#"{ "match": { "addedInLastDays": 30 } }"#).is_ok());
+        assert!(SmartRules::parse(r#"{ "match": { "addedInLastDays": 4000000000 } }"#).is_err());
+
+        // Rules which were saved before are still kept from overflowing.
+        let now = NaiveDate::from_ymd(2026, 10, 20).and_hms(0, 0, 0);
+        let all = Rule::AddedInLastDays(u32::MAX).filter(UUID::new(), now);
+        let query = debug_query::<Sqlite, _>(&song::table.filter(all)).to_string();
+        assert!(!query.contains("WHERE `song`.`time_added`"), "{}", query);
+    }
+
+    #[test]
+    fn unplayed_songs_have_no_stats() {
+        let now = NaiveDate::from_ymd(2026, 10, 20).and_hms(0, 0, 0);
+        let filter = |range| Rule::PlayCount(range).filter(UUID::new(), now);
+
+        let unplayed = debug_query::<Sqlite, _>(&song::table.filter(filter(Range {
+            min: None,
+            max: Some(0),
+        })))
+        .to_string();
+        assert!(unplayed.contains("NOT IN"), "{}", unplayed);
+
+        let played = debug_query::<Sqlite, _>(&song::table.filter(filter(Range {
+            min: Some(1),
+            max: None,
+        })))
+        .to_string();
+        assert!(!played.contains("NOT IN"), "{}", played);
+    }
+
+    /// The names of the songs the rules pick for the user, in their order.
+    fn names(conn: &SqliteConnection, user_id: UUID, rules: &str) -> Vec<String> {
+        let now = NaiveDate::from_ymd(2026, 10, 20).and_hms(0, 0, 0);
+        SmartRules::parse(rules)
+            .unwrap()
+            .songs(conn, user_id, now, 0)
+            .unwrap()
+            .into_iter()
+            .map(|song| song.name)
+            .collect()
+    }
+
+    #[test]
+    fn picks_liked_songs() {
+        let conn = test_database::connection();
+        let user_id = test_database::default_user(&conn);
+        let liked = test_database::add_song(&conn, "Liked", 1);
+        let other = test_database::add_song(&conn, "Other", 2);
+        SongStats::toggle_like(&conn, user_id, liked.id).unwrap();
+        SongStats::toggle_like(&conn, UUID::new(), other.id).unwrap();
+
+        let liked = r#"{ "match": { "liked": true }, "order": "name" }"#;
+        assert_eq!(names(&conn, user_id, liked), vec!["Liked"]);
+        let not_liked = r#"{ "match": { "liked": false }, "order": "name" }"#;
+        assert_eq!(names(&conn, user_id, not_liked), vec!["Other"]);
+    }
+
+    #[test]
+    fn picks_unplayed_songs() {
+        let conn = test_database::connection();
+        let user_id = test_database::default_user(&conn);
+        let now = NaiveDate::from_ymd(2026, 10, 20).and_hms(0, 0, 0);
+        test_database::add_song(&conn, "Never played", 1);
+        let liked = test_database::add_song(&conn, "Liked", 2);
+        let played = test_database::add_song(&conn, "Played", 3);
+        SongStats::toggle_like(&conn, user_id, liked.id).unwrap();
+        SongStats::played(&conn, user_id, played.id, 2, now).unwrap();
+
+        let unplayed = r#"{ "match": { "playCount": 0 }, "order": "name" }"#;
+        assert_eq!(
+            names(&conn, user_id, unplayed),
+            vec!["Liked", "Never played"]
+        );
+        let played = r#"{ "match": { "playCount": { "min": 1 } }, "order": "name" }"#;
+        assert_eq!(names(&conn, user_id, played), vec!["Played"]);
+    }
+
+    #[test]
+    fn picks_songs_released_in_years() {
+        let conn = test_database::connection();
+        let user_id = test_database::default_user(&conn);
+        let years = [Some(1989), Some(1990), Some(1999), Some(2000), None];
+        for (day, year) in years.iter().enumerate() {
+            let name = year.map_or("Unknown".to_string(), |year| year.to_string());
+            let song = test_database::add_song(&conn, &name, day as u32 + 1);
+            diesel::update(album::table.find(song.album_id))
+                .set(album::release_year.eq(year))
+                .execute(&conn)
+                .unwrap();
+        }
+
+        let nineties = r#"{ "match": { "year": { "min": 1990, "max": 1999 } }, "order": "name" }"#;
+        assert_eq!(names(&conn, user_id, nineties), vec!["1990", "1999"]);
+    }
+
+    #[test]
+    fn picks_songs_without_a_genre() {
+        let conn = test_database::connection();
+        let user_id = test_database::default_user(&conn);
+        for (day, genre) in [Some("jazz"), Some("Rock"), None].iter().enumerate() {
+            let name = genre.unwrap_or("None");
+            let song = test_database::add_song(&conn, name, day as u32 + 1);
+            diesel::update(song::table.find(song.id))
+                .set(song::genre.eq(genre))
+                .execute(&conn)
+                .unwrap();
+        }
+
+        let jazz = r#"{ "match": { "genre": "Jazz" }, "order": "name" }"#;
+        assert_eq!(names(&conn, user_id, jazz), vec!["jazz"]);
+        let not_jazz = r#"{ "match": { "not": { "genre": "Jazz" } }, "order": "name" }"#;
+        assert_eq!(names(&conn, user_id, not_jazz), vec!["None", "Rock"]);
+    }
+
+    #[test]
+    fn random_limits_cut_the_same_shuffle() {
+        let conn = test_database::connection();
+        let user_id = test_database::default_user(&conn);
+        let now = NaiveDate::from_ymd(2026, 10, 20).and_hms(0, 0, 0);
+        for day in 1..=10 {
+            test_database::add_song(&conn, &day.to_string(), day);
+        }
+
+        let rules = |limit: &str| {
+            let json = format!(
+                r#"{{ "match": {{ "all": [] }}, "order": "random"{} }}"#,
+                limit
+            );
+            SmartRules::parse(&json).unwrap()
+        };
+        let ids = |rules: &SmartRules, seed| -> Vec<UUID> {
+            let songs = rules.songs(&conn, user_id, now, seed).unwrap();
+            songs.into_iter().map(|song| song.id).collect()
+        };
+
+        let limited = rules(r#", "limit": 3"#);
+        let first = ids(&limited, 7);
+        assert_eq!(first.len(), 3);
+        assert_eq!(ids(&limited, 7), first);
+        assert_eq!(ids(&rules(""), 7)[..3], first[..]);
+    }
+}
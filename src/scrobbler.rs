@@ -0,0 +1,456 @@
+//! Queues listens and the songs playing now for submission to services with the ListenBrainz
+//! API. Submissions stay queued until the service accepts them, so listens aren't lost while
+//! forte or the service is offline. Listens the service rejects as invalid are set aside so they
+//! don't hold up the others.
+
+use crate::database::{play, scrobble_queue, scrobbler};
+use crate::export::listens;
+use crate::models::*;
+use chrono::Duration;
+use diesel::prelude::*;
+use diesel::result;
+
+/// A song playing now which couldn't be submitted for this long is dropped, since it has most
+/// likely finished playing.
+const PLAYING_NOW_TIMEOUT_MINUTES: i64 = 10;
+
+/// The most listens submitted in one request.
+const MAX_BATCH_SIZE: i64 = 100;
+
+/// The time before retrying a failed submission for the first time, doubled on every attempt.
+const FIRST_RETRY_SECONDS: i64 = 30;
+const MAX_RETRY_SECONDS: i64 = 6 * 60 * 60;
+
+pub const LISTEN: &str = "single";
+pub const PLAYING_NOW: &str = "playing_now";
+
+#[derive(Queryable, Insertable, Clone, Debug)]
+#[table_name = "scrobbler"]
+pub struct Scrobbler {
+    pub id: UUID,
+
+    /// The user whose plays are submitted.
+    pub user_id: UUID,
+    pub name: String,
+
+    /// The root of the API, like `https://api.listenbrainz.org`.
+    pub api_url: String,
+    pub token: String,
+    pub time_added: NaiveDateTime,
+}
+
+#[derive(Queryable, Insertable, Debug)]
+#[table_name = "scrobble_queue"]
+pub struct QueuedScrobble {
+    pub id: UUID,
+    pub scrobbler_id: UUID,
+
+    /// The play a listen submits. Songs playing now have none.
+    pub play_id: Option<UUID>,
+
+    /// Either [`LISTEN`] or [`PLAYING_NOW`].
+    pub listen_type: String,
+
+    /// The listen as ListenBrainz JSON.
+    pub payload: String,
+    pub time_added: NaiveDateTime,
+    pub attempts: i32,
+    pub next_attempt: NaiveDateTime,
+    pub last_error: Option<String>,
+
+    /// Whether the scrobbler rejected the listen as invalid. Rejected listens aren't retried.
+    pub rejected: bool,
+}
+
+impl Scrobbler {
+    pub fn add(
+        conn: &SqliteConnection,
+        user_id: UUID,
+        name: &str,
+        api_url: &str,
+        token: &str,
+        now: NaiveDateTime,
+    ) -> QueryResult<Scrobbler> {
+        let scrobbler = Scrobbler {
+            id: UUID::new(),
+            user_id,
+            name: name.to_string(),
+            api_url: api_url.trim_end_matches('/').to_string(),
+            token: token.to_string(),
+            time_added: now,
+        };
+
+        scrobbler
+            .clone()
+            .insert_into(scrobbler::table)
+            .execute(conn)?;
+        Ok(scrobbler)
+    }
+
+    pub fn list(conn: &SqliteConnection) -> QueryResult<Vec<Scrobbler>> {
+        scrobbler::table.order_by(scrobbler::name).load(conn)
+    }
+
+    pub fn for_user(conn: &SqliteConnection, user_id: UUID) -> QueryResult<Vec<Scrobbler>> {
+        scrobbler::table
+            .filter(scrobbler::user_id.eq(user_id))
+            .order_by(scrobbler::name)
+            .load(conn)
+    }
+
+    /// Removes a scrobbler and everything still queued for it. Returns whether it existed.
+    pub fn remove(conn: &SqliteConnection, id: UUID) -> QueryResult<bool> {
+        conn.transaction(|| {
+            diesel::delete(scrobble_queue::table.filter(scrobble_queue::scrobbler_id.eq(id)))
+                .execute(conn)?;
+
+            let deleted = diesel::delete(scrobbler::table.find(id)).execute(conn)?;
+            Ok(deleted > 0)
+        })
+    }
+
+    /// The URL listens are submitted to.
+    pub fn submit_url(&self) -> String {
+        format!("{}/1/submit-listens", self.api_url)
+    }
+
+    /// The number of submissions waiting to be accepted, the number of listens which were
+    /// rejected and the error of the last failed submission.
+    pub fn queue_status(&self, conn: &SqliteConnection) -> QueryResult<(i64, i64, Option<String>)> {
+        let queued = scrobble_queue::table
+            .filter(scrobble_queue::scrobbler_id.eq(self.id))
+            .filter(scrobble_queue::rejected.eq(false))
+            .count()
+            .get_result(conn)?;
+
+        let rejected = scrobble_queue::table
+            .filter(scrobble_queue::scrobbler_id.eq(self.id))
+            .filter(scrobble_queue::rejected.eq(true))
+            .count()
+            .get_result(conn)?;
+
+        let last_error = scrobble_queue::table
+            .filter(scrobble_queue::scrobbler_id.eq(self.id))
+            .filter(scrobble_queue::last_error.is_not_null())
+            .order_by(scrobble_queue::next_attempt.desc())
+            .select(scrobble_queue::last_error)
+            .first(conn)
+            .optional()?
+            .flatten();
+
+        Ok((queued, rejected, last_error))
+    }
+}
+
+fn to_query_error(err: serde_json::Error) -> result::Error {
+    result::Error::SerializationError(Box::new(err))
+}
+
+/// Queues a play for submission to every scrobbler of the user who played it.
+pub fn enqueue_listen(conn: &SqliteConnection, play_id: UUID) -> QueryResult<()> {
+    let user_id = play::table
+        .find(play_id)
+        .select(play::user_id)
+        .first(conn)?;
+
+    let scrobblers = Scrobbler::for_user(conn, user_id)?;
+    if scrobblers.is_empty() {
+        return Ok(());
+    }
+
+    let listen = listens::load_play(conn, play_id)?;
+    let payload = listen.to_listenbrainz_json(false).map_err(to_query_error)?;
+
+    enqueue(
+        conn,
+        &scrobblers,
+        Some(play_id),
+        LISTEN,
+        &payload,
+        listen.time,
+    )
+}
+
+/// Queues telling every scrobbler of the user that a song started playing, replacing the song
+/// which was playing before if it wasn't submitted yet.
+pub fn enqueue_playing_now(
+    conn: &SqliteConnection,
+    user_id: UUID,
+    song_id: UUID,
+    client: Option<String>,
+    now: NaiveDateTime,
+) -> QueryResult<()> {
+    let scrobblers = Scrobbler::for_user(conn, user_id)?;
+    if scrobblers.is_empty() {
+        return Ok(());
+    }
+
+    let listen = listens::load_song(conn, song_id, now, client)?;
+    let payload = listen.to_listenbrainz_json(true).map_err(to_query_error)?;
+
+    let scrobbler_ids: Vec<UUID> = scrobblers.iter().map(|scrobbler| scrobbler.id).collect();
+
+    conn.transaction(|| {
+        diesel::delete(
+            scrobble_queue::table
+                .filter(scrobble_queue::listen_type.eq(PLAYING_NOW))
+                .filter(scrobble_queue::scrobbler_id.eq_any(scrobbler_ids)),
+        )
+        .execute(conn)?;
+
+        enqueue(conn, &scrobblers, None, PLAYING_NOW, &payload, now)
+    })
+}
+
+/// Drops the queued listens of deleted plays, including rejected ones, so they're never
+/// submitted.
+pub fn forget_plays(conn: &SqliteConnection, play_ids: &[UUID]) -> QueryResult<()> {
+    diesel::delete(scrobble_queue::table.filter(scrobble_queue::play_id.eq_any(play_ids)))
+        .execute(conn)?;
+
+    Ok(())
+}
+
+fn enqueue(
+    conn: &SqliteConnection,
+    scrobblers: &[Scrobbler],
+    play_id: Option<UUID>,
+    listen_type: &str,
+    payload: &str,
+    now: NaiveDateTime,
+) -> QueryResult<()> {
+    let entries: Vec<QueuedScrobble> = scrobblers
+        .iter()
+        .map(|scrobbler| QueuedScrobble {
+            id: UUID::new(),
+            scrobbler_id: scrobbler.id,
+            play_id,
+            listen_type: listen_type.to_string(),
+            payload: payload.to_string(),
+            time_added: now,
+            attempts: 0,
+            next_attempt: now,
+            last_error: None,
+            rejected: false,
+        })
+        .collect();
+
+    diesel::insert_into(scrobble_queue::table)
+        .values(&entries)
+        .execute(conn)?;
+
+    Ok(())
+}
+
+/// Queued submissions to one scrobbler which are sent in one request.
+pub struct Batch {
+    pub scrobbler: Scrobbler,
+    pub listen_type: String,
+    pub entries: Vec<QueuedScrobble>,
+}
+
+impl Batch {
+    /// The JSON body of the request to [`Scrobbler::submit_url`].
+    pub fn body(&self) -> String {
+        // Several listens in one request have to be submitted as an import.
+        let listen_type = if self.listen_type == LISTEN && self.entries.len() > 1 {
+            "import"
+        } else {
+            &self.listen_type
+        };
+
+        let payload: Vec<&str> = self
+            .entries
+            .iter()
+            .map(|entry| entry.payload.as_str())
+            .collect();
+
+        format!(
+            r#"{{"listen_type":"{}","payload":[{}]}}"#,
+            listen_type,
+            payload.join(",")
+        )
+    }
+
+    /// Splits a batch of several submissions into its older and newer half, so that the
+    /// submissions in a rejected batch can be told apart from the invalid ones.
+    pub fn split(mut self) -> (Batch, Batch) {
+        let newer = self.entries.split_off(self.entries.len() / 2);
+        let newer = Batch {
+            scrobbler: self.scrobbler.clone(),
+            listen_type: self.listen_type.clone(),
+            entries: newer,
+        };
+
+        (self, newer)
+    }
+}
+
+/// Collects the submissions which are due, dropping songs playing now which are too old to
+/// matter. Listens are submitted oldest first.
+pub fn due_batches(conn: &SqliteConnection, now: NaiveDateTime) -> QueryResult<Vec<Batch>> {
+    let stale = now - Duration::minutes(PLAYING_NOW_TIMEOUT_MINUTES);
+    diesel::delete(
+        scrobble_queue::table
+            .filter(scrobble_queue::listen_type.eq(PLAYING_NOW))
+            .filter(scrobble_queue::time_added.lt(stale)),
+    )
+    .execute(conn)?;
+
+    let mut batches = Vec::new();
+    for scrobbler in Scrobbler::list(conn)? {
+        let due = scrobble_queue::table
+            .filter(scrobble_queue::scrobbler_id.eq(scrobbler.id))
+            .filter(scrobble_queue::rejected.eq(false))
+            .filter(scrobble_queue::next_attempt.le(now));
+
+        let playing_now: Vec<QueuedScrobble> = due
+            .clone()
+            .filter(scrobble_queue::listen_type.eq(PLAYING_NOW))
+            .load(conn)?;
+
+        let listens: Vec<QueuedScrobble> = due
+            .filter(scrobble_queue::listen_type.eq(LISTEN))
+            .order_by(scrobble_queue::time_added.asc())
+            .limit(MAX_BATCH_SIZE)
+            .load(conn)?;
+
+        for entry in playing_now {
+            batches.push(Batch {
+                scrobbler: scrobbler.clone(),
+                listen_type: PLAYING_NOW.to_string(),
+                entries: vec![entry],
+            });
+        }
+
+        if !listens.is_empty() {
+            batches.push(Batch {
+                scrobbler,
+                listen_type: LISTEN.to_string(),
+                entries: listens,
+            });
+        }
+    }
+
+    Ok(batches)
+}
+
+/// Removes a batch the scrobbler accepted from the queue.
+pub fn submitted(conn: &SqliteConnection, batch: &Batch) -> QueryResult<()> {
+    let ids: Vec<UUID> = batch.entries.iter().map(|entry| entry.id).collect();
+    diesel::delete(scrobble_queue::table.filter(scrobble_queue::id.eq_any(ids))).execute(conn)?;
+
+    Ok(())
+}
+
+/// Schedules a batch which failed to submit to be retried later. Songs playing now aren't
+/// retried.
+pub fn failed(
+    conn: &SqliteConnection,
+    batch: &Batch,
+    error: &str,
+    now: NaiveDateTime,
+) -> QueryResult<()> {
+    if batch.listen_type == PLAYING_NOW {
+        return submitted(conn, batch);
+    }
+
+    conn.transaction(|| {
+        for entry in &batch.entries {
+            let attempts = entry.attempts + 1;
+            diesel::update(scrobble_queue::table.find(entry.id))
+                .set((
+                    scrobble_queue::attempts.eq(attempts),
+                    scrobble_queue::next_attempt.eq(now + retry_delay(attempts)),
+                    scrobble_queue::last_error.eq(error),
+                ))
+                .execute(conn)?;
+        }
+
+        Ok(())
+    })
+}
+
+/// Sets aside the submissions of a batch the scrobbler rejected as invalid, so they aren't
+/// retried and don't hold up the listens queued after them. Batches of several submissions should
+/// be split and sent again first, since the scrobbler rejects a batch with any invalid listen.
+pub fn rejected(conn: &SqliteConnection, batch: &Batch, error: &str) -> QueryResult<()> {
+    if batch.listen_type == PLAYING_NOW {
+        return submitted(conn, batch);
+    }
+
+    let ids: Vec<UUID> = batch.entries.iter().map(|entry| entry.id).collect();
+    diesel::update(scrobble_queue::table.filter(scrobble_queue::id.eq_any(ids)))
+        .set((
+            scrobble_queue::rejected.eq(true),
+            scrobble_queue::attempts.eq(scrobble_queue::attempts + 1),
+            scrobble_queue::last_error.eq(error),
+        ))
+        .execute(conn)?;
+
+    Ok(())
+}
+
+/// How long to wait before retrying a submission which failed `attempts` times.
+fn retry_delay(attempts: i32) -> Duration {
+    let doublings = (attempts - 1).max(0).min(20) as u32;
+    Duration::seconds((FIRST_RETRY_SECONDS << doublings).min(MAX_RETRY_SECONDS))
+}
+
+#[cfg(test)]
+mod test {
+    use super::{retry_delay, Batch, QueuedScrobble, Scrobbler, LISTEN};
+    use crate::models::*;
+    use chrono::{Duration, NaiveDate};
+
+    #[test]
+    fn retry_delay_backs_off() {
+        assert_eq!(retry_delay(1), Duration::seconds(30));
+        assert_eq!(retry_delay(2), Duration::seconds(60));
+        assert_eq!(retry_delay(5), Duration::seconds(480));
+        assert_eq!(retry_delay(100), Duration::hours(6));
+    }
+
+    #[test]
+    fn split_keeps_oldest_first() {
+        let now = NaiveDate::from_ymd(2026, 10, 20).and_hms(0, 0, 0);
+        let scrobbler = Scrobbler {
+            id: UUID::new(),
+            user_id: UUID::new(),
+            name: "ListenBrainz".to_string(),
+            api_url: "https://api.listenbrainz.org".to_string(),
+            token: "token".to_string(),
+            time_added: now,
+        };
+        let entries = (0..5)
+            .map(|index| QueuedScrobble {
+                id: UUID::new(),
+                scrobbler_id: scrobbler.id,
+                play_id: None,
+                listen_type: LISTEN.to_string(),
+                payload: index.to_string(),
+                time_added: now,
+                attempts: 0,
+                next_attempt: now,
+                last_error: None,
+                rejected: false,
+            })
+            .collect();
+
+        let (older, newer) = Batch {
+            scrobbler,
+            listen_type: LISTEN.to_string(),
+            entries,
+        }
+        .split();
+
+        assert_eq!(older.body(), r#"{"listen_type":"import","payload":[0,1]}"#);
+        assert_eq!(
+            newer.body(),
+            r#"{"listen_type":"import","payload":[2,3,4]}"#
+        );
+
+        let (single, _) = older.split();
+        assert_eq!(single.body(), r#"{"listen_type":"single","payload":[0]}"#);
+    }
+}
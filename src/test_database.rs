@@ -0,0 +1,70 @@
+//! In-memory databases with every migration run, for tests of queries.
+
+use crate::database::{album, artist, song};
+use crate::models::*;
+use chrono::NaiveDate;
+use diesel::prelude::*;
+use std::path::PathBuf;
+
+embed_migrations!("./migrations");
+
+pub fn connection() -> SqliteConnection {
+    let conn = SqliteConnection::establish(":memory:").expect("in-memory databases always open");
+    embedded_migrations::run(&conn).expect("migrations run on an empty database");
+
+    conn
+}
+
+/// The user the migrations add, who owns everything from before there were users.
+pub fn default_user(conn: &SqliteConnection) -> UUID {
+    User::default_user(conn)
+        .expect("the migrations add a user")
+        .id
+}
+
+/// Adds a song named `name` on an album and by an artist of its own, added `day` days into 2026.
+pub fn add_song(conn: &SqliteConnection, name: &str, day: u32) -> Song {
+    let time_added = NaiveDate::from_yo(2026, day).and_hms(0, 0, 0);
+    let artist = Artist {
+        id: UUID::new(),
+        name: UUID::new().to_string(),
+        time_added,
+    };
+    let album = Album {
+        id: UUID::new(),
+        artwork_path: None,
+        name: name.to_string(),
+        artist_id: artist.id,
+        release_year: None,
+        time_added,
+        mbid: None,
+        directory: None,
+    };
+    let song = Song {
+        id: UUID::new(),
+        name: name.to_string(),
+        album_id: album.id,
+        track_number: 1,
+        disk_number: 1,
+        duration: 1000,
+        time_added,
+        path: PathBuf::from(format!("/music/{}.flac", UUID::new())).into(),
+        mbid: None,
+        genre: None,
+    };
+
+    diesel::insert_into(artist::table)
+        .values(&artist)
+        .execute(conn)
+        .unwrap();
+    diesel::insert_into(album::table)
+        .values(&album)
+        .execute(conn)
+        .unwrap();
+    diesel::insert_into(song::table)
+        .values(&song)
+        .execute(conn)
+        .unwrap();
+
+    song
+}
@@ -0,0 +1,573 @@
+//! Exports what a user did with their library (likes, ratings, play counts and listening
+//! history) in a form which survives rebuilding the database, and imports it back.
+
+use crate::database::album;
+use crate::database::artist;
+use crate::database::play;
+use crate::database::song;
+use crate::database::user_album_stats;
+use crate::database::user_artist_stats;
+use crate::database::user_song_stats;
+use crate::matching::{SongKey, SongMatcher};
+use crate::models::*;
+use diesel::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+
+/// The version of the format written by [`export`].
+pub const VERSION: u32 = 1;
+
+#[derive(Serialize, Deserialize)]
+pub struct UserData {
+    pub version: u32,
+    pub songs: Vec<SongData>,
+    pub albums: Vec<AlbumData>,
+    pub artists: Vec<ArtistData>,
+    pub plays: Vec<PlayData>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct SongData {
+    #[serde(flatten)]
+    pub key: SongKey,
+    pub play_count: i32,
+    pub liked: bool,
+    pub last_played: Option<NaiveDateTime>,
+
+    /// The rating of the song from 1 to 5 stars.
+    #[serde(default)]
+    pub rating: Option<i32>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct AlbumData {
+    /// The MusicBrainz release ID of the album.
+    pub mbid: Option<String>,
+    pub name: String,
+    pub artist: String,
+    pub last_played: Option<NaiveDateTime>,
+
+    /// The rating of the album from 1 to 5 stars.
+    #[serde(default)]
+    pub rating: Option<i32>,
+
+    /// When the album was liked, if it's liked.
+    #[serde(default)]
+    pub time_liked: Option<NaiveDateTime>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct ArtistData {
+    pub name: String,
+    pub last_played: Option<NaiveDateTime>,
+
+    /// When the artist was followed, if they're followed.
+    #[serde(default)]
+    pub time_followed: Option<NaiveDateTime>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct PlayData {
+    /// The index of the song played in [`UserData::songs`].
+    pub song: usize,
+    pub time: NaiveDateTime,
+
+    /// The index of the album the song was played from in [`UserData::albums`].
+    pub album: Option<usize>,
+
+    /// The index of the artist the song was played from in [`UserData::artists`].
+    pub artist: Option<usize>,
+    pub client: Option<String>,
+
+    /// How long the song was listened to in milliseconds.
+    pub duration: Option<i32>,
+}
+
+/// What happened to the data when it was imported.
+#[derive(Default)]
+pub struct ImportReport {
+    pub matched_songs: usize,
+
+    /// Songs which aren't in the library. Their data and plays weren't imported.
+    pub unmatched_songs: Vec<SongKey>,
+    pub matched_albums: usize,
+    pub matched_artists: usize,
+    pub imported_plays: usize,
+
+    /// Plays which were already in the database.
+    pub duplicate_plays: usize,
+}
+
+/// Collects the user's data of every song, album and artist they played, liked or rated. Plays of
+/// songs which aren't in the library anymore can't be matched when importing, so they're left
+/// out.
+pub fn export(conn: &SqliteConnection, user_id: UUID) -> QueryResult<UserData> {
+    let plays: Vec<Play> = play::table
+        .filter(play::user_id.eq(user_id))
+        .order_by(play::time.asc())
+        .then_order_by(play::id.asc())
+        .load(conn)?;
+
+    let mut song_stats: HashMap<UUID, SongStats> = user_song_stats::table
+        .filter(user_song_stats::user_id.eq(user_id))
+        .load::<SongStats>(conn)?
+        .into_iter()
+        .filter(|stats| {
+            stats.play_count > 0
+                || stats.liked
+                || stats.rating.is_some()
+                || stats.last_played.is_some()
+        })
+        .map(|stats| (stats.song_id, stats))
+        .collect();
+
+    let played_songs: HashSet<UUID> = plays.iter().map(|play| play.song_id).collect();
+    let songs: Vec<UUID> = song::table
+        .select(song::id)
+        .order_by(song::id)
+        .load::<UUID>(conn)?
+        .into_iter()
+        .filter(|id| song_stats.contains_key(id) || played_songs.contains(id))
+        .collect();
+
+    let album_last_played = known_times(
+        user_album_stats::table
+            .filter(user_album_stats::user_id.eq(user_id))
+            .select((user_album_stats::album_id, user_album_stats::last_played))
+            .load(conn)?,
+    );
+    let album_ratings: HashMap<UUID, i32> = user_album_stats::table
+        .filter(user_album_stats::user_id.eq(user_id))
+        .select((user_album_stats::album_id, user_album_stats::rating))
+        .load::<(UUID, Option<i32>)>(conn)?
+        .into_iter()
+        .filter_map(|(id, rating)| rating.map(|rating| (id, rating)))
+        .collect();
+    let album_time_liked = known_times(
+        user_album_stats::table
+            .filter(user_album_stats::user_id.eq(user_id))
+            .select((user_album_stats::album_id, user_album_stats::time_liked))
+            .load(conn)?,
+    );
+
+    let played_albums: HashSet<UUID> = plays.iter().filter_map(|play| play.album_id).collect();
+    let albums: Vec<(Album, String)> = album::table
+        .inner_join(artist::table)
+        .select((album::all_columns, artist::name))
+        .order_by(album::id)
+        .load::<(Album, String)>(conn)?
+        .into_iter()
+        .filter(|(album, _)| {
+            album_last_played.contains_key(&album.id)
+                || album_ratings.contains_key(&album.id)
+                || album_time_liked.contains_key(&album.id)
+                || played_albums.contains(&album.id)
+        })
+        .collect();
+
+    let artist_last_played = known_times(
+        user_artist_stats::table
+            .filter(user_artist_stats::user_id.eq(user_id))
+            .select((user_artist_stats::artist_id, user_artist_stats::last_played))
+            .load(conn)?,
+    );
+    let artist_time_followed = known_times(
+        user_artist_stats::table
+            .filter(user_artist_stats::user_id.eq(user_id))
+            .select((
+                user_artist_stats::artist_id,
+                user_artist_stats::time_followed,
+            ))
+            .load(conn)?,
+    );
+
+    let played_artists: HashSet<UUID> = plays.iter().filter_map(|play| play.artist_id).collect();
+    let artists: Vec<Artist> = artist::table
+        .order_by(artist::id)
+        .load::<Artist>(conn)?
+        .into_iter()
+        .filter(|artist| {
+            artist_last_played.contains_key(&artist.id)
+                || artist_time_followed.contains_key(&artist.id)
+                || played_artists.contains(&artist.id)
+        })
+        .collect();
+
+    let song_index = index_of(songs.iter().copied());
+    let album_index = index_of(albums.iter().map(|(album, _)| album.id));
+    let artist_index = index_of(artists.iter().map(|artist| artist.id));
+
+    let mut keys = SongKey::load_all(conn)?;
+
+    Ok(UserData {
+        version: VERSION,
+        songs: songs
+            .into_iter()
+            .map(|id| {
+                let stats = song_stats
+                    .remove(&id)
+                    .unwrap_or_else(|| SongStats::empty(user_id, id));
+
+                SongData {
+                    key: keys.remove(&id).unwrap_or_default(),
+                    play_count: stats.play_count,
+                    liked: stats.liked,
+                    last_played: stats.last_played,
+                    rating: stats.rating,
+                }
+            })
+            .collect(),
+        albums: albums
+            .into_iter()
+            .map(|(album, artist)| AlbumData {
+                mbid: album.mbid,
+                name: album.name,
+                artist,
+                last_played: album_last_played.get(&album.id).copied(),
+                rating: album_ratings.get(&album.id).copied(),
+                time_liked: album_time_liked.get(&album.id).copied(),
+            })
+            .collect(),
+        artists: artists
+            .into_iter()
+            .map(|artist| ArtistData {
+                last_played: artist_last_played.get(&artist.id).copied(),
+                time_followed: artist_time_followed.get(&artist.id).copied(),
+                name: artist.name,
+            })
+            .collect(),
+        plays: plays
+            .into_iter()
+            .filter_map(|play| {
+                Some(PlayData {
+                    song: *song_index.get(&play.song_id)?,
+                    time: play.time,
+                    album: play.album_id.and_then(|id| album_index.get(&id).copied()),
+                    artist: play.artist_id.and_then(|id| artist_index.get(&id).copied()),
+                    client: play.client,
+                    duration: play.duration,
+                })
+            })
+            .collect(),
+    })
+}
+
+fn index_of(ids: impl Iterator<Item = UUID>) -> HashMap<UUID, usize> {
+    ids.enumerate().map(|(index, id)| (id, index)).collect()
+}
+
+/// Keeps the ids which have a time, like the time they were last played or liked.
+fn known_times(times: Vec<(UUID, Option<NaiveDateTime>)>) -> HashMap<UUID, NaiveDateTime> {
+    times
+        .into_iter()
+        .filter_map(|(id, time)| time.map(|time| (id, time)))
+        .collect()
+}
+
+/// Matches exported data with the songs, albums and artists in the library and restores it as
+/// the user's. Play counts, likes and ratings are replaced, last played times only move forward,
+/// and plays which are already in the user's history aren't added again. Songs matching the same
+/// song of the library have their play counts summed and keep the highest rating.
+pub fn import(
+    conn: &SqliteConnection,
+    user_id: UUID,
+    data: &UserData,
+) -> QueryResult<ImportReport> {
+    conn.transaction(|| {
+        let mut report = ImportReport::default();
+        let matcher = SongMatcher::load(conn)?;
+
+        // Several exported songs can match the same song of the library, like copies of a song
+        // which were merged, so their data is combined before it replaces the song's.
+        let mut song_ids = Vec::with_capacity(data.songs.len());
+        let mut song_stats: HashMap<UUID, SongStats> = HashMap::new();
+        for song_data in &data.songs {
+            let id = matcher.find(&song_data.key);
+            match id {
+                Some(id) => {
+                    report.matched_songs += 1;
+
+                    let stats = song_stats
+                        .entry(id)
+                        .or_insert_with(|| SongStats::empty(user_id, id));
+                    stats.play_count += song_data.play_count;
+                    stats.liked |= song_data.liked;
+                    stats.rating = stats.rating.max(song_data.rating);
+                    stats.last_played = stats.last_played.max(song_data.last_played);
+                }
+                None => report.unmatched_songs.push(song_data.key.clone()),
+            }
+
+            song_ids.push(id);
+        }
+
+        for stats in song_stats.values() {
+            SongStats::ensure(conn, user_id, stats.song_id)?;
+
+            let target = user_song_stats::table.find((user_id, stats.song_id));
+            diesel::update(target)
+                .set((
+                    user_song_stats::play_count.eq(stats.play_count),
+                    user_song_stats::liked.eq(stats.liked),
+                    user_song_stats::rating.eq(stats.rating),
+                ))
+                .execute(conn)?;
+
+            if let Some(last_played) = stats.last_played {
+                diesel::update(target)
+                    .filter(
+                        user_song_stats::last_played
+                            .is_null()
+                            .or(user_song_stats::last_played.lt(last_played)),
+                    )
+                    .set(user_song_stats::last_played.eq(last_played))
+                    .execute(conn)?;
+            }
+        }
+
+        let mut album_ids = Vec::with_capacity(data.albums.len());
+        for album_data in &data.albums {
+            let id = find_album(conn, album_data)?;
+            if let Some(id) = id {
+                report.matched_albums += 1;
+                if let Some(last_played) = album_data.last_played {
+                    UserStats::album_played(conn, user_id, id, last_played)?;
+                }
+
+                UserStats::rate_album(conn, user_id, id, album_data.rating)?;
+                UserStats::set_album_liked(conn, user_id, id, album_data.time_liked)?;
+            }
+
+            album_ids.push(id);
+        }
+
+        let mut artist_ids = Vec::with_capacity(data.artists.len());
+        for artist_data in &data.artists {
+            let id: Option<UUID> = artist::table
+                .filter(artist::name.eq(&artist_data.name))
+                .select(artist::id)
+                .first(conn)
+                .optional()?;
+
+            if let Some(id) = id {
+                report.matched_artists += 1;
+                if let Some(last_played) = artist_data.last_played {
+                    UserStats::artist_played(conn, user_id, id, last_played)?;
+                }
+
+                UserStats::set_artist_followed(conn, user_id, id, artist_data.time_followed)?;
+            }
+
+            artist_ids.push(id);
+        }
+
+        for play_data in &data.plays {
+            let song_id = match song_ids.get(play_data.song).copied().flatten() {
+                Some(song_id) => song_id,
+                None => continue,
+            };
+
+            let exists: Option<UUID> = play::table
+                .filter(play::user_id.eq(user_id))
+                .filter(play::song_id.eq(song_id))
+                .filter(play::time.eq(play_data.time))
+                .select(play::id)
+                .first(conn)
+                .optional()?;
+
+            if exists.is_some() {
+                report.duplicate_plays += 1;
+                continue;
+            }
+
+            Play {
+                id: UUID::new(),
+                user_id,
+                song_id,
+                time: play_data.time,
+                album_id: play_data
+                    .album
+                    .and_then(|index| album_ids.get(index).copied().flatten()),
+                artist_id: play_data
+                    .artist
+                    .and_then(|index| artist_ids.get(index).copied().flatten()),
+                client: play_data.client.clone(),
+                duration: play_data.duration,
+            }
+            .insert_into(play::table)
+            .execute(conn)?;
+
+            report.imported_plays += 1;
+        }
+
+        Ok(report)
+    })
+}
+
+fn find_album(conn: &SqliteConnection, album_data: &AlbumData) -> QueryResult<Option<UUID>> {
+    if let Some(mbid) = &album_data.mbid {
+        let id = album::table
+            .filter(album::mbid.eq(mbid))
+            .select(album::id)
+            .first(conn)
+            .optional()?;
+
+        if id.is_some() {
+            return Ok(id);
+        }
+    }
+
+    album::table
+        .inner_join(artist::table)
+        .filter(album::name.eq(&album_data.name))
+        .filter(artist::name.eq(&album_data.artist))
+        .select(album::id)
+        .first(conn)
+        .optional()
+}
+
+#[cfg(test)]
+mod test {
+    use super::{export, import};
+    use crate::database::{album, artist, play, song};
+    use crate::models::*;
+    use crate::test_database;
+    use chrono::NaiveDate;
+    use diesel::prelude::*;
+    use std::path::PathBuf;
+
+    fn day(day: u32) -> NaiveDateTime {
+        NaiveDate::from_ymd(2026, 10, day).and_hms(0, 0, 0)
+    }
+
+    /// Adds a song at `path` by an artist named `artist`, with a MusicBrainz ID when it's given.
+    fn add_song(
+        conn: &SqliteConnection,
+        name: &str,
+        artist: &str,
+        path: &str,
+        mbid: Option<&str>,
+    ) -> Song {
+        let song = test_database::add_song(conn, name, 1);
+        let path: PathWrapper = PathBuf::from(path).into();
+        diesel::update(song::table.find(song.id))
+            .set((song::path.eq(path), song::mbid.eq(mbid)))
+            .execute(conn)
+            .unwrap();
+
+        let artist_id: UUID = album::table
+            .find(song.album_id)
+            .select(album::artist_id)
+            .first(conn)
+            .unwrap();
+        diesel::update(artist::table.find(artist_id))
+            .set(artist::name.eq(artist))
+            .execute(conn)
+            .unwrap();
+
+        song
+    }
+
+    fn add_play(conn: &SqliteConnection, user_id: UUID, song: &Song, time: NaiveDateTime) {
+        Play {
+            id: UUID::new(),
+            user_id,
+            song_id: song.id,
+            time,
+            album_id: Some(song.album_id),
+            artist_id: None,
+            client: None,
+            duration: None,
+        }
+        .insert_into(play::table)
+        .execute(conn)
+        .unwrap();
+    }
+
+    #[test]
+    fn round_trips_onto_a_new_library() {
+        let old = test_database::connection();
+        let old_user = test_database::default_user(&old);
+
+        let by_path = add_song(&old, "By path", "A", "/music/path.flac", None);
+        SongStats::played(&old, old_user, by_path.id, 2, day(3)).unwrap();
+        SongStats::toggle_like(&old, old_user, by_path.id).unwrap();
+        SongStats::rate(&old, old_user, by_path.id, Some(4)).unwrap();
+        UserStats::album_played(&old, old_user, by_path.album_id, day(3)).unwrap();
+        add_play(&old, old_user, &by_path, day(2));
+        add_play(&old, old_user, &by_path, day(3));
+
+        let by_mbid = add_song(&old, "By mbid", "B", "/old/mbid.flac", Some("mbid"));
+        SongStats::played(&old, old_user, by_mbid.id, 1, day(5)).unwrap();
+        add_play(&old, old_user, &by_mbid, day(5));
+
+        let by_tags = add_song(&old, "By tags", "C", "/old/tags.flac", None);
+        SongStats::played(&old, old_user, by_tags.id, 1, day(6)).unwrap();
+        add_play(&old, old_user, &by_tags, day(6));
+
+        // Copies of a song which were merged in the new library.
+        let copy = add_song(&old, "Copy", "D", "/old/copy.flac", None);
+        SongStats::played(&old, old_user, copy.id, 2, day(1)).unwrap();
+        SongStats::rate(&old, old_user, copy.id, Some(3)).unwrap();
+        let other_copy = add_song(&old, "Copy", "E", "/old/other copy.flac", None);
+        SongStats::played(&old, old_user, other_copy.id, 3, day(1)).unwrap();
+        SongStats::rate(&old, old_user, other_copy.id, Some(5)).unwrap();
+        SongStats::toggle_like(&old, old_user, other_copy.id).unwrap();
+
+        let gone = add_song(&old, "Gone", "F", "/old/gone.flac", None);
+        SongStats::played(&old, old_user, gone.id, 1, day(7)).unwrap();
+        add_play(&old, old_user, &gone, day(7));
+
+        let data = export(&old, old_user).unwrap();
+
+        let new = test_database::connection();
+        let user_id = test_database::default_user(&new);
+        let by_path = add_song(&new, "By path", "A", "/music/path.flac", None);
+        let by_mbid = add_song(&new, "By mbid", "B", "/new/mbid.flac", Some("mbid"));
+        let by_tags = add_song(&new, "By tags", "C", "/new/tags.flac", None);
+        let copy = add_song(&new, "Copy", "D", "/new/copy.flac", None);
+
+        // A play which is already in the history, and a song played since the export.
+        add_play(&new, user_id, &by_path, day(3));
+        SongStats::played(&new, user_id, by_mbid.id, 0, day(9)).unwrap();
+
+        let report = import(&new, user_id, &data).unwrap();
+        assert_eq!(report.matched_songs, 5);
+        assert_eq!(report.unmatched_songs.len(), 1);
+        assert_eq!(report.unmatched_songs[0].title, "Gone");
+        assert_eq!(report.imported_plays, 3);
+        assert_eq!(report.duplicate_plays, 1);
+
+        let stats = |song: &Song| SongStats::find(&new, user_id, song.id).unwrap();
+        let path_stats = stats(&by_path);
+        assert_eq!(path_stats.play_count, 2);
+        assert!(path_stats.liked);
+        assert_eq!(path_stats.rating, Some(4));
+        assert_eq!(path_stats.last_played, Some(day(3)));
+
+        let mbid_stats = stats(&by_mbid);
+        assert_eq!(mbid_stats.play_count, 1);
+        assert_eq!(mbid_stats.last_played, Some(day(9)));
+        assert_eq!(stats(&by_tags).last_played, Some(day(6)));
+
+        let copy_stats = stats(&copy);
+        assert_eq!(copy_stats.play_count, 5);
+        assert!(copy_stats.liked);
+        assert_eq!(copy_stats.rating, Some(5));
+
+        let album_stats = UserStats::for_album(&new, user_id, by_path.album_id).unwrap();
+        assert_eq!(album_stats.last_played, Some(day(3)));
+        let plays_from_album: i64 = play::table
+            .filter(play::album_id.eq(by_path.album_id))
+            .count()
+            .get_result(&new)
+            .unwrap();
+        assert_eq!(plays_from_album, 2);
+
+        let again = import(&new, user_id, &data).unwrap();
+        assert_eq!(again.imported_plays, 0);
+        assert_eq!(again.duplicate_plays, 4);
+        let plays: i64 = play::table.count().get_result(&new).unwrap();
+        assert_eq!(plays, 4);
+    }
+}
@@ -0,0 +1,128 @@
+//! Reads the dimensions of embedded pictures from their headers without decoding the image.
+
+const PNG_SIGNATURE: &[u8] = b"\x89PNG\r\n\x1a\n";
+const GIF_SIGNATURES: [&[u8]; 2] = [b"GIF87a", b"GIF89a"];
+
+/// Gets the width and height of a PNG, JPEG or GIF image. Returns `None` for other formats and
+/// for truncated or malformed headers.
+pub fn read(data: &[u8]) -> Option<(u32, u32)> {
+    if data.starts_with(PNG_SIGNATURE) {
+        return read_png(data);
+    }
+
+    if data.starts_with(&[0xff, 0xd8]) {
+        return read_jpeg(data);
+    }
+
+    if GIF_SIGNATURES.iter().any(|sig| data.starts_with(sig)) {
+        return read_gif(data);
+    }
+
+    None
+}
+
+/// The IHDR chunk always comes first and starts with the width and height.
+fn read_png(data: &[u8]) -> Option<(u32, u32)> {
+    let header = data.get(12..24)?;
+    if &header[0..4] != b"IHDR" {
+        return None;
+    }
+
+    Some((be_u32(&header[4..8]), be_u32(&header[8..12])))
+}
+
+/// Walks the JPEG segments until a start of frame segment, which holds the dimensions.
+fn read_jpeg(data: &[u8]) -> Option<(u32, u32)> {
+    let mut offset = 2;
+
+    loop {
+        let marker = data.get(offset..offset + 2)?;
+        if marker[0] != 0xff {
+            return None;
+        }
+
+        let kind = marker[1];
+
+        // Padding and markers which don't have a length.
+        if kind == 0xff {
+            offset += 1;
+            continue;
+        }
+
+        if kind == 0x01 || (0xd0..=0xd7).contains(&kind) {
+            offset += 2;
+            continue;
+        }
+
+        let length = be_u16(data.get(offset + 2..offset + 4)?) as usize;
+
+        // SOF0 to SOF15, excluding DHT (0xc4), JPG (0xc8) and DAC (0xcc).
+        if (0xc0..=0xcf).contains(&kind) && ![0xc4, 0xc8, 0xcc].contains(&kind) {
+            let frame = data.get(offset + 5..offset + 9)?;
+            let height = be_u16(&frame[0..2]) as u32;
+            let width = be_u16(&frame[2..4]) as u32;
+
+            return Some((width, height));
+        }
+
+        offset += 2 + length;
+    }
+}
+
+/// The logical screen descriptor follows the signature.
+fn read_gif(data: &[u8]) -> Option<(u32, u32)> {
+    let screen = data.get(6..10)?;
+
+    Some((le_u16(&screen[0..2]) as u32, le_u16(&screen[2..4]) as u32))
+}
+
+fn be_u32(bytes: &[u8]) -> u32 {
+    u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]])
+}
+
+fn be_u16(bytes: &[u8]) -> u16 {
+    u16::from_be_bytes([bytes[0], bytes[1]])
+}
+
+fn le_u16(bytes: &[u8]) -> u16 {
+    u16::from_le_bytes([bytes[0], bytes[1]])
+}
+
+#[cfg(test)]
+mod test {
+    use super::read;
+
+    #[test]
+    fn png() {
+        let mut data = b"\x89PNG\r\n\x1a\n\x00\x00\x00\x0dIHDR".to_vec();
+        data.extend_from_slice(&500u32.to_be_bytes());
+        data.extend_from_slice(&400u32.to_be_bytes());
+
+        assert_eq!(read(&data), Some((500, 400)));
+    }
+
+    #[test]
+    fn jpeg() {
+        let data = [
+            0xff, 0xd8, // SOI
+            0xff, 0xe0, 0x00, 0x04, 0x00, 0x00, // APP0 with two bytes of payload
+            0xff, 0xc0, 0x00, 0x11, 0x08, 0x02, 0x58, 0x03, 0x20, // SOF0, 800x600
+        ];
+
+        assert_eq!(read(&data), Some((800, 600)));
+    }
+
+    #[test]
+    fn gif() {
+        let data = b"GIF89a\x40\x01\xf0\x00";
+
+        assert_eq!(read(data), Some((320, 240)));
+    }
+
+    #[test]
+    fn truncated() {
+        assert_eq!(read(b"\x89PNG\r\n\x1a\n\x00\x00"), None);
+        assert_eq!(read(&[0xff, 0xd8, 0xff, 0xc0, 0x00]), None);
+        assert_eq!(read(b"not an image"), None);
+    }
+}
@@ -1,4 +1,7 @@
 use mime::Mime;
+use serde::{Serialize, Serializer};
+use std::collections::BTreeMap;
+use std::convert::TryFrom;
 use std::ffi::{CStr, CString};
 use std::fmt;
 use std::fmt::Debug;
@@ -8,6 +11,8 @@ use std::path::{Path, PathBuf};
 use std::str::FromStr;
 use thiserror::Error;
 
+mod dimensions;
+
 #[derive(Error, Debug)]
 pub enum Error {
     #[error("the path '{}' points to a directory instead of a file", .0.display())]
@@ -15,6 +20,48 @@ pub enum Error {
 
     #[error(transparent)]
     NulError(#[from] std::ffi::NulError),
+
+    #[error("the format of '{}' isn't supported", .0.display())]
+    UnsupportedFormat(PathBuf),
+
+    #[error("the file '{}' couldn't be opened or is corrupt", .0.display())]
+    UnreadableFile(PathBuf),
+
+    #[error("the file '{}' doesn't have a tag", .0.display())]
+    MissingTag(PathBuf),
+
+    #[error("the audio properties of '{}' couldn't be read", .0.display())]
+    MissingAudioProperties(PathBuf),
+}
+
+// The reasons reading a file can fail, mirroring the `Status` enum in wrapper.cpp. Any other
+// status is treated as the file being unreadable.
+const STATUS_UNSUPPORTED_FORMAT: i32 = 1;
+const STATUS_MISSING_TAG: i32 = 3;
+const STATUS_MISSING_AUDIO_PROPERTIES: i32 = 4;
+
+impl Error {
+    /// A short, stable identifier of the kind of error, suitable for grouping errors.
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Error::InvalidPathError(_) | Error::NulError(_) => "invalid_path",
+            Error::UnsupportedFormat(_) => "unsupported_format",
+            Error::UnreadableFile(_) => "unreadable_file",
+            Error::MissingTag(_) => "missing_tag",
+            Error::MissingAudioProperties(_) => "missing_audio_properties",
+        }
+    }
+
+    fn from_status(status: i32, path: &Path) -> Error {
+        let path = path.to_path_buf();
+
+        match status {
+            STATUS_UNSUPPORTED_FORMAT => Error::UnsupportedFormat(path),
+            STATUS_MISSING_TAG => Error::MissingTag(path),
+            STATUS_MISSING_AUDIO_PROPERTIES => Error::MissingAudioProperties(path),
+            _ => Error::UnreadableFile(path),
+        }
+    }
 }
 
 unsafe fn from_cstr(cstr: *const c_char) -> Option<String> {
@@ -26,8 +73,18 @@ unsafe fn from_cstr(cstr: *const c_char) -> Option<String> {
 }
 
 extern "C" {
-    fn song_properties(file_name: *const c_char) -> *const SongPropertiesC;
+    fn song_properties(file_name: *const c_char, status: *mut i32) -> *const SongPropertiesC;
     fn destroy_properties(song_properties: *const SongPropertiesC);
+    fn write_rating(file_name: *const c_char, stars: i32) -> i32;
+}
+
+#[repr(C)]
+struct EmbeddedPictureC {
+    data: *const u8,
+    data_len: u32,
+    mime: *const c_char,
+    description: *const c_char,
+    picture_type: i32,
 }
 
 #[repr(C)]
@@ -40,42 +97,156 @@ struct SongPropertiesC {
     year: u32,
     track_number: u32,
     duration: i32,
-    picture_data: *const u8,
-    picture_data_len: u32,
-    picture_mime: *const c_char,
+    pictures: *const EmbeddedPictureC,
+    pictures_len: u32,
+    property_keys: *const *const c_char,
+    property_values: *const *const c_char,
+    properties_len: u32,
+    bitrate: i32,
+    sample_rate: i32,
+    channels: i32,
+    popularimeter: i32,
+}
+
+/// The role of an embedded picture, as defined by the ID3v2 APIC frame and the FLAC PICTURE
+/// block.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, Serialize)]
+pub enum PictureType {
+    Other,
+    FileIcon,
+    OtherFileIcon,
+    FrontCover,
+    BackCover,
+    LeafletPage,
+    Media,
+    LeadArtist,
+    Artist,
+    Conductor,
+    Band,
+    Composer,
+    Lyricist,
+    RecordingLocation,
+    DuringRecording,
+    DuringPerformance,
+    MovieScreenCapture,
+    ColouredFish,
+    Illustration,
+    BandLogo,
+    PublisherLogo,
+}
+
+impl PictureType {
+    const ALL: [PictureType; 21] = [
+        PictureType::Other,
+        PictureType::FileIcon,
+        PictureType::OtherFileIcon,
+        PictureType::FrontCover,
+        PictureType::BackCover,
+        PictureType::LeafletPage,
+        PictureType::Media,
+        PictureType::LeadArtist,
+        PictureType::Artist,
+        PictureType::Conductor,
+        PictureType::Band,
+        PictureType::Composer,
+        PictureType::Lyricist,
+        PictureType::RecordingLocation,
+        PictureType::DuringRecording,
+        PictureType::DuringPerformance,
+        PictureType::MovieScreenCapture,
+        PictureType::ColouredFish,
+        PictureType::Illustration,
+        PictureType::BandLogo,
+        PictureType::PublisherLogo,
+    ];
+
+    /// Converts the numeric picture type used by ID3v2 and FLAC. Unknown values are treated as
+    /// `Other`.
+    pub fn from_raw(raw: i32) -> PictureType {
+        if raw < 0 {
+            return PictureType::Other;
+        }
+
+        PictureType::ALL
+            .get(raw as usize)
+            .copied()
+            .unwrap_or(PictureType::Other)
+    }
+
+    /// The numeric picture type used by ID3v2 and FLAC.
+    pub fn as_raw(self) -> i32 {
+        self as i32
+    }
 }
 
+#[derive(Serialize)]
 pub struct Picture {
+    /// The encoded image. Only its length is serialized.
+    #[serde(rename = "size", serialize_with = "serialize_len")]
     pub data: Vec<u8>,
+
+    #[serde(serialize_with = "serialize_display")]
     pub mime: Mime,
+    pub picture_type: PictureType,
+    pub description: Option<String>,
+
+    /// The width and height of the image in pixels, if they could be read from the image's
+    /// header.
+    pub dimensions: Option<(u32, u32)>,
 }
 
 impl Picture {
-    fn from_raw(data: *const u8, len: u32, raw_mime: *const c_char) -> Option<Picture> {
-        if data.is_null() {
+    unsafe fn from_raw(raw: &EmbeddedPictureC) -> Option<Picture> {
+        if raw.data.is_null() {
             return None;
         }
 
-        unsafe {
-            let bytes = std::slice::from_raw_parts(data, len as usize);
-            let mime_string = from_cstr(raw_mime)?;
-            let mime = Mime::from_str(&mime_string).ok()?;
+        let bytes = std::slice::from_raw_parts(raw.data, raw.data_len as usize);
+        let mime_string = from_cstr(raw.mime)?;
+        let mime = Mime::from_str(&mime_string).ok()?;
 
-            Some(Picture {
-                data: bytes.to_vec(),
-                mime,
-            })
-        }
+        Some(Picture {
+            data: bytes.to_vec(),
+            mime,
+            picture_type: PictureType::from_raw(raw.picture_type),
+            description: from_cstr(raw.description).filter(|d| !d.is_empty()),
+            dimensions: dimensions::read(bytes),
+        })
     }
 }
 
 impl Debug for Picture {
     fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
-        write!(f, "MIME: {:?}", self.mime)
+        write!(
+            f,
+            "{:?} (MIME: {:?}, description: {:?}, dimensions: {:?})",
+            self.picture_type, self.mime, self.description, self.dimensions
+        )
     }
 }
 
-#[derive(Debug)]
+fn serialize_len<S: Serializer>(data: &[u8], serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.serialize_u64(data.len() as u64)
+}
+
+fn serialize_display<T: fmt::Display, S: Serializer>(
+    value: &T,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    serializer.collect_str(value)
+}
+
+#[derive(Debug, Serialize)]
+pub struct AudioProperties {
+    /// The bitrate in kb/s.
+    pub bitrate: i32,
+
+    /// The sample rate in Hz.
+    pub sample_rate: i32,
+    pub channels: i32,
+}
+
+#[derive(Debug, Serialize)]
 pub struct SongProperties {
     pub title: Option<String>,
     pub album: Option<String>,
@@ -84,13 +255,23 @@ pub struct SongProperties {
     pub year: Option<u32>,
     pub disk_number: Option<u32>,
     pub track_number: u32,
+
+    /// The length of the song in milliseconds.
     pub duration: i32,
-    pub cover_artwork: Option<Picture>,
+    pub audio: AudioProperties,
+    pub pictures: Vec<Picture>,
+
+    /// Every property in the tag, keyed by taglib's normalized property names (e.g.
+    /// `MUSICBRAINZ_ALBUMID`).
+    pub properties: BTreeMap<String, Vec<String>>,
+
+    /// The rating of the ID3v2 POPM frame of an MP3 file, from 0 (unknown) to 255.
+    pub popularimeter: Option<u8>,
 }
 
 impl SongProperties {
     #[cfg(unix)]
-    pub fn read(path: &Path) -> Result<Option<SongProperties>, Error> {
+    pub fn read(path: &Path) -> Result<SongProperties, Error> {
         use std::os::unix::ffi::OsStrExt;
 
         if path.is_dir() {
@@ -99,16 +280,37 @@ impl SongProperties {
 
         let file_name = path.as_os_str().as_bytes();
         let file_name_c = CString::new(file_name)?;
-        let props_c = match unsafe { song_properties(file_name_c.as_ptr()).as_ref() } {
+        let mut status = 0;
+        let props_c = match unsafe { song_properties(file_name_c.as_ptr(), &mut status).as_ref() } {
             Some(props_c) => props_c,
-            None => return Ok(None),
+            None => return Err(Error::from_status(status, path)),
         };
 
         let props = unsafe { SongProperties::from(props_c) };
 
         unsafe { destroy_properties(props_c) };
 
-        Ok(Some(props))
+        Ok(props)
+    }
+
+    /// Gets the first value of a property in the tag.
+    pub fn property(&self, key: &str) -> Option<&str> {
+        self.properties
+            .get(key)
+            .and_then(|values| values.first())
+            .map(String::as_str)
+    }
+
+    /// The picture which should be used as the cover of the song's album. A picture marked as the
+    /// front cover is preferred, falling back to a picture with the type `Other`.
+    pub fn cover_artwork(&self) -> Option<&Picture> {
+        [PictureType::FrontCover, PictureType::Other]
+            .iter()
+            .find_map(|picture_type| {
+                self.pictures
+                    .iter()
+                    .find(|picture| picture.picture_type == *picture_type)
+            })
     }
 
     unsafe fn from(song_properties_c: &SongPropertiesC) -> Self {
@@ -127,11 +329,68 @@ impl SongProperties {
             disk_number,
             track_number: (*song_properties_c).track_number,
             duration: (*song_properties_c).duration,
-            cover_artwork: Picture::from_raw(
-                song_properties_c.picture_data,
-                song_properties_c.picture_data_len,
-                song_properties_c.picture_mime,
+            audio: AudioProperties {
+                bitrate: song_properties_c.bitrate,
+                sample_rate: song_properties_c.sample_rate,
+                channels: song_properties_c.channels,
+            },
+            pictures: read_pictures(song_properties_c.pictures, song_properties_c.pictures_len),
+            properties: read_properties(
+                song_properties_c.property_keys,
+                song_properties_c.property_values,
+                song_properties_c.properties_len,
             ),
+            popularimeter: u8::try_from(song_properties_c.popularimeter).ok(),
+        }
+    }
+}
+
+/// Writes a rating of 1 to 5 stars into the tags of a file, or removes it when `stars` is `None`.
+/// The rating is stored as an `FMPS_RATING` property and, in MP3 files, as a POPM frame.
+#[cfg(unix)]
+pub fn write_rating_tags(path: &Path, stars: Option<u8>) -> Result<(), Error> {
+    use std::os::unix::ffi::OsStrExt;
+
+    if path.is_dir() {
+        return Err(Error::InvalidPathError(path.to_path_buf()));
+    }
+
+    let file_name_c = CString::new(path.as_os_str().as_bytes())?;
+    match unsafe { write_rating(file_name_c.as_ptr(), i32::from(stars.unwrap_or(0))) } {
+        0 => Ok(()),
+        status => Err(Error::from_status(status, path)),
+    }
+}
+
+unsafe fn read_pictures(pictures: *const EmbeddedPictureC, len: u32) -> Vec<Picture> {
+    if pictures.is_null() {
+        return Vec::new();
+    }
+
+    std::slice::from_raw_parts(pictures, len as usize)
+        .iter()
+        .filter_map(|raw| Picture::from_raw(raw))
+        .collect()
+}
+
+unsafe fn read_properties(
+    keys: *const *const c_char,
+    values: *const *const c_char,
+    len: u32,
+) -> BTreeMap<String, Vec<String>> {
+    let mut properties: BTreeMap<String, Vec<String>> = BTreeMap::new();
+    if keys.is_null() || values.is_null() {
+        return properties;
+    }
+
+    let keys = std::slice::from_raw_parts(keys, len as usize);
+    let values = std::slice::from_raw_parts(values, len as usize);
+
+    for (&key, &value) in keys.iter().zip(values) {
+        if let (Some(key), Some(value)) = (from_cstr(key), from_cstr(value)) {
+            properties.entry(key).or_default().push(value);
         }
     }
+
+    properties
 }
@@ -1,12 +1,107 @@
+//! Prints everything taglib2-sys reads from audio files as JSON, one object per line.
+//!
+//! Usage: taglib2-sys [--recursive] [--pretty] <path>...
+//!
+//! Built with the `cli` feature, e.g. `cargo run -p taglib2-sys --features cli -- <path>`.
+//!
+//! With `--recursive`, directories are walked and every file in them is inspected. Files whose
+//! format isn't supported are skipped silently in that mode.
+
+use serde::Serialize;
+use std::borrow::Cow;
 use std::env;
-use std::path::Path;
-use taglib2_sys::SongProperties;
+use std::path::{Path, PathBuf};
+use std::process;
+use taglib2_sys::{Error, SongProperties};
+use walkdir::WalkDir;
+
+#[derive(Serialize)]
+struct Inspection<'a> {
+    path: Cow<'a, str>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    properties: Option<SongProperties>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+}
+
+struct Options {
+    recursive: bool,
+    pretty: bool,
+    paths: Vec<PathBuf>,
+}
+
+fn parse_args() -> Result<Options, String> {
+    let mut options = Options {
+        recursive: false,
+        pretty: false,
+        paths: Vec::new(),
+    };
+
+    for arg in env::args().skip(1) {
+        match arg.as_str() {
+            "-r" | "--recursive" => options.recursive = true,
+            "-p" | "--pretty" => options.pretty = true,
+            flag if flag.starts_with('-') => return Err(format!("unknown flag '{}'", flag)),
+            path => options.paths.push(PathBuf::from(path)),
+        }
+    }
+
+    if options.paths.is_empty() {
+        return Err("no paths were given".to_string());
+    }
+
+    Ok(options)
+}
 
 fn main() {
-    env::args().skip(1).for_each(|arg| {
-        let path = Path::new(&arg);
-        let props = SongProperties::read(&path).unwrap();
+    let options = match parse_args() {
+        Ok(options) => options,
+        Err(message) => {
+            eprintln!("Error: {}", message);
+            eprintln!("Usage: taglib2-sys [--recursive] [--pretty] <path>...");
+            process::exit(2);
+        }
+    };
+
+    for path in &options.paths {
+        if options.recursive && path.is_dir() {
+            WalkDir::new(path)
+                .follow_links(true)
+                .into_iter()
+                .filter_map(|entry| entry.ok())
+                .filter(|entry| entry.file_type().is_file())
+                .for_each(|entry| inspect(entry.path(), &options));
+        } else {
+            inspect(path, &options);
+        }
+    }
+}
+
+fn inspect(path: &Path, options: &Options) {
+    let inspection = match SongProperties::read(path) {
+        Ok(properties) => Inspection {
+            path: path.to_string_lossy(),
+            properties: Some(properties),
+            error: None,
+        },
+        Err(Error::UnsupportedFormat(_)) if options.recursive => return,
+        Err(err) => Inspection {
+            path: path.to_string_lossy(),
+            properties: None,
+            error: Some(err.to_string()),
+        },
+    };
+
+    let json = if options.pretty {
+        serde_json::to_string_pretty(&inspection)
+    } else {
+        serde_json::to_string(&inspection)
+    };
 
-        println!("{:#?}", props);
-    });
+    println!(
+        "{}",
+        json.expect("inspection results are always serializable")
+    );
 }
@@ -1,7 +1,11 @@
+#include <stdio.h>
 #include <string.h>
 #include "../taglib/taglib/tag.h"
 #include "../taglib/taglib/fileref.h"
 #include "../taglib/taglib/toolkit/tpicturemap.h"
+#include "../taglib/taglib/mpeg/mpegfile.h"
+#include "../taglib/taglib/mpeg/id3v2/id3v2tag.h"
+#include "../taglib/taglib/mpeg/id3v2/frames/popularimeterframe.h"
 #include "../taglib/taglib/toolkit/tdebuglistener.cpp"
 
 /// A debug listener which does nothing. It is used to mute debug output.
@@ -20,6 +24,24 @@ char *to_cstr(TagLib::String str) {
 }
 
 extern "C" {
+    /// Why reading the properties of a file failed. Mirrored by the `STATUS_*` constants in
+    /// lib.rs.
+    typedef enum {
+        STATUS_OK = 0,
+        STATUS_UNSUPPORTED_FORMAT = 1,
+        STATUS_UNREADABLE_FILE = 2,
+        STATUS_MISSING_TAG = 3,
+        STATUS_MISSING_AUDIO_PROPERTIES = 4,
+    } Status;
+
+    typedef struct {
+        char *data;
+        unsigned int data_len;
+        char *mime;
+        char *description;
+        int picture_type;
+    } EmbeddedPicture;
+
     typedef struct {
         char *title;
         char *album;
@@ -29,47 +51,146 @@ extern "C" {
         unsigned int year;
         unsigned int track_number;
         int duration;
-        char *picture_data;
-        unsigned int picture_data_len;
-        char *picture_mime;
+        EmbeddedPicture *pictures;
+        unsigned int pictures_len;
+        char **property_keys;
+        char **property_values;
+        unsigned int properties_len;
+        int bitrate;
+        int sample_rate;
+        int channels;
+        int popularimeter;
     } SongProperties;
 }
 
-void read_artwork(SongProperties *song, TagLib::PictureMap &map) {
-    TagLib::Picture::Type options[] = {TagLib::Picture::Type::FrontCover, TagLib::Picture::Type::Other};
+void read_pictures(SongProperties *song, const TagLib::PictureMap &map) {
+    unsigned int count = 0;
+    for (TagLib::PictureMap::ConstIterator it = map.begin(); it != map.end(); ++it) {
+        count += it->second.size();
+    }
+
+    if (count == 0) {
+        return;
+    }
+
+    song->pictures = new EmbeddedPicture[count]();
+    song->pictures_len = count;
 
-    for (TagLib::Picture::Type option : options) {
-        if (map.contains(option)) {
-            TagLib::Picture picture = map[option].front();
-            song->picture_mime = to_cstr(picture.mime());
+    unsigned int idx = 0;
+    for (TagLib::PictureMap::ConstIterator it = map.begin(); it != map.end(); ++it) {
+        for (TagLib::PictureList::ConstIterator picture = it->second.begin(); picture != it->second.end(); ++picture) {
+            EmbeddedPicture *embedded = &song->pictures[idx++];
+            embedded->picture_type = picture->type();
+            embedded->mime = to_cstr(picture->mime());
+            embedded->description = to_cstr(picture->description());
 
             // We need to copy the picture data manually because it's not a string
-            TagLib::ByteVector pictureData = picture.data();
+            TagLib::ByteVector pictureData = picture->data();
             size_t pictureSize = pictureData.size();
-            song->picture_data = (char*) malloc(pictureSize);
-            memcpy(song->picture_data, pictureData.data(), pictureSize);
-            song->picture_data_len = pictureSize;
+            embedded->data = (char*) malloc(pictureSize);
+            memcpy(embedded->data, pictureData.data(), pictureSize);
+            embedded->data_len = pictureSize;
+        }
+    }
+}
+
+/// Flattens the property map into parallel arrays of keys and values. A key with multiple values
+/// appears once for each value.
+void read_properties(SongProperties *song, const TagLib::PropertyMap &properties) {
+    unsigned int count = 0;
+    for (TagLib::PropertyMap::ConstIterator it = properties.begin(); it != properties.end(); ++it) {
+        count += it->second.size();
+    }
+
+    if (count == 0) {
+        return;
+    }
+
+    song->property_keys = new char*[count];
+    song->property_values = new char*[count];
+    song->properties_len = count;
 
-            return;
+    unsigned int idx = 0;
+    for (TagLib::PropertyMap::ConstIterator it = properties.begin(); it != properties.end(); ++it) {
+        for (TagLib::StringList::ConstIterator value = it->second.begin(); value != it->second.end(); ++value) {
+            song->property_keys[idx] = to_cstr(it->first);
+            song->property_values[idx] = to_cstr(*value);
+            idx++;
         }
     }
 }
 
+/// The POPM values Windows writes for 1 to 5 stars, which most players read back the same way.
+const int POPULARIMETER_STARS[] = {1, 64, 128, 196, 255};
+
+/// The rating of the first POPM frame in the ID3v2 tag of an MP3 file, from 0 to 255. Returns -1
+/// when there is no such frame.
+int read_popularimeter(TagLib::File *file) {
+    TagLib::MPEG::File *mpeg = dynamic_cast<TagLib::MPEG::File *>(file);
+    if (!mpeg || !mpeg->hasID3v2Tag()) {
+        return -1;
+    }
+
+    const TagLib::ID3v2::FrameList &frames = mpeg->ID3v2Tag()->frameListMap()["POPM"];
+    if (frames.isEmpty()) {
+        return -1;
+    }
+
+    TagLib::ID3v2::PopularimeterFrame *frame =
+        dynamic_cast<TagLib::ID3v2::PopularimeterFrame *>(frames.front());
+    return frame ? (int) frame->rating() : -1;
+}
+
+/// Replaces the POPM frames in the ID3v2 tag of an MP3 file with one for the stars. Other files
+/// are left alone.
+void write_popularimeter(TagLib::File *file, int stars) {
+    TagLib::MPEG::File *mpeg = dynamic_cast<TagLib::MPEG::File *>(file);
+    if (!mpeg) {
+        return;
+    }
+
+    TagLib::ID3v2::Tag *tag = mpeg->ID3v2Tag(true);
+    tag->removeFrames("POPM");
+
+    if (stars > 0) {
+        TagLib::ID3v2::PopularimeterFrame *frame = new TagLib::ID3v2::PopularimeterFrame();
+        frame->setRating(POPULARIMETER_STARS[stars - 1]);
+        tag->addFrame(frame);
+    }
+}
+
 extern "C" {
-    SongProperties *song_properties(const char *fileName) {
+    SongProperties *song_properties(const char *fileName, Status *status) {
         TagLib::setDebugListener(&nopListener);
         TagLib::FileRef file((TagLib::FileName) fileName);
 
+        // No file is created when none of the file type resolvers recognize the file.
+        if(!file.file()) {
+            *status = STATUS_UNSUPPORTED_FORMAT;
+            return NULL;
+        }
+
         // Check if the file was opened
-        if(file.isNull() || !file.tag()) {
+        if(!file.file()->isValid()) {
+            *status = STATUS_UNREADABLE_FILE;
+            return NULL;
+        }
+
+        TagLib::Tag *tag = file.tag();
+        if(!tag) {
+            *status = STATUS_MISSING_TAG;
+            return NULL;
+        }
+
+        TagLib::AudioProperties *audioProperties = file.audioProperties();
+        if(!audioProperties) {
+            *status = STATUS_MISSING_AUDIO_PROPERTIES;
             return NULL;
         }
 
         // Read off song properties
         SongProperties *song_properties = new SongProperties();
 
-        TagLib::Tag *tag = file.tag();
-        TagLib::AudioProperties *audioProperties = file.audioProperties();
         TagLib::PropertyMap properties = tag->properties();
 
         song_properties->title = to_cstr(tag->title());
@@ -91,11 +212,18 @@ extern "C" {
 
         song_properties->year = tag->year();
         song_properties->track_number = tag->track();
-        song_properties->duration = audioProperties->length();
+        song_properties->duration = audioProperties->lengthInMilliseconds();
+        song_properties->bitrate = audioProperties->bitrate();
+        song_properties->sample_rate = audioProperties->sampleRate();
+        song_properties->channels = audioProperties->channels();
+        song_properties->popularimeter = read_popularimeter(file.file());
+
+        read_properties(song_properties, properties);
 
         TagLib::PictureMap map = tag->pictures();
-        read_artwork(song_properties, map);
+        read_pictures(song_properties, map);
 
+        *status = STATUS_OK;
         return song_properties;
     }
 
@@ -104,8 +232,53 @@ extern "C" {
         free(songProperties->album);
         free(songProperties->artist);
         free(songProperties->album_artist);
-        free(songProperties->picture_data);
-        free(songProperties->picture_mime);
+        free(songProperties->disk_number);
+
+        for (unsigned int i = 0; i < songProperties->pictures_len; i++) {
+            free(songProperties->pictures[i].data);
+            free(songProperties->pictures[i].mime);
+            free(songProperties->pictures[i].description);
+        }
+        delete[] songProperties->pictures;
+
+        for (unsigned int i = 0; i < songProperties->properties_len; i++) {
+            free(songProperties->property_keys[i]);
+            free(songProperties->property_values[i]);
+        }
+        delete[] songProperties->property_keys;
+        delete[] songProperties->property_values;
+
         delete songProperties;
     }
+
+    /// Writes a rating of 1 to 5 stars to the file as an FMPS_RATING property and, for MP3 files,
+    /// a POPM frame. A rating of 0 removes the rating.
+    Status write_rating(const char *fileName, int stars) {
+        TagLib::setDebugListener(&nopListener);
+        TagLib::FileRef file((TagLib::FileName) fileName);
+
+        if(!file.file()) {
+            return STATUS_UNSUPPORTED_FORMAT;
+        }
+
+        if(!file.file()->isValid() || file.file()->readOnly() || stars < 0 || stars > 5) {
+            return STATUS_UNREADABLE_FILE;
+        }
+
+        // FMPS ratings go from 0.0 to 1.0 in steps of 0.2 for stars.
+        TagLib::PropertyMap properties = file.file()->properties();
+        if (stars > 0) {
+            char fmps[4];
+            snprintf(fmps, sizeof(fmps), "%.1f", stars / 5.0);
+            properties.replace("FMPS_RATING", TagLib::StringList(fmps));
+        } else {
+            properties.erase("FMPS_RATING");
+        }
+        file.file()->setProperties(properties);
+
+        // Written after the properties so that setting them can't drop the frame.
+        write_popularimeter(file.file(), stars);
+
+        return file.save() ? STATUS_OK : STATUS_UNREADABLE_FILE;
+    }
 }